@@ -0,0 +1,55 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use crate::{
+    config::{Commands, Config},
+    ext::anyhow::Result,
+    logger::{self, GRAY},
+    service::gateway,
+    signal::Interrupt,
+};
+use futures_util::future::try_join_all;
+
+use super::{build::BuildOrder, serve::serve_in_order};
+
+/// Runs the watch or serve command of each project at once, in this process: each one is built
+/// once the projects it depends on are, their files are watched by a single watcher, and their
+/// logs are prefixed with the project name. With a `gateway`, they are also served behind it,
+/// their pages connecting to it for the reload channel. Returns when all of them stopped, which
+/// they do on ctrl-c.
+pub async fn run_all(
+    config: &Config,
+    command: &Commands,
+    open: bool,
+    gateway: Option<SocketAddr>,
+) -> Result<()> {
+    let names: Vec<String> = config.projects.iter().map(|p| p.name.clone()).collect();
+    logger::tag_projects(&names);
+    let _gateway = match gateway {
+        Some(addr) => Some(gateway::spawn(addr, config).await?),
+        None => None,
+    };
+    for proj in &config.projects {
+        log::info!(
+            "All running {} at {}",
+            proj.name,
+            GRAY.paint(proj.site.url().as_str())
+        );
+    }
+
+    let res = match command {
+        Commands::Watch(opts) => super::watch(&config.projects, opts, open).await,
+        Commands::Serve(opts) => {
+            let order = BuildOrder::new(&config.projects);
+            let serves = config.projects.iter().map(|proj: &Arc<_>| {
+                let serve = serve_in_order(proj, open, opts.prod, &order);
+                logger::in_project(&proj.name, serve)
+            });
+            try_join_all(serves).await.map(|_| ())
+        }
+        _ => unreachable!("only serve and watch run all the projects"),
+    };
+    if res.is_err() {
+        Interrupt::request_shutdown().await;
+    }
+    res
+}
@@ -0,0 +1,59 @@
+use std::sync::Arc;
+
+use camino::Utf8Path;
+
+use crate::{
+    compile::{profile_tables, profile_treemap, site_wasm, WasmProfile},
+    config::{AnalyzeOpts, Config, Project},
+    ext::{
+        anyhow::{anyhow, Context, Result},
+        fs,
+    },
+    logger::GRAY,
+};
+
+/// Builds the projects and profiles the size of their wasm, into an html treemap at
+/// `target/leptos-analyze/<project>.html` or the `--out` file.
+pub async fn analyze_all(conf: &Config, opts: &AnalyzeOpts) -> Result<()> {
+    for proj in &conf.projects {
+        let out = match &opts.out {
+            Some(out) if conf.projects.len() > 1 => out.join(format!("{}.html", proj.name)),
+            Some(out) => out.clone(),
+            None => proj
+                .target_dir
+                .join("leptos-analyze")
+                .join(format!("{}.html", proj.name)),
+        };
+        analyze_proj(proj, opts, &out).await?;
+    }
+    Ok(())
+}
+
+async fn analyze_proj(proj: &Arc<Project>, opts: &AnalyzeOpts, out: &Utf8Path) -> Result<()> {
+    if !super::build::build_proj(proj).await.dot()? {
+        return Err(anyhow!("Failed to build {}", proj.name));
+    }
+    let wasm = site_wasm(proj, opts.source)?;
+    let profile =
+        WasmProfile::parse(&fs::read(&wasm).await?).context(format!("Could not analyze {wasm}"))?;
+    log::info!("Analyze {} {}", proj.name, GRAY.paint(wasm.as_str()));
+    for line in profile_tables(&profile, opts.top).lines() {
+        log::info!("Analyze {line}");
+    }
+    if profile
+        .functions
+        .iter()
+        .all(|item| item.krate == "(unnamed)")
+    {
+        log::warn!(
+            "Analyze the wasm has no name section, analyze the one cargo built with --source"
+        );
+    }
+
+    if let Some(parent) = out.parent().filter(|parent| !parent.as_str().is_empty()) {
+        fs::create_dir_all(parent).await?;
+    }
+    fs::write(out, profile_treemap(&proj.name, &profile, opts.top)).await?;
+    log::info!("Analyze treemap written to {}", GRAY.paint(out.as_str()));
+    Ok(())
+}
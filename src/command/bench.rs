@@ -0,0 +1,170 @@
+use std::{io::Write, sync::Arc, time::Duration};
+
+use camino::Utf8Path;
+use libflate::gzip;
+use serde::Serialize;
+use tokio::{process::Command, time::Instant};
+
+use crate::{
+    compile::{build_cargo_front_cmd, build_cargo_server_cmd},
+    config::{BenchOpts, Config, Project},
+    ext::anyhow::{anyhow, Context, Result},
+    logger::GRAY,
+    service::serve,
+};
+
+/// The measures of the site of a project, written to `target/leptos-bench/<project>.json` so
+/// that they can be tracked across commits.
+#[derive(Debug, Default, Serialize)]
+struct SiteBench {
+    wasm_bytes: u64,
+    wasm_gzip_bytes: u64,
+    js_bytes: u64,
+    css_bytes: u64,
+    /// the server render times of the root page, in milliseconds
+    render_ms: Option<RenderTimes>,
+}
+
+#[derive(Debug, Serialize)]
+struct RenderTimes {
+    requests: usize,
+    min: f64,
+    median: f64,
+    max: f64,
+}
+
+/// Runs the cargo benches of the projects, with the bench args after a `--`, and with `--site`
+/// the benchmarks of their site.
+pub async fn bench_all(conf: &Config, opts: &BenchOpts) -> Result<()> {
+    let mut first_failed_project = None;
+
+    for proj in &conf.projects {
+        if !bench_proj(proj, &opts.bench_args).await? && first_failed_project.is_none() {
+            first_failed_project = Some(proj);
+        }
+        if opts.site {
+            bench_site(proj, opts.requests).await?;
+        }
+    }
+
+    if let Some(proj) = first_failed_project {
+        Err(anyhow!("Benches failed for {}", proj.name))
+    } else {
+        Ok(())
+    }
+}
+
+/// Runs `cargo bench` with the server (ssr) features and target dir, or for a csr project with
+/// the lib features and front target dir. Whether it succeeded.
+async fn bench_proj(proj: &Project, bench_args: &[String]) -> Result<bool> {
+    let mut command = Command::new("cargo");
+    let (envs, line) = if proj.bin.is_some() {
+        build_cargo_server_cmd("bench", proj, &mut command)
+    } else {
+        build_cargo_front_cmd("bench", false, proj, &mut command)
+    };
+    if !bench_args.is_empty() {
+        command.arg("--").args(bench_args);
+    }
+
+    let exit_status = command.status().await.dot()?;
+    log::debug!("Cargo envs: {}", GRAY.paint(envs));
+    log::info!("Cargo bench finished {}", GRAY.paint(line));
+    Ok(exit_status.success())
+}
+
+/// Builds the site and measures the size of its wasm, js and css and, with a server, the time
+/// it takes to render the root page.
+async fn bench_site(proj: &Arc<Project>, requests: usize) -> Result<()> {
+    if !super::build::build_proj(proj).await.dot()? {
+        return Err(anyhow!("Failed to build {}", proj.name));
+    }
+    let mut bench = SiteBench::default();
+    measure_sizes(&proj.site.root_relative_pkg_dir(), &mut bench)?;
+    log::info!(
+        "Bench {} wasm {} bytes ({} gzipped), js {} bytes, css {} bytes",
+        proj.name,
+        bench.wasm_bytes,
+        bench.wasm_gzip_bytes,
+        bench.js_bytes,
+        bench.css_bytes
+    );
+
+    if proj.bin.is_some() && requests > 0 {
+        let url = proj.site.local_url();
+        let times = serve::run_against(proj, render_times(&url, requests)).await?;
+        log::info!(
+            "Bench {} rendered {} in {:.2}ms (median of {requests}, min {:.2}ms, max {:.2}ms)",
+            proj.name,
+            GRAY.paint(&url),
+            times.median,
+            times.min,
+            times.max
+        );
+        bench.render_ms = Some(times);
+    }
+
+    let file = proj
+        .target_dir
+        .join("leptos-bench")
+        .join(format!("{}.json", proj.name));
+    std::fs::create_dir_all(file.parent().unwrap_or(&proj.target_dir))?;
+    std::fs::write(&file, serde_json::to_string_pretty(&bench)?)
+        .context(format!("Could not write {file}"))?;
+    log::info!("Bench written to {}", GRAY.paint(file.as_str()));
+    Ok(())
+}
+
+/// adds up the sizes of the wasm, js and css files of the pkg dir
+fn measure_sizes(pkg_dir: &Utf8Path, bench: &mut SiteBench) -> Result<()> {
+    for entry in pkg_dir
+        .read_dir_utf8()
+        .context(format!("Could not read {pkg_dir}"))?
+    {
+        let path = entry?.into_path();
+        let len = path.metadata()?.len();
+        match path.extension() {
+            Some("wasm") => {
+                let mut encoder = gzip::Encoder::new(Vec::new())?;
+                encoder.write_all(&std::fs::read(&path)?)?;
+                bench.wasm_bytes += len;
+                bench.wasm_gzip_bytes += encoder.finish().into_result()?.len() as u64;
+            }
+            Some("js") => bench.js_bytes += len,
+            Some("css") => bench.css_bytes += len,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// the times of the requests of the url, after a warm-up request
+async fn render_times(url: &str, requests: usize) -> Result<RenderTimes> {
+    let client = reqwest::Client::new();
+    let fetch = || async {
+        client
+            .get(url)
+            .send()
+            .await
+            .and_then(|resp| resp.error_for_status())
+            .context(format!("Could not fetch {url}"))?
+            .bytes()
+            .await?;
+        Ok::<_, anyhow::Error>(())
+    };
+    fetch().await?;
+    let mut times = Vec::with_capacity(requests);
+    for _ in 0..requests {
+        let start = Instant::now();
+        fetch().await?;
+        times.push(start.elapsed());
+    }
+    times.sort();
+    let ms = |time: Duration| time.as_secs_f64() * 1000.0;
+    Ok(RenderTimes {
+        requests,
+        min: ms(times[0]),
+        median: ms(times[times.len() / 2]),
+        max: ms(times[times.len() - 1]),
+    })
+}
@@ -1,6 +1,7 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
+use tokio::{sync::watch, time::Instant};
 
-use crate::ext::compress;
+use crate::ext::{compress, Progress};
 use crate::{
     compile,
     compile::ChangeSet,
@@ -9,6 +10,8 @@ use crate::{
         anyhow::{anyhow, Context, Result},
         fs,
     },
+    logger,
+    service::dashboard,
 };
 
 pub async fn build_all(conf: &Config) -> Result<()> {
@@ -28,36 +31,164 @@ pub async fn build_all(conf: &Config) -> Result<()> {
     }
 }
 
+/// The first builds of the projects run at once, with `--all`: each one is started once the
+/// projects it depends on are built, successfully or not, as it may embed their output.
+#[derive(Default)]
+pub struct BuildOrder {
+    built: HashMap<String, watch::Sender<bool>>,
+}
+
+impl BuildOrder {
+    pub fn new(projects: &[Arc<Project>]) -> Self {
+        let built = projects
+            .iter()
+            .map(|proj| (proj.name.clone(), watch::Sender::new(false)))
+            .collect();
+        Self { built }
+    }
+
+    /// waits until the projects `proj` depends on are built
+    pub async fn wait_dependencies(&self, proj: &Project) {
+        for dep in &proj.depends_on {
+            if let Some(built) = self.built.get(dep) {
+                log::debug!("Build of {} waiting for {dep}", proj.name);
+                // the sender is kept by self
+                let _ = built.subscribe().wait_for(|built| *built).await;
+            }
+        }
+    }
+
+    /// lets the projects depending on `proj` build
+    pub fn built(&self, proj: &Project) {
+        if let Some(built) = self.built.get(&proj.name) {
+            built.send_replace(true);
+        }
+    }
+}
+
 /// Build the project. Returns true if the build was successful
 pub async fn build_proj(proj: &Arc<Project>) -> Result<bool> {
     if proj.site.root_dir.exists() {
         fs::rm_dir_content(&proj.site.root_dir).await.dot()?;
     }
     let changes = ChangeSet::all_changes();
+    logger::set_project(&proj.name);
+    compile::clear_stages();
 
-    if !compile::front(proj, &changes).await.await??.is_success() {
+    // the generated css modules are included by the cargo builds
+    if !compile::css_modules(proj, &changes).await?.is_success() {
+        return Ok(false);
+    }
+    if !dashboard::track("front", compile::front(proj, &changes).await)
+        .await??
+        .is_success()
+    {
         return Ok(false);
     }
-    if !compile::assets(proj, &changes).await.await??.is_success() {
+    if !dashboard::track("assets", compile::assets(proj, &changes).await)
+        .await??
+        .is_success()
+    {
         return Ok(false);
     }
-    if !compile::style(proj, &changes).await.await??.is_success() {
+    if !dashboard::track("style", compile::style(proj, &changes).await)
+        .await??
+        .is_success()
+    {
         return Ok(false);
     }
 
+    if proj.bin.is_none() {
+        compile::csr_index(proj).await?;
+    }
+
     if proj.hash_files {
+        let start_time = Instant::now();
         compile::add_hashes_to_site(proj)?;
+        compile::record_stage("hash-files", start_time.elapsed());
+    }
+    // lists the hashed files, and gets precompressed with them
+    if proj.pwa.is_some() {
+        compile::pwa(proj).await?;
     }
 
     // it is important to do the precompression of the static files before building the
     // server to make it possible to include them as assets into the binary itself
     if proj.release && proj.precompress {
-        compress::compress_static_files(proj.site.root_dir.clone().into()).await?;
+        let start_time = Instant::now();
+        let progress = Progress::spinner("Build precompressing the site");
+        compress::compress_static_files(
+            proj.site.root_dir.clone().into(),
+            proj.source_date_epoch.map(|epoch| epoch as u32),
+        )
+        .await?;
+        progress.finish();
+        compile::record_stage("precompress", start_time.elapsed());
     }
 
-    if !compile::server(proj, &changes).await.await??.is_success() {
+    // measures the precompressed copies, if any
+    if proj.release {
+        compile::size_report(proj).await?;
+    }
+    // read by the server at runtime, or included into it
+    if proj.build_info {
+        compile::build_info(proj).await?;
+    }
+    // the server includes the site files, with their precompressed copies
+    if proj.embed_file.is_some() {
+        compile::embed_site(proj).await?;
+    }
+
+    if !dashboard::track("server", compile::server(proj, &changes).await)
+        .await??
+        .is_success()
+    {
         return Ok(false);
     }
 
+    // a wasm server only runs in its runtime, given by the bin-runner
+    if proj
+        .bin
+        .as_ref()
+        .is_some_and(|bin| bin.is_wasm() && bin.runner.is_none())
+    {
+        if proj.critical_css
+            || proj.csp_hashes
+            || !proj.prerender_routes.is_empty()
+            || proj.fallback_route.is_some()
+            || proj.sitemap_base_url.is_some()
+        {
+            log::warn!(
+                "Build skipping the steps running the wasm server of {}, which has no bin-runner",
+                proj.name
+            );
+        }
+        if proj.timings {
+            compile::write_timings_report(proj).await?;
+        }
+        return Ok(true);
+    }
+
+    // needs the server for rendering the root route
+    if proj.critical_css {
+        compile::critical_css(proj).await?;
+    }
+    if proj.csp_hashes {
+        compile::csp_hashes(proj).await?;
+    }
+    if !proj.prerender_routes.is_empty() {
+        compile::prerender_routes(proj).await?;
+    }
+    if proj.fallback_route.is_some() {
+        compile::fallback_page(proj).await?;
+    }
+    if proj.sitemap_base_url.is_some() {
+        compile::sitemap(proj).await?;
+    }
+
+    if proj.timings {
+        compile::write_timings_report(proj).await?;
+    }
+
     Ok(true)
 }
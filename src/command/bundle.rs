@@ -0,0 +1,241 @@
+use std::{fs::File, io::Write, sync::Arc};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use flate2::{write::GzEncoder, Compression};
+
+use crate::{
+    config::{BinPackage, BundleFormat, BundleOpts, Config, HashFileFormat, Project},
+    ext::{
+        anyhow::{anyhow, Context, Result},
+        fs,
+    },
+    logger::GRAY,
+};
+
+/// Builds the projects for release and packages each into `target/bundle/<project>`, or into
+/// the `--out` dir or archive.
+pub async fn bundle_all(conf: &Config, opts: &BundleOpts) -> Result<()> {
+    for proj in &conf.projects {
+        let ext = match opts.format {
+            BundleFormat::Dir => "",
+            BundleFormat::TarGz => ".tar.gz",
+            BundleFormat::Zip => ".zip",
+        };
+        let out = match &opts.out {
+            Some(out) if conf.projects.len() > 1 => out.join(format!("{}{ext}", proj.name)),
+            Some(out) => out.clone(),
+            None => proj
+                .target_dir
+                .join("bundle")
+                .join(format!("{}{ext}", proj.name)),
+        };
+        bundle_proj(proj, opts, &out).await?;
+    }
+    Ok(())
+}
+
+/// Builds the project and assembles the server binary with its hash file, the site root at
+/// `site`, a `run.sh` running the server with the env vars of the site, and a `.env.example`
+/// of the vars to override. In a dir, or archived under a `<project>` dir.
+async fn bundle_proj(proj: &Arc<Project>, opts: &BundleOpts, out: &Utf8Path) -> Result<()> {
+    let format = opts.format;
+    if !super::build::build_proj(proj).await.dot()? {
+        return Err(anyhow!("Failed to build {}", proj.name));
+    }
+
+    let dir = match format {
+        BundleFormat::Dir => out.to_path_buf(),
+        _ => proj.target_dir.join("bundle").join(&proj.name),
+    };
+    if dir.exists() {
+        fs::remove_dir_all(&dir).await?;
+    }
+    fs::create_dir_all(&dir).await?;
+    // an embedded site is served from the server binary
+    if proj.embed_file.is_none() || proj.bin.is_none() {
+        fs::copy_dir_all(&proj.site.root_dir, dir.join("site"))
+            .await
+            .context(format!("Could not copy the site to {dir}"))?;
+    }
+
+    if let Some(bin) = &proj.bin {
+        let exe = proj.working_dir.join(&bin.exe_file);
+        let exe_name = exe
+            .file_name()
+            .ok_or_else(|| anyhow!("Invalid server binary {exe}"))?;
+        fs::copy(&exe, dir.join(exe_name)).await?;
+        if proj.hash_files {
+            fs::copy(&proj.hash_file.abs, dir.join(&proj.hash_file.rel)).await?;
+            let json = proj.hash_file.json_abs();
+            if proj.hash_file.format == HashFileFormat::Json && json.exists() {
+                fs::copy(&json, dir.join(proj.hash_file.json_rel())).await?;
+            }
+        }
+        if let Some(script) = run_script(proj, exe_name) {
+            write_script(&dir.join("run.sh"), &script)?;
+        }
+        fs::write(dir.join(".env.example"), env_template(proj)).await?;
+        if opts.systemd {
+            let install_dir = opts
+                .install_dir
+                .clone()
+                .unwrap_or_else(|| Utf8Path::new("/opt").join(&proj.name));
+            let unit = systemd_unit(proj, bin, exe_name, &install_dir)?;
+            fs::write(dir.join(format!("{}.service", proj.name)), unit).await?;
+        }
+    }
+
+    match format {
+        BundleFormat::Dir => {}
+        BundleFormat::TarGz => write_tar_gz(&dir, &proj.name, out)?,
+        BundleFormat::Zip => write_zip(&dir, &proj.name, out)?,
+    }
+    log::info!(
+        "Bundle of {} written to {}",
+        proj.name,
+        GRAY.paint(out.as_str())
+    );
+    Ok(())
+}
+
+/// the script running the server from the bundle dir, with the env vars of the site as
+/// defaults that the ones of a `.env` file next to it override. A wasm server is run by its
+/// bin-runner, without which the runtime it is deployed to runs it.
+fn run_script(proj: &Project, exe_name: &str) -> Option<String> {
+    let bin = proj.bin.as_ref()?;
+    let exec = match &bin.runner {
+        Some(runner) if bin.is_wasm() => format!("{runner} ./{exe_name}"),
+        None if bin.is_wasm() => return None,
+        _ => format!("./{exe_name}"),
+    };
+    let mut script = format!(
+        "#!/bin/sh\n# generated by cargo-leptos: runs the server of {} with the site next to it\n\
+         cd \"$(dirname \"$0\")\" || exit 1\nset -a\n[ -f .env ] && . ./.env\n",
+        proj.name
+    );
+    for (name, val) in proj.bundle_envs() {
+        script.push_str(&format!(": \"${{{name}:={}}}\"\n", shell_quote(&val)));
+    }
+    script.push_str(&format!("set +a\nexec {exec} \"$@\"\n"));
+    Some(script)
+}
+
+/// The systemd service unit running the server from the install dir, with the env vars of the
+/// site, those of its `.env` file and a restart on failure.
+fn systemd_unit(
+    proj: &Project,
+    bin: &BinPackage,
+    exe_name: &str,
+    install_dir: &Utf8Path,
+) -> Result<String> {
+    let name = &proj.name;
+    let exec = match &bin.runner {
+        Some(runner) if bin.is_wasm() => format!("{runner} {install_dir}/{exe_name}"),
+        None if bin.is_wasm() => {
+            return Err(anyhow!(
+                "The wasm server of {name} needs a bin-runner for the systemd unit"
+            ))
+        }
+        _ => format!("{install_dir}/{exe_name}"),
+    };
+    let mut unit = format!(
+        "# generated by cargo-leptos for the project {name}\n[Unit]\nDescription={name}\n\
+         After=network-online.target\nWants=network-online.target\n\n[Service]\nType=simple\n\
+         WorkingDirectory={install_dir}\n"
+    );
+    for (var, val) in proj.bundle_envs() {
+        // `%` starts the specifiers of systemd
+        let val = val
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('%', "%%");
+        unit.push_str(&format!("Environment=\"{var}={val}\"\n"));
+    }
+    unit.push_str(&format!(
+        "EnvironmentFile=-{install_dir}/.env\nExecStart={exec}\nRestart=on-failure\n\
+         RestartSec=5\n\n[Install]\nWantedBy=multi-user.target\n"
+    ));
+    Ok(unit)
+}
+
+/// the `.env` vars of the bundle, with their defaults commented out, and the ones of the
+/// server-env table to set
+fn env_template(proj: &Project) -> String {
+    let mut template = String::from("# the env vars of the server, copied to .env for run.sh\n");
+    for (name, val) in proj.bundle_envs() {
+        template.push_str(&format!("# {name}={}\n", shell_quote(&val)));
+    }
+    for name in proj.server_env.keys() {
+        template.push_str(&format!("{name}=\n"));
+    }
+    template
+}
+
+fn shell_quote(val: &str) -> String {
+    if val
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "-_./:,=@".contains(c))
+    {
+        val.to_string()
+    } else {
+        format!("'{}'", val.replace('\'', r"'\''"))
+    }
+}
+
+fn write_script(path: &Utf8Path, contents: &str) -> Result<()> {
+    std::fs::write(path, contents).context(format!("Could not write {path}"))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(())
+}
+
+fn create_archive(out: &Utf8Path) -> Result<File> {
+    if let Some(parent) = out.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    File::create(out).context(format!("Could not create {out}"))
+}
+
+fn write_tar_gz(dir: &Utf8Path, name: &str, out: &Utf8Path) -> Result<()> {
+    let encoder = GzEncoder::new(create_archive(out)?, Compression::default());
+    let mut archive = tar::Builder::new(encoder);
+    archive.append_dir_all(name, dir)?;
+    archive.into_inner()?.finish()?;
+    Ok(())
+}
+
+fn write_zip(dir: &Utf8Path, name: &str, out: &Utf8Path) -> Result<()> {
+    let mut archive = zip::ZipWriter::new(create_archive(out)?);
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(current) = dirs.pop() {
+        for entry in current.read_dir_utf8()? {
+            let path = entry?.into_path();
+            let rel = Utf8PathBuf::from(name).join(path.strip_prefix(dir)?);
+            // zip entries are separated by slashes on all platforms
+            let rel = rel
+                .components()
+                .map(|c| c.as_str())
+                .collect::<Vec<_>>()
+                .join("/");
+            if path.is_dir() {
+                archive.add_directory(rel, zip::write::SimpleFileOptions::default())?;
+                dirs.push(path);
+            } else {
+                #[allow(unused_mut)]
+                let mut options = zip::write::SimpleFileOptions::default();
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::PermissionsExt;
+                    options = options.unix_permissions(path.metadata()?.permissions().mode());
+                }
+                archive.start_file(rel, options)?;
+                archive.write_all(&std::fs::read(&path)?)?;
+            }
+        }
+    }
+    archive.finish()?;
+    Ok(())
+}
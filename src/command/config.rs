@@ -0,0 +1,29 @@
+use std::io::Write;
+
+use crate::{config::schema, ext::anyhow::Result};
+use clap::{Args, Subcommand};
+
+#[derive(Clone, Debug, Args, PartialEq, Eq)]
+pub struct ConfigCommand {
+    #[command(subcommand)]
+    pub command: ConfigSubcommand,
+}
+
+#[derive(Clone, Debug, Subcommand, PartialEq, Eq)]
+pub enum ConfigSubcommand {
+    /// Print the JSON Schema of the leptos metadata, for editors to validate and complete the
+    /// `[package.metadata.leptos]` section and Leptos.toml files.
+    Schema,
+}
+
+impl ConfigCommand {
+    pub fn run(&self) -> Result<()> {
+        match self.command {
+            ConfigSubcommand::Schema => {
+                let json = serde_json::to_string_pretty(&schema())?;
+                writeln!(std::io::stdout(), "{json}")?;
+            }
+        }
+        Ok(())
+    }
+}
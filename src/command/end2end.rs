@@ -1,40 +1,56 @@
 use std::sync::Arc;
 
 use anyhow::bail;
-use camino::Utf8Path;
+use camino::{Utf8Path, Utf8PathBuf};
 use tokio::process::Command;
 
-use crate::config::{Config, Project};
-use crate::ext::anyhow::{anyhow, Context, Result};
+use crate::config::{Config, EndToEndOpts, Project};
+use crate::ext::anyhow::{anyhow, ensure, Context, Result};
+use crate::logger::GRAY;
 use crate::service::serve;
 use crate::signal::Interrupt;
 
-pub async fn end2end_all(conf: &Config) -> Result<()> {
+/// Runs the end2end tests of the projects, with the `cmd_args` appended to their command and,
+/// with `--install`, the Playwright setup first.
+pub async fn end2end_all(conf: &Config, opts: &EndToEndOpts) -> Result<()> {
     for proj in &conf.projects {
-        end2end_proj(proj).await?;
+        if let (true, Some(e2e)) = (opts.install, &proj.end2end) {
+            install_playwright(&e2e.dir).await.dot()?;
+        }
+        end2end_proj(proj, &opts.cmd_args).await?;
     }
     Ok(())
 }
 
-pub async fn end2end_proj(proj: &Arc<Project>) -> Result<()> {
+/// Builds the project, starts its server and once it is ready runs the end2end command with
+/// the url of the site in `E2E_BASE_URL`, and its addresses in the `LEPTOS_` env vars. The
+/// server is stopped afterwards, also when the command fails or on ctrl-c.
+pub async fn end2end_proj(proj: &Arc<Project>, cmd_args: &[String]) -> Result<()> {
     if let Some(e2e) = &proj.end2end {
         if !super::build::build_proj(proj).await.dot()? {
             return Ok(());
         }
 
-        let server = serve::spawn(proj).await;
-        try_run(&e2e.cmd, &e2e.dir)
+        if proj.release {
+            log::info!("End2End testing the release build");
+        }
+        let envs = proj.end2end_envs();
+        serve::run_against(proj, try_run(&e2e.cmd, cmd_args, &e2e.dir, &envs))
             .await
             .context(format!("running: {}", &e2e.cmd))?;
-        Interrupt::request_shutdown().await;
-        server.await.dot()??;
+        log::info!("End2End passed {}", GRAY.paint(&e2e.cmd));
     } else {
         log::info!("end2end the Crate.toml package.metadata.leptos.end2end_cmd parameter not set")
     }
     Ok(())
 }
 
-async fn try_run(cmd: &str, dir: &Utf8Path) -> Result<()> {
+async fn try_run(
+    cmd: &str,
+    cmd_args: &[String],
+    dir: &Utf8Path,
+    envs: &[(&str, String)],
+) -> Result<()> {
     let mut parts = cmd.split(' ');
     let exe = parts
         .next()
@@ -42,17 +58,29 @@ async fn try_run(cmd: &str, dir: &Utf8Path) -> Result<()> {
 
     let args = parts.collect::<Vec<_>>();
 
-    log::trace!("End2End running {cmd:?}");
+    log::trace!("End2End running {cmd:?} {cmd_args:?}");
+    log::debug!(
+        "End2End envs: {}",
+        GRAY.paint(
+            envs.iter()
+                .map(|(name, val)| format!("{name}={val}"))
+                .collect::<Vec<_>>()
+                .join(" ")
+        )
+    );
     let mut process = Command::new(exe)
         .args(args)
+        .args(cmd_args)
         .current_dir(dir)
+        .envs(envs.to_vec())
+        .kill_on_drop(true)
         .spawn()
         .context(format!("Could not spawn command {cmd:?}"))?;
 
-    let mut int = Interrupt::subscribe_any();
+    let mut int = Interrupt::subscribe_shutdown();
 
     tokio::select! {
-          _ = int.recv() => Ok(()),
+          _ = int.recv() => bail!("Interrupted"),
           result = process.wait() => {
             let status = result?;
             if !status.success() {
@@ -62,3 +90,92 @@ async fn try_run(cmd: &str, dir: &Utf8Path) -> Result<()> {
         }
     }
 }
+
+/// the marker of the installs, with the hash of the package-lock.json they were done for
+const INSTALL_MARKER: &str = ".cargo-leptos-installed";
+
+/// Installs the npm packages of a Playwright end2end dir with `npm ci`, and its browsers with
+/// `npx playwright install --with-deps`. Each is skipped when it was done for the same
+/// package-lock.json, as recorded by a marker in `node_modules` and in the browsers dir of
+/// Playwright, so that they aren't redone when cached by the CI.
+async fn install_playwright(dir: &Utf8Path) -> Result<()> {
+    if !is_playwright(dir) {
+        log::warn!(
+            "End2End {} isn't a Playwright project, nothing to install",
+            GRAY.paint(dir.as_str())
+        );
+        return Ok(());
+    }
+    let lock = dir.join("package-lock.json");
+    ensure!(
+        lock.is_file(),
+        "The Playwright install runs npm ci, which needs a package-lock.json in {dir}"
+    );
+    let hash = format!("{:x}", seahash::hash(&std::fs::read(&lock)?));
+    let npm = which::which("npm").context("The Playwright install needs npm")?;
+    let npx = which::which("npx").context("The Playwright install needs npx")?;
+
+    let packages = dir.join("node_modules").join(INSTALL_MARKER);
+    if is_marked(&packages, &hash) {
+        log::debug!("End2End npm packages already installed");
+    } else {
+        run_install(dir, Command::new(&npm).arg("ci")).await?;
+        mark(&packages, &hash)?;
+    }
+
+    let browsers = match std::env::var("PLAYWRIGHT_BROWSERS_PATH") {
+        // the browsers are in node_modules then
+        Ok(path) if path == "0" => dir.join("node_modules").join("playwright-core"),
+        Ok(path) => Utf8PathBuf::from(path),
+        Err(_) => Utf8PathBuf::from_path_buf(
+            dirs::cache_dir()
+                .ok_or_else(|| anyhow!("Cache directory does not exist"))?
+                .join("ms-playwright"),
+        )
+        .map_err(|path| anyhow!("Invalid cache dir {path:?}"))?,
+    };
+    let browsers = browsers.join(INSTALL_MARKER);
+    if is_marked(&browsers, &hash) {
+        log::debug!("End2End Playwright browsers already installed");
+    } else {
+        run_install(
+            dir,
+            Command::new(&npx).args(["playwright", "install", "--with-deps"]),
+        )
+        .await?;
+        mark(&browsers, &hash)?;
+    }
+    Ok(())
+}
+
+/// whether the dir has a Playwright config, or the Playwright test runner in its package.json
+fn is_playwright(dir: &Utf8Path) -> bool {
+    ["ts", "js", "mjs", "cjs"]
+        .iter()
+        .any(|ext| dir.join(format!("playwright.config.{ext}")).is_file())
+        || std::fs::read_to_string(dir.join("package.json"))
+            .is_ok_and(|package| package.contains("@playwright/test"))
+}
+
+fn is_marked(marker: &Utf8Path, hash: &str) -> bool {
+    std::fs::read_to_string(marker).is_ok_and(|marked| marked.trim() == hash)
+}
+
+fn mark(marker: &Utf8Path, hash: &str) -> Result<()> {
+    if let Some(parent) = marker.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(marker, hash).context(format!("Could not write {marker}"))
+}
+
+async fn run_install(dir: &Utf8Path, cmd: &mut Command) -> Result<()> {
+    log::info!("End2End running {:?}", cmd.as_std());
+    let status = cmd
+        .current_dir(dir)
+        .kill_on_drop(true)
+        .status()
+        .await
+        .context("Could not run the Playwright install")?;
+    ensure!(status.success(), "The install terminated with {status}");
+    Ok(())
+}
@@ -0,0 +1,61 @@
+use std::sync::Arc;
+
+use camino::Utf8Path;
+
+use crate::{
+    compile,
+    config::{Config, Project},
+    ext::{
+        anyhow::{anyhow, bail, Context, Result},
+        fs,
+    },
+    logger::GRAY,
+};
+
+/// Exports the projects as static sites, into the `out` dir instead of their export-dir when
+/// given (in a dir per project when there are several).
+pub async fn export_all(conf: &Config, out: Option<&Utf8Path>) -> Result<()> {
+    for proj in &conf.projects {
+        let dir = match out {
+            Some(out) if conf.projects.len() > 1 => out.join(&proj.name),
+            Some(out) => out.to_path_buf(),
+            None => proj.export_dir.clone(),
+        };
+        export_proj(proj, &dir).await?;
+    }
+    Ok(())
+}
+
+/// Builds the project and writes a static site into the dir: the files of the site root and the
+/// server rendered pages of the export-routes, and of the pages they link to, with the files of
+/// the static hosts when enabled.
+async fn export_proj(proj: &Arc<Project>, dir: &Utf8Path) -> Result<()> {
+    if proj.bin.is_none() {
+        bail!(
+            "The csr project {} is a static site already, deploy its site root {}",
+            proj.name,
+            proj.site.root_dir
+        );
+    }
+    if !super::build::build_proj(proj).await.dot()? {
+        return Err(anyhow!("Failed to build {}", proj.name));
+    }
+
+    if dir.exists() {
+        fs::remove_dir_all(dir).await?;
+    }
+    fs::copy_dir_all(&proj.site.root_dir, dir)
+        .await
+        .context(format!("Could not copy the site to {dir}"))?;
+
+    let pages = compile::prerender(proj, &proj.export_routes, dir, |_| true).await?;
+    if proj.static_host_files {
+        compile::write_static_host_files(proj, dir).await?;
+    }
+    log::info!(
+        "Export wrote {} pages to {}",
+        pages.len(),
+        GRAY.paint(dir.as_str())
+    );
+    Ok(())
+}
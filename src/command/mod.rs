@@ -1,13 +1,29 @@
+mod all;
+mod analyze;
+mod bench;
 mod build;
+mod bundle;
+mod config;
 mod end2end;
+mod export;
 mod new;
+mod proxy_config;
+mod routes;
 mod serve;
 mod test;
 pub mod watch;
 
+pub use all::run_all;
+pub use analyze::analyze_all;
+pub use bench::bench_all;
 pub use build::build_all;
+pub use bundle::bundle_all;
+pub use config::ConfigCommand;
 pub use end2end::end2end_all;
+pub use export::export_all;
 pub use new::NewCommand;
+pub use proxy_config::proxy_config_all;
+pub use routes::routes_all;
 pub use serve::serve;
-pub use test::test_all;
+pub use test::{coverage_all, test_all};
 pub use watch::watch;
@@ -0,0 +1,32 @@
+use crate::{
+    compile::{caddy_config, nginx_config},
+    config::{Config, ProxyConfigOpts},
+    ext::anyhow::{Context, Result},
+    logger::GRAY,
+};
+
+/// Writes the `nginx.conf` and `Caddyfile` snippets of the projects to
+/// `target/leptos-proxy/<project>`, or to the `--out` dir.
+pub fn proxy_config_all(conf: &Config, opts: &ProxyConfigOpts) -> Result<()> {
+    for proj in &conf.projects {
+        let dir = match &opts.out {
+            Some(out) if conf.projects.len() > 1 => out.join(&proj.name),
+            Some(out) => out.clone(),
+            None => proj.target_dir.join("leptos-proxy").join(&proj.name),
+        };
+        let root = match &opts.root {
+            Some(root) => root.clone(),
+            None => proj.working_dir.join(&proj.site.root_dir),
+        };
+        let root = root.as_str().trim_end_matches('/');
+        std::fs::create_dir_all(&dir).context(format!("Could not create {dir}"))?;
+        std::fs::write(dir.join("nginx.conf"), nginx_config(proj, root.into()))?;
+        std::fs::write(dir.join("Caddyfile"), caddy_config(proj, root.into()))?;
+        log::info!(
+            "Proxy config snippets of {} written to {}",
+            proj.name,
+            GRAY.paint(dir.as_str())
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,96 @@
+use std::process::Stdio;
+
+use cargo_metadata::Metadata;
+use tokio::process::Command;
+
+use crate::{
+    compile::{parse_route_list, routes_harness, RouteList},
+    config::{Config, Project, RoutesFormat, RoutesOpts},
+    ext::{
+        anyhow::{bail, ensure, Context, Result},
+        MetadataExt,
+    },
+    logger::GRAY,
+};
+
+/// Writes the route lists of the projects to `target/leptos-routes/<project>.json`, or to the
+/// `--out` file, or with `--format text` prints them.
+pub async fn routes_all(conf: &Config, opts: &RoutesOpts) -> Result<()> {
+    for proj in &conf.projects {
+        let list = route_list(proj).await?;
+        match opts.format {
+            RoutesFormat::Text => {
+                for route in &list.routes {
+                    println!("{} {}", route.methods.join(","), route.path);
+                }
+                for server_fn in &list.server_fns {
+                    println!("{} {} (server fn)", server_fn.method, server_fn.path);
+                }
+            }
+            RoutesFormat::Json => {
+                let file = match &opts.out {
+                    Some(out) if conf.projects.len() > 1 => out.join(format!("{}.json", proj.name)),
+                    Some(out) => out.clone(),
+                    None => proj
+                        .target_dir
+                        .join("leptos-routes")
+                        .join(format!("{}.json", proj.name)),
+                };
+                if let Some(parent) = file.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(&file, serde_json::to_string_pretty(&list)?)
+                    .context(format!("Could not write {file}"))?;
+                log::info!(
+                    "Routes wrote {} routes and {} server fns to {}",
+                    list.routes.len(),
+                    list.server_fns.len(),
+                    GRAY.paint(file.as_str())
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Compiles and runs the harness printing the route list of the project, in
+/// `target/leptos-routes/<project>`, with the lock file of the workspace.
+async fn route_list(proj: &Project) -> Result<RouteList> {
+    let Some(bin) = &proj.bin else {
+        bail!("The csr project {} has no server routes", proj.name);
+    };
+    let metadata = Metadata::load_cleaned(&bin.abs_dir.join("Cargo.toml")).dot()?;
+    let (manifest, main) = routes_harness(proj, &metadata)?;
+
+    let dir = proj.target_dir.join("leptos-routes").join(&proj.name);
+    std::fs::create_dir_all(dir.join("src"))?;
+    std::fs::write(dir.join("Cargo.toml"), manifest)?;
+    std::fs::write(dir.join("src").join("main.rs"), main)?;
+    let lock = metadata.workspace_root.join("Cargo.lock");
+    if lock.exists() {
+        std::fs::copy(&lock, dir.join("Cargo.lock"))?;
+    }
+
+    log::info!(
+        "Routes generating the route list of {} with {}",
+        proj.name,
+        GRAY.paint(dir.as_str())
+    );
+    let output = Command::new("cargo")
+        .arg("run")
+        .arg("--quiet")
+        .arg(format!("--manifest-path={}", dir.join("Cargo.toml")))
+        .env("CARGO_TARGET_DIR", &proj.target_dir)
+        .envs(proj.to_envs())
+        .stderr(Stdio::inherit())
+        .output()
+        .await
+        .context("Could not run cargo")?;
+    ensure!(
+        output.status.success(),
+        "The route list harness of {} failed with {}",
+        proj.name,
+        output.status
+    );
+    Ok(parse_route_list(&String::from_utf8_lossy(&output.stdout)))
+}
@@ -1,14 +1,63 @@
 use std::sync::Arc;
 
+use super::build::BuildOrder;
 use crate::config::Project;
-use crate::ext::anyhow::{Context, Result};
-use crate::service::serve;
+use crate::ext::anyhow::{ensure, Context, Result};
+use crate::service::{browser, lan, relay, serve, static_server};
 
-pub async fn serve(proj: &Arc<Project>) -> Result<()> {
-    if !super::build::build_proj(proj).await.dot()? {
-        return Ok(());
+/// Builds and serves the project. With `prod`, serves the last release build as it is instead.
+pub async fn serve(proj: &Arc<Project>, open: bool, prod: bool) -> Result<()> {
+    serve_in_order(proj, open, prod, &BuildOrder::default()).await
+}
+
+/// Serves the project as [serve] does, building it once the projects it depends on are built.
+pub async fn serve_in_order(
+    proj: &Arc<Project>,
+    open: bool,
+    prod: bool,
+    order: &BuildOrder,
+) -> Result<()> {
+    if prod {
+        ensure_built(proj)?;
+        log::info!("Serve previewing the release build");
+    } else {
+        order.wait_dependencies(proj).await;
+        let built = super::build::build_proj(proj).await.dot()?;
+        order.built(proj);
+        if !built {
+            return Ok(());
+        }
     }
-    let server = serve::spawn_oneshot(proj).await;
+    let server = if proj.bin.is_some() {
+        serve::spawn_oneshot(proj).await
+    } else {
+        static_server::spawn(proj).await
+    };
+    // terminates the https connections, with --tls
+    let _relay = if proj.site.relay {
+        Some(relay::spawn(proj).await?)
+    } else {
+        None
+    };
+    lan::announce(&proj.site);
+    let _browser = open.then(|| browser::spawn_open(proj, false));
     server.await??;
     Ok(())
 }
+
+fn ensure_built(proj: &Project) -> Result<()> {
+    let hint = "Run `cargo leptos build --release` first";
+    ensure!(
+        proj.site.root_dir.is_dir(),
+        "The site {} isn't built. {hint}",
+        proj.site.root_dir
+    );
+    if let Some(bin) = &proj.bin {
+        ensure!(
+            bin.exe_file.is_file(),
+            "The server {} isn't built. {hint}",
+            bin.exe_file
+        );
+    }
+    Ok(())
+}
@@ -1,13 +1,144 @@
-use crate::compile::{front_cargo_process, server_cargo_process};
+use std::fmt::Display;
+
+use crate::compile::{
+    build_cargo_coverage_cmd, build_cargo_front_cmd, build_cargo_lib_ssr_cmd,
+    build_cargo_server_cmd, front_cargo_process, lib_ssr_cargo_process, server_cargo_process,
+    wasm_test_cargo_process,
+};
 use crate::config::{Config, Project};
-use crate::ext::anyhow::{anyhow, Context, Result};
+use crate::ext::anyhow::{anyhow, bail, ensure, Context, Result};
+use crate::ext::fs;
+use crate::ext::sync::{wait_interruptible, CommandResult};
 use crate::logger::GRAY;
+use crate::signal::{Interrupt, Outcome};
+use tokio::process::{Child, Command};
+
+/// Tests the projects, with `wasm` in a headless browser too.
+pub async fn test_all(conf: &Config, wasm: bool) -> Result<()> {
+    let mut first_failed_project = None;
+
+    for proj in &conf.projects {
+        if !test_proj(proj, wasm).await? && first_failed_project.is_none() {
+            first_failed_project = Some(proj);
+        }
+    }
+
+    if let Some(proj) = first_failed_project {
+        Err(anyhow!("Tests failed for {}", proj.name))
+    } else {
+        Ok(())
+    }
+}
+
+/// The test runs of a project, so that the code behind both feature sets is tested: the server
+/// with the bin (ssr) features, the lib package with them too when it isn't the bin package, and
+/// the front, natively, with the lib (hydrate) features. With `--wasm`, the front also for wasm32
+/// in a headless browser.
+#[derive(Clone, Copy)]
+enum TestRun {
+    Server,
+    LibSsr,
+    Front,
+    Wasm,
+}
+
+impl TestRun {
+    fn of(proj: &Project, wasm: bool) -> Vec<Self> {
+        let mut runs = Vec::new();
+        if proj.bin.is_some() {
+            runs.push(Self::Server);
+        }
+        if proj.lib.ssr_features.is_some() {
+            runs.push(Self::LibSsr);
+        }
+        runs.push(Self::Front);
+        if wasm {
+            runs.push(Self::Wasm);
+        }
+        runs
+    }
+
+    async fn process(self, proj: &Project) -> Result<(String, String, Child)> {
+        match self {
+            Self::Server => server_cargo_process("test", proj),
+            Self::LibSsr => lib_ssr_cargo_process("test", proj),
+            Self::Front => front_cargo_process("test", false, proj),
+            Self::Wasm => wasm_test_cargo_process(proj).await,
+        }
+    }
+
+    /// the cargo command of the run, without spawning it
+    fn cargo_cmd(self, proj: &Project, command: &mut Command) -> Result<()> {
+        match self {
+            Self::Server => {
+                build_cargo_server_cmd("test", proj, command);
+            }
+            Self::LibSsr => {
+                build_cargo_lib_ssr_cmd("test", proj, command)?;
+            }
+            Self::Front => {
+                build_cargo_front_cmd("test", false, proj, command);
+            }
+            Self::Wasm => bail!("The coverage of the wasm tests isn't supported"),
+        }
+        Ok(())
+    }
+}
+
+impl Display for TestRun {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Server => write!(f, "server"),
+            Self::LibSsr => write!(f, "lib ssr"),
+            Self::Front => write!(f, "front"),
+            Self::Wasm => write!(f, "wasm"),
+        }
+    }
+}
+
+pub async fn test_proj(proj: &Project, wasm: bool) -> Result<bool> {
+    let mut passed = true;
+    for run in TestRun::of(proj, wasm) {
+        let (envs, line, mut proc) = run.process(proj).await.dot()?;
 
-pub async fn test_all(conf: &Config) -> Result<()> {
+        let exit_status = proc.wait().await.dot()?;
+        log::debug!("Cargo envs: {}", GRAY.paint(envs));
+        log::info!("Cargo {run} tests finished {}", GRAY.paint(line));
+        passed &= exit_status.success();
+    }
+    Ok(passed)
+}
+
+/// Runs the server and front tests after a rebuild of the watch loop, stopping them when
+/// the sources change again. Whether they passed.
+pub async fn watch_test_proj(proj: &Project) -> Result<Outcome<bool>> {
+    let mut passed = true;
+    for run in TestRun::of(proj, false) {
+        let (envs, line, proc) = run.process(proj).await.dot()?;
+        log::debug!("Cargo envs: {}", GRAY.paint(envs));
+        passed &= match wait_interruptible("Cargo", proc, Interrupt::subscribe_any(proj)).await? {
+            CommandResult::Success(()) => true,
+            CommandResult::Failure(()) => false,
+            CommandResult::Interrupted => return Ok(Outcome::Stopped),
+        };
+        log::info!("Cargo {run} tests finished {}", GRAY.paint(line));
+    }
+    Ok(Outcome::Success(passed))
+}
+
+/// Measures the coverage of the tests of the projects with cargo-llvm-cov: the server (ssr) runs
+/// and, with `hydrate`, the native front run, merged in the lcov report of
+/// `target/coverage/<project>/lcov.info`, with an HTML one in its `html` dir with `html`.
+pub async fn coverage_all(conf: &Config, hydrate: bool, html: bool) -> Result<()> {
+    if which::which("cargo-llvm-cov").is_err() {
+        bail!(
+            "The coverage requires cargo-llvm-cov. Install it with: cargo install cargo-llvm-cov"
+        );
+    }
     let mut first_failed_project = None;
 
     for proj in &conf.projects {
-        if !test_proj(proj).await? && first_failed_project.is_none() {
+        if !coverage_proj(proj, hydrate, html).await? && first_failed_project.is_none() {
             first_failed_project = Some(proj);
         }
     }
@@ -19,18 +150,55 @@ pub async fn test_all(conf: &Config) -> Result<()> {
     }
 }
 
-pub async fn test_proj(proj: &Project) -> Result<bool> {
-    let (envs, line, mut proc) = server_cargo_process("test", proj).dot()?;
+async fn coverage_proj(proj: &Project, hydrate: bool, html: bool) -> Result<bool> {
+    // or the profiles of the previous runs would be merged in
+    llvm_cov(&["clean", "--workspace"]).await?;
 
-    let server_exit_status = proc.wait().await.dot()?;
-    log::debug!("Cargo envs: {}", GRAY.paint(envs));
-    log::info!("Cargo server tests finished {}", GRAY.paint(line));
+    // a csr project only has the front run
+    let runs = TestRun::of(proj, false)
+        .into_iter()
+        .filter(|run| hydrate || proj.bin.is_none() || !matches!(run, TestRun::Front));
+    let mut passed = true;
+    for run in runs {
+        let mut test = Command::new("cargo");
+        run.cargo_cmd(proj, &mut test)?;
+        let mut command = Command::new("cargo");
+        let line = build_cargo_coverage_cmd(&test, &mut command);
 
-    let (envs, line, mut proc) = front_cargo_process("test", false, proj).dot()?;
+        let exit_status = command.status().await.dot()?;
+        log::info!("Cargo {run} coverage finished {}", GRAY.paint(line));
+        passed &= exit_status.success();
+    }
+    if !passed {
+        return Ok(false);
+    }
 
-    let front_exit_status = proc.wait().await.dot()?;
-    log::debug!("Cargo envs: {}", GRAY.paint(envs));
-    log::info!("Cargo front tests finished {}", GRAY.paint(line));
+    let dir = proj.target_dir.join("coverage").join(&proj.name);
+    fs::create_dir_all(&dir).await?;
+    let lcov = dir.join("lcov.info");
+    llvm_cov(&["report", "--lcov", &format!("--output-path={lcov}")]).await?;
+    log::info!("Coverage lcov report {}", GRAY.paint(lcov.as_str()));
+    if html {
+        llvm_cov(&["report", "--html", &format!("--output-dir={dir}")]).await?;
+        log::info!(
+            "Coverage html report {}",
+            GRAY.paint(dir.join("html").join("index.html").as_str())
+        );
+    }
+    Ok(true)
+}
 
-    Ok(server_exit_status.success() && front_exit_status.success())
+async fn llvm_cov(args: &[&str]) -> Result<()> {
+    let status = Command::new("cargo")
+        .arg("llvm-cov")
+        .args(args)
+        .status()
+        .await
+        .dot()?;
+    ensure!(
+        status.success(),
+        "cargo llvm-cov {} failed with {status}",
+        args.join(" ")
+    );
+    Ok(())
 }
@@ -1,87 +1,261 @@
-use super::build::build_proj;
+use super::{
+    build::{build_proj, BuildOrder},
+    test::watch_test_proj,
+};
 use crate::{
-    compile::{self},
-    config::Project,
-    ext::anyhow::Context,
-    service,
+    compile::{self, Change},
+    config::{Project, WatchOpts},
+    ext::anyhow::{ensure, Context},
+    logger,
+    service::{self, dashboard, notification},
     signal::{Interrupt, Outcome, Product, ProductSet, ReloadSignal, ServerRestart},
 };
 use anyhow::Result;
+use futures_util::future::try_join_all;
 use leptos_hot_reload::ViewMacros;
 use std::sync::Arc;
-use tokio::try_join;
+use tokio::{
+    sync::broadcast::{self, error::RecvError},
+    time::timeout,
+    try_join,
+};
 
-pub async fn watch(proj: &Arc<Project>) -> Result<()> {
-    // even if the build fails, we continue
-    build_proj(proj).await?;
+/// Builds, serves and rebuilds the projects on change, side by side when several are watched:
+/// each one is first built once the projects it depends on are, their files are watched by a
+/// single watcher, and each one is rebuilt on its own changes. With `--test`, the tests are run
+/// after each rebuild, with `--ui` the dashboard is shown instead of the logs and with
+/// `--notify` the ends of the builds are notified. With `open`, the browser is opened once a
+/// build succeeded and the site is served.
+pub async fn watch(projects: &[Arc<Project>], opts: &WatchOpts, open: bool) -> Result<()> {
+    ensure!(
+        !opts.ui || projects.len() == 1,
+        "The dashboard of --ui shows a single project. Select it with --project, without --all"
+    );
+    let _dashboard = opts.ui.then(|| dashboard::spawn(&projects[0])).flatten();
+    if opts.notify {
+        notification::enable();
+    }
+    let order = BuildOrder::new(projects);
+    let first_builds = projects.iter().map(|proj| {
+        let build = async {
+            order.wait_dependencies(proj).await;
+            let built = first_build(proj).await;
+            order.built(proj);
+            built
+        };
+        logger::in_project(&proj.name, build)
+    });
+    let built = try_join_all(first_builds).await?;
 
     // but if ctrl-c is pressed, we stop
     if Interrupt::is_shutdown_requested().await {
         return Ok(());
     }
+    let Some(built) = built.into_iter().collect::<Option<Vec<_>>>() else {
+        return Ok(());
+    };
 
-    let view_macros = if proj.hot_reload {
-        // build initial set of view macros for patching
-        let view_macros = ViewMacros::new();
-        view_macros.update_from_paths(&proj.lib.src_paths)?;
-        Some(view_macros)
-    } else {
-        None
+    let _watch = service::notify::spawn(projects).await?;
+    #[cfg(unix)]
+    let _rebuild_signal = Interrupt::run_rebuild_signal_monitor(projects);
+
+    let runs = projects.iter().zip(built).map(|(proj, built)| {
+        logger::in_project(&proj.name, serve_and_rebuild(proj, built, opts.test, open))
+    });
+    let res = try_join_all(runs).await;
+    if res.is_err() {
+        Interrupt::request_shutdown().await;
+    }
+    res.map(|_| ())
+}
+
+/// The first build, which the watch goes on after even when it fails. None when stopped.
+async fn first_build(proj: &Arc<Project>) -> Result<Option<bool>> {
+    dashboard::build_started();
+    let built = match build_hook(proj, "pre-build", &proj.hooks.pre_build).await? {
+        Outcome::Success(()) => {
+            let built = build_proj(proj).await?;
+            dashboard::build_finished(built);
+            match built {
+                true => notification::build_succeeded(proj),
+                false => notification::build_failed(proj, None),
+            }
+            built
+        }
+        Outcome::Stopped => return Ok(None),
+        // the build is skipped, as in the rebuilds
+        Outcome::Failed => {
+            build_failed(proj).await;
+            false
+        }
     };
+    if built {
+        build_hook(proj, "post-build", &proj.hooks.post_build).await?;
+    }
+    Ok(Some(built))
+}
 
-    let _watch = service::notify::spawn(proj).await?;
-    if let Some(view_macros) = view_macros {
-        let _patch = service::patch::spawn(proj, &view_macros).await?;
+/// Serves the project and rebuilds it on its changes, until cargo-leptos shuts down.
+async fn serve_and_rebuild(proj: &Arc<Project>, built: bool, test: bool, open: bool) -> Result<()> {
+    if proj.hot_reload {
+        // build initial set of view macros for patching
+        let view_macros = ViewMacros::new();
+        view_macros.update_from_paths(&proj.lib.front_src_paths)?;
+        service::patch::spawn(proj, &view_macros).await?;
     }
 
-    service::serve::spawn(proj).await;
+    if proj.bin.is_some() {
+        service::serve::spawn(proj).await;
+    } else {
+        service::static_server::spawn(proj).await;
+    }
     service::reload::spawn(proj).await;
+    let _relay = if proj.site.relay {
+        Some(service::relay::spawn(proj).await?)
+    } else {
+        None
+    };
+    let _post_reload = service::hooks::spawn_post_reload(proj);
+    service::lan::announce(&proj.site);
+    let _browser = open.then(|| service::browser::spawn_open(proj, !built));
 
-    let res = run_loop(proj).await;
-    if res.is_err() {
-        Interrupt::request_shutdown().await;
-    }
-    res
+    run_loop(proj, test).await
 }
 
-pub async fn run_loop(proj: &Arc<Project>) -> Result<()> {
-    let mut int = Interrupt::subscribe_any();
+pub async fn run_loop(proj: &Arc<Project>, test: bool) -> Result<()> {
+    let mut int = Interrupt::subscribe_any(proj);
     loop {
         log::debug!("Watch waiting for changes");
 
         int.recv().await.dot()?;
+        debounce(proj, &mut int).await;
 
         if Interrupt::is_shutdown_requested().await {
             log::debug!("Shutting down");
             return Ok(());
         }
 
-        runner(proj).await?;
+        runner(proj, test).await?;
+    }
+}
+
+/// Waits until no change arrived for the debounce time of the pending changes, so that bulk
+/// changes (such as a git checkout or an asset export) are built once.
+async fn debounce(proj: &Project, int: &mut broadcast::Receiver<()>) {
+    loop {
+        let delay = proj.watch_debounce(&Interrupt::get_source_changes(proj).await);
+        match timeout(delay, int.recv()).await {
+            Err(_) | Ok(Err(RecvError::Closed)) => return,
+            Ok(_) if Interrupt::is_shutdown_requested().await => return,
+            Ok(_) => log::trace!("Watch debouncing more changes"),
+        }
+    }
+}
+
+/// sends the errors of the failed steps to the error overlay of the browser
+async fn build_failed(proj: &Project) {
+    log::warn!("Build failed");
+    dashboard::build_finished(false);
+    let errors = compile::take_errors(proj);
+    notification::build_failed(proj, errors.as_deref());
+    match errors {
+        Some(errors) if proj.error_overlay => ReloadSignal::send_error(proj, errors),
+        _ => {}
     }
+    Interrupt::clear_source_changes(proj).await;
 }
 
-pub async fn runner(proj: &Arc<Project>) -> Result<()> {
-    let changes = Interrupt::get_source_changes().await;
+/// runs the pre-build or post-build hook, if configured
+async fn build_hook(proj: &Project, name: &str, cmd: &Option<String>) -> Result<Outcome<()>> {
+    match cmd {
+        Some(cmd) => service::hooks::run_hook(proj, name, cmd).await,
+        None => Ok(Outcome::Success(())),
+    }
+}
+
+pub async fn runner(proj: &Arc<Project>, test: bool) -> Result<()> {
+    let changes = Interrupt::get_source_changes(proj).await;
+    if changes.is_empty() {
+        log::trace!("Watch nothing changed since the last build");
+        return Ok(());
+    }
 
-    let server_hdl = compile::server(proj, &changes).await;
-    let front_hdl = compile::front(proj, &changes).await;
-    let assets_hdl = compile::assets(proj, &changes).await;
-    let style_hdl = compile::style(proj, &changes).await;
+    // the errors of an earlier build are stale
+    compile::take_errors(proj);
 
-    let (server, front, assets, style) = try_join!(server_hdl, front_hdl, assets_hdl, style_hdl)?;
+    match service::hooks::run_change_hooks(proj, &changes.hooks()).await? {
+        Outcome::Success(()) => {}
+        Outcome::Stopped => return Ok(()),
+        Outcome::Failed => {
+            build_failed(proj).await;
+            return Ok(());
+        }
+    }
+    if changes.only_hooks() {
+        // the files written by the hooks are built on their own change
+        Interrupt::clear_hook_changes(proj).await;
+        return Ok(());
+    }
+    dashboard::build_started();
+
+    match build_hook(proj, "pre-build", &proj.hooks.pre_build).await? {
+        Outcome::Success(()) => {}
+        Outcome::Stopped => return Ok(()),
+        Outcome::Failed => {
+            build_failed(proj).await;
+            return Ok(());
+        }
+    }
+    // with the files changed by the hooks
+    let mut changes = Interrupt::get_source_changes(proj).await;
+
+    match compile::css_modules(proj, &changes).await? {
+        Outcome::Success(true) => {
+            changes.add(Change::LibSource);
+            changes.add(Change::BinSource);
+        }
+        Outcome::Success(false) => {}
+        Outcome::Stopped => {
+            log::info!("Build interrupted. Restarting.");
+            return Ok(());
+        }
+        Outcome::Failed => {
+            build_failed(proj).await;
+            return Ok(());
+        }
+    }
+
+    let front_hdl = dashboard::track("front", compile::front(proj, &changes).await);
+    let assets_hdl = dashboard::track("assets", compile::assets(proj, &changes).await);
+    let style_hdl = dashboard::track("style", compile::style(proj, &changes).await);
+
+    let (server, front, assets, style) = if proj.embed_file.is_some() {
+        // the server includes the site files, so is built once they are
+        let (front, assets, style) = try_join!(front_hdl, assets_hdl, style_hdl)?;
+        let built = [&front, &assets, &style]
+            .iter()
+            .all(|res| matches!(res, Ok(Outcome::Success(_))));
+        if built && compile::embed_site(proj).await? {
+            changes.add(Change::BinSource);
+        }
+        let server = dashboard::track("server", compile::server(proj, &changes).await).await?;
+        (server, front, assets, style)
+    } else {
+        let server_hdl = dashboard::track("server", compile::server(proj, &changes).await);
+        try_join!(server_hdl, front_hdl, assets_hdl, style_hdl)?
+    };
 
     let outcomes = vec![server?, front?, assets?, style?];
 
-    let interrupted = outcomes.iter().any(|outcome| *outcome == Outcome::Stopped);
+    let interrupted = outcomes.contains(&Outcome::Stopped);
     if interrupted {
         log::info!("Build interrupted. Restarting.");
         return Ok(());
     }
 
-    let failed = outcomes.iter().any(|outcome| *outcome == Outcome::Failed);
+    let failed = outcomes.contains(&Outcome::Failed);
     if failed {
-        log::warn!("Build failed");
-        Interrupt::clear_source_changes().await;
+        build_failed(proj).await;
         return Ok(());
     }
 
@@ -93,17 +267,42 @@ pub async fn runner(proj: &Arc<Project>) -> Result<()> {
         log::trace!("Build step done with changes: {set}");
     }
 
+    if set.contains_style() {
+        if let Err(e) = compile::update_hashed_stylesheet(proj) {
+            log::warn!("Watch could not update the hashed stylesheet: {e:#}");
+        }
+    }
+
+    if proj.csp_hashes && set.contains(&Product::Assets) {
+        compile::csp_hashes(proj).await?;
+    }
+
+    if build_hook(proj, "post-build", &proj.hooks.post_build).await? == Outcome::Stopped {
+        return Ok(());
+    }
+
     if set.contains(&Product::Server) {
         // send product change, then the server will send the reload once it has restarted
-        ServerRestart::send();
+        ServerRestart::send(proj);
         log::info!("Watch updated {set}. Server restarting")
     } else if set.only_style() {
-        ReloadSignal::send_style();
+        ReloadSignal::send_style(proj);
         log::info!("Watch updated style")
     } else if set.contains_any(&[Product::Front, Product::Assets]) {
-        ReloadSignal::send_full();
+        ReloadSignal::send_full(proj);
         log::info!("Watch updated {set}")
     }
-    Interrupt::clear_source_changes().await;
+    Interrupt::clear_source_changes(proj).await;
+    dashboard::build_finished(true);
+    notification::build_succeeded(proj);
+
+    if test {
+        match watch_test_proj(proj).await? {
+            Outcome::Success(true) => log::info!("Watch tests passed"),
+            Outcome::Success(false) => log::warn!("Watch tests failed"),
+            Outcome::Stopped => log::info!("Watch tests interrupted"),
+            Outcome::Failed => {}
+        }
+    }
     Ok(())
 }
@@ -0,0 +1,254 @@
+use std::{collections::HashMap, fmt::Write};
+
+use camino::Utf8PathBuf;
+use wasmparser::{KnownCustom, Name, Parser, Payload};
+
+use super::size_report::unhashed_name;
+use crate::{
+    config::Project,
+    ext::anyhow::{anyhow, Context, Result},
+};
+
+/// the size of a function of the wasm, with its demangled name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WasmItem {
+    pub name: String,
+    /// the crate of the function, from the first segment of its path
+    pub krate: String,
+    pub size: u64,
+}
+
+/// The sizes of the wasm module: its functions, from the code section, and the other sections.
+#[derive(Debug, Default)]
+pub struct WasmProfile {
+    pub total: u64,
+    pub functions: Vec<WasmItem>,
+    /// the sizes of the data section, of the custom sections and of the others
+    pub sections: Vec<(String, u64)>,
+}
+
+impl WasmProfile {
+    /// Parses the wasm, naming its functions after the name section, and `func[<index>]`
+    /// without it, as when wasm-opt stripped it.
+    pub fn parse(wasm: &[u8]) -> Result<Self> {
+        let mut profile = Self {
+            total: wasm.len() as u64,
+            ..Self::default()
+        };
+        let mut imported = 0u32;
+        let mut bodies = Vec::new();
+        let mut names = HashMap::new();
+        for payload in Parser::new(0).parse_all(wasm) {
+            let payload = payload.context("Could not parse the wasm")?;
+            match payload {
+                Payload::ImportSection(reader) => {
+                    for import in reader {
+                        if matches!(import?.ty, wasmparser::TypeRef::Func(_)) {
+                            imported += 1;
+                        }
+                    }
+                }
+                Payload::CodeSectionEntry(body) => bodies.push(body.range().len() as u64),
+                Payload::DataSection(reader) => {
+                    profile
+                        .sections
+                        .push(("data".to_string(), reader.range().len() as u64));
+                }
+                Payload::CustomSection(reader) => {
+                    profile.sections.push((
+                        format!("custom \"{}\"", reader.name()),
+                        reader.range().len() as u64,
+                    ));
+                    if let KnownCustom::Name(reader) = reader.as_known() {
+                        for name in reader.into_iter().flatten() {
+                            let Name::Function(map) = name else {
+                                continue;
+                            };
+                            for naming in map.into_iter().flatten() {
+                                names.insert(naming.index, naming.name.to_string());
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        let code: u64 = bodies.iter().sum();
+        profile.functions = bodies
+            .into_iter()
+            .enumerate()
+            .map(|(i, size)| {
+                let index = imported + i as u32;
+                let name = names
+                    .remove(&index)
+                    .map(|name| format!("{:#}", rustc_demangle::demangle(&name)))
+                    .unwrap_or_else(|| format!("func[{index}]"));
+                WasmItem {
+                    krate: crate_name(&name),
+                    name,
+                    size,
+                }
+            })
+            .collect();
+        let known = code + profile.sections.iter().map(|(_, size)| size).sum::<u64>();
+        profile.sections.insert(0, ("code".to_string(), code));
+        profile
+            .sections
+            .push(("other".to_string(), profile.total.saturating_sub(known)));
+        profile
+            .functions
+            .sort_by_key(|item| std::cmp::Reverse(item.size));
+        Ok(profile)
+    }
+
+    /// the sizes of the functions by crate, the largest first
+    pub fn crates(&self) -> Vec<(String, u64, usize)> {
+        let mut crates: HashMap<&str, (u64, usize)> = HashMap::new();
+        for item in &self.functions {
+            let entry = crates.entry(&item.krate).or_default();
+            entry.0 += item.size;
+            entry.1 += 1;
+        }
+        let mut crates: Vec<_> = crates
+            .into_iter()
+            .map(|(name, (size, count))| (name.to_string(), size, count))
+            .collect();
+        crates.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        crates
+    }
+}
+
+/// The crate of a demangled name: the first segment of its path, or of the type of a trait
+/// impl (`<alloc::string::String as core::fmt::Display>::fmt` is of `alloc`).
+pub fn crate_name(name: &str) -> String {
+    let path = name.trim_start_matches('<').trim_start_matches('&');
+    match path.split_once("::") {
+        Some((krate, _)) if !krate.contains([' ', '<', '[']) => krate.to_string(),
+        _ if name.starts_with("func[") => "(unnamed)".to_string(),
+        _ => "(other)".to_string(),
+    }
+}
+
+/// The tables of the largest crates and functions, with their share of the code section.
+pub fn profile_tables(profile: &WasmProfile, top: usize) -> String {
+    let code = profile
+        .sections
+        .iter()
+        .find(|(name, _)| name == "code")
+        .map_or(0, |(_, size)| *size)
+        .max(1);
+    let percent = |size: u64| size as f64 * 100.0 / code as f64;
+    let mut tables = format!("{:>10}  {:>6}  {:>5}  crate\n", "bytes", "%", "fns");
+    for (name, size, count) in profile.crates().into_iter().take(top) {
+        let _ = writeln!(
+            tables,
+            "{size:>10}  {:>5.1}%  {count:>5}  {name}",
+            percent(size)
+        );
+    }
+    let _ = write!(tables, "\n{:>10}  {:>6}  function\n", "bytes", "%");
+    for item in profile.functions.iter().take(top) {
+        let _ = writeln!(
+            tables,
+            "{:>10}  {:>5.1}%  {}",
+            item.size,
+            percent(item.size),
+            item.name
+        );
+    }
+    let _ = write!(tables, "\n{:>10}  section\n", "bytes");
+    for (name, size) in &profile.sections {
+        let _ = writeln!(tables, "{size:>10}  {name}");
+    }
+    tables
+}
+
+/// An html treemap of the code section: a box per crate, sized after its code, with the boxes
+/// of its largest functions.
+pub fn profile_treemap(title: &str, profile: &WasmProfile, top: usize) -> String {
+    let title = escape(title);
+    let mut boxes = String::new();
+    for (krate, size, _) in profile.crates() {
+        let _ = write!(
+            boxes,
+            "<div class=\"crate\" style=\"flex-grow: {size}\" title=\"{} ({size} bytes)\">\
+             <div class=\"label\">{} {size}</div><div class=\"fns\">",
+            escape(&krate),
+            escape(&krate)
+        );
+        for item in profile
+            .functions
+            .iter()
+            .filter(|item| item.krate == krate)
+            .take(top)
+        {
+            let _ = write!(
+                boxes,
+                "<div class=\"fn\" style=\"flex-grow: {}\" title=\"{} ({} bytes)\"></div>",
+                item.size,
+                escape(&item.name),
+                item.size
+            );
+        }
+        boxes.push_str("</div></div>\n");
+    }
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>cargo-leptos analyze: {title}</title>
+<style>
+body {{ font-family: sans-serif; margin: 2em; }}
+.map {{ display: flex; flex-wrap: wrap; height: 80vh; }}
+.crate {{ display: flex; flex-direction: column; flex-basis: 8em; min-width: 4em; border: 1px solid #fff; background: #4a7fb5; color: #fff; overflow: hidden; }}
+.label {{ font-size: 0.8em; padding: 0.2em; white-space: nowrap; }}
+.fns {{ display: flex; flex-wrap: wrap; flex-grow: 1; }}
+.fn {{ flex-basis: 1em; min-height: 1em; border: 1px solid #6d9bcb; background: #5b8cc0; }}
+.fn:hover, .crate:hover > .label {{ background: #e8a33d; }}
+</style>
+</head>
+<body>
+<h1>cargo-leptos analyze: {title}</h1>
+<p>{} bytes, of which {} of code in {} functions. Hover the boxes for their names.</p>
+<div class="map">
+{boxes}</div>
+</body>
+</html>
+"#,
+        profile.total,
+        profile.functions.iter().map(|item| item.size).sum::<u64>(),
+        profile.functions.len(),
+    )
+}
+
+/// The wasm of the site as built, with the hash in its name with hash-files, or with `source`
+/// the one cargo built, before wasm-bindgen and wasm-opt, which keeps the names of the functions.
+pub fn site_wasm(proj: &Project, source: bool) -> Result<Utf8PathBuf> {
+    let wasm = &proj.lib.wasm_file;
+    if source {
+        return match wasm.source.is_file() {
+            true => Ok(wasm.source.clone()),
+            false => Err(anyhow!("Could not find the wasm {}", wasm.source)),
+        };
+    }
+    let name = wasm.dest.file_name().unwrap_or_default();
+    let dir = wasm.dest.parent().unwrap_or(&wasm.dest);
+    for entry in dir
+        .read_dir_utf8()
+        .context(format!("Could not read {dir}"))?
+    {
+        let path = entry?.into_path();
+        if path.file_name().map(unhashed_name).as_deref() == Some(name) {
+            return Ok(path);
+        }
+    }
+    Err(anyhow!("Could not find the wasm {name} in {dir}"))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
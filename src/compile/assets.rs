@@ -1,10 +1,18 @@
-use std::sync::Arc;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+};
 
-use super::ChangeSet;
-use crate::config::Project;
+use super::{images::optimize_images, record_stage, ChangeSet};
+use crate::config::{AssetsConfig, Project};
 use crate::ext::anyhow::{Context, Result};
+use crate::service::site::{is_html, AssetStamp, SiteAsset};
 use crate::signal::{Outcome, Product};
-use crate::{ext::PathExt, fs, logger::GRAY};
+use crate::{
+    ext::{PathBufExt, PathExt},
+    fs,
+    logger::{self, GRAY},
+};
 use camino::{Utf8Path, Utf8PathBuf};
 use tokio::task::JoinHandle;
 
@@ -15,27 +23,91 @@ pub async fn assets(
     let changes = changes.clone();
 
     let proj = proj.clone();
-    tokio::spawn(async move {
+    logger::spawn(async move {
         if !changes.need_assets_change() {
             return Ok(Outcome::Success(Product::None));
         }
-        let Some(assets) = &proj.assets else {
-            return Ok(Outcome::Success(Product::None));
-        };
         let dest_root = &proj.site.root_dir;
         let pkg_dir = &proj.site.pkg_dir;
 
-        // if reserved.contains(assets.dir) {
-        //     log::warn!("Assets reserved filename for Leptos. Please remove {watched:?}");
-        //     return Ok(false);
-        // }
-        log::trace!("Assets starting resync");
-        resync(&assets.dir, dest_root, pkg_dir).await?;
-        log::debug!("Assets finished");
+        let mut changed = 0;
+        if let Some(assets) = &proj.assets {
+            // if reserved.contains(assets.dir) {
+            //     log::warn!("Assets reserved filename for Leptos. Please remove {watched:?}");
+            //     return Ok(false);
+            // }
+            log::trace!("Assets starting resync");
+            let start_time = tokio::time::Instant::now();
+            changed = resync(&proj, assets, dest_root, pkg_dir).await?;
+            record_stage("assets", start_time.elapsed());
+            log::debug!("Assets finished with {changed} changed files");
+
+            if let Some(images) = assets
+                .images
+                .as_ref()
+                .filter(|_| proj.release && changed > 0)
+            {
+                let start_time = tokio::time::Instant::now();
+                let files = proj.site.asset_reg.read().await.keys().cloned().collect();
+                let cache_dir = proj
+                    .target_dir
+                    .join("tmp")
+                    .join(format!("images-{}", proj.name));
+                let optimized =
+                    optimize_images(images, &assets.dir, dest_root, &cache_dir, files).await?;
+                record_stage("images", start_time.elapsed());
+                log::info!(
+                    "Assets optimized {} images ({} encoded) in {:.2}s",
+                    optimized.images,
+                    optimized.encoded,
+                    start_time.elapsed().as_secs_f64()
+                );
+            }
+        }
+        // after the resync, which cleans the site root the first time
+        changed += write_site_files(&proj, dest_root).await?;
+
+        if changed == 0 {
+            return Ok(Outcome::Success(Product::None));
+        }
         Ok(Outcome::Success(Product::Assets))
     })
 }
 
+/// Writes the robots.txt and `.well-known/security.txt` of the config into the site root,
+/// overriding the ones of the assets dir. Returns the number of files changed.
+async fn write_site_files(proj: &Project, dest: &Utf8Path) -> Result<usize> {
+    let files = [
+        ("robots.txt", proj.robots_txt.as_ref().map(|r| r.contents())),
+        (
+            ".well-known/security.txt",
+            proj.security_txt.as_ref().map(|s| s.contents()),
+        ),
+    ];
+    let mut changed = 0;
+    for (rel, contents) in files {
+        let Some(contents) = contents else {
+            continue;
+        };
+        let to = dest.join(rel);
+        if fs::read_to_string(&to)
+            .await
+            .is_ok_and(|old| old == contents)
+        {
+            continue;
+        }
+        fs::create_dir_all(to.clone().without_last()).await?;
+        // a link to the asset would be written through
+        if to.is_symlink() {
+            fs::remove_file(&to).await?;
+        }
+        log::debug!("Assets writing {}", GRAY.paint(to.as_str()));
+        fs::write(&to, contents).await?;
+        changed += 1;
+    }
+    Ok(changed)
+}
+
 pub fn reserved(src: &Utf8Path, pkg_dir: &Utf8Path) -> Vec<Utf8PathBuf> {
     vec![src.join("index.html"), pkg_dir.to_path_buf()]
 }
@@ -52,14 +124,127 @@ pub fn reserved(src: &Utf8Path, pkg_dir: &Utf8Path) -> Vec<Utf8PathBuf> {
 //     Ok(())
 // }
 
-async fn resync(src: &Utf8Path, dest: &Utf8Path, pkg_dir: &Utf8Path) -> Result<()> {
-    clean_dest(dest, pkg_dir)
-        .await
-        .context(format!("Cleaning {dest:?}"))?;
+/// Synchronizes the assets dir with the site dir and returns the number of files
+/// copied or removed.
+///
+/// The first sync cleans the site dir and copies everything. Later syncs compare the
+/// modification time and size of each source file with the ones recorded in the site's
+/// asset registry, and only copy new or changed files and remove deleted ones, as
+/// [`sync_plan`] tells.
+async fn resync(
+    proj: &Project,
+    assets: &AssetsConfig,
+    dest: &Utf8Path,
+    pkg_dir: &Utf8Path,
+) -> Result<usize> {
+    let src = &assets.dir;
     let reserved = reserved(src, pkg_dir);
-    mirror(src, dest, &reserved)
-        .await
-        .context(format!("Mirroring {src:?} -> {dest:?}"))
+    let found = scan(assets, &reserved).context(format!("Scanning {src:?}"))?;
+
+    let mut registry = proj.site.asset_reg.write().await;
+    if registry.is_empty() {
+        clean_dest(dest, pkg_dir)
+            .await
+            .context(format!("Cleaning {dest:?}"))?;
+    }
+
+    let link = assets.link && !proj.release;
+    let plan = sync_plan(&found, &registry, |rel| dest.join(rel).exists());
+    for rel in &plan.remove {
+        let to = dest.join(rel);
+        // a link to a deleted file doesn't exist anymore, but needs removing all the same
+        if to.exists() || to.is_symlink() {
+            log::debug!("Assets removing file {}", GRAY.paint(to.as_str()));
+            fs::remove_file(&to).await?;
+            remove_empty_parents(&to, dest);
+        }
+    }
+    for rel in &plan.copy {
+        let to = dest.join(rel);
+        let from = src.join(rel);
+        fs::create_dir_all(to.clone().without_last()).await?;
+        // never copy through a link left by a previous sync, it would overwrite the source
+        if to.is_symlink() || (link && to.exists()) {
+            fs::remove_file(&to).await?;
+        }
+        if link {
+            log::debug!(
+                "Assets link file {} -> {}",
+                GRAY.paint(from.as_str()),
+                GRAY.paint(to.as_str())
+            );
+            link_file(&proj.working_dir.join(&from), &to)
+                .context(format!("Could not link {from:?} to {to:?}"))?;
+        } else {
+            log::debug!(
+                "Assets copy file {} -> {}",
+                GRAY.paint(from.as_str()),
+                GRAY.paint(to.as_str())
+            );
+            if proj.site.minify_html && is_html(&to) {
+                let data = fs::read(&from).await?;
+                fs::write(&to, proj.site.minified(&to, &data)).await?;
+            } else {
+                fs::copy(from, to).await?;
+            }
+        }
+    }
+
+    let deleted = registry
+        .keys()
+        .filter(|rel| !found.contains_key(*rel))
+        .count();
+    // the unchanged assets keep the name they were renamed to
+    *registry = found
+        .into_iter()
+        .map(|(rel, stamp)| {
+            let asset = match registry.remove(&rel) {
+                Some(asset) if !plan.copy.contains(&rel) => asset,
+                _ => SiteAsset {
+                    stamp,
+                    dest: rel.clone(),
+                },
+            };
+            (rel, asset)
+        })
+        .collect();
+    Ok(plan.copy.len() + deleted)
+}
+
+/// The changes of a resync: the assets to copy, relative to the assets dir, and the previous
+/// copies to remove, relative to the site root.
+#[derive(Debug, Default, PartialEq)]
+pub struct SyncPlan {
+    pub copy: Vec<Utf8PathBuf>,
+    pub remove: Vec<Utf8PathBuf>,
+}
+
+/// What a resync does, given the assets found and the ones registered by the previous sync: the
+/// new, changed and missing assets are copied, and the copies of the deleted ones are removed,
+/// as are the ones of the changed assets renamed with their hash, which the new copies don't
+/// overwrite. `exists` tells whether a copy is in the site.
+pub fn sync_plan(
+    found: &HashMap<Utf8PathBuf, AssetStamp>,
+    registry: &HashMap<Utf8PathBuf, SiteAsset>,
+    exists: impl Fn(&Utf8Path) -> bool,
+) -> SyncPlan {
+    let mut plan = SyncPlan::default();
+    for (rel, stamp) in found {
+        match registry.get(rel) {
+            Some(asset) if asset.stamp == *stamp && exists(&asset.dest) => continue,
+            Some(asset) if asset.dest != *rel => plan.remove.push(asset.dest.clone()),
+            _ => {}
+        }
+        plan.copy.push(rel.clone());
+    }
+    for (rel, asset) in registry {
+        if !found.contains_key(rel) {
+            plan.remove.push(asset.dest.clone());
+        }
+    }
+    plan.copy.sort();
+    plan.remove.sort();
+    plan
 }
 
 async fn clean_dest(dest: &Utf8Path, pkg_dir: &Utf8Path) -> Result<()> {
@@ -97,31 +282,64 @@ async fn clean_dest(dest: &Utf8Path, pkg_dir: &Utf8Path) -> Result<()> {
     Ok(())
 }
 
-async fn mirror(src_root: &Utf8Path, dest_root: &Utf8Path, reserved: &[Utf8PathBuf]) -> Result<()> {
-    let mut entries = src_root.read_dir_utf8()?;
-    while let Some(Ok(entry)) = entries.next() {
-        let from = entry.path().to_path_buf();
-        let to = from.rebase(src_root, dest_root)?;
-        if reserved.contains(&from) {
-            log::warn!("");
-            continue;
+/// Lists the asset files to copy, relative to the assets dir, with their modification
+/// time and size.
+fn scan(
+    assets: &AssetsConfig,
+    reserved: &[Utf8PathBuf],
+) -> Result<HashMap<Utf8PathBuf, AssetStamp>> {
+    let src_root = &assets.dir;
+    let mut found = HashMap::new();
+    let mut dirs = VecDeque::from([src_root.clone()]);
+
+    while let Some(dir) = dirs.pop_front() {
+        let mut entries = dir.read_dir_utf8()?;
+        while let Some(Ok(entry)) = entries.next() {
+            let from = entry.path().to_path_buf();
+            if reserved.contains(&from) {
+                log::warn!(
+                    "Assets skipping reserved path {}",
+                    GRAY.paint(from.as_str())
+                );
+                continue;
+            }
+
+            if entry.file_type()?.is_dir() {
+                dirs.push_back(from);
+                continue;
+            }
+
+            let rel = from.unbase(src_root)?;
+            if !assets.is_included(&rel) {
+                log::trace!("Assets ignoring file {}", GRAY.paint(from.as_str()));
+                continue;
+            }
+
+            let metadata = entry.metadata()?;
+            found.insert(rel, (metadata.modified()?, metadata.len()));
         }
+    }
+    Ok(found)
+}
 
-        if entry.file_type()?.is_dir() {
-            log::debug!(
-                "Assets copy folder {} -> {}",
-                GRAY.paint(from.as_str()),
-                GRAY.paint(to.as_str())
-            );
-            fs::copy_dir_all(from, to).await?;
-        } else {
-            log::debug!(
-                "Assets copy file {} -> {}",
-                GRAY.paint(from.as_str()),
-                GRAY.paint(to.as_str())
-            );
-            fs::copy(from, to).await?;
+#[cfg(unix)]
+fn link_file(from: &Utf8Path, to: &Utf8Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(from, to)
+}
+
+// symlinks require extra privileges on windows, so hard links are used instead
+#[cfg(not(unix))]
+fn link_file(from: &Utf8Path, to: &Utf8Path) -> std::io::Result<()> {
+    std::fs::hard_link(from, to)
+}
+
+/// removes the now empty parent dirs of a removed file, up to the site root
+fn remove_empty_parents(file: &Utf8Path, root: &Utf8Path) {
+    let mut dir = file.parent();
+    while let Some(current) = dir {
+        if current == root || !current.starts_with(root) || std::fs::remove_dir(current).is_err() {
+            break;
         }
+        dir = current.parent();
     }
-    Ok(())
 }
@@ -0,0 +1,91 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use camino::Utf8Path;
+use serde_json::{json, Value};
+
+use crate::{
+    config::Project,
+    ext::{anyhow::Result, fs},
+    logger::GRAY,
+};
+
+/// the commit the project is built from
+pub struct GitInfo {
+    pub sha: String,
+    /// the working tree has uncommitted changes
+    pub dirty: bool,
+}
+
+/// Writes the `build-info.json` of the build into the site root, with `build-info`, for the
+/// server to tell the version it runs. Its path is given to the server as LEPTOS_BUILD_INFO.
+pub async fn build_info(proj: &Project) -> Result<()> {
+    let built_at = proj.source_date_epoch.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs())
+    });
+    let info = build_info_json(proj, git_info(&proj.working_dir), built_at);
+    let file = proj.build_info_file();
+    fs::write(&file, serde_json::to_string_pretty(&info)?).await?;
+    log::debug!("Build info written to {}", GRAY.paint(file.as_str()));
+    Ok(())
+}
+
+/// The build info: the commit, the time of the build (the SOURCE_DATE_EPOCH of the
+/// reproducible builds), the cargo-leptos version, and the profile and features of the builds.
+pub fn build_info_json(proj: &Project, git: Option<GitInfo>, built_at: u64) -> Value {
+    let git = git.map(|git| json!({ "sha": git.sha, "dirty": git.dirty }));
+    let mut info = json!({
+        "project": proj.name,
+        "git": git,
+        "built_at": rfc3339(built_at),
+        "cargo_leptos": env!("CARGO_PKG_VERSION"),
+        "profile": if proj.release { "release" } else { "debug" },
+        "lib_features": proj.lib.features,
+    });
+    if let Some(bin) = &proj.bin {
+        info["bin_features"] = json!(bin.features);
+    }
+    info
+}
+
+/// the commit of the working dir, when it is in a git repository
+pub fn git_info(dir: &Utf8Path) -> Option<GitInfo> {
+    Some(GitInfo {
+        sha: git_output(dir, &["rev-parse", "HEAD"])?,
+        dirty: !git_output(dir, &["status", "--porcelain"])?.is_empty(),
+    })
+}
+
+/// the trimmed output of the git command, when it succeeds
+pub fn git_output(dir: &Utf8Path, args: &[&str]) -> Option<String> {
+    std::process::Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// the UTC date and time of the unix timestamp, such as `2024-05-01T12:00:00Z`
+pub fn rfc3339(secs: u64) -> String {
+    let (days, secs) = (secs / 86400, secs % 86400);
+    // the civil date of the days since 1970-01-01, after Howard Hinnant's algorithm
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
@@ -14,6 +14,8 @@ pub enum Change {
     Conf,
     /// Additional file changed
     Additional,
+    /// a file matching the patterns of the on-change hook with the index changed
+    Hook(usize),
 }
 
 #[derive(Debug, Default, Clone)]
@@ -59,6 +61,39 @@ impl ChangeSet {
         self.0.contains(&Change::Asset)
     }
 
+    /// whether only asset and style files changed, without any rust source
+    pub fn only_assets_or_style(&self) -> bool {
+        !self.0.is_empty()
+            && self
+                .0
+                .iter()
+                .all(|change| matches!(change, Change::Asset | Change::Style))
+    }
+
+    /// the indexes of the on-change hooks to run
+    pub fn hooks(&self) -> Vec<usize> {
+        self.0
+            .iter()
+            .filter_map(|change| match change {
+                Change::Hook(index) => Some(*index),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// whether only files of on-change hooks changed, which don't need a build
+    pub fn only_hooks(&self) -> bool {
+        !self.0.is_empty()
+            && self
+                .0
+                .iter()
+                .all(|change| matches!(change, Change::Hook(_)))
+    }
+
+    pub fn remove_hooks(&mut self) {
+        self.0.retain(|change| !matches!(change, Change::Hook(_)))
+    }
+
     pub fn add(&mut self, change: Change) -> bool {
         if !self.0.contains(&change) {
             self.0.push(change);
@@ -0,0 +1,84 @@
+use super::{errors::record_error, sass::compile_sass};
+use crate::{
+    config::{Project, StyleCollector, StyleCollectorConfig},
+    ext::{
+        anyhow::{Context, Result},
+        fs,
+        sync::{wait_piped_interruptible, CommandResult, OutputExt},
+    },
+    logger::GRAY,
+    signal::{Interrupt, Outcome},
+};
+use tokio::process::Command;
+
+/// The css collected by the css-in-rust crate. Stylance's css is bundled by running the
+/// `stylance` cli, while stylers' is written by its macros when the front is compiled.
+pub async fn collected_css(
+    proj: &Project,
+    collector: &StyleCollectorConfig,
+) -> Result<Outcome<String>> {
+    if collector.kind == StyleCollector::Stylance {
+        match run_stylance(proj, collector).await? {
+            Outcome::Success(()) => {}
+            Outcome::Stopped => return Ok(Outcome::Stopped),
+            Outcome::Failed => return Ok(Outcome::Failed),
+        }
+    }
+
+    let mut css = Vec::new();
+    for file in &collector.files {
+        if !file.exists() {
+            log::debug!(
+                "Style collected css {} not written yet",
+                GRAY.paint(file.as_str())
+            );
+            continue;
+        }
+        let file_css = match file.extension() {
+            Some("scss") | Some("sass") => match compile_sass(proj, file)
+                .await
+                .context(format!("compile sass/scss: {file}"))?
+            {
+                Outcome::Success(css) => css,
+                outcome => return Ok(outcome),
+            },
+            _ => fs::read_to_string(file).await?,
+        };
+        log::trace!("Style collected css from {}", GRAY.paint(file.as_str()));
+        css.push(file_css);
+    }
+    Ok(Outcome::Success(css.join("\n")))
+}
+
+async fn run_stylance(proj: &Project, collector: &StyleCollectorConfig) -> Result<Outcome<()>> {
+    let Ok(exe) = which::which("stylance") else {
+        log::warn!(
+            "Style the stylance cli was not found, install it with: cargo install stylance-cli"
+        );
+        return Ok(Outcome::Success(()));
+    };
+
+    for dir in &collector.crates {
+        let mut cmd = Command::new(&exe);
+        // the root package's dir is the working dir
+        let dir = if dir.as_str().is_empty() {
+            "."
+        } else {
+            dir.as_str()
+        };
+        cmd.arg(dir).current_dir(&proj.working_dir);
+
+        log::trace!("Style running stylance {}", GRAY.paint(dir));
+        match wait_piped_interruptible("Stylance", cmd, Interrupt::subscribe_any(proj)).await? {
+            CommandResult::Success(_) => {}
+            CommandResult::Interrupted => return Ok(Outcome::Stopped),
+            CommandResult::Failure(output) => {
+                log::warn!("Stylance failed with:");
+                println!("{}", output.stderr());
+                record_error(proj, "Stylance failed", &output.stderr());
+                return Ok(Outcome::Failed);
+            }
+        }
+    }
+    Ok(Outcome::Success(()))
+}
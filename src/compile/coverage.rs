@@ -0,0 +1,28 @@
+use tokio::process::Command;
+
+/// The cargo-llvm-cov command of the cargo `test` command of a test run, with its arguments and
+/// env vars, that doesn't report. The target dir and the timings are left out: the instrumented
+/// builds and the profiles of all the runs go to the target dir of cargo-llvm-cov, so that
+/// they are merged in one report. Returns the command line.
+pub fn build_cargo_coverage_cmd(test: &Command, command: &mut Command) -> String {
+    let test = test.as_std();
+    let mut args = vec![
+        "llvm-cov".to_string(),
+        "test".to_string(),
+        "--no-report".to_string(),
+    ];
+    args.extend(
+        test.get_args()
+            // the test subcommand
+            .skip(1)
+            .map(|arg| arg.to_string_lossy().to_string())
+            .filter(|arg| !arg.starts_with("--target-dir=") && arg != "--timings"),
+    );
+    for (name, val) in test.get_envs() {
+        if let Some(val) = val {
+            command.env(name, val);
+        }
+    }
+    command.args(&args);
+    super::build_cargo_command_string(args)
+}
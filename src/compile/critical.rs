@@ -0,0 +1,213 @@
+use std::iter::Peekable;
+
+use super::{hash::stylesheet_hash, record_stage, style::browser_lists};
+use crate::{
+    config::{CacheBust, Project},
+    ext::{
+        anyhow::{anyhow, Context, Result},
+        fs, html_selectors, replace_stylesheet_link, HtmlSelectors,
+    },
+    logger::GRAY,
+    service::serve,
+};
+use camino::Utf8PathBuf;
+use lightningcss::{
+    rules::CssRule,
+    stylesheet::{ParserOptions, PrinterOptions, StyleSheet},
+    targets::Targets,
+    traits::ToCss,
+};
+
+/// Extracts the css rules used by the server rendered html of the root route and writes
+/// them, inlined in a `<style>` element, to the critical css file together with a deferred
+/// `<link>` to the full stylesheet. The prerendered and exported pages get that snippet in
+/// place of their stylesheet link, see [`inline_critical_css`], and the server can include it
+/// in the `<head>` of its shell. The snippet has no csp nonce: under a csp without
+/// `'unsafe-inline'` its style and `onload` handler are allowed by the [`super::csp_hashes`].
+pub async fn critical_css(proj: &Project) -> Result<()> {
+    if proj.style.files.is_empty()
+        && proj.style.tailwind.is_none()
+        && proj.style.modules.is_none()
+        && proj.style.collector.is_none()
+    {
+        log::debug!("Critical css skipped as no style is configured");
+        return Ok(());
+    }
+    let start_time = tokio::time::Instant::now();
+
+    let (stylesheet, href) = stylesheet_file(proj)?;
+    let css = fs::read_to_string(&stylesheet)
+        .await
+        .context(format!("Could not read the stylesheet {stylesheet:?}"))?;
+    let html = serve::fetch_pages(proj, &["/"])
+        .await
+        .context("Could not fetch the root route for extracting the critical css")?
+        .remove(0);
+
+    let browsers = browser_lists(&proj.style.browserquery).context("leptos.style.browserquery")?;
+    let critical = extract(&css, &html_selectors(&html), Targets::from(browsers))?;
+    let snippet = format!(
+        r#"<style>{critical}</style>
+<link rel="preload" href="{href}" as="style" onload="this.onload=null;this.rel='stylesheet'">
+<noscript><link rel="stylesheet" href="{href}"></noscript>
+"#
+    );
+
+    let file = proj.critical_css_file();
+    fs::write(&file, snippet).await?;
+    record_stage("critical-css", start_time.elapsed());
+    log::info!(
+        "Critical css of {} bytes (of {}) written to {}",
+        critical.len(),
+        css.len(),
+        GRAY.paint(file.as_str())
+    );
+    Ok(())
+}
+
+/// The page with the critical css snippet in place of the link to the full stylesheet, when
+/// the critical css was extracted. The page is left as is when it doesn't link to the stylesheet.
+pub async fn inline_critical_css(proj: &Project, path: &str, html: String) -> Result<String> {
+    let file = proj.critical_css_file();
+    if !proj.critical_css || !file.exists() {
+        return Ok(html);
+    }
+    let snippet = fs::read_to_string(&file).await?;
+    let (_, href) = stylesheet_file(proj)?;
+    match replace_stylesheet_link(&html, &href, &snippet) {
+        Some(html) => Ok(html),
+        None => {
+            log::debug!("Critical css not inlined in {path}, which doesn't link to {href}");
+            Ok(html)
+        }
+    }
+}
+
+/// the stylesheet in the site dir and its url, which include the hash when hashing files
+fn stylesheet_file(proj: &Project) -> Result<(Utf8PathBuf, String)> {
+    let site_file = &proj.style.site_file;
+    if !proj.hash_files {
+        return Ok((site_file.dest.clone(), proj.site.asset_url(&site_file.site)));
+    }
+
+    let hash = stylesheet_hash(proj)?;
+    if proj.cache_bust == CacheBust::Query {
+        return Ok((
+            site_file.dest.clone(),
+            format!("{}?v={hash}", proj.site.asset_url(&site_file.site)),
+        ));
+    }
+    let name = format!(
+        "{}.{hash}.css",
+        site_file.dest.file_stem().unwrap_or_default()
+    );
+    Ok((
+        site_file.dest.with_file_name(&name),
+        proj.site.asset_url(&site_file.site.with_file_name(&name)),
+    ))
+}
+
+/// keeps the rules whose selectors match the html, and the at-rules that can apply to them
+fn extract(css: &str, used: &HtmlSelectors, targets: Targets) -> Result<String> {
+    let mut stylesheet =
+        StyleSheet::parse(css, ParserOptions::default()).map_err(|e| anyhow!("{e}"))?;
+    retain_critical(&mut stylesheet.rules.0, used);
+    let options = PrinterOptions {
+        targets,
+        minify: true,
+        ..Default::default()
+    };
+    Ok(stylesheet.to_css(options)?.code)
+}
+
+fn retain_critical(rules: &mut Vec<CssRule>, used: &HtmlSelectors) {
+    rules.retain_mut(|rule| match rule {
+        CssRule::Style(style) => {
+            style.selectors.0.retain(|selector| {
+                selector
+                    .to_css_string(PrinterOptions::default())
+                    .map_or(true, |selector| is_used(&selector, used))
+            });
+            !style.selectors.0.is_empty()
+        }
+        CssRule::Media(media) => {
+            retain_critical(&mut media.rules.0, used);
+            !media.rules.0.is_empty()
+        }
+        CssRule::Supports(supports) => {
+            retain_critical(&mut supports.rules.0, used);
+            !supports.rules.0.is_empty()
+        }
+        CssRule::LayerBlock(layer) => {
+            retain_critical(&mut layer.rules.0, used);
+            !layer.rules.0.is_empty()
+        }
+        CssRule::Container(container) => {
+            retain_critical(&mut container.rules.0, used);
+            !container.rules.0.is_empty()
+        }
+        // not needed for the first render
+        CssRule::Import(_) | CssRule::Keyframes(_) | CssRule::Page(_) => false,
+        _ => true,
+    });
+}
+
+/// Whether all the element names, classes and ids of a selector are used by the html.
+/// Pseudo classes, attribute selectors and the arguments of functional pseudo classes
+/// (such as `:not(.a)`) are not checked.
+fn is_used(selector: &str, used: &HtmlSelectors) -> bool {
+    let mut chars = selector.chars().peekable();
+    let mut depth = 0;
+    let mut compound_start = true;
+    while let Some(c) = chars.next() {
+        let starts_compound = compound_start;
+        compound_start = false;
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth -= 1,
+            '\\' => {
+                chars.next();
+            }
+            _ if depth > 0 => {}
+            '.' | '#' => {
+                let name = ident(&mut chars);
+                let names = if c == '.' { &used.classes } else { &used.ids };
+                if !names.contains(&name) {
+                    return false;
+                }
+            }
+            ':' => {
+                if chars.peek() == Some(&':') {
+                    chars.next();
+                }
+                ident(&mut chars);
+            }
+            ' ' | '>' | '+' | '~' => compound_start = true,
+            c if starts_compound && c.is_ascii_alphabetic() => {
+                let tag = format!("{c}{}", ident(&mut chars)).to_ascii_lowercase();
+                if !used.tags.contains(&tag) {
+                    return false;
+                }
+            }
+            _ => {}
+        }
+    }
+    true
+}
+
+/// reads a css identifier, unescaping the escaped characters (as in `.md\:flex`)
+pub(super) fn ident(chars: &mut Peekable<impl Iterator<Item = char>>) -> String {
+    let mut ident = String::new();
+    while let Some(&c) = chars.peek() {
+        if c == '\\' {
+            chars.next();
+            ident.extend(chars.next());
+        } else if c.is_alphanumeric() || c == '-' || c == '_' || !c.is_ascii() {
+            ident.push(c);
+            chars.next();
+        } else {
+            break;
+        }
+    }
+    ident
+}
@@ -0,0 +1,98 @@
+use std::collections::BTreeSet;
+
+use super::record_stage;
+use crate::{
+    config::Project,
+    ext::{
+        anyhow::{Context, Result},
+        fs, inline_code,
+    },
+    logger::GRAY,
+    service::site::is_html,
+};
+use base64ct::{Base64, Encoding};
+use camino::Utf8PathBuf;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+/// the csp source expressions allowing the inline code, by directive
+#[derive(Debug, Default, Serialize)]
+pub struct CspHashes {
+    #[serde(rename = "script-src")]
+    pub script_src: BTreeSet<String>,
+    #[serde(rename = "style-src")]
+    pub style_src: BTreeSet<String>,
+    /// the event handler attributes, which also need `'unsafe-hashes'`
+    #[serde(rename = "script-src-attr")]
+    pub script_src_attr: BTreeSet<String>,
+}
+
+impl CspHashes {
+    pub fn add(&mut self, html: &str) {
+        let code = inline_code(html);
+        self.script_src
+            .extend(code.scripts.iter().map(|s| csp_hash(s)));
+        self.style_src
+            .extend(code.styles.iter().map(|s| csp_hash(s)));
+        self.script_src_attr
+            .extend(code.handlers.iter().map(|s| csp_hash(s)));
+    }
+}
+
+/// Writes the `sha256-...` hashes of the inline scripts, styles and event handlers of the
+/// html written by cargo-leptos (the critical css snippet and the html pages of the site) to
+/// the csp file, so that a strict Content-Security-Policy can allow them.
+pub async fn csp_hashes(proj: &Project) -> Result<()> {
+    let start_time = tokio::time::Instant::now();
+    let mut hashes = CspHashes::default();
+    for file in html_files(proj).await {
+        let html = fs::read_to_string(&file)
+            .await
+            .context(format!("Could not read {file}"))?;
+        hashes.add(&html);
+    }
+    let handlers = hashes.script_src_attr.len();
+    if handlers > 0 {
+        hashes.script_src_attr.insert("'unsafe-hashes'".to_string());
+    }
+
+    let file = proj.csp_file();
+    fs::write(&file, serde_json::to_string_pretty(&hashes)?).await?;
+    record_stage("csp-hashes", start_time.elapsed());
+    log::info!(
+        "Csp hashes of {} scripts, {} styles and {handlers} handlers written to {}",
+        hashes.script_src.len(),
+        hashes.style_src.len(),
+        GRAY.paint(file.as_str())
+    );
+    Ok(())
+}
+
+async fn html_files(proj: &Project) -> Vec<Utf8PathBuf> {
+    let root = &proj.site.root_dir;
+    let mut files = proj
+        .site
+        .asset_reg
+        .read()
+        .await
+        .keys()
+        .filter(|rel| is_html(rel))
+        .map(|rel| root.join(rel))
+        .collect::<Vec<_>>();
+    let index = root.join("index.html");
+    if index.exists() && !files.contains(&index) {
+        files.push(index);
+    }
+    let critical = proj.critical_css_file();
+    if proj.critical_css && critical.exists() {
+        files.push(critical);
+    }
+    files.sort();
+    files
+}
+
+/// the csp source expression of the code: `'sha256-<base64>'`
+pub fn csp_hash(code: &str) -> String {
+    let digest = Sha256::digest(code.as_bytes());
+    format!("'sha256-{}'", Base64::encode_string(&digest))
+}
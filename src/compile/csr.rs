@@ -0,0 +1,47 @@
+use crate::{
+    config::Project,
+    ext::{anyhow::Result, fs},
+    logger::GRAY,
+};
+
+/// Writes the `index.html` of a csr project, loading the stylesheet and the wasm, unless the
+/// assets have one.
+pub async fn csr_index(proj: &Project) -> Result<()> {
+    let index = proj.site.root_dir.join("index.html");
+    if index.exists() {
+        log::debug!(
+            "Site csr index from the assets {}",
+            GRAY.paint(index.as_str())
+        );
+        return Ok(());
+    }
+
+    let js = proj.site.asset_url(&proj.lib.js_file.site);
+    let wasm = proj.site.asset_url(&proj.lib.wasm_file.site);
+    let stylesheet = if proj.style.site_file.dest.exists() {
+        let css = proj.site.asset_url(&proj.style.site_file.site);
+        format!("\n    <link rel=\"stylesheet\" href=\"{css}\">")
+    } else {
+        String::new()
+    };
+    let html = format!(
+        r#"<!DOCTYPE html>
+<html>
+  <head>
+    <meta charset="utf-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1">
+    <title>{name}</title>{stylesheet}
+    <link rel="modulepreload" href="{js}">
+    <link rel="preload" href="{wasm}" as="fetch" type="application/wasm" crossorigin>
+  </head>
+  <body>
+    <script type="module">import init from "{js}"; init({{ module_or_path: "{wasm}" }});</script>
+  </body>
+</html>
+"#,
+        name = proj.lib.output_name,
+    );
+    fs::write(&index, html).await?;
+    log::debug!("Site wrote the csr index {}", GRAY.paint(index.as_str()));
+    Ok(())
+}
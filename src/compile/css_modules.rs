@@ -0,0 +1,173 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use super::{sass::compile_sass, ChangeSet};
+use crate::{
+    config::{CssModulesConfig, Project},
+    ext::{
+        anyhow::{anyhow, Context, Result},
+        fs, PathBufExt, PathExt,
+    },
+    logger::GRAY,
+    signal::Outcome,
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use itertools::Itertools;
+use lightningcss::{
+    css_modules::{self, CssModuleExports, CssModuleReference},
+    stylesheet::{ParserOptions, PrinterOptions, StyleSheet},
+};
+
+const MODULE_EXTENSIONS: [&str; 3] = ["module.css", "module.scss", "module.sass"];
+
+/// Compiles the css modules, writing their css, with hashed class names, to the file
+/// included in the stylesheet, and the generated rust module with the hashed class names.
+///
+/// Runs before the cargo builds, as they include the generated module. Returns true when
+/// the rust module changed.
+pub async fn css_modules(proj: &Project, changes: &ChangeSet) -> Result<Outcome<bool>> {
+    let Some(modules) = &proj.style.modules else {
+        return Ok(Outcome::Success(false));
+    };
+    if !changes.need_style_build(true, false) {
+        return Ok(Outcome::Success(false));
+    }
+
+    let files = find_modules(&modules.dir).context(format!("Scanning {:?}", modules.dir))?;
+    let mut css = Vec::new();
+    let mut exports = BTreeMap::new();
+    for file in &files {
+        let source = match file.extension() {
+            Some("css") => fs::read_to_string(file).await?,
+            _ => match compile_sass(proj, file).await? {
+                Outcome::Success(source) => source,
+                Outcome::Stopped => return Ok(Outcome::Stopped),
+                Outcome::Failed => return Ok(Outcome::Failed),
+            },
+        };
+        let rel = file.unbase(&modules.dir)?;
+        let (code, file_exports) =
+            compile_module(&rel, &source).context(format!("Compiling css module {file}"))?;
+        css.push(format!("/* {rel} */\n{code}"));
+        exports.insert(module_name(&rel), file_exports);
+    }
+
+    fs::create_dir_all(modules.css_file.clone().without_last()).await?;
+    fs::write(&modules.css_file, css.join("\n")).await?;
+
+    let rust = rust_module(modules, &exports);
+    let changed = match fs::read_to_string(&modules.rust_file).await {
+        Ok(current) => current != rust,
+        Err(_) => true,
+    };
+    // rewriting an unchanged module would make cargo rebuild the crates including it
+    if changed {
+        fs::write(&modules.rust_file, rust).await?;
+        log::debug!(
+            "Style css modules written to {}",
+            GRAY.paint(modules.rust_file.as_str())
+        );
+    }
+    log::trace!("Style compiled {} css modules", files.len());
+    Ok(Outcome::Success(changed))
+}
+
+fn find_modules(dir: &Utf8Path) -> Result<Vec<Utf8PathBuf>> {
+    let mut found = Vec::new();
+    let mut dirs = VecDeque::from([dir.to_path_buf()]);
+    while let Some(dir) = dirs.pop_front() {
+        for entry in dir.read_dir_utf8()? {
+            let entry = entry?;
+            let path = entry.path().to_path_buf();
+            if entry.file_type()?.is_dir() {
+                dirs.push_back(path);
+            } else if MODULE_EXTENSIONS
+                .iter()
+                .any(|ext| path.as_str().ends_with(&format!(".{ext}")))
+            {
+                found.push(path);
+            }
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+/// the css with the hashed class names and the hashed names by original class name
+pub fn compile_module(rel: &Utf8Path, source: &str) -> Result<(String, BTreeMap<String, String>)> {
+    let options = ParserOptions {
+        // the hashes are derived from the file name, so it mustn't depend on the machine
+        filename: rel.as_str().replace('\\', "/"),
+        css_modules: Some(css_modules::Config::default()),
+        ..Default::default()
+    };
+    let stylesheet = StyleSheet::parse(source, options).map_err(|e| anyhow!("{e}"))?;
+    let output = stylesheet.to_css(PrinterOptions::default())?;
+    let exports = output.exports.map(class_names).unwrap_or_default();
+    Ok((output.code, exports))
+}
+
+/// the class names to use for each export, including the ones it composes
+fn class_names(exports: CssModuleExports) -> BTreeMap<String, String> {
+    exports
+        .into_iter()
+        .map(|(name, export)| {
+            let composed = export
+                .composes
+                .into_iter()
+                .filter_map(|reference| match reference {
+                    CssModuleReference::Local { name } | CssModuleReference::Global { name } => {
+                        Some(name)
+                    }
+                    CssModuleReference::Dependency { name, specifier } => {
+                        log::warn!("Style composing {name} from {specifier} is not supported");
+                        None
+                    }
+                });
+            let classes = std::iter::once(export.name).chain(composed).join(" ");
+            (name, classes)
+        })
+        .collect()
+}
+
+/// `components/button.module.css` becomes `components_button`
+fn module_name(rel: &Utf8Path) -> String {
+    let name = MODULE_EXTENSIONS
+        .iter()
+        .find_map(|ext| rel.as_str().strip_suffix(&format!(".{ext}")))
+        .unwrap_or(rel.as_str());
+    ident(name).to_lowercase()
+}
+
+/// replaces the characters that aren't valid in a rust identifier
+fn ident(name: &str) -> String {
+    let ident: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if ident.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{ident}")
+    } else {
+        ident
+    }
+}
+
+fn rust_module(
+    modules: &CssModulesConfig,
+    exports: &BTreeMap<String, BTreeMap<String, String>>,
+) -> String {
+    let mut out = format!(
+        "// Generated by cargo-leptos from the css modules in {}. Do not edit.\n",
+        modules.dir
+    );
+    for (module, classes) in exports {
+        out.push_str(&format!("\npub mod {module} {{\n"));
+        for (class, value) in classes {
+            out.push_str(&format!(
+                "    pub const {}: &str = {value:?};\n",
+                ident(class).to_uppercase()
+            ));
+        }
+        out.push_str("}\n");
+    }
+    out
+}
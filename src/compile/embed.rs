@@ -0,0 +1,126 @@
+use std::fmt::Write;
+
+use camino::{Utf8Path, Utf8PathBuf};
+
+use super::record_stage;
+use crate::{
+    config::Project,
+    ext::{
+        anyhow::{Context, Result},
+        fs, PathBufExt,
+    },
+    logger::GRAY,
+};
+
+/// Writes the rust module including the files of the site root into the server, with
+/// `embed-site`. Runs once the site is built and before the server build, which includes the
+/// module. Returns true when the module changed.
+pub async fn embed_site(proj: &Project) -> Result<bool> {
+    let Some(file) = &proj.embed_file else {
+        return Ok(false);
+    };
+    let start_time = tokio::time::Instant::now();
+    let hash_file =
+        (proj.hash_files && proj.hash_file.abs.is_file()).then_some(&proj.hash_file.abs);
+    let module = embed_module(
+        &proj.name,
+        &proj.site.root_dir,
+        hash_file.map(|f| f.as_path()),
+    )?;
+
+    let changed = match fs::read_to_string(file).await {
+        Ok(current) => current != module,
+        Err(_) => true,
+    };
+    // rewriting an unchanged module would make cargo rebuild the server
+    if changed {
+        fs::create_dir_all(file.clone().without_last()).await?;
+        fs::write(file, module).await?;
+        log::debug!("Embed site module written to {}", GRAY.paint(file.as_str()));
+    }
+    record_stage("embed-site", start_time.elapsed());
+    Ok(changed)
+}
+
+/// The rust module of the site files, sorted by their path relative to the site root, as
+/// written by the build (with the hashes in the names of the hashed files), with their `.gz`
+/// and `.br` copies and the contents of the hash file mapping the original names to them.
+pub fn embed_module(name: &str, root: &Utf8Path, hash_file: Option<&Utf8Path>) -> Result<String> {
+    let mut module = format!(
+        "// generated by cargo-leptos: the files of the site root of {name}, included into the\n\
+         // server with `include!(env!(\"LEPTOS_EMBED_FILE\"))`\n\n\
+         /// a file of the site, with its precompressed copies\n\
+         pub struct EmbeddedFile {{\n    \
+         /// the path relative to the site root\n    \
+         pub path: &'static str,\n    \
+         pub data: &'static [u8],\n    \
+         pub gzip: Option<&'static [u8]>,\n    \
+         pub brotli: Option<&'static [u8]>,\n\
+         }}\n\n\
+         /// the files of the site, sorted by path\n\
+         pub static FILES: &[EmbeddedFile] = &[\n"
+    );
+    let include = |path: &Utf8Path| format!("include_bytes!({:?})", path.as_str());
+    for rel in site_files(root)? {
+        let path = root.join(&rel);
+        let copy = |ext: &str| {
+            let copy = Utf8PathBuf::from(format!("{path}.{ext}"));
+            match copy.is_file() {
+                true => format!("Some({})", include(&copy)),
+                false => "None".to_string(),
+            }
+        };
+        let _ = write!(
+            module,
+            "    EmbeddedFile {{\n        path: {:?},\n        data: {},\n        \
+             gzip: {},\n        brotli: {},\n    }},\n",
+            rel.as_str(),
+            include(&path),
+            copy("gz"),
+            copy("br"),
+        );
+    }
+    let hash_file = match hash_file {
+        Some(file) => format!("Some(include_str!({:?}))", file.as_str()),
+        None => "None".to_string(),
+    };
+    let _ = write!(
+        module,
+        "];\n\n\
+         /// the contents of the hash file, with the hashed names of the files\n\
+         pub static HASH_FILE: Option<&str> = {hash_file};\n\n\
+         /// the file at the path relative to the site root\n\
+         pub fn get(path: &str) -> Option<&'static EmbeddedFile> {{\n    \
+         FILES\n        .binary_search_by(|file| file.path.cmp(path))\n        \
+         .ok()\n        .map(|index| &FILES[index])\n\
+         }}\n"
+    );
+    Ok(module)
+}
+
+/// the files of the site root, with `/` separators and sorted, but the precompressed copies
+fn site_files(root: &Utf8Path) -> Result<Vec<String>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in dir
+            .read_dir_utf8()
+            .context(format!("Could not read {dir}"))?
+        {
+            let path = entry?.into_path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if !matches!(path.extension(), Some("gz" | "br")) {
+                let rel = path.strip_prefix(root)?;
+                files.push(
+                    rel.components()
+                        .map(|c| c.as_str())
+                        .collect::<Vec<_>>()
+                        .join("/"),
+                );
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
@@ -0,0 +1,137 @@
+use std::{
+    collections::HashMap,
+    io::{IsTerminal, Write},
+    process::Stdio,
+    sync::Mutex,
+};
+
+use crate::{config::Project, service::dashboard};
+use tokio::{
+    io::AsyncReadExt,
+    process::{Child, Command},
+    task::JoinHandle,
+};
+
+lazy_static::lazy_static! {
+  /// the errors of the build of each project, by name
+  static ref BUILD_ERRORS: Mutex<HashMap<String, Vec<String>>> = Mutex::new(HashMap::new());
+}
+
+/// Records the error output of a failed build step, for the error overlay of the browser.
+pub fn record_error(proj: &Project, step: &str, output: &str) {
+    let text = error_text(output);
+    if dashboard::is_active() {
+        dashboard::error(format!(
+            "{step}: {}",
+            text.lines().next().unwrap_or_default()
+        ));
+    }
+    BUILD_ERRORS
+        .lock()
+        .unwrap()
+        .entry(proj.name.clone())
+        .or_default()
+        .push(format!("{step}\n\n{text}"));
+}
+
+/// the errors of the project recorded since the last call, if any
+pub fn take_errors(proj: &Project) -> Option<String> {
+    let errors = BUILD_ERRORS.lock().unwrap().remove(&proj.name)?;
+    (!errors.is_empty()).then(|| errors.join("\n\n"))
+}
+
+/// The output without the colors and progress bars, from the first error on (the warnings and
+/// the cargo status lines before it are left out).
+pub fn error_text(output: &str) -> String {
+    let lines = strip_ansi(output)
+        .lines()
+        // the progress bar is redrawn after a carriage return
+        .map(|line| {
+            line.rsplit('\r')
+                .next()
+                .unwrap_or_default()
+                .trim_end()
+                .to_string()
+        })
+        .collect::<Vec<_>>();
+    let first = lines
+        .iter()
+        .position(|line| line.starts_with("error"))
+        .unwrap_or(0);
+    lines[first..].join("\n").trim().to_string()
+}
+
+fn strip_ansi(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            stripped.push(c);
+            continue;
+        }
+        // a CSI sequence ends with a letter, other escapes are two chars long
+        if chars.next() == Some('[') {
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        }
+    }
+    stripped
+}
+
+/// Pipes the stderr of the cargo command when the error overlay or the dashboard is on, keeping
+/// the colors and the progress bar of the terminal without the dashboard.
+pub(crate) fn capture_stderr(proj: &Project, command: &mut Command) {
+    if !proj.error_overlay && !dashboard::is_active() {
+        return;
+    }
+    command.stderr(Stdio::piped());
+    if std::io::stderr().is_terminal() && !dashboard::is_active() {
+        let width = std::env::var("COLUMNS").unwrap_or_else(|_| "80".to_string());
+        command
+            .env("CARGO_TERM_COLOR", "always")
+            .env("CARGO_TERM_PROGRESS_WHEN", "always")
+            .env("CARGO_TERM_PROGRESS_WIDTH", width);
+    }
+}
+
+/// Forwards the piped stderr of the process to ours, or to the logs of the dashboard, keeping a
+/// copy. Empty when not piped.
+pub(crate) fn tee_stderr(process: &mut Child) -> JoinHandle<String> {
+    let stderr = process.stderr.take();
+    tokio::spawn(async move {
+        let Some(mut stderr) = stderr else {
+            return String::new();
+        };
+        let forward = |lines: &[u8]| {
+            if dashboard::is_active() {
+                String::from_utf8_lossy(lines)
+                    .lines()
+                    .for_each(dashboard::log_line);
+            }
+        };
+        let mut output = Vec::new();
+        let mut forwarded = 0;
+        let mut buf = [0; 4096];
+        while let Ok(read) = stderr.read(&mut buf).await {
+            if read == 0 {
+                break;
+            }
+            if !dashboard::is_active() {
+                let mut out = std::io::stderr();
+                _ = out.write_all(&buf[..read]);
+                _ = out.flush();
+            }
+            output.extend_from_slice(&buf[..read]);
+            // the complete lines
+            if let Some(end) = output[forwarded..].iter().rposition(|b| *b == b'\n') {
+                forward(&output[forwarded..=forwarded + end]);
+                forwarded += end + 1;
+            }
+        }
+        forward(&output[forwarded..]);
+        String::from_utf8_lossy(&output).to_string()
+    })
+}
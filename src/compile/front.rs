@@ -1,14 +1,17 @@
-use super::ChangeSet;
-use crate::config::Project;
+use super::{
+    errors::{capture_stderr, record_error, tee_stderr},
+    record_stage, ChangeSet,
+};
+use crate::config::{HotReloadClient, Project, WasmPackage};
 use crate::ext::sync::{wait_interruptible, CommandResult};
-use crate::ext::{fs, PathBufExt};
+use crate::ext::{fs, PathBufExt, Progress};
 use crate::signal::{Interrupt, Outcome, Product};
 use crate::{
     ext::{
         anyhow::{Context, Result},
         exe::Exe,
     },
-    logger::GRAY,
+    logger::{self, GRAY},
 };
 use camino::Utf8Path;
 use std::sync::Arc;
@@ -20,13 +23,16 @@ use tokio::process::Child;
 use tokio::{process::Command, sync::broadcast, task::JoinHandle};
 use wasm_bindgen_cli_support::Bindgen;
 
+/// the target of the front builds
+const WASM_TARGET: &str = "wasm32-unknown-unknown";
+
 pub async fn front(
     proj: &Arc<Project>,
     changes: &ChangeSet,
 ) -> JoinHandle<Result<Outcome<Product>>> {
     let proj = proj.clone();
     let changes = changes.clone();
-    tokio::spawn(async move {
+    logger::spawn(async move {
         if !changes.need_front_build() {
             log::trace!("Front no changes to rebuild");
             return Ok(Outcome::Success(Product::None));
@@ -34,17 +40,28 @@ pub async fn front(
 
         fs::create_dir_all(&proj.site.root_relative_pkg_dir()).await?;
 
-        let (envs, line, process) = front_cargo_process("build", true, &proj)?;
+        let (envs, line, mut process) = front_cargo_process("build", true, &proj)?;
+        let stderr = tee_stderr(&mut process);
 
         log::debug!("Running {}", GRAY.paint(&line));
-        match wait_interruptible("Cargo", process, Interrupt::subscribe_any()).await? {
+        match wait_interruptible("Cargo", process, Interrupt::subscribe_any(&proj)).await? {
             CommandResult::Interrupted => return Ok(Outcome::Stopped),
-            CommandResult::Failure(_) => return Ok(Outcome::Failed),
+            CommandResult::Failure(_) => {
+                record_error(&proj, "Cargo front build failed", &stderr.await?);
+                return Ok(Outcome::Failed);
+            }
             _ => {}
         }
         log::debug!("Cargo envs: {}", GRAY.paint(envs));
         log::info!("Cargo finished {}", GRAY.paint(line));
 
+        for package in &proj.lib.extra_wasm {
+            match extra_wasm(&proj, package).await.dot()? {
+                Outcome::Success(_) => {}
+                outcome => return Ok(outcome),
+            }
+        }
+
         bindgen(&proj).await.dot()
     })
 }
@@ -56,6 +73,49 @@ pub fn front_cargo_process(
 ) -> Result<(String, String, Child)> {
     let mut command = Command::new("cargo");
     let (envs, line) = build_cargo_front_cmd(cmd, wasm, proj, &mut command);
+    if cmd == "build" {
+        capture_stderr(proj, &mut command);
+    }
+    Ok((envs, line, command.spawn()?))
+}
+
+/// The cargo tests of the lib for wasm32, run in a headless browser by the wasm-bindgen test
+/// runner with a webdriver: the chromedriver, geckodriver or safaridriver of the env or the path,
+/// or else a downloaded geckodriver.
+pub async fn wasm_test_cargo_process(proj: &Project) -> Result<(String, String, Child)> {
+    let runner = Exe::WasmBindgenTestRunner.get().await.dot()?;
+    let mut command = Command::new("cargo");
+    let (envs, line) = build_cargo_front_cmd("test", true, proj, &mut command);
+    let mut extra = vec![
+        (
+            "CARGO_TARGET_WASM32_UNKNOWN_UNKNOWN_RUNNER".to_string(),
+            runner.to_string_lossy().to_string(),
+        ),
+        // the tests not configured with run_in_browser would otherwise run in node
+        ("WASM_BINDGEN_USE_BROWSER".to_string(), "1".to_string()),
+    ];
+    const DRIVERS: [(&str, &str); 3] = [
+        ("CHROMEDRIVER", "chromedriver"),
+        ("GECKODRIVER", "geckodriver"),
+        ("SAFARIDRIVER", "safaridriver"),
+    ];
+    let found = DRIVERS
+        .iter()
+        .any(|(var, exe)| std::env::var_os(var).is_some() || which::which(exe).is_ok());
+    if !found {
+        let driver = Exe::GeckoDriver.get().await.dot()?;
+        extra.push((
+            "GECKODRIVER".to_string(),
+            driver.to_string_lossy().to_string(),
+        ));
+    }
+    let envs = extra
+        .iter()
+        .map(|(name, val)| format!("{name}={val}"))
+        .chain((!envs.is_empty()).then_some(envs))
+        .collect::<Vec<_>>()
+        .join(" ");
+    command.envs(extra);
     Ok((envs, line, command.spawn()?))
 }
 
@@ -68,12 +128,19 @@ pub fn build_cargo_front_cmd(
     let mut args = vec![
         cmd.to_string(),
         format!("--package={}", proj.lib.name.as_str()),
-        "--lib".to_string(),
-        format!("--target-dir={}", &proj.lib.front_target_path),
     ];
+    // the benches are separate targets
+    if cmd != "bench" {
+        args.push("--lib".to_string());
+    }
+    args.push(format!("--target-dir={}", &proj.lib.front_target_path));
 
     if wasm {
-        args.push("--target=wasm32-unknown-unknown".to_string());
+        args.push(format!("--target={WASM_TARGET}"));
+    }
+
+    if proj.timings {
+        args.push("--timings".to_string());
     }
 
     if !proj.lib.default_features {
@@ -89,9 +156,13 @@ pub fn build_cargo_front_cmd(
         args.extend_from_slice(cargo_args);
     }
 
-    proj.lib.profile.add_to_args(&mut args);
+    // the benches are built with the bench profile
+    if cmd != "bench" {
+        proj.lib.profile.add_to_args(&mut args);
+    }
 
-    let envs = proj.to_envs();
+    let mut envs = proj.to_envs();
+    envs.extend(proj.reproducible_cargo_envs(wasm.then_some(WASM_TARGET)));
 
     let envs_str = envs
         .iter()
@@ -104,13 +175,18 @@ pub fn build_cargo_front_cmd(
     (envs_str, line)
 }
 
+/// the client of the build error overlay and of the server-sent events live-reload fallback,
+/// appended to the front js in watch mode
+const RELOAD_CLIENT: &str = include_str!("reload_client.js");
+
 async fn bindgen(proj: &Project) -> Result<Outcome<Product>> {
     let wasm_file = &proj.lib.wasm_file;
-    let interrupt = Interrupt::subscribe_any();
+    let interrupt = Interrupt::subscribe_any(proj);
 
     log::info!("Front generating JS/WASM with wasm-bindgen");
 
     let start_time = tokio::time::Instant::now();
+    let progress = Progress::spinner("Front wasm-bindgen");
     // see:
     // https://github.com/rustwasm/wasm-bindgen/blob/main/crates/cli-support/src/lib.rs#L95
     // https://github.com/rustwasm/wasm-bindgen/blob/main/crates/cli/src/bin/wasm-bindgen.rs#L13
@@ -126,12 +202,14 @@ async fn bindgen(proj: &Project) -> Result<Outcome<Product>> {
 
     let bindgen_generate_end_time = tokio::time::Instant::now();
 
+    record_stage("wasm-bindgen", bindgen_generate_end_time - start_time);
     log::debug!(
         "Finished generating wasm-bindgen output in {:?}",
         bindgen_generate_end_time - start_time
     );
 
     bindgen.emit(wasm_file.dest.clone().without_last()).dot()?;
+    progress.finish();
 
     let bindgen_emit_end_time = tokio::time::Instant::now();
     log::debug!(
@@ -153,7 +231,10 @@ async fn bindgen(proj: &Project) -> Result<Outcome<Product>> {
     .dot()?;
 
     if proj.release {
-        match optimize(&wasm_file.dest, interrupt).await.dot()? {
+        let progress = Progress::spinner("Front wasm-opt");
+        let result = optimize(&wasm_file.dest, interrupt).await.dot()?;
+        progress.finish();
+        match result {
             CommandResult::Interrupted => return Ok(Outcome::Stopped),
             CommandResult::Failure(_) => return Ok(Outcome::Failed),
             _ => {}
@@ -161,24 +242,52 @@ async fn bindgen(proj: &Project) -> Result<Outcome<Product>> {
     }
 
     let wasm_optimize_end_time = tokio::time::Instant::now();
+    if proj.release {
+        record_stage("wasm-opt", wasm_optimize_end_time - bindgen_emit_end_time);
+    }
     log::debug!(
         "Finished optimizing WASM in {:?}",
         wasm_optimize_end_time - bindgen_emit_end_time
     );
 
+    let mut js = bindgen.js().to_string();
+    if proj.watch {
+        // the port the leptos client connects to
+        let port = proj.site.external_reload_port().to_string();
+        match &proj.hot_reload_client {
+            HotReloadClient::Default => js.push_str(
+                &RELOAD_CLIENT
+                    .replace("RELOAD_PORT", &port)
+                    .replace("ERROR_OVERLAY", &proj.error_overlay.to_string())
+                    .replace("CSR", &proj.bin.is_none().to_string())
+                    .replace("BASE_URL", &serde_json::to_string(&proj.site.base_url)?),
+            ),
+            HotReloadClient::Disabled => {}
+            HotReloadClient::Custom(path) => {
+                let script = fs::read_to_string(proj.working_dir.join(path))
+                    .await
+                    .context("Could not read the hot-reload client script")?;
+                js.push('\n');
+                js.push_str(&script.replace("RELOAD_PORT", &port));
+            }
+        }
+    }
     if proj.js_minify {
         proj.site
-            .updated_with(&proj.lib.js_file, minify(bindgen.js())?.as_bytes())
+            .updated_with(&proj.lib.js_file, minify(js)?.as_bytes())
             .await
             .dot()?
     } else {
         proj.site
-            .updated_with(&proj.lib.js_file, bindgen.js().as_bytes())
+            .updated_with(&proj.lib.js_file, js.as_bytes())
             .await
             .dot()?
     };
 
     let js_minify_end_time = tokio::time::Instant::now();
+    if proj.js_minify {
+        record_stage("js-minify", js_minify_end_time - wasm_optimize_end_time);
+    }
     log::debug!(
         "Finished minifying JS in {:?}",
         js_minify_end_time - wasm_optimize_end_time
@@ -193,6 +302,72 @@ async fn bindgen(proj: &Project) -> Result<Outcome<Product>> {
     Ok(Outcome::Success(Product::Front))
 }
 
+/// Builds an extra wasm package with the profile of the lib and generates its js into the pkg dir
+async fn extra_wasm(proj: &Project, package: &WasmPackage) -> Result<Outcome<Product>> {
+    let mut args = vec![
+        "build".to_string(),
+        format!("--package={}", package.name),
+        "--lib".to_string(),
+        format!("--target-dir={}", &proj.lib.front_target_path),
+        format!("--target={WASM_TARGET}"),
+    ];
+    proj.lib.profile.add_to_args(&mut args);
+
+    let mut command = Command::new("cargo");
+    command
+        .args(&args)
+        .envs(proj.to_envs())
+        .envs(proj.reproducible_cargo_envs(Some(WASM_TARGET)));
+    capture_stderr(proj, &mut command);
+    let line = super::build_cargo_command_string(args);
+    let mut process = command.spawn()?;
+    let stderr = tee_stderr(&mut process);
+
+    log::debug!("Running {}", GRAY.paint(&line));
+    match wait_interruptible("Cargo", process, Interrupt::subscribe_any(proj)).await? {
+        CommandResult::Interrupted => return Ok(Outcome::Stopped),
+        CommandResult::Failure(_) => {
+            record_error(proj, "Cargo extra wasm build failed", &stderr.await?);
+            return Ok(Outcome::Failed);
+        }
+        _ => {}
+    }
+    log::info!("Cargo finished {}", GRAY.paint(line));
+
+    let mut bindgen = Bindgen::new()
+        .debug(proj.wasm_debug)
+        .keep_debug(proj.wasm_debug)
+        .input_path(&package.wasm_file.source)
+        .out_name(&package.output_name)
+        .web(true)
+        .dot()?
+        .generate_output()
+        .dot()?;
+    bindgen
+        .emit(package.wasm_file.dest.clone().without_last())
+        .dot()?;
+
+    if proj.release {
+        match optimize(&package.wasm_file.dest, Interrupt::subscribe_any(proj))
+            .await
+            .dot()?
+        {
+            CommandResult::Interrupted => return Ok(Outcome::Stopped),
+            CommandResult::Failure(_) => return Ok(Outcome::Failed),
+            _ => {}
+        }
+    }
+    if proj.js_minify {
+        let js = minify(bindgen.js())?;
+        fs::write(&package.js_file.dest, js).await.dot()?;
+    }
+    log::info!(
+        "Front generated {}",
+        GRAY.paint(package.js_file.site.as_str())
+    );
+    Ok(Outcome::Success(Product::Front))
+}
+
 async fn optimize(
     file: &Utf8Path,
     interrupt: broadcast::Receiver<()>,
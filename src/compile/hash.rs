@@ -1,25 +1,42 @@
-use crate::config::Project;
+use super::images::IMAGE_MANIFEST;
+use crate::config::{CacheBust, HashFileFormat, Project};
 use crate::ext::anyhow::Context;
+use crate::service::site::is_html;
 use anyhow::Result;
 use base64ct::{Base64UrlUnpadded, Encoding};
-use camino::Utf8PathBuf;
+use camino::{Utf8Path, Utf8PathBuf};
 use md5::{Digest, Md5};
-use std::collections::HashMap;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 
 ///Adds hashes to the filenames of the css, js, and wasm files in the output
+///and, with `hash-assets`, of the files copied from the assets dir.
+///With `cache-bust = "query"`, the files keep their names and the hashes are
+///added to their urls as query strings instead
 pub fn add_hashes_to_site(proj: &Project) -> Result<()> {
     let files_to_hashes = compute_front_file_hashes(proj).dot()?;
 
     log::debug!("Hash computed: {files_to_hashes:?}");
 
-    let renamed_files = rename_files(&files_to_hashes).dot()?;
+    let renamed_files = match proj.cache_bust {
+        CacheBust::Rename => rename_files(&files_to_hashes).dot()?,
+        CacheBust::Query => query_files(&files_to_hashes),
+    };
 
-    replace_in_file(
-        &renamed_files[&proj.lib.js_file.dest],
-        &renamed_files,
-        &proj.site.root_relative_pkg_dir(),
-    );
+    let js_file = match proj.cache_bust {
+        CacheBust::Rename => &renamed_files[&proj.lib.js_file.dest],
+        CacheBust::Query => &proj.lib.js_file.dest,
+    };
+    replace_in_file(js_file, &renamed_files, &proj.site.root_relative_pkg_dir());
+    // the js of the extra wasm packages load their wasm by name
+    for package in &proj.lib.extra_wasm {
+        let js_file = match proj.cache_bust {
+            CacheBust::Rename => &renamed_files[&package.js_file.dest],
+            CacheBust::Query => &package.js_file.dest,
+        };
+        replace_in_file(js_file, &renamed_files, &proj.site.root_relative_pkg_dir());
+    }
 
     fs::create_dir_all(
         proj.hash_file
@@ -29,34 +46,179 @@ pub fn add_hashes_to_site(proj: &Project) -> Result<()> {
     )
     .with_context(|| format!("Failed to create parent dir for {}", proj.hash_file.abs))?;
 
-    fs::write(
-        &proj.hash_file.abs,
-        format!(
-            "{}: {}\n{}: {}\n{}: {}\n",
-            proj.lib
-                .js_file
-                .dest
-                .extension()
-                .ok_or(anyhow::anyhow!("no extension"))?,
-            files_to_hashes[&proj.lib.js_file.dest],
-            proj.lib
-                .wasm_file
-                .dest
-                .extension()
-                .ok_or(anyhow::anyhow!("no extension"))?,
-            files_to_hashes[&proj.lib.wasm_file.dest],
-            proj.style
-                .site_file
-                .dest
-                .extension()
-                .ok_or(anyhow::anyhow!("no extension"))?,
-            files_to_hashes[&proj.style.site_file.dest]
-        ),
-    )
-    .with_context(|| format!("Failed to write hash file to {}", proj.hash_file.abs))?;
+    let hashed_assets = if proj.hash_assets {
+        hash_assets(proj).dot()?
+    } else {
+        Vec::new()
+    };
+
+    let root = &proj.site.root_dir;
+    let mut hashed = hashed_files(root, &files_to_hashes, renamed_files)?;
+    // without a server to read the hash file, the pages of csr projects link the hashed files
+    if proj.cache_bust == CacheBust::Query || proj.bin.is_none() {
+        bust_html(proj, hashed.iter().chain(&hashed_assets).collect()).dot()?;
+    }
 
+    // leptos reads the text file, which is written with the json format too
+    let extra_wasm = hashed.iter().filter(|file| {
+        proj.lib
+            .extra_wasm
+            .iter()
+            .any(|package| file.rel == package.js_file.site || file.rel == package.wasm_file.site)
+    });
+    let listed = extra_wasm.chain(&hashed_assets).collect::<Vec<_>>();
+    let hashes = text_hash_file(proj, &files_to_hashes, &listed)?;
+    fs::write(&proj.hash_file.abs, hashes)
+        .with_context(|| format!("Failed to write hash file to {}", proj.hash_file.abs))?;
     log::debug!("Hash written to {}", proj.hash_file.abs);
 
+    if proj.hash_file.format == HashFileFormat::Json {
+        hashed.extend(hashed_assets);
+        let json = proj.hash_file.json_abs();
+        fs::write(&json, json_hash_file(proj, &hashed)?)
+            .with_context(|| format!("Failed to write hash file to {json}"))?;
+        log::debug!("Hash json written to {json}");
+    }
+
+    Ok(())
+}
+
+/// a file renamed with its hash (or with it as query string), with its paths relative to
+/// the site root
+struct HashedFile {
+    rel: Utf8PathBuf,
+    hashed: Utf8PathBuf,
+    hash: String,
+    size: u64,
+}
+
+/// the renamed (or query string) paths, sorted
+fn hashed_files(
+    root: &Utf8Path,
+    files_to_hashes: &HashMap<Utf8PathBuf, String>,
+    renamed_files: HashMap<Utf8PathBuf, Utf8PathBuf>,
+) -> Result<Vec<HashedFile>> {
+    let mut hashed = renamed_files
+        .into_iter()
+        .map(|(path, new_path)| {
+            let on_disk = if new_path.exists() { &new_path } else { &path };
+            Ok(HashedFile {
+                size: fs::metadata(on_disk)?.len(),
+                rel: path.strip_prefix(root)?.to_path_buf(),
+                hashed: new_path.strip_prefix(root)?.to_path_buf(),
+                hash: files_to_hashes[&path].clone(),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    hashed.sort_by(|a, b| a.rel.cmp(&b.rel));
+    Ok(hashed)
+}
+
+#[derive(Serialize)]
+struct HashManifest {
+    algorithm: &'static str,
+    /// the url the files are served from, once uploaded to a cdn
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prefix: Option<String>,
+    /// the hashed path by original path
+    files: BTreeMap<String, String>,
+    hashes: BTreeMap<String, String>,
+    /// in bytes
+    sizes: BTreeMap<String, u64>,
+}
+
+/// the `js: <hash>` lines read by leptos, followed by the files of the extra wasm packages, the
+/// hashed assets and the `asset-url-prefix`
+fn text_hash_file(
+    proj: &Project,
+    files_to_hashes: &HashMap<Utf8PathBuf, String>,
+    listed: &[&HashedFile],
+) -> Result<String> {
+    let mut hashes = format!(
+        "{}: {}\n{}: {}\n{}: {}\n",
+        proj.lib
+            .js_file
+            .dest
+            .extension()
+            .ok_or(anyhow::anyhow!("no extension"))?,
+        files_to_hashes[&proj.lib.js_file.dest],
+        proj.lib
+            .wasm_file
+            .dest
+            .extension()
+            .ok_or(anyhow::anyhow!("no extension"))?,
+        files_to_hashes[&proj.lib.wasm_file.dest],
+        proj.style
+            .site_file
+            .dest
+            .extension()
+            .ok_or(anyhow::anyhow!("no extension"))?,
+        files_to_hashes[&proj.style.site_file.dest]
+    );
+    // the other files are recorded by their path relative to the site root
+    for asset in listed {
+        hashes.push_str(&format!(
+            "{}: {}\n",
+            url_path(&asset.rel),
+            url_path(&asset.hashed)
+        ));
+    }
+    if let Some(prefix) = &proj.site.asset_url_prefix {
+        hashes.push_str(&format!("prefix: {prefix}\n"));
+    }
+    Ok(hashes)
+}
+
+fn json_hash_file(proj: &Project, hashed: &[HashedFile]) -> Result<String> {
+    let mut manifest = HashManifest {
+        algorithm: "md5-base64url",
+        prefix: proj.site.asset_url_prefix.clone(),
+        files: BTreeMap::new(),
+        hashes: BTreeMap::new(),
+        sizes: BTreeMap::new(),
+    };
+    for file in hashed {
+        let rel = url_path(&file.rel);
+        manifest.files.insert(rel.clone(), url_path(&file.hashed));
+        manifest.hashes.insert(rel.clone(), file.hash.clone());
+        manifest.sizes.insert(rel, file.size);
+    }
+    Ok(serde_json::to_string_pretty(&manifest)?)
+}
+
+/// the hash of the stylesheet, read from the hash file
+pub fn stylesheet_hash(proj: &Project) -> Result<String> {
+    let hashes = fs::read_to_string(&proj.hash_file.abs).context(format!(
+        "Could not read the hash file {}",
+        proj.hash_file.abs
+    ))?;
+    let hash = hashes
+        .lines()
+        .find_map(|line| line.strip_prefix("css: "))
+        .map(str::to_string);
+    hash.ok_or_else(|| anyhow::anyhow!("No css hash in {}", proj.hash_file.abs))
+}
+
+/// The site path of the stylesheet the running server links to: the hashed one when the
+/// files are renamed with their hashes.
+pub fn stylesheet_site_path(proj: &Project) -> Result<Utf8PathBuf> {
+    let site = &proj.style.site_file.site;
+    if !proj.hash_files || proj.cache_bust == CacheBust::Query {
+        return Ok(site.clone());
+    }
+    hashed_path(site, &stylesheet_hash(proj)?)
+}
+
+/// Copies the rebuilt stylesheet over the hashed one the running server links to, as the
+/// server reads the hash file once, so that the browser can swap it in place.
+pub fn update_hashed_stylesheet(proj: &Project) -> Result<()> {
+    if !proj.hash_files || proj.cache_bust == CacheBust::Query {
+        return Ok(());
+    }
+    let dest = &proj.style.site_file.dest;
+    let hashed = hashed_path(dest, &stylesheet_hash(proj)?)?;
+    fs::copy(dest, &hashed).with_context(|| format!("Failed to copy {dest} to {hashed}"))?;
+    log::debug!("Hash updated the stylesheet {hashed}");
     Ok(())
 }
 
@@ -96,14 +258,8 @@ fn compute_front_file_hashes(proj: &Project) -> Result<HashMap<Utf8PathBuf, Stri
                         }
                     }
 
-                    let hash = Base64UrlUnpadded::encode_string(
-                        &Md5::new().chain_update(fs::read(&path)?).finalize(),
-                    );
-
-                    files_to_hashes.insert(
-                        Utf8PathBuf::from_path_buf(path).expect("invalid path"),
-                        hash,
-                    );
+                    let path = Utf8PathBuf::from_path_buf(path).expect("invalid path");
+                    files_to_hashes.insert(path.clone(), file_hash(&path)?);
                 } else if path.is_dir() {
                     stack.push(path);
                 }
@@ -114,20 +270,79 @@ fn compute_front_file_hashes(proj: &Project) -> Result<HashMap<Utf8PathBuf, Stri
     Ok(files_to_hashes)
 }
 
+fn file_hash(path: &Utf8Path) -> Result<String> {
+    Ok(Base64UrlUnpadded::encode_string(
+        &Md5::new().chain_update(fs::read(path)?).finalize(),
+    ))
+}
+
+/// Adds the hashes to the names of the asset files copied to the site, except for the html
+/// pages which are requested by their name. The assets renamed by a previous build, which the
+/// resync left as they were, keep their names, which are recorded in the asset registry.
+/// Returns the original and hashed paths relative to the site root, sorted.
+fn hash_assets(proj: &Project) -> Result<Vec<HashedFile>> {
+    let root = &proj.site.root_dir;
+    let mut registry = proj
+        .site
+        .asset_reg
+        .try_write()
+        .context("The assets are being synchronized")?;
+
+    let mut files_to_hashes = HashMap::new();
+    let mut copied = HashMap::new();
+    let mut renamed_files = HashMap::new();
+    for (rel, asset) in registry.iter().filter(|(rel, _)| !is_html(rel)) {
+        let path = root.join(rel);
+        let hash = file_hash(&root.join(&asset.dest))?;
+        if asset.dest == *rel {
+            copied.insert(path.clone(), hash.clone());
+        } else {
+            renamed_files.insert(path.clone(), root.join(&asset.dest));
+        }
+        files_to_hashes.insert(path, hash);
+    }
+    renamed_files.extend(match proj.cache_bust {
+        CacheBust::Rename => rename_files(&copied)?,
+        CacheBust::Query => query_files(&copied),
+    });
+    if proj.cache_bust == CacheBust::Rename {
+        for (rel, asset) in registry.iter_mut() {
+            if let Some(renamed) = renamed_files.get(&root.join(rel)) {
+                asset.dest = renamed.strip_prefix(root)?.to_path_buf();
+            }
+        }
+    }
+    let hashed = hashed_files(root, &files_to_hashes, renamed_files)?;
+
+    // the optimized images are listed by their original path
+    let manifest = root.join(IMAGE_MANIFEST);
+    if manifest.exists() {
+        let mut contents = fs::read_to_string(&manifest)?;
+        for asset in &hashed {
+            contents = contents.replace(
+                &format!("\"{}\"", url_path(&asset.rel)),
+                &format!("\"{}\"", url_path(&asset.hashed)),
+            );
+        }
+        fs::write(&manifest, contents)?;
+    }
+
+    log::debug!("Hash added to {} asset files", hashed.len());
+    Ok(hashed)
+}
+
+/// the path as used in urls
+fn url_path(rel: &Utf8Path) -> String {
+    rel.as_str().replace('\\', "/")
+}
+
 fn rename_files(
     files_to_hashes: &HashMap<Utf8PathBuf, String>,
 ) -> Result<HashMap<Utf8PathBuf, Utf8PathBuf>> {
     let mut old_to_new_paths = HashMap::new();
 
     for (path, hash) in files_to_hashes {
-        let mut new_path = path.clone();
-
-        new_path.set_file_name(format!(
-            "{}.{}.{}",
-            path.file_stem().ok_or(anyhow::anyhow!("no file stem"))?,
-            hash,
-            path.extension().ok_or(anyhow::anyhow!("no extension"))?,
-        ));
+        let new_path = hashed_path(path, hash)?;
 
         fs::rename(path, &new_path)
             .with_context(|| format!("Failed to rename {path} to {new_path}"))?;
@@ -138,6 +353,61 @@ fn rename_files(
     Ok(old_to_new_paths)
 }
 
+/// the path with the hash before the extension: `app.<hash>.css`
+fn hashed_path(path: &Utf8Path, hash: &str) -> Result<Utf8PathBuf> {
+    let mut new_path = path.to_path_buf();
+    new_path.set_file_name(format!(
+        "{}.{}.{}",
+        path.file_stem().ok_or(anyhow::anyhow!("no file stem"))?,
+        hash,
+        path.extension().ok_or(anyhow::anyhow!("no extension"))?,
+    ));
+    Ok(new_path)
+}
+
+/// the paths with the hash as query string, for keeping the file names
+fn query_files(
+    files_to_hashes: &HashMap<Utf8PathBuf, String>,
+) -> HashMap<Utf8PathBuf, Utf8PathBuf> {
+    files_to_hashes
+        .iter()
+        .map(|(path, hash)| (path.clone(), Utf8PathBuf::from(format!("{path}?v={hash}"))))
+        .collect()
+}
+
+/// Adds the query strings (or the hashes of the renamed files) to the absolute urls of the hashed
+/// files in the html pages of the site, such as the `index.html` of client side rendered apps.
+fn bust_html(proj: &Project, hashed: Vec<&HashedFile>) -> Result<()> {
+    let root = &proj.site.root_dir;
+    let mut pages = proj
+        .site
+        .asset_reg
+        .try_read()
+        .context("The assets are being synchronized")?
+        .keys()
+        .filter(|rel| is_html(rel))
+        .map(|rel| root.join(rel))
+        .collect::<Vec<_>>();
+    let index = root.join("index.html");
+    if index.exists() && !pages.contains(&index) {
+        pages.push(index);
+    }
+
+    for page in pages {
+        let mut contents = fs::read_to_string(&page)?;
+        for file in &hashed {
+            for quote in ['"', '\''] {
+                contents = contents.replace(
+                    &format!("/{}{quote}", url_path(&file.rel)),
+                    &format!("/{}{quote}", url_path(&file.hashed)),
+                );
+            }
+        }
+        fs::write(&page, contents)?;
+    }
+    Ok(())
+}
+
 fn replace_in_file(
     path: &Utf8PathBuf,
     old_to_new_paths: &HashMap<Utf8PathBuf, Utf8PathBuf>,
@@ -146,6 +416,12 @@ fn replace_in_file(
     let mut contents = fs::read_to_string(path)
         .unwrap_or_else(|e| panic!("error {e}: could not read file {}", path));
 
+    // longest paths first so that a path is never replaced inside a longer one,
+    // which also makes the output independent of the hash map's iteration order
+    let mut old_to_new_paths = old_to_new_paths.iter().collect::<Vec<_>>();
+    old_to_new_paths
+        .sort_by(|(a, _), (b, _)| b.as_str().len().cmp(&a.as_str().len()).then(a.cmp(b)));
+
     for (old_path, new_path) in old_to_new_paths {
         let old_path = old_path
             .strip_prefix(root_dir)
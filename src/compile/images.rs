@@ -0,0 +1,355 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io::Cursor,
+};
+
+use crate::{
+    config::{ImageFormat, ImagesConfig},
+    ext::anyhow::{anyhow, bail, Context, Result},
+    logger::GRAY,
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use image::{
+    codecs::{
+        avif::AvifEncoder,
+        jpeg::JpegEncoder,
+        png::{CompressionType, FilterType, PngEncoder},
+    },
+    DynamicImage,
+};
+use serde::{Deserialize, Serialize};
+
+/// the name of the manifest written to the site root
+pub const IMAGE_MANIFEST: &str = "assets-manifest.json";
+
+/// the file of a cached image listing its files, written after them
+const CACHED_ENTRY: &str = "entry.json";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ManifestEntry {
+    width: u32,
+    height: u32,
+    variants: Vec<Variant>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Variant {
+    path: String,
+    format: String,
+    width: u32,
+    height: u32,
+}
+
+/// The optimized image as cached: its manifest entry and the names of the files generated
+/// next to it, with the recompressed original when it was smaller.
+#[derive(Debug, Serialize, Deserialize)]
+struct Cached {
+    entry: ManifestEntry,
+    files: Vec<String>,
+}
+
+/// the files generated for an image, by name
+type Outputs = Vec<(String, Vec<u8>)>;
+
+/// the number of optimized images, and of those encoded rather than taken from the cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImagesOptimized {
+    pub images: usize,
+    pub encoded: usize,
+}
+
+/// Recompresses the png and jpeg files among the given assets (relative to the assets dir and
+/// to the site root they are copied to), generates the configured format and width variants
+/// next to them and writes `assets-manifest.json`, mapping each original image to its variants.
+///
+/// The generated files are kept in the `cache_dir` by the content of the source image and the
+/// settings, so that the images unchanged since a previous sync are copied rather than encoded.
+pub async fn optimize_images(
+    config: &ImagesConfig,
+    assets_dir: &Utf8Path,
+    site_root: &Utf8Path,
+    cache_dir: &Utf8Path,
+    mut files: Vec<Utf8PathBuf>,
+) -> Result<ImagesOptimized> {
+    let config = config.clone();
+    let src = assets_dir.to_path_buf();
+    let root = site_root.to_path_buf();
+    let cache_dir = cache_dir.to_path_buf();
+
+    tokio::task::spawn_blocking(move || {
+        files.sort();
+        let images: Vec<&Utf8PathBuf> = files
+            .iter()
+            .filter(|rel| source_format(rel).is_some())
+            .collect();
+        check_collisions(&images, &files, &config.formats)?;
+
+        let mut manifest = BTreeMap::new();
+        let mut keys = HashSet::new();
+        let mut encoded = 0;
+        for rel in images {
+            let source = std::fs::read(src.join(rel)).context(format!("Could not read {rel:?}"))?;
+            let key = cache_key(rel, &source, &config);
+            let cached = cache_dir.join(&key);
+            let dest_dir = root.join(rel).parent().unwrap_or(&root).to_path_buf();
+            let entry = match read_cached(&cached) {
+                Some(Cached { entry, files }) => {
+                    log::trace!("Assets image {} unchanged", GRAY.paint(rel.as_str()));
+                    for name in files {
+                        std::fs::copy(cached.join(&name), dest_dir.join(&name))?;
+                    }
+                    entry
+                }
+                None => {
+                    let (entry, outputs) = optimize(rel, &source, &config)
+                        .context(format!("Could not optimize image {rel:?}"))?;
+                    encoded += 1;
+                    write_outputs(&dest_dir, &cached, entry, outputs)?
+                }
+            };
+            keys.insert(key);
+            manifest.insert(url_path(rel), entry);
+        }
+        prune_cache(&cache_dir, &keys);
+
+        let file = root.join(IMAGE_MANIFEST);
+        let json = serde_json::to_string_pretty(&manifest)?;
+        std::fs::write(&file, json).context(format!("Could not write {file:?}"))?;
+        log::debug!(
+            "Assets image manifest written to {}",
+            GRAY.paint(file.as_str())
+        );
+        Ok(ImagesOptimized {
+            images: manifest.len(),
+            encoded,
+        })
+    })
+    .await?
+}
+
+/// the originals that get optimized. Other formats are left as they are
+fn source_format(rel: &Utf8Path) -> Option<image::ImageFormat> {
+    match rel.extension()?.to_lowercase().as_str() {
+        "png" => Some(image::ImageFormat::Png),
+        "jpg" | "jpeg" => Some(image::ImageFormat::Jpeg),
+        _ => None,
+    }
+}
+
+/// Fails when two images would generate the same variant, as `a.png` and `a.jpg` both generate
+/// `a.webp`, or when a variant would overwrite another asset.
+fn check_collisions(
+    images: &[&Utf8PathBuf],
+    files: &[Utf8PathBuf],
+    formats: &[ImageFormat],
+) -> Result<()> {
+    let assets: HashSet<&Utf8Path> = files.iter().map(Utf8PathBuf::as_path).collect();
+    let mut generated: HashMap<Utf8PathBuf, &Utf8Path> = HashMap::new();
+    for rel in images {
+        for format in formats {
+            let to = rel.with_extension(format.extension());
+            if assets.contains(to.as_path()) {
+                bail!("The variant {to} of the image {rel} would overwrite the asset, rename one of them");
+            }
+            if let Some(other) = generated.insert(to.clone(), rel) {
+                bail!("The images {other} and {rel} would both generate {to}, rename one of them");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// the cache entry of the source image with the settings and the version of cargo-leptos,
+/// whose encoders may change
+fn cache_key(rel: &Utf8Path, source: &[u8], config: &ImagesConfig) -> String {
+    let mut data = format!(
+        "{}\n{}\n{:?}\n{:?}\n{}\n",
+        env!("CARGO_PKG_VERSION"),
+        url_path(rel),
+        config.formats,
+        config.widths,
+        config.quality
+    )
+    .into_bytes();
+    data.extend_from_slice(source);
+    format!("{:016x}", seahash::hash(&data))
+}
+
+/// the cached image, when all its files are there
+fn read_cached(cached: &Utf8Path) -> Option<Cached> {
+    let json = std::fs::read_to_string(cached.join(CACHED_ENTRY)).ok()?;
+    let entry: Cached = serde_json::from_str(&json).ok()?;
+    entry
+        .files
+        .iter()
+        .all(|name| cached.join(name).is_file())
+        .then_some(entry)
+}
+
+/// writes the generated files next to the image and into its cache entry
+fn write_outputs(
+    dest_dir: &Utf8Path,
+    cached: &Utf8Path,
+    entry: ManifestEntry,
+    outputs: Outputs,
+) -> Result<ManifestEntry> {
+    std::fs::create_dir_all(cached).context(format!("Could not create {cached:?}"))?;
+    let mut files = Vec::new();
+    for (name, data) in outputs {
+        std::fs::write(dest_dir.join(&name), &data)?;
+        std::fs::write(cached.join(&name), &data)?;
+        files.push(name);
+    }
+    let cache = Cached { entry, files };
+    std::fs::write(
+        cached.join(CACHED_ENTRY),
+        serde_json::to_string_pretty(&cache)?,
+    )?;
+    Ok(cache.entry)
+}
+
+/// removes the cache entries of the images that were changed or removed
+fn prune_cache(cache_dir: &Utf8Path, keys: &HashSet<String>) {
+    let Ok(entries) = cache_dir.read_dir_utf8() else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if !keys.contains(entry.file_name()) {
+            log::trace!("Assets removing cached image {}", entry.file_name());
+            _ = std::fs::remove_dir_all(entry.path());
+        }
+    }
+}
+
+/// The recompressed original, when smaller, and the variants of the image, by file name.
+fn optimize(
+    rel: &Utf8Path,
+    source: &[u8],
+    config: &ImagesConfig,
+) -> Result<(ManifestEntry, Outputs)> {
+    let source_format = source_format(rel).unwrap();
+    let img = image::load_from_memory_with_format(source, source_format)?;
+    let quality = config.quality;
+    let mut outputs = Vec::new();
+
+    // only replace the original when the recompressed file is actually smaller
+    let recompressed = encode_original(&img, source_format, quality)?;
+    if recompressed.len() < source.len() {
+        log::trace!(
+            "Assets recompressed {} from {} to {} bytes",
+            GRAY.paint(rel.as_str()),
+            source.len(),
+            recompressed.len()
+        );
+        let name = rel.file_name().unwrap_or_default().to_string();
+        outputs.push((name, recompressed));
+    }
+
+    let mut variants = Vec::new();
+    let resized = config
+        .widths
+        .iter()
+        .filter(|width| **width < img.width())
+        .map(|width| {
+            let resized = img.resize(*width, u32::MAX, image::imageops::FilterType::Lanczos3);
+            (Some(*width), resized)
+        });
+
+    for (width, img) in std::iter::once((None, img.clone())).chain(resized) {
+        let stem = match width {
+            Some(width) => format!("{}-{width}w", rel.file_stem().unwrap_or_default()),
+            None => rel.file_stem().unwrap_or_default().to_string(),
+        };
+        let ext = rel.extension().unwrap_or_default();
+
+        if width.is_some() {
+            let name = format!("{stem}.{ext}");
+            variants.push(variant(&rel.with_file_name(&name), ext, &img));
+            outputs.push((name, encode_original(&img, source_format, quality)?));
+        }
+
+        for format in &config.formats {
+            let name = format!("{stem}.{}", format.extension());
+            variants.push(variant(
+                &rel.with_file_name(&name),
+                format.extension(),
+                &img,
+            ));
+            outputs.push((name, encode(&img, *format, quality)?));
+        }
+    }
+
+    log::debug!(
+        "Assets optimized image {} with {} variants",
+        GRAY.paint(rel.as_str()),
+        variants.len()
+    );
+    let entry = ManifestEntry {
+        width: img.width(),
+        height: img.height(),
+        variants,
+    };
+    Ok((entry, outputs))
+}
+
+fn variant(rel: &Utf8Path, format: &str, img: &DynamicImage) -> Variant {
+    Variant {
+        path: url_path(rel),
+        format: match format.to_lowercase().as_str() {
+            "png" => "png",
+            "jpg" | "jpeg" => "jpeg",
+            "webp" => "webp",
+            _ => "avif",
+        }
+        .to_string(),
+        width: img.width(),
+        height: img.height(),
+    }
+}
+
+fn encode_original(img: &DynamicImage, format: image::ImageFormat, quality: u8) -> Result<Vec<u8>> {
+    let mut buf = Cursor::new(Vec::new());
+    if format == image::ImageFormat::Png {
+        img.write_with_encoder(PngEncoder::new_with_quality(
+            &mut buf,
+            CompressionType::Best,
+            FilterType::Adaptive,
+        ))?;
+    } else {
+        // jpeg has no alpha channel
+        DynamicImage::ImageRgb8(img.to_rgb8())
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut buf, quality))?;
+    }
+    Ok(buf.into_inner())
+}
+
+fn encode(img: &DynamicImage, format: ImageFormat, quality: u8) -> Result<Vec<u8>> {
+    let mut buf = Cursor::new(Vec::new());
+    let img = if img.color().has_alpha() {
+        DynamicImage::ImageRgba8(img.to_rgba8())
+    } else {
+        DynamicImage::ImageRgb8(img.to_rgb8())
+    };
+    match format {
+        // the webp encoder of the image crate is lossless only
+        ImageFormat::WebP => {
+            let encoder = match img.color().has_alpha() {
+                true => webp::Encoder::from_rgba(img.as_bytes(), img.width(), img.height()),
+                false => webp::Encoder::from_rgb(img.as_bytes(), img.width(), img.height()),
+            };
+            let webp = encoder
+                .encode_simple(false, f32::from(quality))
+                .map_err(|e| anyhow!("Could not encode the webp: {e:?}"))?;
+            return Ok(webp.to_vec());
+        }
+        ImageFormat::Avif => {
+            img.write_with_encoder(AvifEncoder::new_with_speed_quality(&mut buf, 6, quality))?
+        }
+    }
+    Ok(buf.into_inner())
+}
+
+/// the path as used in urls, relative to the site root
+fn url_path(rel: &Utf8Path) -> String {
+    rel.as_str().replace('\\', "/")
+}
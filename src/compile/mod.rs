@@ -1,21 +1,63 @@
 #[cfg(test)]
 mod tests;
 
+mod analyze;
 mod assets;
+mod build_info;
 mod change;
+mod collector;
+mod coverage;
+mod critical;
+mod csp;
+mod csr;
+mod css_modules;
+mod embed;
+mod errors;
 mod front;
 mod hash;
+mod images;
+mod postcss;
+mod prerender;
+mod proxy_config;
+mod purge;
+mod pwa;
+mod routes;
 mod sass;
 mod server;
+mod sitemap;
+mod size_report;
+mod static_host;
 mod style;
 mod tailwind;
+mod timings;
 
+pub use analyze::{profile_tables, profile_treemap, site_wasm, WasmProfile};
 pub use assets::assets;
+pub use build_info::build_info;
 pub use change::{Change, ChangeSet};
-pub use front::{front, front_cargo_process};
-pub use hash::add_hashes_to_site;
-pub use server::{server, server_cargo_process};
+pub use coverage::build_cargo_coverage_cmd;
+pub use critical::{critical_css, inline_critical_css};
+pub use csp::csp_hashes;
+pub use csr::csr_index;
+pub use css_modules::css_modules;
+pub use embed::{embed_module, embed_site};
+pub use errors::{error_text, record_error, take_errors};
+pub use front::{build_cargo_front_cmd, front, front_cargo_process, wasm_test_cargo_process};
+pub use hash::{add_hashes_to_site, stylesheet_site_path, update_hashed_stylesheet};
+pub use prerender::{fallback_page, prerender, prerender_routes};
+pub use proxy_config::{caddy_config, nginx_config};
+pub use pwa::pwa;
+pub use routes::{parse_route_list, routes_harness, RouteList};
+pub use sass::sass_dependencies;
+pub use server::{
+    build_cargo_lib_ssr_cmd, build_cargo_server_cmd, lib_ssr_cargo_process, server,
+    server_cargo_process,
+};
+pub use sitemap::{sitemap, sitemap_xml};
+pub use size_report::size_report;
+pub use static_host::write_static_host_files;
 pub use style::style;
+pub use timings::{clear_stages, record_stage, write_timings_report};
 
 use itertools::Itertools;
 
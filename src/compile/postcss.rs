@@ -0,0 +1,47 @@
+use super::errors::record_error;
+use crate::{
+    config::Project,
+    ext::{
+        anyhow::{bail, Result},
+        sync::{wait_piped_with_input_interruptible, CommandResult, OutputExt},
+    },
+    logger::GRAY,
+    signal::{Interrupt, Outcome},
+};
+use tokio::process::Command;
+
+/// Pipes the css through the configured postcss command, which reads it from stdin and
+/// writes the processed css to stdout.
+pub async fn run_postcss(proj: &Project, cmd_line: &str, css: String) -> Result<Outcome<String>> {
+    let Some(args) = shlex::split(cmd_line) else {
+        bail!("Could not parse the postcss-cmd {cmd_line:?}");
+    };
+    let Some((exe, args)) = args.split_first() else {
+        bail!("The postcss-cmd is empty");
+    };
+
+    let mut cmd = Command::new(exe);
+    cmd.args(args)
+        .envs(proj.to_envs())
+        .current_dir(&proj.working_dir);
+
+    log::trace!("Style running {}", GRAY.paint(cmd_line));
+
+    match wait_piped_with_input_interruptible(
+        "PostCSS",
+        cmd,
+        Some(css),
+        Interrupt::subscribe_any(proj),
+    )
+    .await?
+    {
+        CommandResult::Success(output) => Ok(Outcome::Success(output.stdout())),
+        CommandResult::Interrupted => Ok(Outcome::Stopped),
+        CommandResult::Failure(output) => {
+            log::warn!("PostCSS failed with:");
+            println!("{}", output.stderr());
+            record_error(proj, "PostCSS failed", &output.stderr());
+            Ok(Outcome::Failed)
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::{HashSet, VecDeque};
+
+use super::{critical::inline_critical_css, record_stage};
+use crate::{
+    config::Project,
+    ext::{
+        anyhow::{bail, Context, Result},
+        fs, glob_base, glob_set,
+    },
+    logger::GRAY,
+    service::{serve, site::local_addr},
+};
+use camino::{Utf8Path, Utf8PathBuf};
+
+/// Prerenders the prerender-routes into the site root, for them to be served as static html
+/// while the other routes are server rendered. A route with a glob pattern, such as `/blog/*`,
+/// stands for the matching pages that the other routes link to, directly or through other
+/// matching pages.
+pub async fn prerender_routes(proj: &Project) -> Result<()> {
+    let start_time = tokio::time::Instant::now();
+    let (patterns, paths): (Vec<String>, Vec<String>) = proj
+        .prerender_routes
+        .iter()
+        .cloned()
+        .partition(|route| glob_base(route).is_some());
+    let patterns = glob_set(&patterns)?;
+    let pages = prerender(proj, &paths, &proj.site.root_dir, |path| {
+        patterns.is_match(path)
+    })
+    .await?;
+    log::info!(
+        "Prerender wrote {} pages to {}",
+        pages.len(),
+        GRAY.paint(proj.site.root_dir.as_str())
+    );
+    record_stage("prerender", start_time.elapsed());
+    Ok(())
+}
+
+/// Prerenders the fallback-route into `404.html` in the site root, for the static hosts and
+/// CDNs to serve as the not-found page. The route may answer with a 404 status, as the
+/// not-found page of the app does.
+pub async fn fallback_page(proj: &Project) -> Result<()> {
+    let Some(route) = &proj.fallback_route else {
+        return Ok(());
+    };
+    let start_time = tokio::time::Instant::now();
+    let url = format!(
+        "http://{}{}{route}",
+        local_addr(proj.site.server_addr),
+        proj.site.base_url
+    );
+    let html = serve::run_against(proj, async {
+        log::debug!("Prerender fetching {}", GRAY.paint(&url));
+        let resp = reqwest::get(&url)
+            .await
+            .context(format!("Could not fetch {url}"))?;
+        let status = resp.status();
+        if !status.is_success() && status != reqwest::StatusCode::NOT_FOUND {
+            bail!("The fallback-route {route} answered with {status}");
+        }
+        Ok(resp.text().await?)
+    })
+    .await?;
+    let html = inline_critical_css(proj, route, html).await?;
+    let file = proj.site.root_dir.join("404.html");
+    fs::write(&file, proj.site.minified(&file, html.as_bytes())).await?;
+    log::info!(
+        "Prerender wrote the fallback page {}",
+        GRAY.paint(file.as_str())
+    );
+    record_stage("fallback", start_time.elapsed());
+    Ok(())
+}
+
+/// Starts the built server and writes the server rendered html of the paths, relative to the
+/// base url, into the dir: at `<path>/index.html`, or at the path itself for the `.html` ones.
+/// The paths linked from the pages that `follow` accepts are rendered too. The pages that don't
+/// answer with a success status are skipped, and the others get the critical css inlined when
+/// extracted, and are minified with `minify-html`. Returns the paths written.
+pub async fn prerender(
+    proj: &Project,
+    paths: &[String],
+    dir: &Utf8Path,
+    follow: impl Fn(&str) -> bool,
+) -> Result<Vec<String>> {
+    let mut written = Vec::new();
+    for (path, html) in crawl(proj, paths, follow).await? {
+        let file = page_file(dir, &path)?;
+        if let Some(parent) = file.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        let html = inline_critical_css(proj, &path, html).await?;
+        fs::write(&file, proj.site.minified(&file, html.as_bytes())).await?;
+        log::debug!("Prerender wrote {}", GRAY.paint(file.as_str()));
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// Starts the built server and fetches the pages at the paths, relative to the base url, and
+/// the ones they link to that `follow` accepts. Returns the paths and html of the pages that
+/// answered with a success status, in the order they were fetched.
+pub async fn crawl(
+    proj: &Project,
+    paths: &[String],
+    follow: impl Fn(&str) -> bool,
+) -> Result<Vec<(String, String)>> {
+    let base = format!(
+        "http://{}{}",
+        local_addr(proj.site.server_addr),
+        proj.site.base_url
+    );
+    let base_url = proj.site.base_url.as_str();
+    serve::run_against(proj, async {
+        let client = reqwest::Client::new();
+        let mut queue: VecDeque<String> = paths.iter().cloned().collect();
+        let mut seen: HashSet<String> = queue.iter().cloned().collect();
+        let mut pages = Vec::new();
+        while let Some(path) = queue.pop_front() {
+            let url = format!("{base}{path}");
+            log::debug!("Prerender fetching {}", GRAY.paint(&url));
+            let resp = client
+                .get(&url)
+                .send()
+                .await
+                .context(format!("Could not fetch {url}"))?;
+            if !resp.status().is_success() {
+                log::warn!("Prerender skipped {path}, answered with {}", resp.status());
+                continue;
+            }
+            let html = resp.text().await?;
+            for link in linked_paths(&html, base_url) {
+                if follow(&link) && seen.insert(link.clone()) {
+                    queue.push_back(link);
+                }
+            }
+            pages.push((path, html));
+        }
+        Ok(pages)
+    })
+    .await
+}
+
+/// the file of the page at the url path, relative to the base url
+pub fn page_file(dir: &Utf8Path, path: &str) -> Result<Utf8PathBuf> {
+    let rel = path.trim_matches('/');
+    if rel.split('/').any(|segment| segment == "..") {
+        bail!("Invalid page path {path}");
+    }
+    Ok(if rel.is_empty() {
+        dir.join("index.html")
+    } else if Utf8Path::new(rel).extension() == Some("html") {
+        dir.join(rel)
+    } else {
+        dir.join(rel).join("index.html")
+    })
+}
+
+/// The paths of the pages of the site linked by the `href`s of the html, relative to the base
+/// url and without their query and fragment. The links to files other than `.html` ones are
+/// left out.
+pub fn linked_paths(html: &str, base_url: &str) -> Vec<String> {
+    html.split("href=")
+        .skip(1)
+        .filter_map(|rest| {
+            let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+            let value = rest[1..].split(quote).next()?;
+            let path = value.split(['?', '#']).next()?;
+            let rel = path.strip_prefix(base_url)?;
+            if !rel.starts_with('/') || rel.starts_with("//") {
+                return None;
+            }
+            matches!(Utf8Path::new(rel).extension(), None | Some("html")).then(|| rel.to_string())
+        })
+        .collect()
+}
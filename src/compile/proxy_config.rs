@@ -0,0 +1,118 @@
+use std::fmt::Write;
+
+use camino::Utf8Path;
+
+use crate::{config::Project, service::site::local_addr};
+
+/// the Cache-Control of the files whose urls change with their contents
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+
+/// whether the build writes the `.gz` and `.br` files next to the site files
+fn precompressed(proj: &Project) -> bool {
+    proj.release && proj.precompress
+}
+
+/// the Cache-Control of the pkg dir files: immutable when their urls have their hash
+fn pkg_cache_control(proj: &Project) -> &'static str {
+    if proj.hash_files {
+        IMMUTABLE
+    } else {
+        "no-cache"
+    }
+}
+
+/// An nginx snippet to include in the `server` block of the site: the site files are served
+/// from the root, with the hashed pkg files cached as immutable and the precompressed files
+/// served as they are, and the other requests are proxied to the server at the site-addr.
+pub fn nginx_config(proj: &Project, root: &Utf8Path) -> String {
+    let base = &proj.site.base_url;
+    let pkg_dir = &proj.site.pkg_dir;
+    let mut conf = format!(
+        "# generated by cargo-leptos for the project {}\n",
+        proj.name
+    );
+    let compression = if precompressed(proj) {
+        "    gzip_static on;\n    # needs the ngx_brotli module\n    brotli_static on;\n"
+    } else {
+        ""
+    };
+
+    let _ = write!(
+        conf,
+        "\nlocation ^~ {base}/{pkg_dir}/ {{\n    alias {root}/{pkg_dir}/;\n    \
+         add_header Cache-Control \"{}\";\n{compression}}}\n",
+        pkg_cache_control(proj)
+    );
+    match &proj.bin {
+        Some(_) => {
+            let _ = write!(
+                conf,
+                "\nlocation {base}/ {{\n    alias {root}/;\n    try_files $uri @leptos;\n{compression}}}\n\
+                 \nlocation @leptos {{\n    proxy_pass http://{};\n    \
+                 proxy_http_version 1.1;\n    proxy_set_header Host $host;\n    \
+                 proxy_set_header X-Forwarded-For $proxy_add_x_forwarded_for;\n    \
+                 proxy_set_header X-Forwarded-Proto $scheme;\n}}\n",
+                local_addr(proj.site.addr)
+            );
+        }
+        // the index.html of a csr site renders every route
+        None => {
+            let _ = write!(
+                conf,
+                "\nlocation {base}/ {{\n    alias {root}/;\n    \
+                 try_files $uri $uri/ {base}/index.html;\n{compression}}}\n"
+            );
+        }
+    }
+    conf
+}
+
+/// A Caddyfile snippet to import in the site block, serving the site as the nginx one does.
+pub fn caddy_config(proj: &Project, root: &Utf8Path) -> String {
+    let base = &proj.site.base_url;
+    let mut conf = format!(
+        "# generated by cargo-leptos for the project {}\n",
+        proj.name
+    );
+    let handle = if base.is_empty() {
+        "handle {".to_string()
+    } else {
+        format!("handle_path {base}/* {{")
+    };
+    let compression = if precompressed(proj) {
+        " {\n            precompressed br gzip\n        }"
+    } else {
+        ""
+    };
+    let _ = write!(
+        conf,
+        "{handle}\n    root * {root}\n\n    @pkg path /{}/*\n    \
+         header @pkg Cache-Control \"{}\"\n\n",
+        proj.site.pkg_dir,
+        pkg_cache_control(proj)
+    );
+    match &proj.bin {
+        Some(_) => {
+            let _ = write!(
+                conf,
+                "    @static file\n    handle @static {{\n        file_server{compression}\n    }}\n\n    \
+                 handle {{\n"
+            );
+            if !base.is_empty() {
+                let _ = writeln!(conf, "        rewrite * {base}{{uri}}");
+            }
+            let _ = write!(
+                conf,
+                "        reverse_proxy {}\n    }}\n}}\n",
+                local_addr(proj.site.addr)
+            );
+        }
+        None => {
+            let _ = write!(
+                conf,
+                "    try_files {{path}} /index.html\n    file_server{compression}\n}}\n"
+            );
+        }
+    }
+    conf
+}
@@ -0,0 +1,154 @@
+use std::collections::{HashSet, VecDeque};
+
+use super::critical::ident;
+use crate::{
+    config::{Project, PurgeConfig},
+    ext::anyhow::{anyhow, Context, Result},
+    fs,
+};
+use camino::Utf8PathBuf;
+use itertools::Itertools;
+use lightningcss::{
+    rules::CssRule,
+    stylesheet::{ParserFlags, ParserOptions, PrinterOptions, StyleSheet},
+    traits::ToCss,
+};
+
+/// Removes the rules with classes that aren't found in the lib and bin sources (or the css
+/// modules), unless the class is safelisted. The sources aren't parsed: every word in them is a class candidate.
+pub async fn purge_css(proj: &Project, config: &PurgeConfig, css: &str) -> Result<String> {
+    let mut used = HashSet::new();
+    let dirs = proj
+        .lib
+        .src_paths
+        .iter()
+        .chain(proj.bin.iter().flat_map(|bin| &bin.src_paths))
+        .unique();
+    for file in source_files(dirs).context("Scanning the sources for class names")? {
+        used.extend(class_candidates(&fs::read_to_string(&file).await?));
+    }
+    // with the hashed class names of the css modules
+    if let Some(modules) = &proj.style.modules {
+        if modules.rust_file.exists() {
+            used.extend(class_candidates(
+                &fs::read_to_string(&modules.rust_file).await?,
+            ));
+        }
+    }
+
+    let (purged, removed) = purge(css, &|class| {
+        used.contains(class) || config.is_safelisted(class)
+    })?;
+    log::debug!(
+        "Style purged {removed} unused selectors, from {} to {} bytes",
+        css.len(),
+        purged.len()
+    );
+    Ok(purged)
+}
+
+fn source_files<'a>(dirs: impl Iterator<Item = &'a Utf8PathBuf>) -> Result<Vec<Utf8PathBuf>> {
+    let mut found = Vec::new();
+    let mut dirs = dirs.cloned().collect::<VecDeque<_>>();
+    while let Some(dir) = dirs.pop_front() {
+        if !dir.is_dir() {
+            continue;
+        }
+        for entry in dir.read_dir_utf8()? {
+            let entry = entry?;
+            let path = entry.path().to_path_buf();
+            if entry.file_type()?.is_dir() {
+                dirs.push_back(path);
+            } else if path.extension() == Some("rs") {
+                found.push(path);
+            }
+        }
+    }
+    Ok(found)
+}
+
+/// The words of the source that could be class names. `class:active=...` and
+/// `"md:flex"` give both the whole word and its parts.
+pub fn class_candidates(source: &str) -> HashSet<String> {
+    let mut candidates = HashSet::new();
+    let words = source
+        .split(|c: char| c.is_whitespace() || "\"'`<>=(){}[],;!&|".contains(c))
+        .filter(|word| !word.is_empty());
+    for word in words {
+        candidates.insert(word.to_string());
+        if word.contains([':', '.']) {
+            candidates.extend(
+                word.split([':', '.'])
+                    .filter(|part| !part.is_empty())
+                    .map(str::to_string),
+            );
+        }
+    }
+    candidates
+}
+
+/// the purged css and the number of removed selectors
+pub fn purge(css: &str, is_used: &dyn Fn(&str) -> bool) -> Result<(String, usize)> {
+    let options = ParserOptions {
+        flags: ParserFlags::CUSTOM_MEDIA,
+        ..Default::default()
+    };
+    let mut stylesheet = StyleSheet::parse(css, options).map_err(|e| anyhow!("{e}"))?;
+    let mut removed = 0;
+    retain_used(&mut stylesheet.rules.0, is_used, &mut removed);
+    Ok((stylesheet.to_css(PrinterOptions::default())?.code, removed))
+}
+
+fn retain_used(rules: &mut Vec<CssRule>, is_used: &dyn Fn(&str) -> bool, removed: &mut usize) {
+    rules.retain_mut(|rule| match rule {
+        CssRule::Style(style) => {
+            let before = style.selectors.0.len();
+            style.selectors.0.retain(|selector| {
+                selector
+                    .to_css_string(PrinterOptions::default())
+                    .map_or(true, |selector| {
+                        selector_classes(&selector).all(|class| is_used(&class))
+                    })
+            });
+            *removed += before - style.selectors.0.len();
+            !style.selectors.0.is_empty()
+        }
+        CssRule::Media(media) => {
+            retain_used(&mut media.rules.0, is_used, removed);
+            !media.rules.0.is_empty()
+        }
+        CssRule::Supports(supports) => {
+            retain_used(&mut supports.rules.0, is_used, removed);
+            !supports.rules.0.is_empty()
+        }
+        CssRule::LayerBlock(layer) => {
+            retain_used(&mut layer.rules.0, is_used, removed);
+            !layer.rules.0.is_empty()
+        }
+        CssRule::Container(container) => {
+            retain_used(&mut container.rules.0, is_used, removed);
+            !container.rules.0.is_empty()
+        }
+        _ => true,
+    });
+}
+
+/// The classes a selector requires. Those in the arguments of functional pseudo classes
+/// (such as `:not(.a)`) and in attribute selectors aren't.
+fn selector_classes(selector: &str) -> impl Iterator<Item = String> {
+    let mut chars = selector.chars().peekable();
+    let mut depth = 0;
+    let mut classes = Vec::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth -= 1,
+            '\\' => {
+                chars.next();
+            }
+            '.' if depth == 0 => classes.push(ident(&mut chars)),
+            _ => {}
+        }
+    }
+    classes.into_iter()
+}
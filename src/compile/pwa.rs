@@ -0,0 +1,167 @@
+use camino::{Utf8Path, Utf8PathBuf};
+use serde_json::{json, Value};
+
+use super::record_stage;
+use crate::{
+    config::Project,
+    ext::{
+        anyhow::{anyhow, Context, Result},
+        fs,
+    },
+    logger::GRAY,
+    service::site::is_html,
+};
+
+/// the service worker, with the cache version, file list and start url to replace
+const SERVICE_WORKER: &str = include_str!("service_worker.js");
+
+/// Writes the `manifest.webmanifest` of the pwa config and the service worker `sw.js`
+/// precaching the site files into the site root. Run once the names of the files are hashed,
+/// for the service worker to list them, and its cache to be replaced when they change.
+pub async fn pwa(proj: &Project) -> Result<()> {
+    let Some(pwa) = &proj.pwa else {
+        return Ok(());
+    };
+    let start_time = tokio::time::Instant::now();
+    let root = &proj.site.root_dir;
+    let start_url = format!("{}/", proj.site.base_url);
+
+    let icons = pwa
+        .icons
+        .iter()
+        .map(|icon| icon_json(proj, icon))
+        .collect::<Result<Vec<_>>>()?;
+    let mut manifest = json!({
+        "name": pwa.name,
+        "start_url": start_url,
+        "scope": start_url,
+        "display": "standalone",
+        "icons": icons,
+    });
+    for (key, value) in [
+        ("short_name", &pwa.short_name),
+        ("theme_color", &pwa.theme_color),
+        ("background_color", &pwa.background_color),
+    ] {
+        if let Some(value) = value {
+            manifest[key] = Value::from(value.as_str());
+        }
+    }
+    fs::write(
+        root.join("manifest.webmanifest"),
+        serde_json::to_string_pretty(&manifest)?,
+    )
+    .await?;
+
+    let files = precache_files(root)?;
+    let mut contents = Vec::new();
+    for rel in &files {
+        contents.extend_from_slice(rel.as_str().as_bytes());
+        contents.extend(std::fs::read(root.join(rel))?);
+    }
+    let urls = std::iter::once(start_url.clone())
+        .chain(files.iter().map(|rel| proj.site.asset_url(rel)))
+        .collect::<Vec<_>>();
+    let file = root.join("sw.js");
+    fs::write(
+        &file,
+        SERVICE_WORKER
+            .replace("CACHE_VERSION", &format!("{:x}", seahash::hash(&contents)))
+            .replace("PRECACHE_FILES", &serde_json::to_string(&urls)?)
+            .replace("PWA_START_URL", &start_url),
+    )
+    .await?;
+    log::info!(
+        "Pwa service worker precaching {} files written to {}",
+        urls.len(),
+        GRAY.paint(file.as_str())
+    );
+    record_stage("pwa", start_time.elapsed());
+    Ok(())
+}
+
+/// The files of the site root precached by the service worker, relative to it and sorted: all
+/// but the html pages, fetched from the network first, the precompressed copies and the files of
+/// the dot dirs such as `.well-known`.
+pub fn precache_files(root: &Utf8Path) -> Result<Vec<Utf8PathBuf>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in dir
+            .read_dir_utf8()
+            .context(format!("Could not read {dir}"))?
+        {
+            let path = entry?.into_path();
+            let name = path.file_name().unwrap_or_default();
+            if name.starts_with('.') {
+                continue;
+            }
+            if path.is_dir() {
+                dirs.push(path);
+            } else if !is_html(&path)
+                && name != "sw.js"
+                && !matches!(path.extension(), Some("gz" | "br"))
+            {
+                files.push(path.strip_prefix(root)?.to_path_buf());
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// The icon of the web manifest, at its hashed name when the assets are hashed, with its size
+/// and type.
+fn icon_json(proj: &Project, icon: &str) -> Result<Value> {
+    let rel = icon_file(
+        &proj.site.root_dir,
+        Utf8Path::new(icon.trim_start_matches('/')),
+    )?;
+    let path = proj.site.root_dir.join(&rel);
+    let ext = rel.extension().unwrap_or_default().to_lowercase();
+    let mime = match ext.as_str() {
+        "svg" => "image/svg+xml".to_string(),
+        "jpg" | "jpeg" => "image/jpeg".to_string(),
+        "ico" => "image/x-icon".to_string(),
+        _ => format!("image/{ext}"),
+    };
+    let sizes = if ext == "svg" {
+        "any".to_string()
+    } else {
+        let (width, height) = image::image_dimensions(&path)
+            .context(format!("Could not read the size of the pwa icon {path}"))?;
+        format!("{width}x{height}")
+    };
+    Ok(json!({
+        "src": proj.site.asset_url(&rel),
+        "sizes": sizes,
+        "type": mime,
+    }))
+}
+
+/// the file of the icon in the site root, or of its hashed copy (`<stem>.<hash>.<ext>`)
+pub fn icon_file(root: &Utf8Path, icon: &Utf8Path) -> Result<Utf8PathBuf> {
+    if root.join(icon).is_file() {
+        return Ok(icon.to_path_buf());
+    }
+    let not_found = || anyhow!("The pwa icon {icon} is not in the site root {root}");
+    let (stem, ext) = icon
+        .file_stem()
+        .zip(icon.extension())
+        .ok_or_else(not_found)?;
+    let dir = icon.parent().unwrap_or(Utf8Path::new(""));
+    let hashed = root
+        .join(dir)
+        .read_dir_utf8()
+        .map_err(|_| not_found())?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string())
+        .find(|name| {
+            name.strip_prefix(stem)
+                .and_then(|rest| rest.strip_suffix(ext))
+                .and_then(|hash| hash.strip_prefix('.')?.strip_suffix('.'))
+                .is_some_and(|hash| !hash.is_empty() && !hash.contains('.'))
+        })
+        .ok_or_else(not_found)?;
+    Ok(dir.join(hashed))
+}
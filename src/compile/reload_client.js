@@ -0,0 +1,138 @@
+
+// The client of `cargo leptos watch`, appended to the front js: shows the build error overlay,
+// keeps the scroll position and the form values across the live-reloads, and takes over the
+// live-reload with server-sent events when the websocket can't connect (e.g. through proxies
+// and tunnels that break websockets).
+(() => {
+  if (typeof window === "undefined") return;
+  const port = RELOAD_PORT;
+  const overlay = ERROR_OVERLAY;
+  // no leptos client in the pages of csr projects
+  const csr = CSR;
+  // the stylesheets are served under the base-url of the site
+  const baseUrl = BASE_URL;
+  const host = window.location.hostname;
+  const id = "cargo-leptos-error-overlay";
+  const hide = () => document.getElementById(id)?.remove();
+  const show = (errors) => {
+    hide();
+    const overlay = document.createElement("div");
+    overlay.id = id;
+    overlay.style.cssText =
+      "position:fixed;inset:0;z-index:2147483647;overflow:auto;padding:2rem;" +
+      "background:rgba(20,20,20,0.95);color:#eee;font:13px/1.5 monospace;";
+    const close = document.createElement("button");
+    close.textContent = "×";
+    close.title = "Dismiss (Esc)";
+    close.style.cssText =
+      "position:absolute;top:1rem;right:1rem;font-size:1.5rem;background:none;" +
+      "border:none;color:#eee;cursor:pointer;";
+    close.onclick = hide;
+    const title = document.createElement("div");
+    title.textContent = "Build failed";
+    title.style.cssText = "color:#ff5555;font-size:1.25rem;margin-bottom:1rem;";
+    const text = document.createElement("pre");
+    text.textContent = errors;
+    text.style.cssText = "white-space:pre-wrap;margin:0;";
+    overlay.append(close, title, text);
+    document.body.append(overlay);
+  };
+  document.addEventListener("keydown", (ev) => {
+    if (ev.key === "Escape") hide();
+  });
+  const onError = (msg) => {
+    if (!overlay) return;
+    if (msg.error) show(msg.error);
+    else hide();
+  };
+  // the scroll position and the unsubmitted form values, restored after the reload
+  const stateKey = "cargo-leptos-reload-state";
+  const fields = () =>
+    [...document.querySelectorAll("input, textarea, select")].filter(
+      (el) => !["password", "file", "hidden", "submit", "button"].includes(el.type),
+    );
+  const fieldKey = (el, index) => `${el.tagName}:${el.name || el.id || index}`;
+  const stash = () => {
+    const values = {};
+    fields().forEach((el, index) => {
+      values[fieldKey(el, index)] =
+        el.type === "checkbox" || el.type === "radio" ? el.checked : el.value;
+    });
+    const state = { url: window.location.href, x: window.scrollX, y: window.scrollY, values };
+    sessionStorage.setItem(stateKey, JSON.stringify(state));
+  };
+  const restore = () => {
+    const state = JSON.parse(sessionStorage.getItem(stateKey) || "null");
+    sessionStorage.removeItem(stateKey);
+    if (!state || state.url !== window.location.href) return;
+    fields().forEach((el, index) => {
+      const value = state.values[fieldKey(el, index)];
+      if (value === undefined) return;
+      if (typeof value === "boolean") el.checked = value;
+      else el.value = value;
+      // so that the signals bound to the field follow
+      el.dispatchEvent(new Event(typeof value === "boolean" ? "change" : "input", { bubbles: true }));
+    });
+    // the page may still be growing
+    let tries = 0;
+    const scroll = () => {
+      window.scrollTo(state.x, state.y);
+      if (window.scrollY < state.y && ++tries < 20) setTimeout(scroll, 50);
+    };
+    scroll();
+  };
+  // restored once hydrated, when this is the js of a hydrated app
+  if (typeof hydrate === "function") {
+    const hydrateApp = hydrate;
+    hydrate = (...args) => {
+      const res = hydrateApp(...args);
+      restore();
+      return res;
+    };
+  } else if (document.readyState === "complete") {
+    setTimeout(restore);
+  } else {
+    window.addEventListener("load", () => setTimeout(restore));
+  }
+  // with the websocket down (or in csr), the leptos client doesn't reload either
+  const onReload = (msg) => {
+    if (msg.all || msg.view) {
+      stash();
+      window.location.reload();
+    } else if (msg.css) {
+      document.querySelectorAll("link").forEach((link) => {
+        if (link.getAttribute("href")?.includes(msg.css)) {
+          link.setAttribute("href", `${baseUrl}/${msg.css}?version=${Date.now()}`);
+        }
+      });
+    }
+  };
+  const sse = () => {
+    const protocol = window.location.protocol === "https:" ? "https://" : "http://";
+    const events = new EventSource(`${protocol}${host}:${port}/live_reload/events`);
+    events.onmessage = (ev) => {
+      const msg = JSON.parse(ev.data);
+      onError(msg);
+      onReload(msg);
+    };
+  };
+  const protocol = window.location.protocol === "https:" ? "wss://" : "ws://";
+  const ws = new WebSocket(`${protocol}${host}:${port}/live_reload`);
+  let opened = false;
+  ws.onopen = () => {
+    opened = true;
+  };
+  ws.onclose = () => {
+    if (!opened) {
+      console.warn("cargo-leptos: live-reload websocket failed, falling back to server-sent events");
+      sse();
+    }
+  };
+  ws.onmessage = (ev) => {
+    const msg = JSON.parse(ev.data);
+    // sent ahead of the reload by the leptos client
+    if (msg.reloading || msg.all) stash();
+    onError(msg);
+    if (csr) onReload(msg);
+  };
+})();
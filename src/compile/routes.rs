@@ -0,0 +1,216 @@
+use cargo_metadata::{Dependency, DependencyKind, Metadata};
+use serde::Serialize;
+
+use crate::{
+    config::Project,
+    ext::{
+        anyhow::{anyhow, bail, Result},
+        PackageExt,
+    },
+};
+
+/// the server integrations the route list is generated with
+const INTEGRATIONS: [(&str, &str); 2] = [("leptos_axum", "axum"), ("leptos_actix", "actix")];
+
+/// The routes of the app, as generated by its server integration, and the paths of its server
+/// functions.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct RouteList {
+    pub routes: Vec<Route>,
+    pub server_fns: Vec<ServerFnRoute>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Route {
+    pub path: String,
+    pub methods: Vec<String>,
+    pub segments: Vec<Segment>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "kind", content = "name", rename_all = "kebab-case")]
+pub enum Segment {
+    Static(String),
+    /// a `:name` or `{name}` segment
+    Param(String),
+    /// a `*name`, `{*name}` or `{name:.*}` segment, matching the rest of the path
+    Wildcard(String),
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ServerFnRoute {
+    pub path: String,
+    pub method: String,
+}
+
+/// The Cargo.toml and main.rs of the harness printing the route list of the project: a bin
+/// depending on the lib package with the server features, and on the leptos and server
+/// integration versions of the bin package.
+pub fn routes_harness(proj: &Project, metadata: &Metadata) -> Result<(String, String)> {
+    let Some(bin) = &proj.bin else {
+        bail!("The csr project {} has no server routes", proj.name);
+    };
+    let packages = metadata.workspace_packages();
+    let find = |name: &str| {
+        packages
+            .iter()
+            .find(|p| p.name == name)
+            .ok_or_else(|| anyhow!("Could not find the package {name}"))
+    };
+    let bin_package = find(&bin.name)?;
+    let lib_package = find(&proj.lib.name)?;
+    let dependency = |name: &str| {
+        bin_package
+            .dependencies
+            .iter()
+            .find(|dep| dep.name == name && dep.kind == DependencyKind::Normal)
+    };
+    let Some((integration, server_fn_mod, integration_dep)) = INTEGRATIONS
+        .iter()
+        .find_map(|(name, module)| dependency(name).map(|dep| (name, module, dep)))
+    else {
+        bail!(
+            "The route list needs leptos_axum or leptos_actix, the bin package {} depends on neither",
+            bin.name
+        );
+    };
+    let leptos_dep = dependency("leptos")
+        .ok_or_else(|| anyhow!("The bin package {} doesn't depend on leptos", bin.name))?;
+
+    let (features, default_features) = if bin.name == proj.lib.name {
+        (bin.features.clone(), bin.default_features)
+    } else {
+        (proj.lib.ssr_features.clone().unwrap_or_default(), true)
+    };
+    let mut app = toml::Table::new();
+    app.insert("path".into(), proj.lib.abs_dir.as_str().into());
+    app.insert("features".into(), features.into());
+    app.insert("default-features".into(), default_features.into());
+
+    let mut dependencies = toml::Table::new();
+    dependencies.insert(proj.lib.name.clone(), app.into());
+    dependencies.insert("leptos".into(), dependency_toml(leptos_dep).into());
+    dependencies.insert(
+        integration.to_string(),
+        dependency_toml(integration_dep).into(),
+    );
+
+    let mut package = toml::Table::new();
+    package.insert("name".into(), format!("{}-routes", proj.name).into());
+    package.insert("version".into(), "0.0.0".into());
+    package.insert("edition".into(), "2021".into());
+    package.insert("publish".into(), false.into());
+
+    let mut manifest = toml::Table::new();
+    manifest.insert("package".into(), package.into());
+    manifest.insert("dependencies".into(), dependencies.into());
+    // not a member of the workspace of the project
+    manifest.insert("workspace".into(), toml::Table::new().into());
+
+    let lib_crate = lib_package
+        .targets
+        .iter()
+        .find(|t| t.is_lib() || t.is_rlib() || t.is_cdylib())
+        .map(|t| t.name.replace('-', "_"))
+        .ok_or_else(|| {
+            anyhow!(
+                "The lib package has no lib target: {}",
+                lib_package.target_list()
+            )
+        })?;
+    let main = format!(
+        r#"fn main() {{
+    for route in {integration}::generate_route_list({lib_crate}::{app}) {{
+        for method in route.methods() {{
+            println!("route {{method:?}} {{}}", route.path());
+        }}
+    }}
+    for (path, method) in leptos::server_fn::{server_fn_mod}::server_fn_paths() {{
+        println!("server-fn {{method}} {{path}}");
+    }}
+}}
+"#,
+        app = proj.app_component
+    );
+    Ok((toml::to_string(&manifest)?, main))
+}
+
+/// the dependency as in the Cargo.toml of the bin package, its path or git source included
+fn dependency_toml(dep: &Dependency) -> toml::Table {
+    let mut table = toml::Table::new();
+    if let Some(path) = &dep.path {
+        table.insert("path".into(), path.as_str().into());
+    } else if let Some(git) = dep.source.as_deref().and_then(|s| s.strip_prefix("git+")) {
+        let (url, query) = git.split_once('?').unwrap_or((git, ""));
+        table.insert("git".into(), url.split('#').next().unwrap_or(url).into());
+        let query = query.split('#').next().unwrap_or_default();
+        for (key, value) in query.split('&').filter_map(|param| param.split_once('=')) {
+            if matches!(key, "branch" | "tag" | "rev") {
+                table.insert(key.into(), value.into());
+            }
+        }
+    }
+    table.insert("version".into(), dep.req.to_string().into());
+    if !dep.features.is_empty() {
+        table.insert("features".into(), dep.features.clone().into());
+    }
+    if !dep.uses_default_features {
+        table.insert("default-features".into(), false.into());
+    }
+    table
+}
+
+/// the route list of the output of the harness, with the methods of each route
+pub fn parse_route_list(output: &str) -> RouteList {
+    let mut list = RouteList::default();
+    for line in output.lines() {
+        let mut parts = line.splitn(3, ' ');
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some("route"), Some(method), Some(path)) => {
+                let method = method.to_uppercase();
+                match list.routes.iter_mut().find(|route| route.path == path) {
+                    Some(route) => route.methods.push(method),
+                    None => list.routes.push(Route {
+                        path: path.to_string(),
+                        methods: vec![method],
+                        segments: segments(path),
+                    }),
+                }
+            }
+            (Some("server-fn"), Some(method), Some(path)) => list.server_fns.push(ServerFnRoute {
+                path: path.to_string(),
+                method: method.to_string(),
+            }),
+            _ => log::trace!("Routes ignoring output {line:?}"),
+        }
+    }
+    list
+}
+
+/// the segments of a route path, in the axum (`:name`, `*name`, `{name}`, `{*name}`) or actix
+/// (`{name}`, `{name:.*}`) syntax
+fn segments(path: &str) -> Vec<Segment> {
+    path.split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| {
+            if let Some(inner) = segment.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+                match inner.split_once(':') {
+                    Some((name, pattern)) if pattern.contains(".*") => {
+                        Segment::Wildcard(name.to_string())
+                    }
+                    Some((name, _)) => Segment::Param(name.to_string()),
+                    None => match inner.strip_prefix('*') {
+                        Some(name) => Segment::Wildcard(name.to_string()),
+                        None => Segment::Param(inner.to_string()),
+                    },
+                }
+            } else if let Some(name) = segment.strip_prefix(':') {
+                Segment::Param(name.to_string())
+            } else if let Some(name) = segment.strip_prefix('*') {
+                Segment::Wildcard(name.to_string())
+            } else {
+                Segment::Static(segment.to_string())
+            }
+        })
+        .collect()
+}
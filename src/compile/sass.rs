@@ -1,3 +1,4 @@
+use super::errors::record_error;
 use crate::{
     ext::{
         anyhow::{Context, Result},
@@ -6,13 +7,17 @@ use crate::{
     logger::GRAY,
     signal::{Interrupt, Outcome},
 };
+use std::collections::{HashSet, VecDeque};
 use tokio::process::Command;
 
-use crate::{ext::Exe, service::site::SourcedSiteFile};
+use crate::{
+    config::{Project, StyleConfig},
+    ext::{Exe, PathBufExt},
+};
+use camino::{Utf8Path, Utf8PathBuf};
 
-pub async fn compile_sass(style_file: &SourcedSiteFile, optimise: bool) -> Result<Outcome<String>> {
-    let mut args = vec![style_file.source.as_str()];
-    optimise.then(|| args.push("--no-source-map"));
+pub async fn compile_sass(proj: &Project, source: &Utf8Path) -> Result<Outcome<String>> {
+    let args = sass_args(source, &proj.style, proj.release);
 
     let exe = Exe::Sass.get().await.dot()?;
 
@@ -24,13 +29,122 @@ pub async fn compile_sass(style_file: &SourcedSiteFile, optimise: bool) -> Resul
         GRAY.paint(format!("sass {}", args.join(" ")))
     );
 
-    match wait_piped_interruptible("Dart Sass", cmd, Interrupt::subscribe_any()).await? {
+    match wait_piped_interruptible("Dart Sass", cmd, Interrupt::subscribe_any(proj)).await? {
         CommandResult::Success(output) => Ok(Outcome::Success(output.stdout())),
         CommandResult::Interrupted => Ok(Outcome::Stopped),
         CommandResult::Failure(output) => {
             log::warn!("Dart Sass failed with:");
             println!("{}", output.stderr());
+            record_error(proj, "Dart Sass failed", &output.stderr());
             Ok(Outcome::Failed)
         }
     }
 }
+
+pub fn sass_args(source: &Utf8Path, style: &StyleConfig, optimise: bool) -> Vec<String> {
+    let mut args = style
+        .sass_load_paths
+        .iter()
+        .map(|dir| format!("--load-path={dir}"))
+        .collect::<Vec<_>>();
+    args.extend(style.sass_args.iter().cloned());
+    optimise.then(|| args.push("--no-source-map".to_string()));
+    args.push(source.to_string());
+    args
+}
+
+/// Lists the files `@use`d, `@forward`ed and `@import`ed, directly or not, by the given sass
+/// files, resolved like sass does: relative to the importing file, then to the load paths.
+pub fn sass_dependencies(
+    sources: &[Utf8PathBuf],
+    load_paths: &[Utf8PathBuf],
+) -> HashSet<Utf8PathBuf> {
+    let mut found = HashSet::new();
+    let mut queue = sources
+        .iter()
+        .filter(|file| matches!(file.extension(), Some("scss" | "sass")))
+        .cloned()
+        .collect::<VecDeque<_>>();
+
+    while let Some(file) = queue.pop_front() {
+        let Ok(content) = std::fs::read_to_string(&file) else {
+            continue;
+        };
+        let dir = file.parent().unwrap_or(Utf8Path::new(""));
+        for url in import_urls(&content) {
+            let resolved = std::iter::once(dir)
+                .chain(load_paths.iter().map(|p| p.as_path()))
+                .find_map(|base| resolve_import(base, &url));
+            match resolved {
+                Some(dep) if found.insert(dep.clone()) => queue.push_back(dep),
+                Some(_) => {}
+                None => log::trace!("Style could not resolve {url:?} imported by {file}"),
+            }
+        }
+    }
+    found
+}
+
+/// the urls of the `@use`, `@forward` and `@import` rules, without the built-in modules,
+/// remote and plain css imports
+fn import_urls(content: &str) -> Vec<String> {
+    let mut urls = Vec::new();
+    for line in content.lines().map(str::trim) {
+        let Some(rule) = ["@use", "@forward", "@import"]
+            .iter()
+            .find_map(|rule| line.strip_prefix(rule))
+            .filter(|rest| rest.starts_with(char::is_whitespace))
+        else {
+            continue;
+        };
+        let rule = rule.split(';').next().unwrap_or_default();
+
+        // `@import "a", "b";` imports several files
+        let quoted = rule
+            .split(['"', '\''])
+            .skip(1)
+            .step_by(2)
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        let rule_urls = if quoted.is_empty() {
+            // the indented syntax allows unquoted imports
+            rule.split_whitespace()
+                .take(1)
+                .map(str::to_string)
+                .collect()
+        } else {
+            quoted
+        };
+
+        urls.extend(rule_urls.into_iter().filter(|url| {
+            !url.starts_with("sass:")
+                && !url.starts_with("http://")
+                && !url.starts_with("https://")
+                && !url.starts_with("url(")
+        }));
+    }
+    urls
+}
+
+fn resolve_import(base: &Utf8Path, url: &str) -> Option<Utf8PathBuf> {
+    let path = base.join(url);
+    let name = path.file_name()?;
+    let with_partial = |path: &Utf8Path| {
+        let partial = path.with_file_name(format!("_{}", path.file_name().unwrap_or_default()));
+        [path.to_path_buf(), partial]
+    };
+
+    let candidates = if path.is_ext_any(&["scss", "sass", "css"]) {
+        with_partial(&path).to_vec()
+    } else {
+        let mut candidates = Vec::new();
+        for ext in ["scss", "sass", "css"] {
+            candidates.extend(with_partial(&path.with_file_name(format!("{name}.{ext}"))));
+        }
+        for ext in ["scss", "sass", "css"] {
+            candidates.extend(with_partial(&path.join(format!("index.{ext}"))));
+        }
+        candidates
+    };
+    candidates.into_iter().find(|candidate| candidate.is_file())
+}
@@ -1,11 +1,14 @@
 use std::sync::Arc;
 
-use super::ChangeSet;
+use super::{
+    errors::{capture_stderr, record_error, tee_stderr},
+    ChangeSet,
+};
 use crate::{
     config::Project,
-    ext::anyhow::{Context, Result},
+    ext::anyhow::{bail, Context, Result},
     ext::sync::{wait_interruptible, CommandResult},
-    logger::GRAY,
+    logger::{self, GRAY},
     signal::{Interrupt, Outcome, Product},
 };
 use shlex::Shlex;
@@ -21,21 +24,25 @@ pub async fn server(
     let proj = proj.clone();
     let changes = changes.clone();
 
-    tokio::spawn(async move {
+    logger::spawn(async move {
+        let Some(bin) = &proj.bin else {
+            return Ok(Outcome::Success(Product::None));
+        };
         if !changes.need_server_build() {
             return Ok(Outcome::Success(Product::None));
         }
 
-        let (envs, line, process) = server_cargo_process("build", &proj)?;
+        let (envs, line, mut process) = server_cargo_process("build", &proj)?;
+        let stderr = tee_stderr(&mut process);
         log::debug!("CARGO SERVER COMMAND: {:?}", process);
-        match wait_interruptible("Cargo", process, Interrupt::subscribe_any()).await? {
+        match wait_interruptible("Cargo", process, Interrupt::subscribe_any(&proj)).await? {
             CommandResult::Success(_) => {
                 log::debug!("Cargo envs: {}", GRAY.paint(envs));
                 log::info!("Cargo finished {}", GRAY.paint(line));
 
                 let changed = proj
                     .site
-                    .did_external_file_change(&proj.bin.exe_file)
+                    .did_external_file_change(&bin.exe_file)
                     .await
                     .dot()?;
                 if changed {
@@ -47,13 +54,19 @@ pub async fn server(
                 }
             }
             CommandResult::Interrupted => Ok(Outcome::Stopped),
-            CommandResult::Failure(_) => Ok(Outcome::Failed),
+            CommandResult::Failure(_) => {
+                record_error(&proj, "Cargo server build failed", &stderr.await?);
+                Ok(Outcome::Failed)
+            }
         }
     })
 }
 
 pub fn server_cargo_process(cmd: &str, proj: &Project) -> Result<(String, String, Child)> {
-    let raw_command = proj.bin.cargo_command.as_deref().unwrap_or("cargo");
+    let Some(bin) = &proj.bin else {
+        bail!("The csr project {} has no server", proj.name);
+    };
+    let raw_command = bin.cargo_command.as_deref().unwrap_or("cargo");
     let mut command_iter = Shlex::new(raw_command);
 
     if command_iter.had_error {
@@ -69,54 +82,105 @@ pub fn server_cargo_process(cmd: &str, proj: &Project) -> Result<(String, String
     command.args(args);
 
     let (envs, line) = build_cargo_server_cmd(cmd, proj, &mut command);
+    if cmd == "build" {
+        capture_stderr(proj, &mut command);
+    }
     Ok((envs, line, command.spawn()?))
 }
 
-pub fn build_cargo_server_cmd(
+/// the cargo command of the lib package with the features of the server, in its target dir, for
+/// testing the code of the lib behind them
+pub fn lib_ssr_cargo_process(cmd: &str, proj: &Project) -> Result<(String, String, Child)> {
+    let mut command = Command::new("cargo");
+    let (envs, line) = build_cargo_lib_ssr_cmd(cmd, proj, &mut command)?;
+    Ok((envs, line, command.spawn()?))
+}
+
+pub fn build_cargo_lib_ssr_cmd(
     cmd: &str,
     proj: &Project,
     command: &mut Command,
-) -> (String, String) {
+) -> Result<(String, String)> {
+    let Some(bin) = &proj.bin else {
+        bail!("The csr project {} has no server", proj.name);
+    };
+    let features = proj.lib.ssr_features.clone().unwrap_or_default();
     let mut args = vec![
         cmd.to_string(),
-        format!("--package={}", proj.bin.name.as_str()),
+        format!("--package={}", proj.lib.name),
+        "--lib".to_string(),
     ];
+    if let Some(target_dir) = &bin.target_dir {
+        args.push(format!("--target-dir={target_dir}"));
+    }
+    if !bin.default_features {
+        args.push("--no-default-features".to_string());
+    }
+    args.push(format!("--features={}", features.join(",")));
+    bin.profile.add_to_args(&mut args);
 
-    // If we're building the bin target for wasm, we want it to be a lib so it
-    // can be run by wasmtime or spin or wasmer or whatever
-    let server_is_wasm = match &proj.bin.target_triple {
-        Some(t) => t.contains("wasm"),
-        None => false,
-    };
-    if cmd != "test" && !server_is_wasm {
-        args.push(format!("--bin={}", proj.bin.target))
-    } else if cmd != "test" && server_is_wasm {
+    let envs = proj.to_envs();
+    let envs_str = envs
+        .iter()
+        .map(|(name, val)| format!("{name}={val}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+    command.args(&args).envs(envs);
+    let line = super::build_cargo_command_string(args);
+    Ok((envs_str, line))
+}
+
+pub fn build_cargo_server_cmd(
+    cmd: &str,
+    proj: &Project,
+    command: &mut Command,
+) -> (String, String) {
+    // never called for csr projects, see server_cargo_process
+    let bin = proj.bin.as_ref().expect("a csr project has no server");
+    let mut args = vec![cmd.to_string(), format!("--package={}", bin.name.as_str())];
+
+    // the tests and benches of all the targets of the package
+    let all_targets = cmd == "test" || cmd == "bench";
+    if !all_targets && bin.example {
+        args.push(format!("--example={}", bin.target))
+    } else if !all_targets && bin.wasm_lib {
+        // the cdylib component run by spin, wasmCloud or the like
         args.push("--lib".to_string())
+    } else if !all_targets {
+        args.push(format!("--bin={}", bin.target))
     }
 
-    if let Some(target_dir) = &proj.bin.target_dir {
+    if let Some(target_dir) = &bin.target_dir {
         args.push(format!("--target-dir={target_dir}"));
     }
-    if let Some(triple) = &proj.bin.target_triple {
+    if let Some(triple) = &bin.target_triple {
         args.push(format!("--target={triple}"));
     }
 
-    if !proj.bin.default_features {
+    if proj.timings {
+        args.push("--timings".to_string());
+    }
+
+    if !bin.default_features {
         args.push("--no-default-features".to_string());
     }
 
-    if !proj.bin.features.is_empty() {
-        args.push(format!("--features={}", proj.bin.features.join(",")));
+    if !bin.features.is_empty() {
+        args.push(format!("--features={}", bin.features.join(",")));
     }
 
-    log::debug!("BIN CARGO ARGS: {:?}", &proj.bin.cargo_args);
+    log::debug!("BIN CARGO ARGS: {:?}", &bin.cargo_args);
     // Add cargo flags to cargo command
-    if let Some(cargo_args) = &proj.bin.cargo_args {
+    if let Some(cargo_args) = &bin.cargo_args {
         args.extend_from_slice(cargo_args);
     }
-    proj.bin.profile.add_to_args(&mut args);
+    // the benches are built with the bench profile
+    if cmd != "bench" {
+        bin.profile.add_to_args(&mut args);
+    }
 
-    let envs = proj.to_envs();
+    let mut envs = proj.to_envs();
+    envs.extend(proj.reproducible_cargo_envs(bin.target_triple.as_deref()));
 
     let envs_str = envs
         .iter()
@@ -0,0 +1,57 @@
+// The service worker generated by cargo-leptos for the `pwa` config: precaches the files of the
+// site, under a cache named after their hash so that each build replaces the previous one, and
+// serves them from the cache. The pages are fetched from the network, falling back to their
+// cached copy, or to the start page's, when offline.
+const CACHE = "cargo-leptos-CACHE_VERSION";
+const FILES = PRECACHE_FILES;
+const START_URL = "PWA_START_URL";
+
+self.addEventListener("install", (event) => {
+  event.waitUntil(
+    caches
+      .open(CACHE)
+      .then((cache) => cache.addAll(FILES))
+      .then(() => self.skipWaiting()),
+  );
+});
+
+self.addEventListener("activate", (event) => {
+  event.waitUntil(
+    caches
+      .keys()
+      .then((keys) =>
+        Promise.all(
+          keys
+            .filter((key) => key.startsWith("cargo-leptos-") && key !== CACHE)
+            .map((key) => caches.delete(key)),
+        ),
+      )
+      .then(() => self.clients.claim()),
+  );
+});
+
+self.addEventListener("fetch", (event) => {
+  const request = event.request;
+  if (request.method !== "GET") return;
+  if (request.mode === "navigate") {
+    event.respondWith(
+      fetch(request)
+        .then((response) => {
+          if (response.ok) {
+            const copy = response.clone();
+            caches.open(CACHE).then((cache) => cache.put(request, copy));
+          }
+          return response;
+        })
+        .catch(() =>
+          caches
+            .match(request)
+            .then((cached) => cached || caches.match(START_URL)),
+        ),
+    );
+    return;
+  }
+  event.respondWith(
+    caches.match(request).then((cached) => cached || fetch(request)),
+  );
+});
@@ -0,0 +1,58 @@
+use std::fmt::Write;
+
+use super::{prerender::crawl, record_stage};
+use crate::{
+    config::Project,
+    ext::{anyhow::Result, fs, glob_set},
+    logger::GRAY,
+};
+
+/// Writes the `sitemap.xml` of the site into the site root, with the pages of the routes the
+/// server renders that are linked from the root page, directly or through other pages. The
+/// paths matching the sitemap-exclude patterns are left out, and not followed.
+pub async fn sitemap(proj: &Project) -> Result<()> {
+    let Some(site_url) = &proj.sitemap_base_url else {
+        return Ok(());
+    };
+    let start_time = tokio::time::Instant::now();
+    let exclude = glob_set(&proj.sitemap_exclude)?;
+    let pages = crawl(proj, &["/".to_string()], |path| !exclude.is_match(path)).await?;
+    let paths = pages
+        .iter()
+        .map(|(path, _)| path.as_str())
+        .filter(|path| !exclude.is_match(path))
+        .collect::<Vec<_>>();
+
+    let file = proj.site.root_dir.join("sitemap.xml");
+    let url = format!("{}{}", site_url.trim_end_matches('/'), proj.site.base_url);
+    fs::write(&file, sitemap_xml(&url, &paths)).await?;
+    log::info!(
+        "Sitemap of {} pages written to {}",
+        paths.len(),
+        GRAY.paint(file.as_str())
+    );
+    record_stage("sitemap", start_time.elapsed());
+    Ok(())
+}
+
+/// the sitemap of the paths of the site at the url
+pub fn sitemap_xml(url: &str, paths: &[&str]) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+    for path in paths {
+        let loc = format!("{url}{path}");
+        let _ = writeln!(xml, "  <url><loc>{}</loc></url>", escape(&loc));
+    }
+    xml.push_str("</urlset>\n");
+    xml
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
@@ -0,0 +1,309 @@
+use std::{collections::BTreeMap, fmt::Write as _, io::Write};
+
+use brotli::enc::BrotliEncoderParams;
+use camino::Utf8Path;
+use libflate::gzip;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    build_info::{git_info, git_output},
+    record_stage,
+};
+use crate::{
+    config::Project,
+    ext::anyhow::{anyhow, Context, Result},
+    logger::GRAY,
+};
+
+/// the sizes of a file of the pkg dir, in bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileSizes {
+    pub raw: u64,
+    pub gzip: u64,
+    pub brotli: u64,
+}
+
+/// the sizes of the pkg files of a release build of a project in the size ledger
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    /// the git commit, with a `-dirty` suffix when the working tree had changes
+    pub sha: String,
+    pub project: String,
+    pub files: BTreeMap<String, FileSizes>,
+}
+
+/// Logs the raw, gzip and brotli sizes of the files of the pkg dir after a release build, with
+/// their change since the previous one, whose sizes are kept in the tmp dir. With
+/// `--size-report`, they are appended to the ledger, and compared with the ones of the
+/// `--size-baseline`.
+pub async fn size_report(proj: &Project) -> Result<()> {
+    let start_time = tokio::time::Instant::now();
+    let pkg_dir = proj.site.root_relative_pkg_dir();
+    let sizes = tokio::task::spawn_blocking(move || pkg_sizes(&pkg_dir)).await??;
+
+    let file = proj
+        .target_dir
+        .join("tmp")
+        .join(format!("sizes-{}.json", proj.name));
+    let previous: BTreeMap<String, FileSizes> = std::fs::read_to_string(&file)
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default();
+    for line in size_table(&sizes, &previous).lines() {
+        log::info!("Size {line}");
+    }
+
+    std::fs::create_dir_all(file.parent().unwrap_or(&proj.target_dir))?;
+    std::fs::write(&file, serde_json::to_string_pretty(&sizes)?)
+        .context(format!("Could not write {file}"))?;
+    log::debug!("Size report kept in {}", GRAY.paint(file.as_str()));
+
+    if let Some(ledger) = &proj.size_ledger {
+        let ledger = proj.working_dir.join(ledger);
+        let mut entries = read_ledger(&ledger)?;
+        match git_info(&proj.working_dir) {
+            Some(git) => {
+                let sha = match git.dirty {
+                    true => format!("{}-dirty", git.sha),
+                    false => git.sha,
+                };
+                // a rebuild of the commit replaces its sizes
+                entries.retain(|entry| entry.sha != sha || entry.project != proj.name);
+                entries.push(LedgerEntry {
+                    sha,
+                    project: proj.name.clone(),
+                    files: sizes.clone(),
+                });
+                write_ledger(&ledger, &entries)?;
+                log::info!("Size ledger updated at {}", GRAY.paint(ledger.as_str()));
+            }
+            None => log::warn!(
+                "Size ledger not updated, {} is not in a git repository",
+                proj.working_dir
+            ),
+        }
+        if let Some(baseline) = &proj.size_baseline {
+            let sha = git_output(&proj.working_dir, &["rev-parse", baseline])
+                .ok_or_else(|| anyhow!("The size baseline {baseline} is not a git ref"))?;
+            match entries
+                .iter()
+                .find(|entry| entry.sha == sha && entry.project == proj.name)
+            {
+                Some(entry) => {
+                    log::info!(
+                        "Size compared with {baseline} ({})",
+                        &sha[..7.min(sha.len())]
+                    );
+                    for line in size_table(&sizes, &entry.files).lines() {
+                        log::info!("Size {line}");
+                    }
+                }
+                None => {
+                    log::warn!("Size ledger has no sizes of {baseline} ({sha}) to compare with")
+                }
+            }
+        }
+    }
+    record_stage("size-report", start_time.elapsed());
+    Ok(())
+}
+
+/// the entries of the ledger, none when it doesn't exist yet
+pub fn read_ledger(path: &Utf8Path) -> Result<Vec<LedgerEntry>> {
+    let Ok(text) = std::fs::read_to_string(path) else {
+        return Ok(Vec::new());
+    };
+    if path.extension() == Some("csv") {
+        parse_ledger_csv(&text).context(format!("Could not parse the size ledger {path}"))
+    } else {
+        serde_json::from_str(&text).context(format!("Could not parse the size ledger {path}"))
+    }
+}
+
+fn write_ledger(path: &Utf8Path, entries: &[LedgerEntry]) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    let text = if path.extension() == Some("csv") {
+        ledger_csv(entries)
+    } else {
+        serde_json::to_string_pretty(entries)?
+    };
+    std::fs::write(path, text).context(format!("Could not write the size ledger {path}"))
+}
+
+const CSV_HEADER: &str = "sha,project,file,raw,gzip,brotli";
+
+/// the ledger as CSV, with a row per file
+pub fn ledger_csv(entries: &[LedgerEntry]) -> String {
+    let mut csv = format!("{CSV_HEADER}\n");
+    for entry in entries {
+        for (file, size) in &entry.files {
+            let _ = writeln!(
+                csv,
+                "{},{},{file},{},{},{}",
+                entry.sha, entry.project, size.raw, size.gzip, size.brotli
+            );
+        }
+    }
+    csv
+}
+
+/// the entries of the CSV ledger, whose rows are grouped by commit and project
+pub fn parse_ledger_csv(csv: &str) -> Result<Vec<LedgerEntry>> {
+    let mut entries: Vec<LedgerEntry> = Vec::new();
+    for line in csv.lines().skip(1).filter(|line| !line.is_empty()) {
+        let fields: Vec<&str> = line.split(',').collect();
+        let [sha, project, file, raw, gzip, brotli] = fields[..] else {
+            return Err(anyhow!("Invalid size ledger row {line:?}"));
+        };
+        let size = FileSizes {
+            raw: raw.parse()?,
+            gzip: gzip.parse()?,
+            brotli: brotli.parse()?,
+        };
+        match entries
+            .iter_mut()
+            .find(|entry| entry.sha == sha && entry.project == project)
+        {
+            Some(entry) => {
+                entry.files.insert(file.to_string(), size);
+            }
+            None => entries.push(LedgerEntry {
+                sha: sha.to_string(),
+                project: project.to_string(),
+                files: [(file.to_string(), size)].into(),
+            }),
+        }
+    }
+    Ok(entries)
+}
+
+/// The sizes of the files of the pkg dir, by their name without the hash, so that they compare
+/// across builds. The precompressed copies are measured when the build wrote them.
+pub fn pkg_sizes(pkg_dir: &Utf8Path) -> Result<BTreeMap<String, FileSizes>> {
+    let mut sizes = BTreeMap::new();
+    for entry in pkg_dir
+        .read_dir_utf8()
+        .context(format!("Could not read {pkg_dir}"))?
+    {
+        let path = entry?.into_path();
+        if !path.is_file() || matches!(path.extension(), Some("gz" | "br")) {
+            continue;
+        }
+        let data = std::fs::read(&path)?;
+        let copy_len = |ext: &str| std::fs::metadata(format!("{path}.{ext}")).map(|m| m.len());
+        let gzip = match copy_len("gz") {
+            Ok(len) => len,
+            Err(_) => {
+                let mut encoder = gzip::Encoder::new(Vec::new())?;
+                encoder.write_all(&data)?;
+                encoder.finish().into_result()?.len() as u64
+            }
+        };
+        let brotli = match copy_len("br") {
+            Ok(len) => len,
+            Err(_) => {
+                let mut output = Vec::new();
+                brotli::BrotliCompress(
+                    &mut data.as_slice(),
+                    &mut output,
+                    &BrotliEncoderParams::default(),
+                )?;
+                output.len() as u64
+            }
+        };
+        let name = unhashed_name(path.file_name().unwrap_or_default());
+        let raw = data.len() as u64;
+        sizes.insert(name, FileSizes { raw, gzip, brotli });
+    }
+    Ok(sizes)
+}
+
+/// the file name without the hash added by hash-files, as in `app.<hash>.wasm`
+pub(crate) fn unhashed_name(name: &str) -> String {
+    let parts: Vec<&str> = name.split('.').collect();
+    // the md5 hashes are 22 base64url characters
+    let is_hash = |part: &str| {
+        part.len() == 22
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    };
+    match parts.len() {
+        3.. if is_hash(parts[parts.len() - 2]) => {
+            let mut parts = parts;
+            parts.remove(parts.len() - 2);
+            parts.join(".")
+        }
+        _ => name.to_string(),
+    }
+}
+
+/// The table of the sizes, with the change of their brotli size since the previous build, and
+/// the total.
+pub fn size_table(
+    sizes: &BTreeMap<String, FileSizes>,
+    previous: &BTreeMap<String, FileSizes>,
+) -> String {
+    let total = |sizes: &BTreeMap<String, FileSizes>| {
+        sizes.values().fold(
+            FileSizes {
+                raw: 0,
+                gzip: 0,
+                brotli: 0,
+            },
+            |total, size| FileSizes {
+                raw: total.raw + size.raw,
+                gzip: total.gzip + size.gzip,
+                brotli: total.brotli + size.brotli,
+            },
+        )
+    };
+    let mut rows: Vec<(&str, FileSizes, Option<FileSizes>)> = sizes
+        .iter()
+        .map(|(name, size)| (name.as_str(), *size, previous.get(name).copied()))
+        .collect();
+    rows.push((
+        "total",
+        total(sizes),
+        (!previous.is_empty()).then(|| total(previous)),
+    ));
+
+    let width = rows.iter().map(|(name, ..)| name.len()).max().unwrap_or(0);
+    let mut table = format!(
+        "{:width$}  {:>10}  {:>10}  {:>10}  {:>10}\n",
+        "file", "raw", "gzip", "brotli", "change"
+    );
+    for (name, size, previous) in rows {
+        let change = match previous {
+            Some(previous) => format_change(size.brotli as i64 - previous.brotli as i64),
+            None => "new".to_string(),
+        };
+        let _ = writeln!(
+            table,
+            "{name:width$}  {:>10}  {:>10}  {:>10}  {change:>10}",
+            format_bytes(size.raw),
+            format_bytes(size.gzip),
+            format_bytes(size.brotli),
+        );
+    }
+    table
+}
+
+fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        0..=1023 => format!("{bytes} B"),
+        1024..=1_048_575 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.2} MiB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+/// the change of the brotli size
+fn format_change(delta: i64) -> String {
+    match delta {
+        0 => "=".to_string(),
+        1.. => format!("+{}", format_bytes(delta as u64)),
+        _ => format!("-{}", format_bytes(delta.unsigned_abs())),
+    }
+}
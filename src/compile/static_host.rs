@@ -0,0 +1,93 @@
+use std::fmt::Write;
+
+use camino::Utf8Path;
+
+use crate::{
+    config::Project,
+    ext::{anyhow::Result, fs},
+    logger::GRAY,
+};
+
+/// the headers of the cross-origin isolation that wasm threads (`SharedArrayBuffer`) need
+const ISOLATION_HEADERS: [(&str, &str); 2] = [
+    ("Cross-Origin-Opener-Policy", "same-origin"),
+    ("Cross-Origin-Embedder-Policy", "require-corp"),
+];
+
+/// Writes the `_headers` and `_redirects` files of the static hosts such as Netlify and
+/// Cloudflare Pages into the dir of a static site.
+pub async fn write_static_host_files(proj: &Project, dir: &Utf8Path) -> Result<()> {
+    let threads = wasm_threads(|name| std::env::var(name).ok());
+    let files = [
+        ("_headers", headers_file(proj, threads)),
+        ("_redirects", redirects_file(proj)),
+    ];
+    for (name, contents) in files {
+        let Some(contents) = contents else {
+            continue;
+        };
+        let file = dir.join(name);
+        fs::write(&file, contents).await?;
+        log::debug!("Export wrote {}", GRAY.paint(file.as_str()));
+    }
+    Ok(())
+}
+
+/// whether the rustflags of the wasm build enable the atomics that wasm threads need
+pub fn wasm_threads(var: impl Fn(&str) -> Option<String>) -> bool {
+    [
+        "CARGO_TARGET_WASM32_UNKNOWN_UNKNOWN_RUSTFLAGS",
+        "CARGO_ENCODED_RUSTFLAGS",
+        "RUSTFLAGS",
+    ]
+    .iter()
+    .filter_map(|name| var(name))
+    .any(|flags| flags.contains("+atomics"))
+}
+
+/// The `_headers` file: the hashed pkg files are cached as immutable, and all the files get the
+/// headers of the headers table, with the cross-origin isolation of the wasm threads.
+pub fn headers_file(proj: &Project, threads: bool) -> Option<String> {
+    let base = &proj.site.base_url;
+    let mut file = String::new();
+    if proj.hash_files {
+        let _ = write!(
+            file,
+            "{base}/{}/*\n  Cache-Control: public, max-age=31536000, immutable\n",
+            proj.site.pkg_dir
+        );
+    }
+
+    let mut headers: Vec<(String, String)> = proj
+        .headers
+        .iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect();
+    if threads {
+        for (name, value) in ISOLATION_HEADERS {
+            if !headers.iter().any(|(n, _)| n.eq_ignore_ascii_case(name)) {
+                headers.push((name.to_string(), value.to_string()));
+            }
+        }
+    }
+    if !headers.is_empty() {
+        if !file.is_empty() {
+            file.push('\n');
+        }
+        let _ = writeln!(file, "{base}/*");
+        for (name, value) in headers {
+            let _ = writeln!(file, "  {name}: {value}");
+        }
+    }
+    (!file.is_empty()).then_some(file)
+}
+
+/// The `_redirects` file, falling back to the root page for the paths without a file as a
+/// single page app, unless the fallback-route wrote the `404.html` the hosts serve then.
+pub fn redirects_file(proj: &Project) -> Option<String> {
+    if proj.fallback_route.is_some() {
+        return None;
+    }
+    let base = &proj.site.base_url;
+    Some(format!("{base}/*  {base}/index.html  200\n"))
+}
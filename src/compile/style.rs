@@ -1,17 +1,20 @@
-use super::ChangeSet;
+use super::{record_stage, ChangeSet};
 use crate::{
-    compile::{sass::compile_sass, tailwind::compile_tailwind},
+    compile::{
+        collector::collected_css, postcss::run_postcss, purge::purge_css, sass::compile_sass,
+        tailwind::compile_tailwind,
+    },
     config::Project,
     ext::{
         anyhow::{anyhow, bail, Context, Result},
         PathBufExt,
     },
     fs,
-    logger::GRAY,
+    logger::{self, GRAY},
     signal::{Outcome, Product},
 };
 use lightningcss::{
-    stylesheet::{MinifyOptions, ParserOptions, PrinterOptions, StyleSheet},
+    stylesheet::{MinifyOptions, ParserFlags, ParserOptions, PrinterOptions, StyleSheet},
     targets::Browsers,
     targets::Targets,
 };
@@ -25,7 +28,7 @@ pub async fn style(
     let changes = changes.clone();
     let proj = proj.clone();
 
-    tokio::spawn(async move {
+    logger::spawn(async move {
         let css_in_source = proj.style.tailwind.is_some();
         if !changes.need_style_build(true, css_in_source) {
             log::debug!("Style no build needed {changes:?}");
@@ -36,31 +39,39 @@ pub async fn style(
 }
 fn build_sass(proj: &Arc<Project>) -> JoinHandle<Result<Outcome<String>>> {
     let proj = proj.clone();
-    tokio::spawn(async move {
-        let Some(style_file) = &proj.style.file else {
+    logger::spawn(async move {
+        if proj.style.files.is_empty() {
             log::trace!("Style not configured");
             return Ok(Outcome::Success("".to_string()));
-        };
+        }
 
-        log::trace!("Style found: {}", &style_file);
-        fs::create_dir_all(style_file.dest.clone().without_last())
-            .await
-            .dot()?;
-        match style_file.source.extension() {
-            Some("sass") | Some("scss") => compile_sass(style_file, proj.release)
+        let mut css = Vec::new();
+        for style_file in &proj.style.files {
+            log::trace!("Style found: {}", &style_file);
+            fs::create_dir_all(style_file.dest.clone().without_last())
                 .await
-                .context(format!("compile sass/scss: {}", &style_file)),
-            Some("css") => Ok(Outcome::Success(
-                fs::read_to_string(&style_file.source).await.dot()?,
-            )),
-            _ => bail!("Not a css/sass/scss style file: {}", &style_file),
+                .dot()?;
+            let outcome = match style_file.source.extension() {
+                Some("sass") | Some("scss") => compile_sass(&proj, &style_file.source)
+                    .await
+                    .context(format!("compile sass/scss: {}", &style_file))?,
+                Some("css") => {
+                    Outcome::Success(fs::read_to_string(&style_file.source).await.dot()?)
+                }
+                _ => bail!("Not a css/sass/scss style file: {}", &style_file),
+            };
+            match outcome {
+                Outcome::Success(file_css) => css.push(file_css),
+                outcome => return Ok(outcome),
+            }
         }
+        Ok(Outcome::Success(css.join("\n")))
     })
 }
 
 fn build_tailwind(proj: &Arc<Project>) -> JoinHandle<Result<Outcome<String>>> {
     let proj = proj.clone();
-    tokio::spawn(async move {
+    logger::spawn(async move {
         let Some(tw_conf) = proj.style.tailwind.as_ref() else {
             log::trace!("Tailwind not configured");
             return Ok(Outcome::Success("".to_string()));
@@ -71,6 +82,7 @@ fn build_tailwind(proj: &Arc<Project>) -> JoinHandle<Result<Outcome<String>>> {
 }
 
 async fn build(proj: &Arc<Project>) -> Result<Outcome<Product>> {
+    let start_time = tokio::time::Instant::now();
     let css_handle = build_sass(proj);
     let tw_handle = build_tailwind(proj);
     let css = css_handle.await??;
@@ -82,37 +94,87 @@ async fn build(proj: &Arc<Project>) -> Result<Outcome<Product>> {
         (Failed, _) | (_, Failed) => return Ok(Failed),
         (Success(css), Success(tw)) => format!("{css}\n{tw}"),
     };
-    Ok(Success(process_css(proj, css).await?))
-}
-
-fn browser_lists(query: &str) -> Result<Option<Browsers>> {
-    Browsers::from_browserslist([query]).context(format!("Error in browserlist query: {query}"))
+    let css = match &proj.style.modules {
+        // written by the css modules step, which runs first
+        Some(modules) if modules.css_file.exists() => {
+            format!("{css}\n{}", fs::read_to_string(&modules.css_file).await?)
+        }
+        _ => css,
+    };
+    // the collected css's class names are generated by macros, so they aren't in the sources
+    let css = match &proj.style.purge {
+        Some(purge) if proj.release => purge_css(proj, purge, &css).await?,
+        _ => css,
+    };
+    let css = match &proj.style.collector {
+        Some(collector) => match collected_css(proj, collector).await? {
+            Success(collected) => format!("{css}\n{collected}"),
+            Stopped => return Ok(Stopped),
+            Failed => return Ok(Failed),
+        },
+        None => css,
+    };
+    let css = match &proj.style.postcss {
+        Some(cmd) => match run_postcss(proj, cmd, css).await? {
+            Success(css) => css,
+            Stopped => return Ok(Stopped),
+            Failed => return Ok(Failed),
+        },
+        None => css,
+    };
+    let product = process_css(proj, css).await?;
+    record_stage("style", start_time.elapsed());
+    Ok(Success(product))
 }
 
-async fn process_css(proj: &Project, css: String) -> Result<Product> {
-    let browsers = browser_lists(&proj.style.browserquery).context("leptos.style.browserquery")?;
+/// Runs the css through lightningcss: vendor prefixes are added and the syntax unsupported by
+/// the browsers matching the `browserquery` (such as nesting) is lowered. The output is minified
+/// in release builds.
+pub fn transform_css(
+    css: &str,
+    browserquery: &str,
+    release: bool,
+    filename: &str,
+) -> Result<String> {
+    let browsers = browser_lists(browserquery).context("leptos.style.browserquery")?;
+    log::trace!("Style browserquery {browserquery:?} targets {browsers:?}");
     let targets = Targets::from(browsers);
 
-    let mut stylesheet =
-        StyleSheet::parse(&css, ParserOptions::default()).map_err(|e| anyhow!("{e}"))?;
+    let parser_options = ParserOptions {
+        filename: filename.to_string(),
+        flags: ParserFlags::CUSTOM_MEDIA,
+        ..Default::default()
+    };
+    let mut stylesheet = StyleSheet::parse(css, parser_options).map_err(|e| anyhow!("{e}"))?;
 
-    if proj.release {
-        let minify_options = MinifyOptions {
-            targets,
-            ..Default::default()
-        };
-        stylesheet.minify(minify_options)?;
-    }
+    // prefixing happens while minifying, so it is done in dev builds too
+    let minify_options = MinifyOptions {
+        targets,
+        ..Default::default()
+    };
+    stylesheet.minify(minify_options)?;
 
     let options = PrinterOptions::<'_> {
         targets,
-        minify: proj.release,
+        minify: release,
         ..Default::default()
     };
+    Ok(stylesheet.to_css(options)?.code)
+}
 
-    let style_output = stylesheet.to_css(options)?;
+pub fn browser_lists(query: &str) -> Result<Option<Browsers>> {
+    Browsers::from_browserslist([query]).context(format!("Error in browserlist query: {query}"))
+}
+
+async fn process_css(proj: &Project, css: String) -> Result<Product> {
+    let style_output = transform_css(
+        &css,
+        &proj.style.browserquery,
+        proj.release,
+        proj.style.site_file.site.as_str(),
+    )?;
 
-    let bytes = style_output.code.as_bytes();
+    let bytes = style_output.as_bytes();
 
     let prod = match proj.site.updated_with(&proj.style.site_file, bytes).await? {
         true => {
@@ -1,3 +1,4 @@
+use super::errors::record_error;
 use anyhow::Result;
 use tokio::process::Command;
 
@@ -18,9 +19,9 @@ pub async fn compile_tailwind(proj: &Project, tw_conf: &TailwindConfig) -> Resul
         create_default_tailwind_config(tw_conf).await?;
     }
 
-    let (line, process) = tailwind_process(&proj, "tailwindcss", tw_conf).await?;
+    let (line, process) = tailwind_process(proj, "tailwindcss", tw_conf).await?;
 
-    match wait_piped_interruptible("Tailwind", process, Interrupt::subscribe_any()).await? {
+    match wait_piped_interruptible("Tailwind", process, Interrupt::subscribe_any(proj)).await? {
         CommandResult::Success(output) => {
             let done = output
                 .stderr()
@@ -41,6 +42,7 @@ pub async fn compile_tailwind(proj: &Project, tw_conf: &TailwindConfig) -> Resul
             } else {
                 log::warn!("Tailwind failed {}", GRAY.paint(line));
                 println!("{}\n{}", output.stdout(), output.stderr());
+                record_error(proj, "Tailwind failed", &output.stderr());
                 Ok(Outcome::Failed)
             }
         }
@@ -51,6 +53,7 @@ pub async fn compile_tailwind(proj: &Project, tw_conf: &TailwindConfig) -> Resul
                 println!("{}", output.stdout());
             }
             println!("{}", output.stderr());
+            record_error(proj, "Tailwind failed", &output.stderr());
             Ok(Outcome::Failed)
         }
     }
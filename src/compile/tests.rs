@@ -1,11 +1,38 @@
+use crate::service::site::SiteAsset;
 use crate::{
     compile::front::build_cargo_front_cmd,
-    config::{Config, Opts},
+    config::{Config, ImageFormat, ImagesConfig, Opts},
 };
+use camino::Utf8PathBuf;
 use insta::assert_snapshot;
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime},
+};
 use tokio::process::Command;
 
-use super::server::build_cargo_server_cmd;
+use super::{
+    analyze::{crate_name, profile_tables, WasmProfile},
+    assets::{sync_plan, SyncPlan},
+    build_info::{build_info_json, rfc3339, GitInfo},
+    coverage::build_cargo_coverage_cmd,
+    csp::{csp_hash, CspHashes},
+    css_modules::compile_module,
+    embed::embed_module,
+    errors::error_text,
+    images::{optimize_images, ImagesOptimized},
+    prerender::{linked_paths, page_file},
+    proxy_config::{caddy_config, nginx_config},
+    purge::{class_candidates, purge},
+    pwa::{icon_file, precache_files},
+    routes::parse_route_list,
+    sass::{sass_args, sass_dependencies},
+    server::build_cargo_server_cmd,
+    sitemap::sitemap_xml,
+    size_report::{ledger_csv, parse_ledger_csv, pkg_sizes, size_table, FileSizes, LedgerEntry},
+    static_host::{headers_file, redirects_file, wasm_threads},
+    style::transform_css,
+};
 
 fn release_opts() -> Opts {
     Opts {
@@ -13,7 +40,8 @@ fn release_opts() -> Opts {
         js_minify: true,
         precompress: false, // if set to true, testing could take quite a while longer
         hot_reload: false,
-        project: None,
+        project: Vec::new(),
+        tag: Vec::new(),
         verbose: 0,
         features: Vec::new(),
         bin_features: Vec::new(),
@@ -21,6 +49,20 @@ fn release_opts() -> Opts {
         bin_cargo_args: None,
         lib_cargo_args: None,
         wasm_debug: false,
+        timings: false,
+        reproducible: false,
+        size_report: None,
+        size_baseline: None,
+        tls: false,
+        env: None,
+        addr: None,
+        host: None,
+        port: None,
+        reload_port: None,
+        port_fallback: false,
+        gateway: None,
+        all: false,
+        non_interactive: false,
     }
 }
 fn dev_opts() -> Opts {
@@ -29,7 +71,8 @@ fn dev_opts() -> Opts {
         js_minify: false,
         precompress: false,
         hot_reload: false,
-        project: None,
+        project: Vec::new(),
+        tag: Vec::new(),
         verbose: 0,
         features: Vec::new(),
         bin_features: Vec::new(),
@@ -37,6 +80,20 @@ fn dev_opts() -> Opts {
         bin_cargo_args: None,
         lib_cargo_args: None,
         wasm_debug: false,
+        timings: false,
+        reproducible: false,
+        size_report: None,
+        size_baseline: None,
+        tls: false,
+        env: None,
+        addr: None,
+        host: None,
+        port: None,
+        reload_port: None,
+        port_fallback: false,
+        gateway: None,
+        all: false,
+        non_interactive: false,
     }
 }
 
@@ -194,3 +251,711 @@ fn test_extra_cargo_args() {
         "--target=wasm32-unknown-unknown --no-default-features --features=hydrate -j 8"
     ));
 }
+
+#[test]
+fn test_timings() {
+    let cli = Opts {
+        timings: true,
+        ..dev_opts()
+    };
+    let conf = Config::test_load(cli, "examples", "examples/project/Cargo.toml", true, None);
+
+    let mut command = Command::new("cargo");
+    let (_, cargo) = build_cargo_server_cmd("build", &conf.projects[0], &mut command);
+
+    assert_snapshot!(cargo, @"cargo build --package=example --bin=example --timings --no-default-features --features=ssr");
+
+    let mut command = Command::new("cargo");
+    let (_, cargo) = build_cargo_front_cmd("build", true, &conf.projects[0], &mut command);
+
+    assert!(cargo.contains("--target=wasm32-unknown-unknown --timings"));
+}
+
+#[test]
+fn test_bench_cmd() {
+    let cli = release_opts();
+    let conf = Config::test_load(cli, "examples", "examples/project/Cargo.toml", true, None);
+
+    let mut command = Command::new("cargo");
+    let (_, cargo) = build_cargo_server_cmd("bench", &conf.projects[0], &mut command);
+
+    // all the bench targets, with the bench profile
+    assert_snapshot!(cargo, @"cargo bench --package=example --no-default-features --features=ssr");
+}
+
+#[test]
+fn test_coverage_cmd() {
+    let cli = Opts {
+        timings: true,
+        ..dev_opts()
+    };
+    let conf = Config::test_load(cli, "examples", "examples/project/Cargo.toml", true, None);
+
+    let mut test = Command::new("cargo");
+    build_cargo_server_cmd("test", &conf.projects[0], &mut test);
+    let mut command = Command::new("cargo");
+    let cargo = build_cargo_coverage_cmd(&test, &mut command);
+
+    assert_snapshot!(cargo, @"cargo llvm-cov test --no-report --package=example --no-default-features --features=ssr");
+    assert!(command
+        .as_std()
+        .get_envs()
+        .any(|(name, val)| name == "LEPTOS_OUTPUT_NAME" && val.is_some_and(|v| v == "example")));
+
+    let mut test = Command::new("cargo");
+    build_cargo_front_cmd("test", false, &conf.projects[0], &mut test);
+    let mut command = Command::new("cargo");
+    let cargo = build_cargo_coverage_cmd(&test, &mut command);
+
+    // the separate front target dir is left to cargo-llvm-cov
+    assert_snapshot!(cargo, @"cargo llvm-cov test --no-report --package=example --lib --no-default-features --features=hydrate");
+}
+
+#[test]
+fn test_reproducible() {
+    let cli = Opts {
+        reproducible: true,
+        ..release_opts()
+    };
+    let conf = Config::test_load(cli, "examples", "examples/project/Cargo.toml", true, None);
+
+    let mut command = Command::new("cargo");
+    let (envs, cargo) = build_cargo_server_cmd("build", &conf.projects[0], &mut command);
+
+    assert!(envs.contains("SOURCE_DATE_EPOCH="));
+    // in the env, as cargo ignores build.rustflags when target rustflags are set
+    assert!(envs.contains("CARGO_ENCODED_RUSTFLAGS="));
+    assert!(envs.contains("--remap-path-prefix="));
+    assert!(!cargo.contains("--config"));
+
+    let mut command = Command::new("cargo");
+    let (envs, cargo) = build_cargo_front_cmd("build", true, &conf.projects[0], &mut command);
+
+    assert!(envs.contains("CARGO_ENCODED_RUSTFLAGS="));
+    assert!(envs.contains("--remap-path-prefix="));
+    assert!(!cargo.contains("--config"));
+}
+
+#[test]
+fn test_transform_css() {
+    let css = ".a { user-select: none; .b { color: red } }";
+
+    let dev = transform_css(css, "safari 10", false, "app.css").unwrap();
+    assert!(dev.contains("-webkit-user-select: none"));
+    assert!(dev.contains(".a .b {"));
+
+    let release = transform_css(css, "safari 10", true, "app.css").unwrap();
+    assert_snapshot!(release, @".a{-webkit-user-select:none;user-select:none}.a .b{color:red}");
+
+    assert!(transform_css(css, "not a query", false, "app.css").is_err());
+}
+
+#[test]
+fn test_css_module() {
+    let css = ".button { color: red } .primary { composes: button; font-weight: bold }";
+    let (code, exports) = compile_module("components/button.module.css".into(), css).unwrap();
+
+    assert_snapshot!(format!("{exports:?}"), @r#"{"button": "_6U86uq_button", "primary": "_6U86uq_primary _6U86uq_button"}"#);
+    assert!(code.contains(&format!(".{} {{", exports["button"])));
+}
+
+#[test]
+fn test_sass_args() {
+    let conf = Config::test_load(
+        release_opts(),
+        "examples",
+        "examples/project/Cargo.toml",
+        true,
+        None,
+    );
+    let mut style = conf.projects[0].style.clone();
+    style.sass_load_paths = vec!["node_modules".into()];
+    style.sass_args = vec!["--quiet-deps".to_string()];
+
+    let args = sass_args("style/main.scss".into(), &style, true);
+    assert_eq!(
+        args,
+        [
+            "--load-path=node_modules",
+            "--quiet-deps",
+            "--no-source-map",
+            "style/main.scss"
+        ]
+    );
+}
+
+#[test]
+fn test_sass_dependencies() {
+    let dir = temp_dir::TempDir::new().unwrap();
+    let root = camino::Utf8PathBuf::from_path_buf(dir.path().to_path_buf()).unwrap();
+    let write = |file: &str, content: &str| {
+        let path = root.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    };
+    write(
+        "style/main.scss",
+        "@use 'sass:math';\n@use \"theme\" as t;\n@import 'shared/a', 'lib';\n",
+    );
+    write("style/_theme.scss", "@forward '../other/colors';");
+    write("other/colors.scss", "$red: red;");
+    write("style/shared/_a.scss", "");
+    write("vendor/lib/_index.scss", "@import 'url(x.css)';");
+
+    let mut deps = sass_dependencies(&[root.join("style/main.scss")], &[root.join("vendor")])
+        .into_iter()
+        .map(|dep| dep.strip_prefix(&root).unwrap().to_string())
+        .collect::<Vec<_>>();
+    deps.sort();
+    assert_eq!(
+        deps,
+        [
+            "style/../other/colors.scss",
+            "style/_theme.scss",
+            "style/shared/_a.scss",
+            "vendor/lib/_index.scss"
+        ]
+    );
+}
+
+#[test]
+fn test_purge_css() {
+    let used = class_candidates(
+        r#"view! { <div class="card md:flex" class:active=move || on><p>"x"</p></div> }"#,
+    );
+    let css = ".card { color: red }
+.unused, .card > .title { margin: 0 }
+.md\\:flex { display: flex }
+@media (width > 100px) { .active { color: blue } .gone { color: blue } }
+.btn-primary, p:not(.other) { padding: 0 }
+";
+    let (purged, removed) = purge(css, &|class| {
+        used.contains(class) || class.starts_with("btn-")
+    })
+    .unwrap();
+    assert_eq!(removed, 3);
+    assert_snapshot!(purged, @r"
+    .card {
+      color: red;
+    }
+
+    .md\:flex {
+      display: flex;
+    }
+
+    @media (width > 100px) {
+      .active {
+        color: #00f;
+      }
+    }
+
+    .btn-primary, p:not(.other) {
+      padding: 0;
+    }
+    ");
+}
+
+#[test]
+fn test_csp_hashes() {
+    assert_eq!(
+        csp_hash(""),
+        "'sha256-47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU='"
+    );
+
+    let mut hashes = CspHashes::default();
+    hashes.add(r#"<style>p{}</style><script src="/pkg/app.js"></script><script>a()</script>"#);
+    hashes.add(r#"<link rel="preload" onload="this.rel='stylesheet'"><script>a()</script>"#);
+    assert_eq!(hashes.script_src.len(), 1);
+    assert_eq!(hashes.style_src.len(), 1);
+    assert_eq!(hashes.script_src_attr.len(), 1);
+}
+
+#[test]
+fn test_error_text() {
+    let output = "\x1b[1m\x1b[32m   Compiling\x1b[0m app v0.1.0\n\
+                  \x1b[33mwarning\x1b[0m: unused variable: `a`\n\
+                  \x1b[36m    Building\x1b[0m [=>  ] 1/2: app\r\x1b[K\x1b[1m\x1b[91merror[E0425]\x1b[0m: cannot find value `b`\n\
+                  \x20--> src/lib.rs:3:5\n\
+                  \n\
+                  error: could not compile `app`\n";
+    assert_snapshot!(error_text(output), @r"
+    error[E0425]: cannot find value `b`
+     --> src/lib.rs:3:5
+
+    error: could not compile `app`
+    ");
+}
+
+#[test]
+fn test_prerender_paths() {
+    let html = r#"<link href="/pkg/app.css" rel="stylesheet"><a href="/about">About</a>
+        <a href='/blog/first?ref=home#top'>First</a><a href="https://example.com/">Out</a>
+        <a href="//cdn.example.com/x">Cdn</a><a href="/old.html">Old</a><a href=/bare>Bare</a>"#;
+    assert_eq!(
+        linked_paths(html, ""),
+        ["/about", "/blog/first", "/old.html"]
+    );
+
+    // under the base url
+    let html = r#"<a href="/app/about">About</a><a href="/other">Other</a>"#;
+    assert_eq!(linked_paths(html, "/app"), ["/about"]);
+
+    let dir = camino::Utf8Path::new("out");
+    assert_eq!(page_file(dir, "/").unwrap(), dir.join("index.html"));
+    assert_eq!(
+        page_file(dir, "/blog/first/").unwrap(),
+        dir.join("blog/first").join("index.html")
+    );
+    assert_eq!(page_file(dir, "/old.html").unwrap(), dir.join("old.html"));
+    assert!(page_file(dir, "/../etc").is_err());
+}
+
+#[test]
+fn test_route_list() {
+    let output = "route Get /\nroute Get /post/:id\nroute Post /post/:id\n\
+        route Get /files/{*path}\nroute Get /docs/{tail:.*}\nserver-fn POST /api/add_todo\n";
+    let list = parse_route_list(output);
+    assert_snapshot!(serde_json::to_string(&list).unwrap(), @r###"{"routes":[{"path":"/","methods":["GET"],"segments":[]},{"path":"/post/:id","methods":["GET","POST"],"segments":[{"kind":"static","name":"post"},{"kind":"param","name":"id"}]},{"path":"/files/{*path}","methods":["GET"],"segments":[{"kind":"static","name":"files"},{"kind":"wildcard","name":"path"}]},{"path":"/docs/{tail:.*}","methods":["GET"],"segments":[{"kind":"static","name":"docs"},{"kind":"wildcard","name":"tail"}]}],"server_fns":[{"path":"/api/add_todo","method":"POST"}]}"###);
+}
+
+#[test]
+fn test_proxy_config() {
+    let mut cli = release_opts();
+    cli.precompress = true;
+    let conf = Config::test_load(cli, "examples", "examples/project/Cargo.toml", true, None);
+    let proj = &conf.projects[0];
+    let root = camino::Utf8Path::new("/srv/site");
+
+    assert_snapshot!(nginx_config(proj, root), @r###"
+    # generated by cargo-leptos for the project example
+
+    location ^~ /pkg/ {
+        alias /srv/site/pkg/;
+        add_header Cache-Control "public, max-age=31536000, immutable";
+        gzip_static on;
+        # needs the ngx_brotli module
+        brotli_static on;
+    }
+
+    location / {
+        alias /srv/site/;
+        try_files $uri @leptos;
+        gzip_static on;
+        # needs the ngx_brotli module
+        brotli_static on;
+    }
+
+    location @leptos {
+        proxy_pass http://127.0.0.1:3000;
+        proxy_http_version 1.1;
+        proxy_set_header Host $host;
+        proxy_set_header X-Forwarded-For $proxy_add_x_forwarded_for;
+        proxy_set_header X-Forwarded-Proto $scheme;
+    }
+    "###);
+    assert_snapshot!(caddy_config(proj, root), @r###"
+    # generated by cargo-leptos for the project example
+    handle {
+        root * /srv/site
+
+        @pkg path /pkg/*
+        header @pkg Cache-Control "public, max-age=31536000, immutable"
+
+        @static file
+        handle @static {
+            file_server {
+                precompressed br gzip
+            }
+        }
+
+        handle {
+            reverse_proxy 127.0.0.1:3000
+        }
+    }
+    "###);
+}
+
+#[test]
+fn test_static_host_files() {
+    let conf = Config::test_load(
+        release_opts(),
+        "examples",
+        "examples/project/Cargo.toml",
+        true,
+        None,
+    );
+    let proj = &conf.projects[0];
+
+    assert_snapshot!(headers_file(proj, false).unwrap(), @r###"
+    /pkg/*
+      Cache-Control: public, max-age=31536000, immutable
+    "###);
+    assert_snapshot!(headers_file(proj, true).unwrap(), @r###"
+    /pkg/*
+      Cache-Control: public, max-age=31536000, immutable
+
+    /*
+      Cross-Origin-Opener-Policy: same-origin
+      Cross-Origin-Embedder-Policy: require-corp
+    "###);
+    assert_eq!(redirects_file(proj).unwrap(), "/*  /index.html  200\n");
+
+    let flags = |name: &str| {
+        (name == "RUSTFLAGS").then(|| "-C target-feature=+atomics,+bulk-memory".to_string())
+    };
+    assert!(wasm_threads(flags));
+    assert!(!wasm_threads(|_| None));
+}
+
+#[test]
+fn test_pwa_files() {
+    let dir = temp_dir::TempDir::new().unwrap();
+    let root = camino::Utf8Path::from_path(dir.path()).unwrap();
+    for file in [
+        "index.html",
+        "sw.js",
+        "pkg/app.1a2b.js",
+        "pkg/app.1a2b.js.br",
+        "pkg/app.1a2b.wasm",
+        "icons/icon-192.3c4d.png",
+        ".well-known/security.txt",
+    ] {
+        let path = root.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, "").unwrap();
+    }
+    assert_eq!(
+        precache_files(root).unwrap(),
+        [
+            "icons/icon-192.3c4d.png",
+            "pkg/app.1a2b.js",
+            "pkg/app.1a2b.wasm"
+        ]
+    );
+
+    // at its hashed name
+    assert_eq!(
+        icon_file(root, "icons/icon-192.png".into()).unwrap(),
+        "icons/icon-192.3c4d.png"
+    );
+    assert!(icon_file(root, "icons/icon-512.png".into()).is_err());
+}
+
+#[test]
+fn test_sitemap_xml() {
+    let xml = sitemap_xml("https://example.com/app", &["/", "/blog/a&b"]);
+    assert_snapshot!(xml, @r###"
+    <?xml version="1.0" encoding="UTF-8"?>
+    <urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+      <url><loc>https://example.com/app/</loc></url>
+      <url><loc>https://example.com/app/blog/a&amp;b</loc></url>
+    </urlset>
+    "###);
+}
+
+#[test]
+fn test_embed_module() {
+    let dir = temp_dir::TempDir::new().unwrap();
+    let root = camino::Utf8Path::from_path(dir.path()).unwrap();
+    for file in [
+        "index.html",
+        "pkg/app.1a2b.js",
+        "pkg/app.1a2b.js.gz",
+        "hash.txt",
+    ] {
+        let path = root.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, "").unwrap();
+    }
+    let module = embed_module("app", root, Some(&root.join("hash.txt"))).unwrap();
+    assert_snapshot!(module.replace(root.as_str(), "ROOT"), @r###"
+    // generated by cargo-leptos: the files of the site root of app, included into the
+    // server with `include!(env!("LEPTOS_EMBED_FILE"))`
+
+    /// a file of the site, with its precompressed copies
+    pub struct EmbeddedFile {
+        /// the path relative to the site root
+        pub path: &'static str,
+        pub data: &'static [u8],
+        pub gzip: Option<&'static [u8]>,
+        pub brotli: Option<&'static [u8]>,
+    }
+
+    /// the files of the site, sorted by path
+    pub static FILES: &[EmbeddedFile] = &[
+        EmbeddedFile {
+            path: "hash.txt",
+            data: include_bytes!("ROOT/hash.txt"),
+            gzip: None,
+            brotli: None,
+        },
+        EmbeddedFile {
+            path: "index.html",
+            data: include_bytes!("ROOT/index.html"),
+            gzip: None,
+            brotli: None,
+        },
+        EmbeddedFile {
+            path: "pkg/app.1a2b.js",
+            data: include_bytes!("ROOT/pkg/app.1a2b.js"),
+            gzip: Some(include_bytes!("ROOT/pkg/app.1a2b.js.gz")),
+            brotli: None,
+        },
+    ];
+
+    /// the contents of the hash file, with the hashed names of the files
+    pub static HASH_FILE: Option<&str> = Some(include_str!("ROOT/hash.txt"));
+
+    /// the file at the path relative to the site root
+    pub fn get(path: &str) -> Option<&'static EmbeddedFile> {
+        FILES
+            .binary_search_by(|file| file.path.cmp(path))
+            .ok()
+            .map(|index| &FILES[index])
+    }
+    "###);
+}
+
+#[test]
+fn test_build_info() {
+    assert_eq!(rfc3339(0), "1970-01-01T00:00:00Z");
+    assert_eq!(rfc3339(951_825_599), "2000-02-29T11:59:59Z");
+    assert_eq!(rfc3339(1_714_564_800), "2024-05-01T12:00:00Z");
+
+    let conf = Config::test_load(
+        release_opts(),
+        "examples",
+        "examples/project/Cargo.toml",
+        true,
+        None,
+    );
+    let git = GitInfo {
+        sha: "1a2b3c".to_string(),
+        dirty: true,
+    };
+    let info = build_info_json(&conf.projects[0], Some(git), 1_714_564_800);
+    assert_eq!(info["git"]["sha"], "1a2b3c");
+    assert_eq!(info["git"]["dirty"], true);
+    assert_eq!(info["built_at"], "2024-05-01T12:00:00Z");
+    assert_eq!(info["profile"], "release");
+    assert_eq!(info["cargo_leptos"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(info["bin_features"], serde_json::json!(["ssr"]));
+}
+
+#[test]
+fn test_size_report() {
+    let dir = temp_dir::TempDir::new().unwrap();
+    let pkg_dir = camino::Utf8Path::from_path(dir.path()).unwrap();
+    std::fs::write(pkg_dir.join("app.Fd3kPq0a9Zx_Yw-7BcDeFg.wasm"), [0u8; 2048]).unwrap();
+    std::fs::write(pkg_dir.join("app.css"), "body {}").unwrap();
+    std::fs::write(pkg_dir.join("app.css.br"), "1234").unwrap();
+
+    // by the names without their hash, with the precompressed copies
+    let sizes = pkg_sizes(pkg_dir).unwrap();
+    assert_eq!(sizes.keys().collect::<Vec<_>>(), ["app.css", "app.wasm"]);
+    assert_eq!(sizes["app.wasm"].raw, 2048);
+    assert_eq!(sizes["app.css"].brotli, 4);
+
+    let size = |raw, gzip, brotli| FileSizes { raw, gzip, brotli };
+    let sizes = [
+        ("app.css".to_string(), size(300, 200, 150)),
+        ("app.wasm".to_string(), size(2_500_000, 800_000, 600_000)),
+    ]
+    .into();
+    let previous = [("app.wasm".to_string(), size(2_400_000, 790_000, 590_000))].into();
+    assert_snapshot!(size_table(&sizes, &previous), @r###"
+    file             raw        gzip      brotli      change
+    app.css        300 B       200 B       150 B         new
+    app.wasm    2.38 MiB   781.2 KiB   585.9 KiB    +9.8 KiB
+    total       2.38 MiB   781.4 KiB   586.1 KiB    +9.9 KiB
+    "###);
+
+    // a row per file in the csv ledger
+    let entries = vec![
+        LedgerEntry {
+            sha: "1a2b".to_string(),
+            project: "app".to_string(),
+            files: previous,
+        },
+        LedgerEntry {
+            sha: "3c4d-dirty".to_string(),
+            project: "app".to_string(),
+            files: sizes,
+        },
+    ];
+    let csv = ledger_csv(&entries);
+    assert_snapshot!(csv, @r###"
+    sha,project,file,raw,gzip,brotli
+    1a2b,app,app.wasm,2400000,790000,590000
+    3c4d-dirty,app,app.css,300,200,150
+    3c4d-dirty,app,app.wasm,2500000,800000,600000
+    "###);
+    assert_eq!(parse_ledger_csv(&csv).unwrap(), entries);
+}
+
+#[test]
+fn test_analyze() {
+    // a module importing a function and defining two, named in its name section
+    let section = |id: u8, contents: Vec<u8>| {
+        let mut section = vec![id, contents.len() as u8];
+        section.extend(contents);
+        section
+    };
+    let name = |text: &str| {
+        let mut name = vec![text.len() as u8];
+        name.extend(text.as_bytes());
+        name
+    };
+    let mut imports = vec![1];
+    imports.extend(name("env"));
+    imports.extend(name("log"));
+    imports.extend([0, 0]);
+    let mut function_names = vec![2, 1];
+    function_names.extend(name("_ZN4core3fmt5write17h0123456789abcdefE"));
+    function_names.push(2);
+    function_names.extend(name("<leptos::View as core::fmt::Debug>::fmt"));
+    let mut names = name("name");
+    names.extend(section(1, function_names));
+
+    let mut wasm = b"\0asm\x01\0\0\0".to_vec();
+    wasm.extend(section(1, vec![1, 0x60, 0, 0]));
+    wasm.extend(section(2, imports));
+    wasm.extend(section(3, vec![2, 0, 0]));
+    wasm.extend(section(10, vec![2, 2, 0, 0x0b, 4, 0, 1, 1, 0x0b]));
+    wasm.extend(section(0, names));
+
+    let profile = WasmProfile::parse(&wasm).unwrap();
+    assert_snapshot!(profile_tables(&profile, 10), @r###"
+    bytes       %    fns  crate
+        4   66.7%      1  leptos
+        2   33.3%      1  core
+
+    bytes       %  function
+        4   66.7%  <leptos::View as core::fmt::Debug>::fmt
+        2   33.3%  core::fmt::write
+
+    bytes  section
+        6  code
+       89  custom "name"
+       39  other
+    "###);
+
+    assert_eq!(crate_name("func[3]"), "(unnamed)");
+    assert_eq!(crate_name("<&str as core::fmt::Display>::fmt"), "(other)");
+    assert_eq!(crate_name("alloc::raw_vec::finish_grow"), "alloc");
+}
+
+#[tokio::test]
+async fn test_optimize_images() {
+    let dir = temp_dir::TempDir::new().unwrap();
+    let dir = camino::Utf8Path::from_path(dir.path()).unwrap();
+    let (assets, root, cache) = (dir.join("assets"), dir.join("site"), dir.join("cache"));
+    std::fs::create_dir_all(assets.join("img")).unwrap();
+    std::fs::create_dir_all(root.join("img")).unwrap();
+    let gradient =
+        image::RgbImage::from_fn(64, 32, |x, y| image::Rgb([x as u8 * 4, y as u8 * 8, 128]));
+    gradient.save(assets.join("img/a.png")).unwrap();
+    std::fs::copy(assets.join("img/a.png"), root.join("img/a.png")).unwrap();
+
+    let config = ImagesConfig {
+        formats: vec![ImageFormat::WebP],
+        widths: vec![16, 128],
+        quality: 50,
+    };
+    let files = || vec!["img/a.png".into(), "favicon.ico".into()];
+    let optimized = optimize_images(&config, &assets, &root, &cache, files())
+        .await
+        .unwrap();
+    assert_eq!(
+        optimized,
+        ImagesOptimized {
+            images: 1,
+            encoded: 1
+        }
+    );
+    let manifest = std::fs::read_to_string(root.join("assets-manifest.json")).unwrap();
+    for path in ["img/a.webp", "img/a-16w.png", "img/a-16w.webp"] {
+        assert!(manifest.contains(&format!("\"{path}\"")), "{manifest}");
+        assert!(root.join(path).is_file());
+    }
+    assert!(!manifest.contains("a-128w"));
+    // lossy, with the VP8 rather than the VP8L chunk
+    let webp = std::fs::read(root.join("img/a.webp")).unwrap();
+    assert_eq!(&webp[12..16], b"VP8 ");
+
+    // unchanged, the files come from the cache after the site root was cleaned
+    std::fs::remove_file(root.join("img/a-16w.webp")).unwrap();
+    let optimized = optimize_images(&config, &assets, &root, &cache, files())
+        .await
+        .unwrap();
+    assert_eq!(
+        optimized,
+        ImagesOptimized {
+            images: 1,
+            encoded: 0
+        }
+    );
+    assert!(root.join("img/a-16w.webp").is_file());
+
+    // changed settings encode it again, and the stale entry is pruned
+    let config = ImagesConfig {
+        quality: 60,
+        ..config
+    };
+    let optimized = optimize_images(&config, &assets, &root, &cache, files())
+        .await
+        .unwrap();
+    assert_eq!(optimized.encoded, 1);
+    assert_eq!(cache.read_dir_utf8().unwrap().count(), 1);
+
+    gradient.save(assets.join("img/a.jpg")).unwrap();
+    let files = vec!["img/a.png".into(), "img/a.jpg".into()];
+    let err = optimize_images(&config, &assets, &root, &cache, files)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "The images img/a.jpg and img/a.png would both generate img/a.webp, rename one of them"
+    );
+}
+
+#[test]
+fn test_assets_sync_plan() {
+    let old = (SystemTime::UNIX_EPOCH, 10);
+    let new = (SystemTime::UNIX_EPOCH + Duration::from_secs(60), 10);
+    let asset = |stamp, dest: &str| SiteAsset {
+        stamp,
+        dest: dest.into(),
+    };
+    let paths = |paths: &[&str]| paths.iter().map(Utf8PathBuf::from).collect::<Vec<_>>();
+    let registry = HashMap::from([
+        ("a.txt".into(), asset(old, "a.txt")),
+        ("b.txt".into(), asset(old, "b.txt")),
+        ("gone.txt".into(), asset(old, "gone.txt")),
+        ("logo.png".into(), asset(old, "logo.Xy3.png")),
+        ("icon.png".into(), asset(old, "icon.Ab1.png")),
+        ("gone.png".into(), asset(old, "gone.Cd2.png")),
+    ]);
+    let found = HashMap::from([
+        ("a.txt".into(), old),
+        ("b.txt".into(), new),
+        ("c.txt".into(), new),
+        ("logo.png".into(), old),
+        ("icon.png".into(), new),
+    ]);
+    // the copies of the assets are in the site under their hashed names
+    let plan = sync_plan(&found, &registry, |rel| rel != "b.txt" && rel != "logo.png");
+    assert_eq!(
+        plan,
+        SyncPlan {
+            // unchanged a.txt and logo.png skipped, though the latter was renamed
+            copy: paths(&["b.txt", "c.txt", "icon.png"]),
+            // the deleted assets and the hashed copy of the changed one
+            remove: paths(&["gone.Cd2.png", "gone.txt", "icon.Ab1.png"]),
+        }
+    );
+
+    // a copy removed from the site is copied again
+    let plan = sync_plan(&found, &registry, |rel| rel != "a.txt");
+    assert!(plan.copy.contains(&"a.txt".into()));
+}
@@ -0,0 +1,123 @@
+use crate::{
+    config::Project,
+    ext::{
+        anyhow::{Context, Result},
+        fs,
+    },
+    logger::{self, GRAY},
+    service::dashboard,
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use std::{sync::Mutex, time::Duration};
+
+lazy_static::lazy_static! {
+  static ref STAGES: Mutex<Vec<(&'static str, Duration)>> = Mutex::new(Vec::new());
+}
+
+/// the file name cargo uses for the latest `--timings` report
+const CARGO_TIMING_REPORT: &str = "cargo-timings/cargo-timing.html";
+
+/// Records the duration of one of cargo-leptos' own build stages (bindgen, wasm-opt, style, ...)
+/// so that it can be included in the combined `--timings` report.
+pub fn record_stage(stage: &'static str, duration: Duration) {
+    logger::log_stage(stage, duration);
+    dashboard::stage_done(stage, duration);
+    STAGES.lock().unwrap().push((stage, duration));
+}
+
+pub fn clear_stages() {
+    STAGES.lock().unwrap().clear();
+}
+
+/// Writes `target/cargo-leptos-timings.html` which lists the cargo-leptos stage timings and
+/// links to (and embeds) the reports generated by the front and server cargo builds.
+pub async fn write_timings_report(proj: &Project) -> Result<()> {
+    let file = proj.target_dir.join("cargo-leptos-timings.html");
+
+    let front_report = proj.lib.front_target_path.join(CARGO_TIMING_REPORT);
+    let server_report = proj.bin.as_ref().map(|bin| {
+        match &bin.target_dir {
+            Some(dir) => proj.working_dir.join(dir),
+            None => proj.target_dir.clone(),
+        }
+        .join(CARGO_TIMING_REPORT)
+    });
+
+    let stages = STAGES.lock().unwrap().clone();
+    let mut reports = vec![("Front (wasm) cargo build", &front_report)];
+    if let Some(server_report) = &server_report {
+        reports.push(("Server cargo build", server_report));
+    }
+    let html = render(&proj.name, &stages, &reports, &proj.target_dir);
+
+    fs::write(&file, html)
+        .await
+        .context("Could not write the timings report")?;
+    log::info!("Timings report written to {}", GRAY.paint(file.as_str()));
+    Ok(())
+}
+
+fn render(
+    name: &str,
+    stages: &[(&'static str, Duration)],
+    reports: &[(&str, &Utf8PathBuf)],
+    base: &Utf8Path,
+) -> String {
+    let total: Duration = stages.iter().map(|(_, d)| *d).sum();
+    let rows = stages
+        .iter()
+        .map(|(stage, duration)| {
+            format!(
+                "<tr><td>{stage}</td><td>{:.2}s</td></tr>",
+                duration.as_secs_f64()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let sections = reports
+        .iter()
+        .map(|(title, report)| {
+            if report.exists() {
+                let href =
+                    pathdiff::diff_utf8_paths(report, base).unwrap_or_else(|| report.to_path_buf());
+                format!(
+                    r#"<h2>{title}</h2>
+<p><a href="{href}">{href}</a></p>
+<iframe src="{href}"></iframe>"#
+                )
+            } else {
+                format!("<h2>{title}</h2>\n<p>No report found at {report}</p>")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>cargo-leptos timings: {name}</title>
+<style>
+body {{ font-family: sans-serif; margin: 2em; }}
+table {{ border-collapse: collapse; }}
+td, th {{ border: 1px solid #ccc; padding: 0.3em 1em; text-align: left; }}
+iframe {{ width: 100%; height: 60vh; border: 1px solid #ccc; }}
+</style>
+</head>
+<body>
+<h1>cargo-leptos timings: {name}</h1>
+<h2>cargo-leptos stages</h2>
+<table>
+<tr><th>Stage</th><th>Duration</th></tr>
+{rows}
+<tr><th>Total</th><th>{:.2}s</th></tr>
+</table>
+{sections}
+</body>
+</html>
+"#,
+        total.as_secs_f64()
+    )
+}
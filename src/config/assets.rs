@@ -1,23 +1,59 @@
-use camino::Utf8PathBuf;
+use camino::{Utf8Path, Utf8PathBuf};
+use globset::GlobSet;
 
-use crate::ext::PathBufExt;
+use crate::ext::{
+    anyhow::{bail, Result},
+    glob_set, PathBufExt,
+};
 
 use super::ProjectConfig;
 
 pub struct AssetsConfig {
     pub dir: Utf8PathBuf,
+    /// glob patterns, relative to the assets dir, of files not to copy
+    pub ignore: Vec<String>,
+    /// glob patterns, relative to the assets dir, of the only files to copy
+    pub include: Option<Vec<String>>,
+    /// symlink (hard link on windows) the files instead of copying them, in dev builds
+    pub link: bool,
+    /// the image optimization settings, when enabled
+    pub images: Option<ImagesConfig>,
+    ignore_set: GlobSet,
+    include_set: Option<GlobSet>,
 }
 
 impl AssetsConfig {
-    pub fn resolve(config: &ProjectConfig) -> Option<Self> {
+    pub fn resolve(config: &ProjectConfig) -> Result<Option<Self>> {
         let Some(assets_dir) = &config.assets_dir else {
-            return None;
+            return Ok(None);
         };
 
-        Some(Self {
+        let include_set = match &config.assets_include {
+            Some(include) => Some(glob_set(include)?),
+            None => None,
+        };
+
+        Ok(Some(Self {
             // relative to the configuration file
             dir: config.config_dir.join(assets_dir),
-        })
+            ignore: config.assets_ignore.clone(),
+            include: config.assets_include.clone(),
+            link: config.assets_link,
+            images: ImagesConfig::resolve(config)?,
+            ignore_set: glob_set(&config.assets_ignore)?,
+            include_set,
+        }))
+    }
+
+    /// whether a file (relative to the assets dir) should be copied to the site
+    pub fn is_included(&self, rel_path: &Utf8Path) -> bool {
+        if self.ignore_set.is_match(rel_path) {
+            return false;
+        }
+        match &self.include_set {
+            Some(include) => include.is_match(rel_path),
+            None => true,
+        }
     }
 }
 
@@ -25,6 +61,72 @@ impl std::fmt::Debug for AssetsConfig {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("AssetsConfig")
             .field("dir", &self.dir.test_string())
+            .field("ignore", &self.ignore)
+            .field("include", &self.include)
+            .field("link", &self.link)
+            .field("images", &self.images)
             .finish()
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    WebP,
+    Avif,
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::WebP => "webp",
+            Self::Avif => "avif",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ImagesConfig {
+    /// the formats to generate variants in, in addition to the original one
+    pub formats: Vec<ImageFormat>,
+    /// the widths of the responsive variants. Only those smaller than the image are generated
+    pub widths: Vec<u32>,
+    /// the quality of the lossy encoders (jpeg, webp, avif)
+    pub quality: u8,
+}
+
+impl ImagesConfig {
+    fn resolve(config: &ProjectConfig) -> Result<Option<Self>> {
+        if !config.assets_optimize_images {
+            return Ok(None);
+        }
+
+        let formats = match &config.assets_image_formats {
+            Some(formats) => formats
+                .iter()
+                .map(|format| match format.to_lowercase().as_str() {
+                    "webp" => Ok(ImageFormat::WebP),
+                    "avif" => Ok(ImageFormat::Avif),
+                    _ => bail!(
+                        "Unsupported assets-image-formats value {format:?}, expected \"webp\" or \"avif\""
+                    ),
+                })
+                .collect::<Result<Vec<_>>>()?,
+            None => vec![ImageFormat::WebP],
+        };
+
+        let quality = config.assets_image_quality.unwrap_or(80);
+        if !(1..=100).contains(&quality) {
+            bail!("The assets-image-quality must be between 1 and 100, got {quality}");
+        }
+
+        let mut widths = config.assets_image_widths.clone();
+        widths.sort_unstable();
+        widths.dedup();
+
+        Ok(Some(Self {
+            formats,
+            widths,
+            quality,
+        }))
+    }
+}
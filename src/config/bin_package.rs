@@ -5,7 +5,7 @@ use super::{project::ProjectDefinition, Profile, ProjectConfig};
 use crate::{
     config::Opts,
     ext::{
-        anyhow::{anyhow, bail, Error, Result},
+        anyhow::{anyhow, bail, ensure, Error, Result},
         MetadataExt, PackageExt, PathBufExt, PathExt,
     },
 };
@@ -14,7 +14,10 @@ pub struct BinPackage {
     pub abs_dir: Utf8PathBuf,
     pub rel_dir: Utf8PathBuf,
     pub exe_file: Utf8PathBuf,
+    /// the bin target, or the example with `bin-example`
     pub target: String,
+    /// the server is a cargo example, built with `--example`
+    pub example: bool,
     pub features: Vec<String>,
     pub default_features: bool,
     /// all source paths, including path dependencies'
@@ -25,6 +28,10 @@ pub struct BinPackage {
     pub cargo_command: Option<String>,
     pub cargo_args: Option<Vec<String>>,
     pub bin_args: Option<Vec<String>>,
+    /// the server is the cdylib target of the package, built with `--lib` for a wasm runtime
+    pub wasm_lib: bool,
+    /// the command running the wasm server, given the wasm file as last argument
+    pub runner: Option<String>,
 }
 
 impl BinPackage {
@@ -47,13 +54,28 @@ impl BinPackage {
         features.extend(cli.features.clone());
 
         let name = project.bin_package.clone();
+        let example = config.bin_example.is_some();
+        ensure!(
+            !example || config.bin_target.is_empty(),
+            "The bin-target and bin-example of the project {name} are exclusive"
+        );
+        let wasm = config
+            .bin_target_triple
+            .as_ref()
+            .is_some_and(|triple| triple.starts_with("wasm32-"));
         let packages = metadata.workspace_packages();
         let package = packages
             .iter()
-            .find(|p| p.name == name && p.has_bin_target())
+            .find(|p| {
+                p.name == name
+                    && (example || p.has_bin_target() || (wasm && p.cdylib_target().is_some()))
+            })
             .ok_or_else(|| anyhow!(r#"Could not find the project bin-package "{name}""#,))?;
 
         let package = (*package).clone();
+        // the components of spin, wasmCloud and the like are cdylibs rather than bins
+        let wasm_lib = wasm && !example && config.bin_target.is_empty();
+        let wasm_lib = wasm_lib.then(|| package.cdylib_target().cloned()).flatten();
 
         let targets = package
             .targets
@@ -61,7 +83,20 @@ impl BinPackage {
             .filter(|t| t.is_bin())
             .collect::<Vec<&Target>>();
 
-        let target: Target = if !&config.bin_target.is_empty() {
+        let target: Target = if let Some(lib) = &wasm_lib {
+            lib.clone()
+        } else if let Some(bin_example) = &config.bin_example {
+            package
+                .targets
+                .iter()
+                .find(|t| t.is_example() && t.name == *bin_example)
+                .ok_or_else(|| {
+                    anyhow!(
+                        r#"Could not find the example specified: bin-example = "{bin_example}" in {name}"#
+                    )
+                })?
+                .clone()
+        } else if !&config.bin_target.is_empty() {
             targets
                 .into_iter()
                 .find(|t| t.name == config.bin_target)
@@ -87,14 +122,10 @@ impl BinPackage {
                 && config
                     .bin_target_triple
                     .as_ref()
-                    .is_none_or(|triple| triple.contains("-pc-windows-"))
+                    .map_or(true, |triple| triple.contains("-pc-windows-"))
             {
                 "exe"
-            } else if config
-                .bin_target_triple
-                .as_ref()
-                .is_some_and(|target| target.starts_with("wasm32-"))
-            {
+            } else if wasm {
                 "wasm"
             } else {
                 ""
@@ -109,14 +140,20 @@ impl BinPackage {
             if let Some(triple) = &config.bin_target_triple {
                 file = file.join(triple)
             };
+            file = file.join(profile.to_string());
+            // cargo puts the examples in a dir of their own
             let name = if let Some(name) = &config.bin_exe_name {
                 name
+            } else if example {
+                file = file.join("examples");
+                &target.name
+            } else if wasm_lib.is_some() {
+                // cargo names the libs after their crate
+                &target.name.replace('-', "_")
             } else {
                 &name
             };
-            file.join(profile.to_string())
-                .join(name)
-                .with_extension(file_ext)
+            file.join(name).with_extension(file_ext)
         };
 
         let mut src_paths = metadata.src_path_dependencies(&package.id);
@@ -138,6 +175,7 @@ impl BinPackage {
             rel_dir,
             exe_file,
             target: target.name,
+            example,
             features,
             default_features: config.bin_default_features,
             src_paths,
@@ -147,10 +185,19 @@ impl BinPackage {
             cargo_command: config.bin_cargo_command.clone(),
             cargo_args,
             bin_args: bin_args.map(ToOwned::to_owned),
+            wasm_lib: wasm_lib.is_some(),
+            runner: config.bin_runner.clone(),
         })
     }
 }
 
+impl BinPackage {
+    /// whether the server is built for a wasm runtime (wasi, spin, ...) rather than natively
+    pub fn is_wasm(&self) -> bool {
+        self.exe_file.extension() == Some("wasm")
+    }
+}
+
 impl std::fmt::Debug for BinPackage {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("BinPackage")
@@ -158,6 +205,7 @@ impl std::fmt::Debug for BinPackage {
             .field("rel_dir", &self.rel_dir.test_string())
             .field("exe_file", &self.exe_file.test_string())
             .field("target", &self.target)
+            .field("example", &self.example)
             .field("features", &self.features)
             .field("default_features", &self.default_features)
             .field(
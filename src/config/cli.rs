@@ -1,13 +1,63 @@
-use crate::command::NewCommand;
+use crate::command::{ConfigCommand, NewCommand};
 use camino::Utf8PathBuf;
 use clap::{Parser, Subcommand, ValueEnum};
+use log::LevelFilter;
+use std::{
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+};
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+/// a subsystem whose logs are selected with `--log`
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Log {
     /// WASM build (wasm, wasm-opt, walrus)
     Wasm,
     /// Internal reload and csr server (hyper, axum)
     Server,
+    /// the logs of cargo-leptos tagged with the stage, such as `notify`, `reload` or `cargo`
+    Stage(String),
+}
+
+/// A `--log` directive: the subsystem, with the level it is logged at, as `notify=trace`.
+/// Without one, the dependencies are logged at the level of the verbosity and the stages at
+/// debug.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogDirective {
+    pub log: Log,
+    pub level: Option<LevelFilter>,
+}
+
+impl FromStr for LogDirective {
+    type Err = String;
+
+    fn from_str(directive: &str) -> Result<Self, Self::Err> {
+        let (name, level) = match directive.split_once('=') {
+            Some((name, level)) => {
+                let level = level
+                    .parse()
+                    .map_err(|_| format!("invalid log level {level:?}"))?;
+                (name, Some(level))
+            }
+            None => (directive, None),
+        };
+        let log = match name.to_ascii_lowercase().as_str() {
+            "" => return Err(format!("missing log subsystem in {directive:?}")),
+            "wasm" => Log::Wasm,
+            "server" => Log::Server,
+            name => Log::Stage(name.to_string()),
+        };
+        Ok(Self { log, level })
+    }
+}
+
+/// the format of the logs of cargo-leptos
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// colored lines, tagged with the stage
+    #[default]
+    Text,
+    /// a json object per line, with the level, target, stage, project and duration fields
+    Json,
 }
 
 #[derive(Debug, Clone, Parser, PartialEq, Default)]
@@ -24,9 +74,20 @@ pub struct Opts {
     #[arg(long)]
     pub hot_reload: bool,
 
-    /// Which project to use, from a list of projects defined in a workspace
+    /// Which projects to use, from a list of projects defined in a workspace. Repeatable, and
+    /// accepts glob patterns such as `admin-*`.
     #[arg(short, long)]
-    pub project: Option<String>,
+    pub project: Vec<String>,
+
+    /// Use the projects with this tag, among the selected ones. Repeatable: the projects with
+    /// any of the tags.
+    #[arg(long)]
+    pub tag: Vec<String>,
+
+    /// Never ask which project to use when several are available and none is selected, as when
+    /// the input isn't a terminal.
+    #[arg(long)]
+    pub non_interactive: bool,
 
     /// The features to use when compiling all targets
     #[arg(long)]
@@ -56,6 +117,68 @@ pub struct Opts {
     #[arg(short, action = clap::ArgAction::Count)]
     pub verbose: u8,
 
+    /// Pass `--timings` to both cargo builds and combine their reports, together with the
+    /// cargo-leptos stages, into `target/cargo-leptos-timings.html`.
+    #[arg(long)]
+    pub timings: bool,
+
+    /// Append the sizes of the pkg files of the release builds, by git commit, to this JSON
+    /// ledger (or CSV, with a `.csv` extension) for tracking them across commits.
+    #[arg(long)]
+    pub size_report: Option<Utf8PathBuf>,
+
+    /// Compare the sizes of the release builds with the ones of this git ref (such as `main`) in
+    /// the `--size-report` ledger.
+    #[arg(long, requires = "size_report")]
+    pub size_baseline: Option<String>,
+
+    /// Produce byte-identical site output for identical sources: remaps absolute paths in the
+    /// compiled output and honors SOURCE_DATE_EPOCH (defaulting to the last git commit time).
+    #[arg(long)]
+    pub reproducible: bool,
+
+    /// The environment whose `[package.metadata.leptos.env.<ENV>]` section overrides the
+    /// metadata, such as staging or production.
+    #[arg(long)]
+    pub env: Option<String>,
+
+    /// The address the site is served at, instead of the site-addr ones.
+    #[arg(long, conflicts_with_all = ["host", "port"])]
+    pub addr: Option<SocketAddr>,
+
+    /// The IP the site is served at, instead of the one of the site-addr. With 0.0.0.0, the site
+    /// can be opened from the local network, at the urls printed with a QR code.
+    #[arg(long)]
+    pub host: Option<IpAddr>,
+
+    /// The port the site is served at, instead of the one of the site-addr.
+    #[arg(long)]
+    pub port: Option<u16>,
+
+    /// The port of the reload channel, instead of the reload-port.
+    #[arg(long)]
+    pub reload_port: Option<u16>,
+
+    /// When the site or reload port is in use, serve on the next free port above it instead of
+    /// failing. A port 0 always gets a random free port.
+    #[arg(long)]
+    pub port_fallback: bool,
+
+    /// The address of the gateway the projects are run behind, with `--gateway`, at the ip of
+    /// which their reload channels are relayed.
+    #[arg(skip)]
+    pub gateway: Option<SocketAddr>,
+
+    /// The projects are run at once, with `--all`, so they are checked to be served at distinct
+    /// ports.
+    #[arg(skip)]
+    pub all: bool,
+
+    /// Serve the site and the reload channel over https and wss, with the `tls-cert` and `tls-key`
+    /// certificate or else one generated with mkcert.
+    #[arg(long)]
+    pub tls: bool,
+
     /// Minify javascript assets with swc. Applies to release builds only.
     #[arg(long, default_value = "true", value_parser=clap::builder::BoolishValueParser::new(), action = clap::ArgAction::Set)]
     pub js_minify: bool,
@@ -63,6 +186,21 @@ pub struct Opts {
 
 #[derive(Debug, Clone, Parser, PartialEq, Default)]
 pub struct BinOpts {
+    /// Open the site in the default browser once it is served.
+    #[arg(long)]
+    pub open: bool,
+
+    /// Run all the (selected) projects of the workspace at once, with their files watched by a
+    /// single watcher and their output prefixed with the project name.
+    #[arg(long)]
+    pub all: bool,
+
+    /// Run all the (selected) projects, as with `--all`, behind a reverse proxy at this address
+    /// that forwards the base-url of each project to its site, and relays the reload channel of
+    /// each project, like the ingress of a deployment.
+    #[arg(long)]
+    pub gateway: Option<SocketAddr>,
+
     #[command(flatten)]
     opts: Opts,
 
@@ -70,6 +208,208 @@ pub struct BinOpts {
     bin_args: Vec<String>,
 }
 
+#[derive(Debug, Clone, Parser, PartialEq, Default)]
+pub struct ServeOpts {
+    /// Serve the release server and site as they were last built, without building: a preview
+    /// of what gets deployed.
+    #[arg(long)]
+    pub prod: bool,
+
+    #[command(flatten)]
+    bin_opts: BinOpts,
+}
+
+#[derive(Debug, Clone, Parser, PartialEq, Default)]
+pub struct WatchOpts {
+    /// Run the server and front tests after each successful rebuild.
+    #[arg(long)]
+    pub test: bool,
+
+    /// Show a dashboard of the build stages, the server, the recent errors and the logs, with
+    /// the output of cargo and of the server, instead of the logs. It shows a single project, so
+    /// it can't be combined with `--all` or `--gateway`.
+    #[arg(long, conflicts_with_all = ["all", "gateway"])]
+    pub ui: bool,
+
+    /// Show a desktop notification when a build finishes or fails.
+    #[arg(long)]
+    pub notify: bool,
+
+    #[command(flatten)]
+    bin_opts: BinOpts,
+}
+
+#[derive(Debug, Clone, Parser, PartialEq, Default)]
+pub struct TestOpts {
+    /// Also build the tests of the lib for wasm32 and run them in a headless browser, with the
+    /// wasm-bindgen test runner.
+    #[arg(long)]
+    pub wasm: bool,
+
+    #[command(flatten)]
+    opts: Opts,
+}
+
+#[derive(Debug, Clone, Parser, PartialEq, Default)]
+pub struct CoverageOpts {
+    /// Also measure the native tests of the lib with its (hydrate) features, merged with the
+    /// server (ssr) ones.
+    #[arg(long)]
+    pub hydrate: bool,
+
+    /// Also write an HTML report, next to the lcov one.
+    #[arg(long)]
+    pub html: bool,
+
+    #[command(flatten)]
+    opts: Opts,
+}
+
+#[derive(Debug, Clone, Parser, PartialEq, Default)]
+pub struct EndToEndOpts {
+    /// When the end2end-dir is a Playwright project, install its npm packages with `npm ci` and
+    /// its browsers with `npx playwright install --with-deps` first, unless already done for the
+    /// same package-lock.json.
+    #[arg(long)]
+    pub install: bool,
+
+    /// Serve the site of the tests at a free port instead of the configured one, which is
+    /// passed to the end2end-cmd in `LEPTOS_SITE_ADDR`.
+    #[arg(long)]
+    pub random_port: bool,
+
+    #[command(flatten)]
+    opts: Opts,
+
+    /// The arguments appended to the end2end-cmd, after a `--`, such as the test filters.
+    #[arg(trailing_var_arg = true)]
+    pub cmd_args: Vec<String>,
+}
+
+#[derive(Debug, Clone, Parser, PartialEq, Default)]
+pub struct BenchOpts {
+    /// Also build the site and measure the size of its wasm, js and css and the server render
+    /// time of its root page, into `target/leptos-bench/<project>.json`.
+    #[arg(long)]
+    pub site: bool,
+
+    /// The number of requests of the root page timed with `--site`.
+    #[arg(long, default_value = "20")]
+    pub requests: usize,
+
+    #[command(flatten)]
+    opts: Opts,
+
+    /// The arguments of the bench harness, after a `--`, such as a filter.
+    #[arg(trailing_var_arg = true)]
+    pub bench_args: Vec<String>,
+}
+
+/// the format of the route list
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum RoutesFormat {
+    /// written to `target/leptos-routes/<project>.json`
+    #[default]
+    Json,
+    /// printed, a route per line
+    Text,
+}
+
+#[derive(Debug, Clone, Parser, PartialEq, Default)]
+pub struct RoutesOpts {
+    /// The format of the route list.
+    #[arg(long, value_enum, default_value_t)]
+    pub format: RoutesFormat,
+
+    /// The file to write the json route list to (a dir with a file per project when there are
+    /// several).
+    #[arg(long)]
+    pub out: Option<Utf8PathBuf>,
+
+    #[command(flatten)]
+    opts: Opts,
+}
+
+#[derive(Debug, Clone, Parser, PartialEq, Default)]
+pub struct ProxyConfigOpts {
+    /// The dir the site root is deployed to, instead of the local one.
+    #[arg(long)]
+    pub root: Option<Utf8PathBuf>,
+
+    /// The dir to write the snippets to (in a dir per project when there are several).
+    #[arg(long)]
+    pub out: Option<Utf8PathBuf>,
+
+    #[command(flatten)]
+    opts: Opts,
+}
+
+/// the format of the bundle
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum BundleFormat {
+    /// a dir
+    #[default]
+    Dir,
+    /// a gzipped tarball
+    TarGz,
+    /// a zip archive
+    Zip,
+}
+
+#[derive(Debug, Clone, Parser, PartialEq, Default)]
+pub struct BundleOpts {
+    /// The format of the bundle.
+    #[arg(long, value_enum, default_value_t)]
+    pub format: BundleFormat,
+
+    /// The dir, or archive file, to write the bundle to (a dir with one per project when there are
+    /// several).
+    #[arg(long)]
+    pub out: Option<Utf8PathBuf>,
+
+    /// Also write a systemd service unit `<project>.service` running the server.
+    #[arg(long)]
+    pub systemd: bool,
+
+    /// The dir the bundle is installed to, the working dir of the systemd unit. Defaults to
+    /// `/opt/<project>`.
+    #[arg(long)]
+    pub install_dir: Option<Utf8PathBuf>,
+
+    #[command(flatten)]
+    opts: Opts,
+}
+
+#[derive(Debug, Clone, Parser, PartialEq)]
+pub struct AnalyzeOpts {
+    /// The number of crates and functions of the tables.
+    #[arg(long, default_value = "20")]
+    pub top: usize,
+
+    /// Analyze the wasm cargo built, before wasm-bindgen and wasm-opt, which keeps the names of
+    /// the functions.
+    #[arg(long)]
+    pub source: bool,
+
+    /// The html treemap file to write (a dir with a file per project when there are several),
+    /// instead of `target/leptos-analyze/<project>.html`.
+    #[arg(long)]
+    pub out: Option<Utf8PathBuf>,
+
+    #[command(flatten)]
+    opts: Opts,
+}
+
+#[derive(Debug, Clone, Parser, PartialEq, Default)]
+pub struct ExportOpts {
+    /// The dir to write the static site to, instead of the export-dir.
+    #[arg(long)]
+    pub out: Option<Utf8PathBuf>,
+
+    #[command(flatten)]
+    opts: Opts,
+}
+
 #[derive(Debug, Parser)]
 #[clap(version)]
 pub struct Cli {
@@ -77,9 +417,14 @@ pub struct Cli {
     #[arg(long)]
     pub manifest_path: Option<Utf8PathBuf>,
 
-    /// Output logs from dependencies (multiple --log accepted).
-    #[arg(long)]
-    pub log: Vec<Log>,
+    /// Output logs from dependencies (wasm or server), or set the level of the logs of a stage,
+    /// as in `--log notify=trace,reload=debug,cargo=warn` (multiple --log accepted).
+    #[arg(long, value_delimiter = ',')]
+    pub log: Vec<LogDirective>,
+
+    /// The format of the logs, structured json records for the CI log processors.
+    #[arg(long, value_enum, default_value_t)]
+    pub log_format: LogFormat,
 
     #[command(subcommand)]
     pub command: Commands,
@@ -87,21 +432,106 @@ pub struct Cli {
 
 impl Cli {
     pub fn opts(&self) -> Option<Opts> {
-        use Commands::{Build, EndToEnd, New, Serve, Test, Watch};
+        use Commands::{
+            Analyze, Bench, Build, Bundle, Config, Coverage, EndToEnd, Export, New, ProxyConfig,
+            Routes, Serve, Test, Watch,
+        };
         match &self.command {
-            New(_) => None,
-            Serve(bin_opts) | Watch(bin_opts) => Some(bin_opts.opts.clone()),
-            Build(opts) | Test(opts) | EndToEnd(opts) => Some(opts.clone()),
+            New(_) | Config(_) => None,
+            Serve(serve_opts) => {
+                let mut opts = serve_opts.bin_opts.opts.clone();
+                // the release build is previewed
+                opts.release |= serve_opts.prod;
+                opts.all = self.all();
+                opts.gateway = self.gateway();
+                Some(opts)
+            }
+            Watch(watch_opts) => {
+                let mut opts = watch_opts.bin_opts.opts.clone();
+                opts.all = self.all();
+                opts.gateway = self.gateway();
+                Some(opts)
+            }
+            Test(test_opts) => Some(test_opts.opts.clone()),
+            Coverage(coverage_opts) => Some(coverage_opts.opts.clone()),
+            Bench(bench_opts) => {
+                let mut opts = bench_opts.opts.clone();
+                // the site is benchmarked as deployed, served at a free port
+                if bench_opts.site {
+                    opts.release = true;
+                    if opts.addr.is_none() && opts.port.is_none() {
+                        opts.port = Some(0);
+                    }
+                }
+                Some(opts)
+            }
+            EndToEnd(e2e_opts) => {
+                let mut opts = e2e_opts.opts.clone();
+                // the server of the tests is served at a free port when asked to
+                if e2e_opts.random_port && opts.addr.is_none() && opts.port.is_none() {
+                    opts.port = Some(0);
+                }
+                Some(opts)
+            }
+            Export(export_opts) => {
+                let mut opts = export_opts.opts.clone();
+                // the pages are rendered by the server at a free port
+                if opts.addr.is_none() && opts.port.is_none() {
+                    opts.port = Some(0);
+                }
+                Some(opts)
+            }
+            Routes(routes_opts) => Some(routes_opts.opts.clone()),
+            Bundle(bundle_opts) => {
+                let mut opts = bundle_opts.opts.clone();
+                // what gets deployed
+                opts.release = true;
+                Some(opts)
+            }
+            ProxyConfig(proxy_opts) => Some(proxy_opts.opts.clone()),
+            Analyze(analyze_opts) => Some(analyze_opts.opts.clone()),
+            Build(opts) => Some(opts.clone()),
         }
     }
 
     pub fn bin_args(&self) -> Option<&[String]> {
         use Commands::{Serve, Watch};
         match &self.command {
-            Serve(bin_opts) | Watch(bin_opts) => Some(bin_opts.bin_args.as_ref()),
+            Serve(serve_opts) => Some(serve_opts.bin_opts.bin_args.as_ref()),
+            Watch(watch_opts) => Some(watch_opts.bin_opts.bin_args.as_ref()),
             _ => None,
         }
     }
+
+    /// whether to run all the selected projects at once
+    pub fn all(&self) -> bool {
+        use Commands::{Serve, Watch};
+        match &self.command {
+            Serve(serve_opts) => serve_opts.bin_opts.all || serve_opts.bin_opts.gateway.is_some(),
+            Watch(watch_opts) => watch_opts.bin_opts.all || watch_opts.bin_opts.gateway.is_some(),
+            _ => false,
+        }
+    }
+
+    /// the address of the gateway the selected projects are run behind
+    pub fn gateway(&self) -> Option<SocketAddr> {
+        use Commands::{Serve, Watch};
+        match &self.command {
+            Serve(serve_opts) => serve_opts.bin_opts.gateway,
+            Watch(watch_opts) => watch_opts.bin_opts.gateway,
+            _ => None,
+        }
+    }
+
+    /// whether to open the browser once the site is served
+    pub fn open(&self) -> bool {
+        use Commands::{Serve, Watch};
+        match &self.command {
+            Serve(serve_opts) => serve_opts.bin_opts.open,
+            Watch(watch_opts) => watch_opts.bin_opts.open,
+            _ => false,
+        }
+    }
 }
 
 #[derive(Debug, Subcommand, PartialEq)]
@@ -109,13 +539,35 @@ pub enum Commands {
     /// Build the server (feature ssr) and the client (wasm with feature hydrate).
     Build(Opts),
     /// Run the cargo tests for app, client and server.
-    Test(Opts),
-    /// Start the server and end-2-end tests.
-    EndToEnd(Opts),
+    Test(TestOpts),
+    /// Run the cargo benches with the server (ssr) features, and with `--site` the size and render
+    /// time of the site.
+    Bench(BenchOpts),
+    /// Measure the coverage of the tests with cargo-llvm-cov, into an lcov report.
+    Coverage(CoverageOpts),
+    /// Start the server and end-2-end tests. With `--release`, of the optimized build.
+    EndToEnd(EndToEndOpts),
     /// Serve. Defaults to hydrate mode.
-    Serve(BinOpts),
+    Serve(ServeOpts),
     /// Serve and automatically reload when files change.
-    Watch(BinOpts),
+    Watch(WatchOpts),
+    /// Build and write a static site: the site files and the server rendered pages of the
+    /// export-routes and of the pages they link to.
+    Export(ExportOpts),
+    /// Build for release and package the server binary, the site and a run script into a dir or
+    /// an archive, ready to deploy.
+    Bundle(BundleOpts),
+    /// Write the route list of the app, generated by its server integration, with the paths of
+    /// its server functions.
+    Routes(RoutesOpts),
+    /// Write nginx and Caddyfile snippets serving the site files and proxying the other requests
+    /// to the server, as built with the same options.
+    ProxyConfig(ProxyConfigOpts),
+    /// Build and profile the size of the wasm: its largest crates and functions, after the name
+    /// section, and an html treemap of its code.
+    Analyze(AnalyzeOpts),
     /// Start a wizard for creating a new project (using cargo-generate).
     New(NewCommand),
+    /// Tools for the leptos metadata config.
+    Config(ConfigCommand),
 }
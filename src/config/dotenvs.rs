@@ -4,23 +4,36 @@ use crate::ext::exe;
 use camino::{Utf8Path, Utf8PathBuf};
 use std::{env, fs};
 
-pub fn load_dotenvs(directory: &Utf8Path) -> Result<Option<Vec<(String, String)>>> {
-    let candidate = directory.join(".env");
+/// The vars of the dotenv files of the nearest dir that has any, from the lowest precedence to
+/// the highest: `.env`, `.env.local`, `.env.<env>` and `.env.<env>.local`.
+pub fn load_dotenvs(
+    directory: &Utf8Path,
+    env: Option<&str>,
+) -> Result<Option<Vec<(String, String)>>> {
+    let mut names = vec![".env".to_string(), ".env.local".to_string()];
+    if let Some(env) = env {
+        names.push(format!(".env.{env}"));
+        names.push(format!(".env.{env}.local"));
+    }
 
-    if let Ok(metadata) = fs::metadata(&candidate) {
-        if metadata.is_file() {
-            let mut dotenvs = vec![];
+    let candidates = names
+        .iter()
+        .map(|name| directory.join(name))
+        .filter(|candidate| fs::metadata(candidate).is_ok_and(|metadata| metadata.is_file()))
+        .collect::<Vec<_>>();
+    if !candidates.is_empty() {
+        let mut dotenvs = vec![];
+        for candidate in candidates {
             for entry in dotenvy::from_path_iter(&candidate)? {
                 let (key, val) = entry?;
                 dotenvs.push((key, val));
             }
-
-            return Ok(Some(dotenvs));
         }
+        return Ok(Some(dotenvs));
     }
 
     if let Some(parent) = directory.parent() {
-        load_dotenvs(parent)
+        load_dotenvs(parent, env)
     } else {
         Ok(None)
     }
@@ -40,21 +53,58 @@ fn overlay(conf: &mut ProjectConfig, envs: impl Iterator<Item = (String, String)
             "LEPTOS_OUTPUT_NAME" => conf.output_name = val,
             "LEPTOS_SITE_ROOT" => conf.site_root = Utf8PathBuf::from(val),
             "LEPTOS_SITE_PKG_DIR" => conf.site_pkg_dir = Utf8PathBuf::from(val),
-            "LEPTOS_STYLE_FILE" => conf.style_file = Some(Utf8PathBuf::from(val)),
+            "LEPTOS_BASE_URL" => conf.base_url = val,
+            "LEPTOS_ASSET_URL_PREFIX" => conf.asset_url_prefix = Some(val),
+            "LEPTOS_STYLE_FILE" => conf.style_file = vec![Utf8PathBuf::from(val)],
             "LEPTOS_ASSETS_DIR" => conf.assets_dir = Some(Utf8PathBuf::from(val)),
-            "LEPTOS_SITE_ADDR" => conf.site_addr = val.parse()?,
+            "LEPTOS_SITE_ADDR" => {
+                conf.site_addr = val
+                    .split(',')
+                    .map(|addr| addr.trim().parse())
+                    .collect::<Result<_, _>>()?
+            }
             "LEPTOS_RELOAD_PORT" => conf.reload_port = val.parse()?,
+            "LEPTOS_RELOAD_EXTERNAL_PORT" => conf.reload_external_port = Some(val.parse()?),
+            "LEPTOS_RELOAD_HEALTH_PATH" => conf.reload_health_path = Some(val),
+            "LEPTOS_RELOAD_DELAY_MS" => conf.reload_delay_ms = val.parse()?,
+            "LEPTOS_RELOAD_VIA_SITE_ADDR" => conf.reload_via_site_addr = val.parse()?,
+            "LEPTOS_OPEN_PATH" => conf.open_path = val,
+            "LEPTOS_TLS_CERT" => conf.tls_cert = Some(Utf8PathBuf::from(val)),
+            "LEPTOS_TLS_KEY" => conf.tls_key = Some(Utf8PathBuf::from(val)),
+            "LEPTOS_HOT_RELOAD_CLIENT" => conf.hot_reload_client = val.parse()?,
+            "LEPTOS_HOT_PATCH_FALLBACK" => conf.hot_patch_fallback = val.parse()?,
             "LEPTOS_END2END_CMD" => conf.end2end_cmd = Some(val),
             "LEPTOS_END2END_DIR" => conf.end2end_dir = Some(Utf8PathBuf::from(val)),
+            "LEPTOS_EXPORT_DIR" => conf.export_dir = Some(Utf8PathBuf::from(val)),
+            "LEPTOS_STATIC_HOST_FILES" => conf.static_host_files = val.parse()?,
+            "LEPTOS_FALLBACK_ROUTE" => conf.fallback_route = Some(val),
+            "LEPTOS_SITEMAP_BASE_URL" => conf.sitemap_base_url = Some(val),
+            "LEPTOS_ROBOTS_TXT" => conf.robots_txt = Some(val.parse()?),
+            "LEPTOS_PWA" => conf.pwa = val.parse()?,
+            "LEPTOS_EMBED_SITE" => conf.embed_site = val.parse()?,
+            "LEPTOS_WRITE_BUILD_INFO" => conf.build_info = val.parse()?,
             "LEPTOS_HASH_FILES" => conf.hash_files = val.parse()?,
+            "LEPTOS_HASH_FILE_FORMAT" => conf.hash_file_format = val.parse()?,
+            "LEPTOS_CACHE_BUST" => conf.cache_bust = val.parse()?,
+            "LEPTOS_HASH_ASSETS" => conf.hash_assets = val.parse()?,
             "LEPTOS_HASH_FILE_NAME" => conf.hash_file_name = Some(val.parse()?),
+            "LEPTOS_ERROR_OVERLAY" => conf.error_overlay = val.parse()?,
+            "LEPTOS_WATCH_GITIGNORE" => conf.watch_gitignore = val.parse()?,
+            "LEPTOS_WATCH_BACKEND" => conf.watch_backend = val.parse()?,
+            "LEPTOS_WATCH_POLL_INTERVAL" => conf.watch_poll_interval = val.parse()?,
+            "LEPTOS_WATCH_DEBOUNCE_MS" => conf.watch_debounce_ms = val.parse()?,
+            "LEPTOS_WATCH_DEBOUNCE_ASSETS_MS" => conf.watch_debounce_assets_ms = Some(val.parse()?),
             "LEPTOS_BROWSERQUERY" => conf.browserquery = val,
             "LEPTOS_BIN_EXE_NAME" => conf.bin_exe_name = Some(val),
+            "LEPTOS_BIN_RUNNER" => conf.bin_runner = Some(val),
             "LEPTOS_BIN_TARGET" => conf.bin_target = val,
+            "LEPTOS_BIN_EXAMPLE" => conf.bin_example = Some(val),
             "LEPTOS_BIN_TARGET_TRIPLE" => conf.bin_target_triple = Some(val),
             "LEPTOS_BIN_TARGET_DIR" => conf.bin_target_dir = Some(val),
+            "LEPTOS_FRONT_TARGET_DIR" => conf.front_target_dir = Some(Utf8PathBuf::from(val)),
             "LEPTOS_BIN_CARGO_COMMAND" => conf.bin_cargo_command = Some(val),
             "LEPTOS_JS_MINIFY" => conf.js_minify = val.parse()?,
+            "LEPTOS_HTML_MINIFY" => conf.html_minify = val.parse()?,
             "SERVER_FN_PREFIX" => conf.server_fn_prefix = Some(val),
             "DISABLE_SERVER_FN_HASH" => conf.disable_server_fn_hash = true,
             // put these here to suppress the warning, but there's no
@@ -1,10 +1,62 @@
-use camino::Utf8PathBuf;
+use camino::{Utf8Path, Utf8PathBuf};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::str::FromStr;
+
+use crate::ext::anyhow::{bail, Result};
 
 use super::bin_package::BinPackage;
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum HashFileFormat {
+    /// `js: <hash>` lines, as read by leptos
+    #[default]
+    Text,
+    /// the text file, and a json one next to it with the original and hashed names, hashes and
+    /// sizes of all the hashed files
+    Json,
+}
+
+impl FromStr for HashFileFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => bail!("Unknown hash file format {s:?}, expected \"text\" or \"json\""),
+        }
+    }
+}
+
+/// how the hashes are added to the urls of the files
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum CacheBust {
+    /// the files are renamed to `app.<hash>.js`
+    #[default]
+    Rename,
+    /// the files keep their names and are referenced as `app.js?v=<hash>`
+    Query,
+}
+
+impl FromStr for CacheBust {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "rename" => Ok(Self::Rename),
+            "query" => Ok(Self::Query),
+            _ => bail!("Unknown cache bust mode {s:?}, expected \"rename\" or \"query\""),
+        }
+    }
+}
+
 pub struct HashFile {
     pub abs: Utf8PathBuf,
     pub rel: Utf8PathBuf,
+    pub format: HashFileFormat,
 }
 
 impl HashFile {
@@ -12,10 +64,9 @@ impl HashFile {
         workspace_root: Option<&Utf8PathBuf>,
         bin: &BinPackage,
         rel: Option<&Utf8PathBuf>,
+        format: HashFileFormat,
     ) -> Self {
-        let rel = rel
-            .cloned()
-            .unwrap_or(Utf8PathBuf::from("hash.txt".to_string()));
+        let rel = rel.cloned().unwrap_or(Utf8PathBuf::from(DEFAULT_NAME));
 
         let exe_file_dir = bin.exe_file.parent().unwrap();
         let abs;
@@ -25,6 +76,33 @@ impl HashFile {
         } else {
             abs = bin.abs_dir.join(exe_file_dir).join(&rel);
         }
-        Self { abs, rel }
+        Self { abs, rel, format }
+    }
+
+    /// the hash file in the dir, for the projects without a server
+    pub fn in_dir(dir: &Utf8Path, rel: Option<&Utf8PathBuf>, format: HashFileFormat) -> Self {
+        let rel = rel.cloned().unwrap_or_else(|| DEFAULT_NAME.into());
+        Self {
+            abs: dir.join(&rel),
+            rel,
+            format,
+        }
+    }
+
+    /// the json hash file of the json format, next to the text one: `hash.json` for `hash.txt`
+    pub fn json_rel(&self) -> Utf8PathBuf {
+        match self.rel.extension() {
+            Some("json") => self.rel.with_extension("manifest.json"),
+            _ => self.rel.with_extension("json"),
+        }
+    }
+
+    pub fn json_abs(&self) -> Utf8PathBuf {
+        let json_rel = self.json_rel();
+        self.abs
+            .with_file_name(json_rel.file_name().unwrap_or_default())
     }
 }
+
+/// the text hash file read by leptos
+const DEFAULT_NAME: &str = "hash.txt";
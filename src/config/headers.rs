@@ -0,0 +1,22 @@
+use std::collections::BTreeMap;
+
+use axum::http::{HeaderName, HeaderValue};
+
+use crate::ext::anyhow::{Context, Result};
+
+/// The extra headers of the responses of the static dev server, from the
+/// `[package.metadata.leptos.headers]` table: `"Cross-Origin-Opener-Policy" = "same-origin"`
+pub fn resolve_headers(
+    config: &BTreeMap<String, String>,
+) -> Result<Vec<(HeaderName, HeaderValue)>> {
+    config
+        .iter()
+        .map(|(name, value)| {
+            let name = HeaderName::try_from(name.as_str())
+                .with_context(|| format!("Invalid header name {name:?}"))?;
+            let value = HeaderValue::try_from(value.as_str())
+                .with_context(|| format!("Invalid value {value:?} of the header {name}"))?;
+            Ok((name, value))
+        })
+        .collect()
+}
@@ -0,0 +1,84 @@
+use camino::Utf8Path;
+use globset::GlobSet;
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use crate::ext::{anyhow::Result, glob_set};
+
+/// the `[package.metadata.leptos.hooks]` table
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub struct HooksConfig {
+    /// run before each build of the watch loop
+    pub pre_build: Option<String>,
+    /// run after each successful build of the watch loop
+    pub post_build: Option<String>,
+    /// run after the browser is told to reload
+    pub post_reload: Option<String>,
+    /// run when a file matching the patterns changes
+    #[serde(default)]
+    pub on_change: Vec<ChangeHookConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct ChangeHookConfig {
+    /// glob patterns, relative to the workspace root
+    pub patterns: Vec<String>,
+    pub cmd: String,
+}
+
+/// The shell commands the watch loop runs, from the workspace root
+#[derive(Debug, Default)]
+pub struct Hooks {
+    pub pre_build: Option<String>,
+    pub post_build: Option<String>,
+    pub post_reload: Option<String>,
+    pub on_change: Vec<ChangeHook>,
+}
+
+impl Hooks {
+    pub fn resolve(config: &HooksConfig) -> Result<Self> {
+        let on_change = config
+            .on_change
+            .iter()
+            .map(|hook| {
+                Ok(ChangeHook {
+                    patterns: hook.patterns.clone(),
+                    set: glob_set(&hook.patterns)?,
+                    cmd: hook.cmd.clone(),
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            pre_build: config.pre_build.clone(),
+            post_build: config.post_build.clone(),
+            post_reload: config.post_reload.clone(),
+            on_change,
+        })
+    }
+
+    /// the indexes of the on-change hooks matching the file (relative to the workspace root)
+    pub fn matching(&self, path: &Utf8Path) -> impl Iterator<Item = usize> + '_ {
+        let path = path.to_path_buf();
+        self.on_change
+            .iter()
+            .enumerate()
+            .filter(move |(_, hook)| hook.set.is_match(&path))
+            .map(|(index, _)| index)
+    }
+}
+
+pub struct ChangeHook {
+    pub patterns: Vec<String>,
+    set: GlobSet,
+    pub cmd: String,
+}
+
+impl std::fmt::Debug for ChangeHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChangeHook")
+            .field("patterns", &self.patterns)
+            .field("cmd", &self.cmd)
+            .finish()
+    }
+}
@@ -1,15 +1,15 @@
 use crate::{
     config::Opts,
     ext::{
-        anyhow::{anyhow, Result},
-        MetadataExt, PathBufExt, PathExt,
+        anyhow::{anyhow, ensure, Result},
+        MetadataExt, PackageExt, PathBufExt, PathExt,
     },
     service::site::{SiteFile, SourcedSiteFile},
 };
 use camino::Utf8PathBuf;
 use cargo_metadata::Metadata;
 
-use super::{project::ProjectDefinition, Profile, ProjectConfig};
+use super::{bin_package::BinPackage, project::ProjectDefinition, Profile, ProjectConfig};
 
 pub struct LibPackage {
     pub name: String,
@@ -22,9 +22,35 @@ pub struct LibPackage {
     pub default_features: bool,
     pub output_name: String,
     pub src_paths: Vec<Utf8PathBuf>,
+    /// the src dirs of the local crates in the dependency closure of the front build, with the
+    /// optional ones its features enable. The view macros in them are hot-reloaded
+    pub front_src_paths: Vec<Utf8PathBuf>,
     pub front_target_path: Utf8PathBuf,
     pub profile: Profile,
     pub cargo_args: Option<Vec<String>>,
+    /// the `extra-wasm-packages`, built with the profile of the lib
+    pub extra_wasm: Vec<WasmPackage>,
+    /// the bin features the lib is also tested with, when it isn't the bin package
+    pub ssr_features: Option<Vec<String>>,
+}
+
+/// An additional wasm crate of the front, such as a web worker, built for wasm32 and run through
+/// wasm-bindgen into the pkg dir. Its wasm keeps the `_bg` name its js loads it by.
+pub struct WasmPackage {
+    pub name: String,
+    pub output_name: String,
+    pub wasm_file: SourcedSiteFile,
+    pub js_file: SiteFile,
+}
+
+impl std::fmt::Debug for WasmPackage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WasmPackage")
+            .field("name", &self.name)
+            .field("wasm_file", &self.wasm_file)
+            .field("js_file", &self.js_file)
+            .finish_non_exhaustive()
+    }
 }
 
 impl LibPackage {
@@ -66,10 +92,9 @@ impl LibPackage {
             &config.lib_profile_dev,
         );
 
+        let front_target_dir = front_target_dir(metadata, config);
         let wasm_file = {
-            let source = metadata
-                .rel_target_dir()
-                .join("front")
+            let source = front_target_dir
                 .join("wasm32-unknown-unknown")
                 .join(profile.to_string())
                 .join(name.replace('-', "_"))
@@ -95,7 +120,70 @@ impl LibPackage {
             src_deps.push(rel_dir.join("src"));
         }
 
-        let front_target_path = metadata.target_directory.join("front");
+        let mut extra_wasm = Vec::new();
+        for extra in &config.extra_wasm_packages {
+            let extra_package = packages
+                .iter()
+                .find(|p| p.name == *extra)
+                .ok_or_else(|| anyhow!(r#"Could not find the extra wasm package "{extra}""#))?;
+            ensure!(
+                extra_package.cdylib_target().is_some(),
+                r#"The extra wasm package "{extra}" is missing a cdylib library target"#
+            );
+            // its changes rebuild the front
+            for dir in metadata.src_path_dependencies(&extra_package.id) {
+                if !src_deps.contains(&dir) {
+                    src_deps.push(dir);
+                }
+            }
+            let extra_dir = extra_package
+                .manifest_path
+                .clone()
+                .without_last()
+                .unbase(&metadata.workspace_root)?;
+            let extra_src = if extra_dir == "." {
+                "src".into()
+            } else {
+                extra_dir.join("src")
+            };
+            if !src_deps.contains(&extra_src) {
+                src_deps.push(extra_src);
+            }
+
+            let extra_output = extra.replace('-', "_");
+            let source = front_target_dir
+                .join("wasm32-unknown-unknown")
+                .join(profile.to_string())
+                .join(&extra_output)
+                .with_extension("wasm");
+            let site = config
+                .site_pkg_dir
+                .join(format!("{extra_output}_bg"))
+                .with_extension("wasm");
+            let dest = config.site_root.join(&site);
+            let js_site = config.site_pkg_dir.join(&extra_output).with_extension("js");
+            extra_wasm.push(WasmPackage {
+                name: extra.clone(),
+                output_name: extra_output,
+                wasm_file: SourcedSiteFile { source, dest, site },
+                js_file: SiteFile {
+                    dest: config.site_root.join(&js_site),
+                    site: js_site,
+                },
+            });
+        }
+
+        let mut front_src_paths =
+            metadata.feature_src_paths(&package.id, &features, config.lib_default_features);
+        front_src_paths.extend(
+            src_deps
+                .iter()
+                .filter(|dir| !front_src_paths.contains(dir))
+                .cloned()
+                .collect::<Vec<_>>(),
+        );
+
+        let front_target_path = metadata.workspace_root.join(&front_target_dir);
         let cargo_args = cli
             .lib_cargo_args
             .clone()
@@ -111,11 +199,43 @@ impl LibPackage {
             default_features: config.lib_default_features,
             output_name,
             src_paths: src_deps,
+            front_src_paths,
             front_target_path,
             profile,
             cargo_args,
+            extra_wasm,
+            ssr_features: None,
         })
     }
+
+    /// The features of the server the lib package is also tested with: the bin features, when the
+    /// lib is another package that declares all of them, as the `ssr` one of the usual setups.
+    pub fn ssr_features(&self, metadata: &Metadata, bin: &BinPackage) -> Option<Vec<String>> {
+        if bin.name == self.name || bin.features.is_empty() {
+            return None;
+        }
+        let package = metadata
+            .workspace_packages()
+            .into_iter()
+            .find(|p| p.name == self.name)?;
+        bin.features
+            .iter()
+            .all(|feature| package.features.contains_key(feature))
+            .then(|| bin.features.clone())
+    }
+}
+
+/// the target dir of the front build, relative to the workspace root unless absolute: the
+/// `front` subdir of the target dir by default, so that the front and server builds don't wait
+/// for each other's lock nor invalidate each other's artifacts
+fn front_target_dir(metadata: &Metadata, config: &ProjectConfig) -> Utf8PathBuf {
+    if let Some(dir) = &config.front_target_dir {
+        dir.clone()
+    } else if config.separate_front_target_dir == Some(false) {
+        metadata.rel_target_dir()
+    } else {
+        metadata.rel_target_dir().join("front")
+    }
 }
 
 impl std::fmt::Debug for LibPackage {
@@ -137,7 +257,18 @@ impl std::fmt::Debug for LibPackage {
                     .collect::<Vec<_>>()
                     .join(", "),
             )
+            .field(
+                "front_src_paths",
+                &self
+                    .front_src_paths
+                    .iter()
+                    .map(|p| p.test_string())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            )
             .field("profile", &self.profile)
+            .field("extra_wasm", &self.extra_wasm)
+            .field("ssr_features", &self.ssr_features)
             .finish_non_exhaustive()
     }
 }
@@ -7,26 +7,52 @@ mod cli;
 mod dotenvs;
 mod end2end;
 mod hash_file;
+mod headers;
+mod hooks;
 mod lib_package;
 mod profile;
 mod project;
+mod proxy;
+mod rustflags;
+mod schema;
+mod server_env;
+mod site_files;
 mod style;
 mod tailwind;
+mod tls;
+mod watch;
 
-use std::{fmt::Debug, sync::Arc};
+use std::{fmt::Debug, io::IsTerminal, sync::Arc};
 
-pub use self::cli::{Cli, Commands, Log, Opts};
+pub use self::cli::{
+    AnalyzeOpts, BenchOpts, BundleFormat, BundleOpts, Cli, Commands, CoverageOpts, EndToEndOpts,
+    ExportOpts, Log, LogDirective, LogFormat, Opts, ProxyConfigOpts, RoutesFormat, RoutesOpts,
+    ServeOpts, TestOpts, WatchOpts,
+};
 use crate::ext::{
     anyhow::{Context, Result},
     MetadataExt,
 };
 use anyhow::bail;
+pub use assets::{AssetsConfig, ImageFormat, ImagesConfig};
+pub use bin_package::BinPackage;
 use camino::{Utf8Path, Utf8PathBuf};
 use cargo_metadata::Metadata;
+use dialoguer::{theme::ColorfulTheme, Select};
+use globset::Glob;
+pub use hash_file::{CacheBust, HashFileFormat};
+pub use hooks::{ChangeHook, Hooks};
+pub use lib_package::WasmPackage;
 pub use profile::Profile;
 pub use project::{Project, ProjectConfig};
-pub use style::StyleConfig;
+pub use proxy::ProxyRoute;
+pub use schema::schema;
+pub use server_env::interpolate_server_env;
+pub use site_files::{PwaConfig, RobotsMode, RobotsTxt, SecurityTxt};
+pub use style::{CssModulesConfig, PurgeConfig, StyleCollector, StyleCollectorConfig, StyleConfig};
 pub use tailwind::TailwindConfig;
+pub use tls::Tls;
+pub use watch::{HotPatchFallback, HotReloadClient, WatchBackend};
 
 pub struct Config {
     /// absolute path to the working dir
@@ -62,15 +88,11 @@ impl Config {
             bail!("Please define leptos projects in the workspace Cargo.toml sections [[workspace.metadata.leptos]]")
         }
 
-        if let Some(proj_name) = &cli.project {
-            if let Some(proj) = projects.iter().find(|p| p.name == *proj_name) {
-                projects = vec![proj.clone()];
-            } else {
-                bail!(
-                    r#"The specified project "{proj_name}" not found. Available projects: {}"#,
-                    names(&projects)
-                )
-            }
+        if !cli.project.is_empty() {
+            projects = select(&projects, &cli.project)?;
+        }
+        if !cli.tag.is_empty() {
+            projects = with_tags(projects, &cli.tag)?;
         }
 
         Ok(Self {
@@ -99,15 +121,92 @@ impl Config {
         Self::load(cli, &cwd, &manifest_path, watch, bin_args).unwrap()
     }
 
+    /// Asks which project to use when several are available, none is selected with `--project`
+    /// or `--tag` and cargo-leptos runs in a terminal without `--non-interactive`. With `all`, the
+    /// first choice keeps all of them.
+    pub fn pick_project(&mut self, all: bool) -> Result<()> {
+        let interactive = !self.cli.non_interactive
+            && std::io::stdin().is_terminal()
+            && std::io::stderr().is_terminal();
+        let selected = !self.cli.project.is_empty() || !self.cli.tag.is_empty();
+        if self.projects.len() < 2 || selected || !interactive {
+            return Ok(());
+        }
+        let mut items: Vec<&str> = self.projects.iter().map(|p| p.name.as_str()).collect();
+        if all {
+            items.insert(0, "all projects");
+        }
+        let choice = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Which project?")
+            .items(&items)
+            .default(0)
+            .interact_opt()
+            .context("Could not ask which project to use")?;
+        match choice {
+            None => bail!("No project selected"),
+            Some(0) if all => {}
+            Some(index) => {
+                let proj = self.projects[index - usize::from(all)].clone();
+                log::debug!("Config selected the project {}", proj.name);
+                self.projects = vec![proj];
+            }
+        }
+        Ok(())
+    }
+
     pub fn current_project(&self) -> Result<Arc<Project>> {
         if self.projects.len() == 1 {
             Ok(self.projects[0].clone())
         } else {
-            bail!("There are several projects available ({}). Please select one of them with the command line parameter --project", names(&self.projects));
+            bail!("There are several projects available ({}). Please select one of them with the command line parameter --project, or run them all with --all", names(&self.projects));
         }
     }
 }
 
+/// the projects matching any of the names or glob patterns, in their definition order
+fn select(projects: &[Arc<Project>], patterns: &[String]) -> Result<Vec<Arc<Project>>> {
+    let mut selected = vec![false; projects.len()];
+    for pattern in patterns {
+        let matcher = Glob::new(pattern)
+            .context(format!("Invalid project pattern {pattern:?}"))?
+            .compile_matcher();
+        let mut found = false;
+        for (proj, selected) in projects.iter().zip(selected.iter_mut()) {
+            if matcher.is_match(&proj.name) {
+                *selected = true;
+                found = true;
+            }
+        }
+        if !found {
+            bail!(
+                r#"The specified project "{pattern}" not found. Available projects: {}"#,
+                names(projects)
+            )
+        }
+    }
+    Ok(projects
+        .iter()
+        .zip(selected)
+        .filter(|(_, selected)| *selected)
+        .map(|(proj, _)| proj.clone())
+        .collect())
+}
+
+/// the projects with any of the tags
+fn with_tags(projects: Vec<Arc<Project>>, tags: &[String]) -> Result<Vec<Arc<Project>>> {
+    let (tagged, others): (Vec<_>, Vec<_>) = projects
+        .into_iter()
+        .partition(|proj| proj.tags.iter().any(|tag| tags.contains(tag)));
+    if tagged.is_empty() {
+        bail!(
+            "No project has the tag {}. Available projects: {}",
+            tags.join(" or "),
+            names(&others)
+        )
+    }
+    Ok(tagged)
+}
+
 fn names(projects: &[Arc<Project>]) -> String {
     projects
         .iter()
@@ -1,17 +1,21 @@
-use crate::config::hash_file::HashFile;
+use crate::compile::ChangeSet;
+use crate::config::hash_file::{CacheBust, HashFile, HashFileFormat};
 use crate::{
     config::lib_package::LibPackage,
     ext::{
-        anyhow::{bail, ensure, Result},
-        PackageExt, PathBufExt, PathExt,
+        anyhow::{anyhow, bail, ensure, Context, Result},
+        glob_base, glob_set, MetadataExt, PackageExt, PathBufExt, PathExt,
     },
     logger::GRAY,
-    service::site::Site,
+    service::site::{free_port, Site},
 };
+use axum::http::{HeaderName, HeaderValue};
 use camino::{Utf8Path, Utf8PathBuf};
 use cargo_metadata::{Metadata, Package};
-use serde::Deserialize;
-use std::{fmt::Debug, net::SocketAddr, sync::Arc};
+use globset::GlobSet;
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer};
+use std::{collections::BTreeMap, env, fmt::Debug, net::SocketAddr, sync::Arc, time::Duration};
 
 use super::{
     assets::AssetsConfig,
@@ -19,7 +23,15 @@ use super::{
     cli::Opts,
     dotenvs::{load_dotenvs, overlay_env},
     end2end::End2EndConfig,
-    style::StyleConfig,
+    headers::resolve_headers,
+    hooks::{Hooks, HooksConfig},
+    proxy::ProxyRoute,
+    rustflags::{cargo_home, encoded_rustflags, env_rustflags, CargoRustflags},
+    schema::one_or_many_schema,
+    site_files::{PwaConfig, RobotsMode, RobotsTxt, SecurityTxt},
+    style::{StyleCollector, StyleConfig},
+    tls::Tls,
+    watch::{HotPatchFallback, HotReloadClient, WatchBackend},
 };
 
 /// If the site root path starts with this marker, the marker should be replaced with the Cargo target directory
@@ -30,23 +42,101 @@ const CARGO_BUILD_TARGET_DIR_MARKER: &str = "CARGO_BUILD_TARGET_DIR";
 pub struct Project {
     /// absolute path to the working dir
     pub working_dir: Utf8PathBuf,
+    /// absolute path to the cargo target dir
+    pub target_dir: Utf8PathBuf,
     pub name: String,
+    /// the tags the project is selected by with `--tag`
+    pub tags: Vec<String>,
     pub lib: LibPackage,
-    pub bin: BinPackage,
+    /// the server, none for client side rendered projects (`csr = true`)
+    pub bin: Option<BinPackage>,
     pub style: StyleConfig,
     pub watch: bool,
     pub release: bool,
     pub precompress: bool,
     pub hot_reload: bool,
+    pub hot_patch_fallback: HotPatchFallback,
+    /// show the build errors in the browser. Watch mode only
+    pub error_overlay: bool,
+    /// the path polled after a server restart, until it answers successfully, before reloading
+    pub reload_health_path: Option<String>,
+    /// the delay before the browser is told to reload
+    pub reload_delay: Duration,
+    /// the path of the site opened with `--open`
+    pub open_path: String,
+    pub hot_reload_client: HotReloadClient,
     pub wasm_debug: bool,
+    pub timings: bool,
+    pub reproducible: bool,
+    /// the rustflags of the cargo config, read for the reproducible builds
+    pub cargo_rustflags: CargoRustflags,
+    /// the ledger the sizes of the release builds are appended to
+    pub size_ledger: Option<Utf8PathBuf>,
+    /// the git ref whose sizes in the ledger the release builds are compared with
+    pub size_baseline: Option<String>,
+    /// the timestamp used for generated files, see https://reproducible-builds.org/docs/source-date-epoch/
+    pub source_date_epoch: Option<u64>,
     pub site: Arc<Site>,
     pub end2end: Option<End2EndConfig>,
     pub assets: Option<AssetsConfig>,
     pub js_dir: Utf8PathBuf,
     pub watch_additional_files: Vec<Utf8PathBuf>,
+    /// glob patterns of additional files. Changes to the matching files trigger rebuilds
+    pub watch_additional_globs: Vec<String>,
+    watch_additional_set: GlobSet,
+    /// glob patterns, relative to the workspace root, of the files whose changes are ignored
+    pub watch_ignore: Vec<String>,
+    watch_ignore_set: GlobSet,
+    /// whether the changes to the files ignored by git (and the `.ignore` files) are ignored
+    pub watch_gitignore: bool,
+    pub watch_backend: WatchBackend,
+    pub watch_poll_interval: Duration,
+    /// how long the watcher waits for more changes before rebuilding
+    pub watch_debounce: Duration,
+    /// the debounce when only asset and style files changed
+    pub watch_debounce_assets: Duration,
+    /// the commands run by the watch loop
+    pub hooks: Hooks,
+    /// the path prefixes the static dev server forwards to backends
+    pub proxy: Vec<ProxyRoute>,
+    /// the extra headers of the responses of the static dev server
+    pub headers: Vec<(HeaderName, HeaderValue)>,
+    /// the env vars of the server process, before their interpolation
+    pub server_env: BTreeMap<String, String>,
+    /// the names of the projects built before this one
+    pub depends_on: Vec<String>,
+    /// the paths of the pages written by `export`, with the ones they link to
+    pub export_routes: Vec<String>,
+    /// the dir `export` writes the static site to, relative to the working dir
+    pub export_dir: Utf8PathBuf,
+    /// the static sites get the `_headers` and `_redirects` files of the static hosts
+    pub static_host_files: bool,
+    /// the paths, or glob patterns, of the pages prerendered into the site root by the builds
+    pub prerender_routes: Vec<String>,
+    /// the path of the page prerendered into `404.html` in the site root by the builds
+    pub fallback_route: Option<String>,
+    /// the path of the root component in the lib crate, for generating the route list
+    pub app_component: String,
+    /// the url of the deployed site in the sitemap written by the release builds, if any
+    pub sitemap_base_url: Option<String>,
+    /// the glob patterns of the paths left out of the sitemap
+    pub sitemap_exclude: Vec<String>,
+    pub robots_txt: Option<RobotsTxt>,
+    pub security_txt: Option<SecurityTxt>,
+    pub pwa: Option<PwaConfig>,
+    /// the generated rust module including the site files into the server
+    pub embed_file: Option<Utf8PathBuf>,
+    /// write the `build-info.json` of the builds into the site root
+    pub build_info: bool,
     pub hash_file: HashFile,
     pub hash_files: bool,
+    pub hash_assets: bool,
+    pub cache_bust: CacheBust,
     pub js_minify: bool,
+    /// extract the critical css of the root route. Release builds only
+    pub critical_css: bool,
+    /// write the csp hashes of the inline code written by cargo-leptos
+    pub csp_hashes: bool,
     pub server_fn_prefix: Option<String>,
     pub disable_server_fn_hash: bool,
     pub server_fn_mod_path: bool,
@@ -56,6 +146,7 @@ impl Debug for Project {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Project")
             .field("name", &self.name)
+            .field("tags", &self.tags)
             .field("lib", &self.lib)
             .field("bin", &self.bin)
             .field("style", &self.style)
@@ -64,9 +155,41 @@ impl Debug for Project {
             .field("precompress", &self.precompress)
             .field("js_minify", &self.js_minify)
             .field("hot_reload", &self.hot_reload)
+            .field("hot_patch_fallback", &self.hot_patch_fallback)
+            .field("error_overlay", &self.error_overlay)
+            .field("reload_health_path", &self.reload_health_path)
+            .field("reload_delay", &self.reload_delay)
+            .field("open_path", &self.open_path)
+            .field("hot_reload_client", &self.hot_reload_client)
             .field("site", &self.site)
             .field("end2end", &self.end2end)
             .field("assets", &self.assets)
+            .field("watch_additional_globs", &self.watch_additional_globs)
+            .field("watch_ignore", &self.watch_ignore)
+            .field("watch_gitignore", &self.watch_gitignore)
+            .field("watch_backend", &self.watch_backend)
+            .field("watch_poll_interval", &self.watch_poll_interval)
+            .field("watch_debounce", &self.watch_debounce)
+            .field("watch_debounce_assets", &self.watch_debounce_assets)
+            .field("hooks", &self.hooks)
+            .field("proxy", &self.proxy)
+            .field("headers", &self.headers)
+            .field("server_env", &self.server_env)
+            .field("depends_on", &self.depends_on)
+            .field("export_routes", &self.export_routes)
+            .field("export_dir", &self.export_dir)
+            .field("static_host_files", &self.static_host_files)
+            .field("prerender_routes", &self.prerender_routes)
+            .field("fallback_route", &self.fallback_route)
+            .field("app_component", &self.app_component)
+            .field("sitemap_base_url", &self.sitemap_base_url)
+            .field("sitemap_exclude", &self.sitemap_exclude)
+            .field("robots_txt", &self.robots_txt)
+            .field("security_txt", &self.security_txt)
+            .field("pwa", &self.pwa)
+            .field("build_info", &self.build_info)
+            .field("hash_assets", &self.hash_assets)
+            .field("cache_bust", &self.cache_bust)
             .field("server_fn_prefix", &self.server_fn_prefix)
             .field("disable_server_fn_hash", &self.disable_server_fn_hash)
             .field("server_fn_mod_path", &self.server_fn_mod_path)
@@ -82,56 +205,187 @@ impl Project {
         watch: bool,
         bin_args: Option<&[String]>,
     ) -> Result<Vec<Arc<Project>>> {
-        let projects = ProjectDefinition::parse(metadata)?;
+        let mut projects = ProjectDefinition::parse(metadata, cli)?;
+        for (_, config) in &mut projects {
+            config.override_addrs(cli)?;
+        }
+        if cli.all {
+            // the reload channel of a relayed site goes through its site-addr
+            unique_ports(&mut projects, |config| {
+                cli.tls || (watch && config.reload_via_site_addr)
+            })?;
+        }
 
         let mut resolved = Vec::new();
         for (project, mut config) in projects {
+            // dev builds are served by cargo-leptos
+            if !cli.release {
+                config.asset_url_prefix = None;
+            }
             if config.output_name.is_empty() {
                 config.output_name = project.name.to_string();
             }
 
-            let lib = LibPackage::resolve(cli, metadata, &project, &config)?;
+            let mut lib = LibPackage::resolve(cli, metadata, &project, &config)?;
 
             let js_dir = config
                 .js_dir
                 .clone()
                 .unwrap_or_else(|| Utf8PathBuf::from("src"));
 
-            let watch_additional_files = config.watch_additional_files.clone().unwrap_or_default();
+            let (watch_additional_globs, watch_additional_files): (Vec<_>, Vec<_>) = config
+                .watch_additional_files
+                .clone()
+                .unwrap_or_default()
+                .into_iter()
+                .partition(|file| glob_base(file.as_str()).is_some());
+            let watch_additional_globs = watch_additional_globs
+                .into_iter()
+                .map(|glob| glob.to_string())
+                .collect::<Vec<_>>();
 
-            let bin = BinPackage::resolve(cli, metadata, &project, &config, bin_args)?;
+            let bin = if config.csr {
+                None
+            } else {
+                Some(BinPackage::resolve(
+                    cli, metadata, &project, &config, bin_args,
+                )?)
+            };
+            if let Some(bin) = &bin {
+                lib.ssr_features = lib.ssr_features(metadata, bin);
+            }
 
             // If there's more than 1 workspace member, we're a workspace. Probably
             let is_workspace = metadata.workspace_members.len() > 1;
             log::debug!("Detected Workspace: {is_workspace}");
-            let hash_file = match is_workspace {
-                true => HashFile::new(
+            let hash_file = match (&bin, is_workspace) {
+                (Some(bin), true) => HashFile::new(
                     Some(&metadata.workspace_root),
-                    &bin,
+                    bin,
                     config.hash_file_name.as_ref(),
+                    config.hash_file_format,
                 ),
-                false => HashFile::new(None, &bin, config.hash_file_name.as_ref()),
+                (Some(bin), false) => HashFile::new(
+                    None,
+                    bin,
+                    config.hash_file_name.as_ref(),
+                    config.hash_file_format,
+                ),
+                // without a server, in the site
+                (None, _) => HashFile::in_dir(
+                    &metadata.workspace_root.join(&config.site_root),
+                    config.hash_file_name.as_ref(),
+                    config.hash_file_format,
+                ),
+            };
+
+            let tls = if cli.tls {
+                Some(Tls::resolve(
+                    config.tls_cert.as_ref(),
+                    config.tls_key.as_ref(),
+                    &metadata.workspace_root,
+                    &metadata.target_directory,
+                )?)
+            } else {
+                None
             };
 
             let proj = Project {
                 working_dir: metadata.workspace_root.clone(),
+                target_dir: metadata.target_directory.clone(),
                 name: project.name.clone(),
+                tags: config.tags.clone(),
                 lib,
                 bin,
-                style: StyleConfig::new(&config)?,
+                style: StyleConfig::new(&config, metadata)?,
                 watch,
                 release: cli.release,
                 precompress: cli.precompress,
                 hot_reload: cli.hot_reload,
+                hot_patch_fallback: config.hot_patch_fallback,
+                error_overlay: watch && config.error_overlay,
+                reload_health_path: config.reload_health_path.clone(),
+                reload_delay: Duration::from_millis(config.reload_delay_ms),
+                open_path: config.open_path.clone(),
+                hot_reload_client: config.hot_reload_client.clone(),
                 wasm_debug: cli.wasm_debug,
-                site: Arc::new(Site::new(&config)),
+                timings: cli.timings,
+                reproducible: cli.reproducible,
+                cargo_rustflags: cargo_rustflags(cli, &metadata.workspace_root)?,
+                size_ledger: cli.size_report.clone(),
+                size_baseline: cli.size_baseline.clone(),
+                source_date_epoch: source_date_epoch(cli, &metadata.workspace_root)?,
+                site: Arc::new(Site::new(
+                    &config,
+                    cli.release && config.html_minify,
+                    watch && config.reload_via_site_addr,
+                    tls,
+                    cli.port_fallback,
+                    gateway_port(cli)?,
+                )?),
                 end2end: End2EndConfig::resolve(&config),
-                assets: AssetsConfig::resolve(&config),
+                assets: AssetsConfig::resolve(&config)?,
                 js_dir,
                 watch_additional_files,
+                watch_additional_set: glob_set(&watch_additional_globs)?,
+                watch_additional_globs,
+                watch_ignore: config.watch_ignore.clone(),
+                watch_ignore_set: glob_set(&config.watch_ignore)?,
+                watch_gitignore: config.watch_gitignore,
+                watch_backend: config.watch_backend,
+                watch_poll_interval: Duration::from_millis(config.watch_poll_interval),
+                watch_debounce: Duration::from_millis(config.watch_debounce_ms),
+                watch_debounce_assets: Duration::from_millis(
+                    config
+                        .watch_debounce_assets_ms
+                        .unwrap_or(config.watch_debounce_ms),
+                ),
+                hooks: Hooks::resolve(&config.hooks)?,
+                proxy: ProxyRoute::resolve(&config.proxy)?,
+                headers: resolve_headers(&config.headers)?,
+                server_env: config.server_env.clone(),
+                depends_on: config.depends_on.clone(),
+                export_routes: if config.export_routes.is_empty() {
+                    vec!["/".to_string()]
+                } else {
+                    config.export_routes.clone()
+                },
+                export_dir: config.export_dir.clone().unwrap_or_else(|| {
+                    metadata.rel_target_dir().join("export").join(&project.name)
+                }),
+                static_host_files: config.static_host_files,
+                prerender_routes: if config.csr {
+                    Vec::new()
+                } else {
+                    config.prerender_routes.clone()
+                },
+                fallback_route: config.fallback_route.clone().filter(|_| !config.csr),
+                app_component: config
+                    .app_component
+                    .clone()
+                    .unwrap_or_else(|| "app::App".to_string()),
+                sitemap_base_url: config
+                    .sitemap_base_url
+                    .clone()
+                    .filter(|_| cli.release && !config.csr),
+                sitemap_exclude: config.sitemap_exclude.clone(),
+                robots_txt: RobotsTxt::resolve(&config, cli.release),
+                security_txt: SecurityTxt::resolve(&config)?,
+                pwa: PwaConfig::resolve(&config, &project.name),
+                embed_file: (config.embed_site && !config.csr).then(|| {
+                    metadata
+                        .target_directory
+                        .join("leptos-embed")
+                        .join(format!("{}.rs", project.name))
+                }),
+                build_info: config.build_info,
                 hash_file,
                 hash_files: config.hash_files,
+                hash_assets: config.hash_files && config.hash_assets,
+                cache_bust: config.cache_bust,
                 js_minify: cli.release && cli.js_minify && config.js_minify,
+                critical_css: cli.release && config.critical_css,
+                csp_hashes: config.csp_hashes,
                 server_fn_prefix: config.server_fn_prefix,
                 disable_server_fn_hash: config.disable_server_fn_hash,
                 server_fn_mod_path: config.server_fn_mod_path,
@@ -139,9 +393,16 @@ impl Project {
             resolved.push(Arc::new(proj));
         }
 
+        let resolved = in_build_order(resolved)?;
+
         let projects_in_cwd = resolved
             .iter()
-            .filter(|p| p.bin.abs_dir.starts_with(cwd) || p.lib.abs_dir.starts_with(cwd))
+            .filter(|p| {
+                p.bin
+                    .as_ref()
+                    .is_some_and(|bin| bin.abs_dir.starts_with(cwd))
+                    || p.lib.abs_dir.starts_with(cwd)
+            })
             .collect::<Vec<_>>();
 
         if projects_in_cwd.len() == 1 {
@@ -158,16 +419,63 @@ impl Project {
             ("LEPTOS_SITE_ROOT", self.site.root_dir.to_string()),
             ("LEPTOS_SITE_PKG_DIR", self.site.pkg_dir.to_string()),
             ("LEPTOS_SITE_ADDR", self.site.addr.to_string()),
-            ("LEPTOS_RELOAD_PORT", self.site.reload.port().to_string()),
+            ("LEPTOS_RELOAD_PORT", self.site.reload_port().to_string()),
             ("LEPTOS_LIB_DIR", self.lib.rel_dir.to_string()),
-            ("LEPTOS_BIN_DIR", self.bin.rel_dir.to_string()),
-            ("LEPTOS_JS_MINIFY", self.js_minify.to_string()),
-            ("LEPTOS_HASH_FILES", self.hash_files.to_string()),
         ];
+        if self.site.external_reload_port() != self.site.reload_port() {
+            let port = self.site.external_reload_port().to_string();
+            vec.push(("LEPTOS_RELOAD_EXTERNAL_PORT", port));
+        }
+        // leptos reads a single address, the server binds the others itself
+        if let Some(addrs) = self.site.other_addrs_string() {
+            vec.push(("LEPTOS_SITE_ADDRS", addrs));
+        }
+        // where leptos links the pkg files from
+        let pkg_root = match &self.site.asset_url_prefix {
+            Some(prefix) => Some(prefix.as_str()),
+            None => Some(self.site.base_url.as_str()).filter(|url| !url.is_empty()),
+        };
+        if let Some(root) = pkg_root {
+            vec.push(("CDN_PKG_PATH", format!("{root}/{}", self.site.pkg_dir)));
+        }
+        if let Some(bin) = &self.bin {
+            vec.push(("LEPTOS_BIN_DIR", bin.rel_dir.to_string()));
+        }
+        vec.extend([
+            ("LEPTOS_JS_MINIFY", self.js_minify.to_string()),
+            // leptos looks for the renamed files when set
+            (
+                "LEPTOS_HASH_FILES",
+                (self.hash_files && self.cache_bust == CacheBust::Rename).to_string(),
+            ),
+        ]);
         if self.hash_files {
             vec.push(("LEPTOS_HASH_FILE_NAME", self.hash_file.rel.to_string()));
         }
-        if self.watch {
+        if let Some(modules) = &self.style.modules {
+            vec.push(("LEPTOS_CSS_MODULES_FILE", modules.rust_file.to_string()));
+        }
+        if self.critical_css {
+            vec.push((
+                "LEPTOS_CRITICAL_CSS_FILE",
+                self.critical_css_file().to_string(),
+            ));
+        }
+        if self.csp_hashes {
+            vec.push(("LEPTOS_CSP_FILE", self.csp_file().to_string()));
+        }
+        if let Some(file) = &self.embed_file {
+            vec.push(("LEPTOS_EMBED_FILE", file.to_string()));
+        }
+        if self.build_info {
+            vec.push(("LEPTOS_BUILD_INFO", self.build_info_file().to_string()));
+        }
+        // the leptos live-reload client connects over wss
+        if self.site.tls.is_some() {
+            vec.push(("LEPTOS_RELOAD_WS_PROTOCOL", "wss".to_string()));
+        }
+        // leptos injects its live-reload client when set
+        if self.watch && self.hot_reload_client == HotReloadClient::Default {
             vec.push(("LEPTOS_WATCH", true.to_string()))
         }
         if let Some(prefix) = self.server_fn_prefix.as_ref() {
@@ -179,48 +487,436 @@ impl Project {
         if self.server_fn_mod_path {
             vec.push(("SERVER_FN_MOD_PATH", true.to_string()));
         }
+        if let Some(epoch) = self.source_date_epoch {
+            vec.push(("SOURCE_DATE_EPOCH", epoch.to_string()));
+        }
         vec
     }
+
+    /// the paths watched for the additional files: the literal paths and the dirs of the globs
+    pub fn watch_additional_paths(&self) -> Vec<Utf8PathBuf> {
+        let glob_dirs = self
+            .watch_additional_globs
+            .iter()
+            .filter_map(|glob| glob_base(glob));
+        self.watch_additional_files
+            .iter()
+            .cloned()
+            .chain(glob_dirs)
+            .collect()
+    }
+
+    /// whether the file (relative to the workspace root) is one of the additional files
+    pub fn is_additional_file(&self, path: &Utf8Path) -> bool {
+        self.watch_additional_files
+            .iter()
+            .any(|file| path.starts_with(file))
+            || self.watch_additional_set.is_match(path)
+    }
+
+    /// whether the changes of the front sources are only patched into the page, not rebuilt
+    pub fn patch_only(&self) -> bool {
+        self.hot_reload && self.hot_patch_fallback == HotPatchFallback::Strict
+    }
+
+    /// how long to wait for more changes before rebuilding for the given ones
+    pub fn watch_debounce(&self, changes: &ChangeSet) -> Duration {
+        if changes.only_assets_or_style() {
+            self.watch_debounce_assets
+        } else {
+            self.watch_debounce
+        }
+    }
+
+    /// whether changes to the file (relative to the workspace root) are ignored when watching
+    pub fn is_watch_ignored(&self, path: &Utf8Path) -> bool {
+        self.watch_ignore_set.is_match(path)
+    }
+
+    /// the `build-info.json` in the site root
+    pub fn build_info_file(&self) -> Utf8PathBuf {
+        self.site.root_dir.join("build-info.json")
+    }
+
+    pub fn csp_file(&self) -> Utf8PathBuf {
+        self.site
+            .root_relative_pkg_dir()
+            .join(format!("{}.csp.json", self.lib.output_name))
+    }
+
+    /// The env vars of the server run from a bundle, with the site root at `site` next to it: the
+    /// ones leptos reads at runtime, in production mode.
+    pub fn bundle_envs(&self) -> Vec<(&'static str, String)> {
+        const BUILD_ONLY: [&str; 11] = [
+            "LEPTOS_LIB_DIR",
+            "LEPTOS_BIN_DIR",
+            "LEPTOS_JS_MINIFY",
+            "LEPTOS_CSS_MODULES_FILE",
+            "LEPTOS_EMBED_FILE",
+            "LEPTOS_WATCH",
+            "LEPTOS_RELOAD_WS_PROTOCOL",
+            "SERVER_FN_PREFIX",
+            "DISABLE_SERVER_FN_HASH",
+            "SERVER_FN_MOD_PATH",
+            "SOURCE_DATE_EPOCH",
+        ];
+        let root = self.site.root_dir.as_str();
+        let mut vec: Vec<_> = self
+            .to_envs()
+            .into_iter()
+            .filter(|(name, _)| !BUILD_ONLY.contains(name))
+            .map(|(name, val)| match val.strip_prefix(root) {
+                Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+                    (name, format!("site{rest}"))
+                }
+                _ => (name, val),
+            })
+            .collect();
+        vec.push(("LEPTOS_ENV", "PROD".to_string()));
+        vec
+    }
+
+    /// The env vars of the end2end command, so that its config needn't repeat the addresses: the
+    /// url of the site, its addresses, the reload port and the absolute site root.
+    pub fn end2end_envs(&self) -> Vec<(&'static str, String)> {
+        let mut vec = vec![
+            ("E2E_BASE_URL", self.site.local_url()),
+            ("LEPTOS_SITE_ADDR", self.site.addr.to_string()),
+            ("LEPTOS_RELOAD_PORT", self.site.reload_port().to_string()),
+            (
+                "LEPTOS_SITE_ROOT",
+                self.working_dir.join(&self.site.root_dir).to_string(),
+            ),
+            ("LEPTOS_SITE_PKG_DIR", self.site.pkg_dir.to_string()),
+        ];
+        if let Some(addrs) = self.site.other_addrs_string() {
+            vec.push(("LEPTOS_SITE_ADDRS", addrs));
+        }
+        if !self.site.base_url.is_empty() {
+            vec.push(("LEPTOS_BASE_URL", self.site.base_url.clone()));
+        }
+        vec
+    }
+
+    /// the html snippet with the inlined critical css and the deferred stylesheet
+    pub fn critical_css_file(&self) -> Utf8PathBuf {
+        self.site
+            .root_relative_pkg_dir()
+            .join(format!("{}.critical.html", self.lib.output_name))
+    }
+
+    /// The rustflags of the target (the default one without) with the path remaps of the
+    /// absolute workspace and cargo home paths appended, when building reproducibly. They are
+    /// the ones of the env or else of the cargo config, as setting them in the env replaces both.
+    pub fn reproducible_cargo_envs(&self, triple: Option<&str>) -> Vec<(&'static str, String)> {
+        if !self.reproducible {
+            return vec![];
+        }
+        let flags = env_rustflags(|name| env::var(name).ok())
+            .unwrap_or_else(|| self.cargo_rustflags.for_target(triple));
+        vec![encoded_rustflags(
+            flags.into_iter().chain(self.path_remaps()),
+        )]
+    }
+
+    fn path_remaps(&self) -> Vec<String> {
+        let mut remaps = vec![format!("--remap-path-prefix={}=.", self.working_dir)];
+        if let Some(cargo_home) = cargo_home() {
+            remaps.push(format!("--remap-path-prefix={cargo_home}=/cargo"));
+        }
+        remaps
+    }
+}
+
+/// the rustflags of the cargo config, which the reproducible builds extend unless the env sets
+/// the rustflags
+fn cargo_rustflags(cli: &Opts, workspace_root: &Utf8Path) -> Result<CargoRustflags> {
+    if !cli.reproducible || env_rustflags(|name| env::var(name).ok()).is_some() {
+        return Ok(CargoRustflags::default());
+    }
+    CargoRustflags::read(workspace_root)
+}
+
+fn gateway_port(cli: &Opts) -> Result<Option<u16>> {
+    cli.gateway
+        .map(|addr| free_port(SocketAddr::new(addr.ip(), 0), false, &[]))
+        .transpose()
+        .map(|addr| addr.map(|addr| addr.port()))
+}
+
+fn source_date_epoch(cli: &Opts, workspace_root: &Utf8Path) -> Result<Option<u64>> {
+    if !cli.reproducible {
+        return Ok(None);
+    }
+    if let Ok(epoch) = env::var("SOURCE_DATE_EPOCH") {
+        return Ok(Some(epoch.parse().map_err(|e| {
+            anyhow!("Invalid SOURCE_DATE_EPOCH {epoch:?}: {e}")
+        })?));
+    }
+    let commit_time = std::process::Command::new("git")
+        .args(["log", "-1", "--format=%ct"])
+        .current_dir(workspace_root)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8_lossy(&output.stdout).trim().parse().ok());
+    if commit_time.is_none() {
+        log::warn!("Reproducible build could not determine the last git commit time, using SOURCE_DATE_EPOCH=0");
+    }
+    Ok(Some(commit_time.unwrap_or(0)))
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, JsonSchema)]
 #[serde(rename_all = "kebab-case")]
 pub struct ProjectConfig {
     #[serde(default)]
     pub output_name: String,
-    #[serde(default = "default_site_addr")]
-    pub site_addr: SocketAddr,
+    /// a client side rendered project: only the lib package is built, into a static site
+    #[serde(default)]
+    pub csr: bool,
+    /// the addresses the site is served at: the first one, plus the others for dual-stack and
+    /// container setups
+    #[serde(default = "default_site_addr", deserialize_with = "one_or_many")]
+    #[schemars(schema_with = "one_or_many_schema::<SocketAddr>")]
+    pub site_addr: Vec<SocketAddr>,
     #[serde(default = "default_site_root")]
+    #[schemars(with = "String")]
     pub site_root: Utf8PathBuf,
     #[serde(default = "default_pkg_dir")]
+    #[schemars(with = "String")]
     pub site_pkg_dir: Utf8PathBuf,
-    pub style_file: Option<Utf8PathBuf>,
+    /// the path prefix the site is hosted under, such as `/myapp` behind a reverse proxy
+    #[serde(default)]
+    pub base_url: String,
+    /// the url the site files are served from instead, once uploaded to a cdn
+    pub asset_url_prefix: Option<String>,
+    /// one or more style files, compiled in order into the site's stylesheet
+    #[serde(default, deserialize_with = "one_or_many")]
+    #[schemars(schema_with = "one_or_many_schema::<String>")]
+    pub style_file: Vec<Utf8PathBuf>,
+    /// dirs in which sass looks for the `@use`d and `@import`ed files, relative to the config file
+    #[serde(default)]
+    #[schemars(with = "Vec<String>")]
+    pub sass_load_paths: Vec<Utf8PathBuf>,
+    /// extra arguments passed to sass
+    #[serde(default)]
+    pub sass_args: Vec<String>,
+    /// pipe the compiled css through postcss
+    #[serde(default)]
+    pub postcss: bool,
+    /// the postcss command, reading the css from stdin and writing it to stdout
+    pub postcss_cmd: Option<String>,
+    /// dir with the css modules (`*.module.css`, `*.module.scss`) of the components
+    #[schemars(with = "Option<String>")]
+    pub css_modules_dir: Option<Utf8PathBuf>,
+    /// remove the rules whose classes aren't used by the sources from the stylesheet. Release only
+    #[serde(default)]
+    pub css_purge: bool,
+    /// glob patterns of the class names that are never purged
+    #[serde(default)]
+    pub css_purge_safelist: Vec<String>,
+    /// add the css collected by a css-in-rust crate (stylance or stylers) to the stylesheet
+    pub style_collector: Option<StyleCollector>,
+    /// the css file written by the style collector, relative to the config file
+    #[schemars(with = "Option<String>")]
+    pub style_collector_file: Option<Utf8PathBuf>,
     /// text file where the hashes of the frontend files are stored
+    #[schemars(with = "Option<String>")]
     pub hash_file_name: Option<Utf8PathBuf>,
+    /// the format of the hash file: text (read by leptos) or json
+    #[serde(default)]
+    pub hash_file_format: HashFileFormat,
     /// whether to hash the frontend files content and add them to the file names
     #[serde(default = "default_hash_files")]
     pub hash_files: bool,
+    /// whether to also hash the files copied from the assets dir, except the html pages
+    #[serde(default)]
+    pub hash_assets: bool,
+    /// whether the hashes are added to the file names or as a query string
+    #[serde(default)]
+    pub cache_bust: CacheBust,
+    #[schemars(with = "Option<String>")]
     pub tailwind_input_file: Option<Utf8PathBuf>,
+    #[schemars(with = "Option<String>")]
     pub tailwind_config_file: Option<Utf8PathBuf>,
     /// assets dir. content will be copied to the target/site dir
+    #[schemars(with = "Option<String>")]
     pub assets_dir: Option<Utf8PathBuf>,
+    /// glob patterns (relative to the assets dir) of files that are not copied to the site
+    #[serde(default)]
+    pub assets_ignore: Vec<String>,
+    /// glob patterns (relative to the assets dir). When set, only matching files are copied
+    pub assets_include: Option<Vec<String>>,
+    /// link the asset files into the site dir instead of copying them. Dev builds only
+    #[serde(default)]
+    pub assets_link: bool,
+    /// recompress the png/jpeg assets and generate variants of them. Release builds only
+    #[serde(default)]
+    pub assets_optimize_images: bool,
+    /// the image formats ("webp", "avif") to generate variants in. Defaults to webp
+    pub assets_image_formats: Option<Vec<String>>,
+    /// the widths (in pixels) of the responsive variants to generate
+    #[serde(default)]
+    pub assets_image_widths: Vec<u32>,
+    /// the quality (1-100) of the lossy encoders
+    pub assets_image_quality: Option<u8>,
     /// js dir. changes triggers rebuilds.
+    #[schemars(with = "Option<String>")]
     pub js_dir: Option<Utf8PathBuf>,
     #[serde(default = "default_js_minify")]
     pub js_minify: bool,
+    /// minify the html files written to the site. Applies to release builds only
+    #[serde(default = "default_html_minify")]
+    pub html_minify: bool,
+    /// inline the css used by the root route and defer the stylesheet. Release builds only
+    #[serde(default)]
+    pub critical_css: bool,
+    /// write the csp hashes of the inline scripts and styles of the generated html
+    #[serde(default)]
+    pub csp_hashes: bool,
+    /// show the build errors over the page in the browser, in watch mode
+    #[serde(default = "default_error_overlay")]
+    pub error_overlay: bool,
     /// additional files to watch. changes triggers rebuilds.
+    #[schemars(with = "Option<Vec<String>>")]
     pub watch_additional_files: Option<Vec<Utf8PathBuf>>,
+    /// glob patterns of the files whose changes never trigger rebuilds
+    #[serde(default)]
+    pub watch_ignore: Vec<String>,
+    /// ignore the changes to the files ignored by the `.gitignore`, `.ignore` and git exclude files
+    #[serde(default = "default_watch_gitignore")]
+    pub watch_gitignore: bool,
+    /// how the files are watched: auto, native or poll
+    #[serde(default)]
+    pub watch_backend: WatchBackend,
+    /// the interval, in milliseconds, at which the files are compared when polling
+    #[serde(default = "default_watch_poll_interval")]
+    pub watch_poll_interval: u64,
+    /// milliseconds without changes the watcher waits for before rebuilding
+    #[serde(default = "default_watch_debounce_ms")]
+    pub watch_debounce_ms: u64,
+    /// the debounce when only asset and style files changed. Defaults to watch-debounce-ms
+    pub watch_debounce_assets_ms: Option<u64>,
+    /// the commands run by the watch loop
+    #[serde(default)]
+    pub hooks: HooksConfig,
+    /// the path prefixes the static dev server forwards, by the backend urls
+    #[serde(default)]
+    pub proxy: BTreeMap<String, String>,
+    /// the extra headers of the responses of the static dev server, by name
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// the env vars set for the server in serve and watch mode, by name. `${NAME}` is replaced by
+    /// the env var NAME
+    #[serde(default)]
+    pub server_env: BTreeMap<String, String>,
+    /// the names of the projects built before this one, such as the ones whose output it embeds
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    /// the tags the project is selected by with `--tag`
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// the paths of the pages written by `export`, which also writes the ones they link to.
+    /// Defaults to the root
+    #[serde(default)]
+    pub export_routes: Vec<String>,
+    /// the dir `export` writes the static site to. Defaults to target/export/<project>
+    #[schemars(with = "Option<String>")]
+    pub export_dir: Option<Utf8PathBuf>,
+    /// writes the `_headers` and `_redirects` files of Netlify and Cloudflare Pages into the
+    /// static sites
+    #[serde(default)]
+    pub static_host_files: bool,
+    /// the paths of the pages prerendered into the site root by the builds, served as static
+    /// html. Glob patterns, such as "/blog/*", match the pages the other ones link to
+    #[serde(default)]
+    pub prerender_routes: Vec<String>,
+    /// the path of the page prerendered into `404.html` in the site root by the builds, for the
+    /// static hosts and CDNs to serve as the not-found page, such as "/404"
+    pub fallback_route: Option<String>,
+    /// the path of the root component in the lib crate, that `routes` generates the route list
+    /// of. Defaults to "app::App"
+    pub app_component: Option<String>,
+    /// the url of the deployed site, such as "https://example.com", for the release builds to
+    /// write a sitemap.xml of the pages linked from the root into the site root
+    pub sitemap_base_url: Option<String>,
+    /// the glob patterns of the paths left out of the sitemap, such as "/admin/*"
+    #[serde(default)]
+    pub sitemap_exclude: Vec<String>,
+    /// writes a robots.txt into the site root, that allows the crawlers or disallows everything,
+    /// such as in the env section of a staging site
+    pub robots_txt: Option<RobotsMode>,
+    /// the paths the robots.txt disallows, when it allows the site
+    #[serde(default)]
+    pub robots_disallow: Vec<String>,
+    /// the uris to report vulnerabilities to, for a `.well-known/security.txt` in the site root
+    #[serde(default)]
+    pub security_contact: Vec<String>,
+    /// the RFC 3339 date and time the security.txt expires, required with a security-contact
+    pub security_expires: Option<String>,
+    /// the url of the security policy in the security.txt
+    pub security_policy: Option<String>,
+    /// writes a `manifest.webmanifest` and a service worker `sw.js` precaching the site files into
+    /// the site root in the builds, for the app to be installable and to work offline
+    #[serde(default)]
+    pub pwa: bool,
+    /// the name of the app in the web manifest. Defaults to the project name
+    pub pwa_name: Option<String>,
+    pub pwa_short_name: Option<String>,
+    pub pwa_theme_color: Option<String>,
+    pub pwa_background_color: Option<String>,
+    /// the icons of the app in the web manifest, relative to the site root
+    #[serde(default)]
+    pub pwa_icons: Vec<String>,
+    /// generates a rust module including the files of the site root, and their precompressed
+    /// copies, for the server to serve them from its binary. Its path is given to the server
+    /// build as LEPTOS_EMBED_FILE, for `include!(env!("LEPTOS_EMBED_FILE"))`
+    #[serde(default)]
+    pub embed_site: bool,
+    /// writes a `build-info.json` with the git commit, the build time, the cargo-leptos version
+    /// and the features into the site root in the builds. Its path is given to the server as
+    /// LEPTOS_BUILD_INFO
+    #[serde(default)]
+    pub build_info: bool,
     #[serde(default = "default_reload_port")]
     pub reload_port: u16,
+    /// the port the browser reaches the reload server at, behind a proxy
+    pub reload_external_port: Option<u16>,
+    /// the server path polled after a restart, until it answers successfully, before reloading
+    pub reload_health_path: Option<String>,
+    /// relay the reload channel through the site addr, so that only it needs to be reachable
+    #[serde(default)]
+    pub reload_via_site_addr: bool,
+    /// the milliseconds waited before the browser is told to reload
+    #[serde(default)]
+    pub reload_delay_ms: u64,
+    /// the path of the site opened with `--open`
+    #[serde(default = "default_open_path")]
+    pub open_path: String,
+    /// the PEM certificate served with `--tls`, relative to the workspace root
+    #[schemars(with = "Option<String>")]
+    pub tls_cert: Option<Utf8PathBuf>,
+    /// the PEM private key of the `tls-cert`, relative to the workspace root
+    #[schemars(with = "Option<String>")]
+    pub tls_key: Option<Utf8PathBuf>,
+    /// `false` to inject no live-reload client, or the path of a script to inject instead
+    #[serde(default)]
+    pub hot_reload_client: HotReloadClient,
+    /// with `--hot-reload`, what happens to the front changes that can't be patched
+    #[serde(default)]
+    pub hot_patch_fallback: HotPatchFallback,
     /// command for launching end-2-end integration tests
     pub end2end_cmd: Option<String>,
     /// the dir used when launching end-2-end integration tests
+    #[schemars(with = "Option<String>")]
     pub end2end_dir: Option<Utf8PathBuf>,
     #[serde(default = "default_browserquery")]
     pub browserquery: String,
     /// the bin target to use for building the server
     #[serde(default)]
     pub bin_target: String,
+    /// the cargo example to use as the server instead of a bin target
+    pub bin_example: Option<String>,
     /// the bin output target triple to use for building the server
     pub bin_target_triple: Option<String>,
     /// the directory to put the generated server artifacts
@@ -231,12 +927,17 @@ pub struct ProjectConfig {
     pub bin_cargo_args: Option<Vec<String>>,
     /// An optional override, if you've changed the name of your bin file in your project you'll need to set it here as well.
     pub bin_exe_name: Option<String>,
+    /// the command running the server built for a wasm target, such as `wasmtime serve`
+    pub bin_runner: Option<String>,
     #[serde(default)]
     pub features: Vec<String>,
     #[serde(default)]
     pub lib_features: Vec<String>,
     #[serde(default)]
     pub lib_default_features: bool,
+    /// the wasm crates built next to the lib into the pkg dir, such as web workers
+    #[serde(default)]
+    pub extra_wasm_packages: Vec<String>,
     /// cargo flags to pass to cargo when building the WASM frontend
     pub lib_cargo_args: Option<Vec<String>>,
     #[serde(default)]
@@ -278,9 +979,11 @@ pub struct ProjectConfig {
     #[serde(skip)]
     pub tmp_dir: Utf8PathBuf,
 
-    /// Deprecated. Keeping this here to warn users to remove it in case they have it in their config.
-    #[deprecated = "This option is deprecated since cargo-leptos 0.2.3 (when it became unconditionally enabled). You may remove it from your config."]
+    /// `false` to build the front in the target dir of the server, instead of its `front` subdir
     pub separate_front_target_dir: Option<bool>,
+    /// the target dir of the front build, relative to the workspace root
+    #[schemars(with = "Option<String>")]
+    pub front_target_dir: Option<Utf8PathBuf>,
 
     // Profiles
     pub lib_profile_dev: Option<String>,
@@ -294,11 +997,14 @@ impl ProjectConfig {
         dir: &Utf8Path,
         metadata: &serde_json::Value,
         cargo_metadata: &Metadata,
+        cli: &Opts,
     ) -> Result<Self> {
-        let mut conf: ProjectConfig = serde_json::from_value(metadata.clone())?;
+        let env = cli.env.as_deref();
+        let metadata = with_overrides(with_env(metadata, env), cli.release);
+        let mut conf: ProjectConfig = serde_json::from_value(metadata)?;
         conf.config_dir = dir.to_path_buf();
         conf.tmp_dir = cargo_metadata.target_directory.join("tmp");
-        let dotenvs = load_dotenvs(dir)?;
+        let dotenvs = load_dotenvs(dir, env)?;
         overlay_env(&mut conf, dotenvs)?;
         if conf.site_root == "/"
             || conf.site_root == "."
@@ -334,27 +1040,67 @@ impl ProjectConfig {
                 path
             };
         }
-        if conf.site_addr.port() == conf.reload_port {
-            bail!(
-                "The site-addr port and reload-port cannot be the same: {}",
-                conf.reload_port
-            );
+        ensure!(!conf.site_addr.is_empty(), "The site-addr list is empty");
+        conf.base_url = normalize_base_url(&conf.base_url)?;
+        if let Some(prefix) = &conf.asset_url_prefix {
+            conf.asset_url_prefix = Some(normalize_asset_url_prefix(prefix)?);
         }
+        conf.check_ports()?;
+        // the server renders the links to the pkg files, which leptos doesn't add queries to
+        ensure!(
+            conf.csr || !conf.hash_files || conf.cache_bust != CacheBust::Query,
+            "cache-bust = \"query\" is only supported by csr projects, whose html cargo-leptos \
+             writes. Use cache-bust = \"rename\" with a server"
+        );
 
-        #[allow(deprecated)]
-        if conf.separate_front_target_dir.is_some() {
-            log::warn!("Deprecated: the `separate-front-target-dir` option is deprecated since cargo-leptos 0.2.3");
-            log::warn!("It is now unconditionally enabled; you can remove it from your Cargo.toml")
+        if conf.separate_front_target_dir == Some(false) && conf.front_target_dir.is_none() {
+            log::warn!("Config separate-front-target-dir = false: the front and server builds share the target dir, so each one waits for the lock of the other and they run one at a time");
         }
 
         Ok(conf)
     }
+
+    /// the `--addr`, `--host`, `--port` and `--reload-port` given on the command line take the
+    /// place of the configured ones
+    fn override_addrs(&mut self, cli: &Opts) -> Result<()> {
+        if let Some(addr) = cli.addr {
+            self.site_addr = vec![addr];
+        }
+        if let Some(host) = cli.host {
+            self.site_addr = vec![SocketAddr::new(host, self.site_addr[0].port())];
+        }
+        if let Some(port) = cli.port {
+            self.site_addr
+                .iter_mut()
+                .for_each(|addr| addr.set_port(port));
+        }
+        if let Some(port) = cli.reload_port {
+            self.reload_port = port;
+        }
+        self.check_ports()
+    }
+
+    fn check_ports(&self) -> Result<()> {
+        if self
+            .site_addr
+            .iter()
+            .any(|addr| addr.port() == self.reload_port && addr.port() != 0)
+        {
+            bail!(
+                "The site-addr port and reload-port cannot be the same: {}",
+                self.reload_port
+            );
+        }
+        Ok(())
+    }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 #[serde(rename_all = "kebab-case")]
 pub struct ProjectDefinition {
     name: String,
+    /// empty for csr projects
+    #[serde(default)]
     pub bin_package: String,
     pub lib_package: String,
 }
@@ -363,12 +1109,18 @@ impl ProjectDefinition {
         metadata: &serde_json::Value,
         dir: &Utf8Path,
         cargo_metadata: &Metadata,
+        cli: &Opts,
     ) -> Result<Vec<(Self, ProjectConfig)>> {
         let mut found = Vec::new();
         if let Some(arr) = metadata.as_array() {
             for section in arr {
-                let conf = ProjectConfig::parse(dir, section, cargo_metadata)?;
+                let conf = ProjectConfig::parse(dir, section, cargo_metadata, cli)?;
                 let def: Self = serde_json::from_value(section.clone())?;
+                ensure!(
+                    conf.csr || !def.bin_package.is_empty(),
+                    "The workspace project {} is missing a bin-package (or csr = true)",
+                    def.name
+                );
                 found.push((def, conf))
             }
         }
@@ -380,8 +1132,9 @@ impl ProjectDefinition {
         metadata: &serde_json::Value,
         dir: &Utf8Path,
         cargo_metadata: &Metadata,
+        cli: &Opts,
     ) -> Result<(Self, ProjectConfig)> {
-        let conf = ProjectConfig::parse(dir, metadata, cargo_metadata)?;
+        let conf = ProjectConfig::parse(dir, metadata, cargo_metadata, cli)?;
 
         ensure!(
             package.cdylib_target().is_some(),
@@ -389,7 +1142,7 @@ impl ProjectDefinition {
             GRAY.paint(package.manifest_path.as_str())
         );
         ensure!(
-            package.has_bin_target(),
+            conf.csr || conf.bin_example.is_some() || package.has_bin_target(),
             "Cargo.toml has leptos metadata but is missing a bin target. {}",
             GRAY.paint(package.manifest_path.as_str())
         );
@@ -404,24 +1157,41 @@ impl ProjectDefinition {
         ))
     }
 
-    fn parse(metadata: &Metadata) -> Result<Vec<(Self, ProjectConfig)>> {
+    /// The projects of the leptos metadata. A `Leptos.toml` file takes precedence: at the
+    /// workspace root, with its `[[project]]` array, over the workspace metadata, and next to a
+    /// package manifest, with the keys of the package metadata, over the package metadata.
+    fn parse(metadata: &Metadata, cli: &Opts) -> Result<Vec<(Self, ProjectConfig)>> {
         let workspace_dir = &metadata.workspace_root;
-        let mut found: Vec<(Self, ProjectConfig)> =
-            if let Some(md) = leptos_metadata(&metadata.workspace_metadata) {
-                Self::from_workspace(md, &Utf8PathBuf::default(), metadata)?
-            } else {
-                Default::default()
-            };
+        let workspace_file = read_config_file(workspace_dir)?;
+        let workspace_projects = match workspace_file
+            .as_ref()
+            .and_then(|file| file.get(WORKSPACE_PROJECTS))
+        {
+            Some(projects) => Some(projects),
+            None => leptos_metadata(&metadata.workspace_metadata),
+        };
+        let mut found: Vec<(Self, ProjectConfig)> = if let Some(md) = workspace_projects {
+            Self::from_workspace(md, &Utf8PathBuf::default(), metadata, cli)?
+        } else {
+            Default::default()
+        };
 
         for package in metadata.workspace_packages() {
             let dir = package.manifest_path.unbase(workspace_dir)?.without_last();
 
-            if let Some(leptos_metadata) = leptos_metadata(&package.metadata) {
+            let file = match package.manifest_path.parent() {
+                Some(package_dir) => read_config_file(package_dir)?,
+                None => None,
+            };
+            // the one of the workspace is not a package config
+            let file = file.filter(|file| file.get(WORKSPACE_PROJECTS).is_none());
+            if let Some(leptos_metadata) = file.as_ref().or(leptos_metadata(&package.metadata)) {
                 found.push(Self::from_project(
                     package,
                     leptos_metadata,
                     &dir,
                     metadata,
+                    cli,
                 )?);
             }
         }
@@ -429,12 +1199,146 @@ impl ProjectDefinition {
     }
 }
 
+/// Checks that the projects run at once, with `--all`, are served at distinct ports. The ones
+/// whose reload port is taken by a project before them get a free one instead, unless `relayed`.
+fn unique_ports(
+    projects: &mut [(ProjectDefinition, ProjectConfig)],
+    relayed: impl Fn(&ProjectConfig) -> bool,
+) -> Result<()> {
+    let mut site_ports: Vec<(u16, &str)> = Vec::new();
+    for (def, config) in projects.iter() {
+        for port in config.site_addr.iter().map(|addr| addr.port()) {
+            if let Some((_, other)) = site_ports.iter().find(|(p, _)| *p == port && port != 0) {
+                bail!(
+                    "The projects {other} and {} are both served at port {port}. Change the site-addr of one of them",
+                    def.name
+                );
+            }
+            site_ports.push((port, &def.name));
+        }
+    }
+
+    let mut taken: Vec<u16> = site_ports.iter().map(|(port, _)| *port).collect();
+    for (def, config) in projects.iter_mut() {
+        let port = config.reload_port;
+        if relayed(config) || port == 0 || !taken.contains(&port) {
+            taken.push(port);
+            continue;
+        }
+        let mut reload = config.site_addr[0];
+        reload.set_port(port);
+        let free = free_port(reload, true, &taken)?.port();
+        log::warn!(
+            "Serve {} reload port {port} is taken by another project, using {free}",
+            def.name
+        );
+        taken.push(free);
+        config.reload_port = free;
+    }
+    Ok(())
+}
+
+/// The projects ordered so that each one comes after the projects it depends on, keeping the
+/// definition order otherwise.
+fn in_build_order(projects: Vec<Arc<Project>>) -> Result<Vec<Arc<Project>>> {
+    for proj in &projects {
+        for dep in &proj.depends_on {
+            ensure!(
+                projects.iter().any(|p| &p.name == dep),
+                "The project {} depends on {dep}, which is not a project of the workspace",
+                proj.name
+            );
+        }
+    }
+    let mut remaining = projects;
+    let mut ordered: Vec<Arc<Project>> = Vec::with_capacity(remaining.len());
+    while !remaining.is_empty() {
+        let Some(next) = remaining.iter().position(|proj| {
+            proj.depends_on
+                .iter()
+                .all(|dep| ordered.iter().any(|p| &p.name == dep))
+        }) else {
+            let names = remaining
+                .iter()
+                .map(|p| p.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ");
+            bail!("The depends-on of the projects {names} form a cycle");
+        };
+        ordered.push(remaining.remove(next));
+    }
+    Ok(ordered)
+}
+
+/// the config file that can be used instead of the leptos metadata of the Cargo.toml
+const CONFIG_FILE: &str = "Leptos.toml";
+/// the array of the projects of a workspace `Leptos.toml`
+const WORKSPACE_PROJECTS: &str = "project";
+
+fn read_config_file(dir: &Utf8Path) -> Result<Option<serde_json::Value>> {
+    let file = dir.join(CONFIG_FILE);
+    if !file.is_file() {
+        return Ok(None);
+    }
+    log::debug!("Config reading {}", GRAY.paint(file.as_str()));
+    let text = std::fs::read_to_string(&file).with_context(|| format!("Could not read {file}"))?;
+    let value = toml::from_str(&text).with_context(|| format!("Invalid {file}"))?;
+    Ok(Some(value))
+}
+
 fn leptos_metadata(metadata: &serde_json::Value) -> Option<&serde_json::Value> {
     metadata.as_object().and_then(|o| o.get("leptos"))
 }
 
-fn default_site_addr() -> SocketAddr {
-    SocketAddr::new([127, 0, 0, 1].into(), 3000)
+/// The metadata without its `env` table, overridden by the section of the environment: its
+/// tables are merged into the ones of the metadata, its other values replace them.
+fn with_env(metadata: &serde_json::Value, env: Option<&str>) -> serde_json::Value {
+    let mut metadata = metadata.clone();
+    let envs = metadata.as_object_mut().and_then(|o| o.remove("env"));
+    if let Some(env) = env {
+        match envs.as_ref().and_then(|envs| envs.get(env)) {
+            Some(section) => merge(&mut metadata, section),
+            None => log::warn!("Env no env.{env} section in the leptos metadata"),
+        }
+    }
+    metadata
+}
+
+/// The metadata without its `overrides` table, overridden by the section of the profile,
+/// `release` or `dev`, and then by the one of the `bin-target-triple`, merged as the env sections.
+fn with_overrides(mut metadata: serde_json::Value, release: bool) -> serde_json::Value {
+    let Some(overrides) = metadata.as_object_mut().and_then(|o| o.remove("overrides")) else {
+        return metadata;
+    };
+    let profile = if release { "release" } else { "dev" };
+    if let Some(section) = overrides.get(profile) {
+        merge(&mut metadata, section);
+    }
+    let triple = metadata.get("bin-target-triple").and_then(|t| t.as_str());
+    if let Some(section) = triple.and_then(|triple| overrides.get(triple)) {
+        merge(&mut metadata, section);
+    }
+    metadata
+}
+
+fn merge(base: &mut serde_json::Value, over: &serde_json::Value) {
+    match (base, over) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(over)) => {
+            for (key, value) in over {
+                match base.get_mut(key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (base, over) => *base = over.clone(),
+    }
+}
+
+fn default_site_addr() -> Vec<SocketAddr> {
+    vec![SocketAddr::new([127, 0, 0, 1].into(), 3000)]
 }
 
 fn default_pkg_dir() -> Utf8PathBuf {
@@ -449,14 +1353,82 @@ fn default_reload_port() -> u16 {
     3001
 }
 
+fn default_open_path() -> String {
+    "/".to_string()
+}
+
 fn default_browserquery() -> String {
     "defaults".to_string()
 }
 
+fn default_watch_poll_interval() -> u64 {
+    500
+}
+
+fn default_watch_debounce_ms() -> u64 {
+    100
+}
+
 fn default_hash_files() -> bool {
     false
 }
 
+/// the base url without its trailing slash, empty for the root
+fn normalize_base_url(base_url: &str) -> Result<String> {
+    let base_url = base_url.trim_end_matches('/');
+    ensure!(
+        base_url.is_empty() || base_url.starts_with('/'),
+        "The base-url {base_url:?} must start with a /"
+    );
+    ensure!(
+        !base_url.contains(['?', '#', ' ']),
+        "The base-url {base_url:?} must be a plain path"
+    );
+    Ok(base_url.to_string())
+}
+
+/// the asset url prefix without its trailing slash
+fn normalize_asset_url_prefix(prefix: &str) -> Result<String> {
+    let prefix = prefix.trim_end_matches('/');
+    ensure!(
+        ["https://", "http://", "//"]
+            .iter()
+            .any(|scheme| prefix.starts_with(scheme)),
+        "The asset-url-prefix {prefix:?} must be an absolute url"
+    );
+    Ok(prefix.to_string())
+}
+
+/// accepts either a single value or a list of values
+fn one_or_many<'de, D, T>(deserializer: D) -> std::result::Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany<T> {
+        One(T),
+        Many(Vec<T>),
+    }
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => vec![value],
+        OneOrMany::Many(values) => values,
+    })
+}
+
 fn default_js_minify() -> bool {
     true
 }
+
+fn default_html_minify() -> bool {
+    true
+}
+
+fn default_error_overlay() -> bool {
+    true
+}
+
+fn default_watch_gitignore() -> bool {
+    true
+}
@@ -0,0 +1,47 @@
+use std::collections::BTreeMap;
+
+use reqwest::Url;
+
+use crate::ext::anyhow::{ensure, Context, Result};
+
+/// A path prefix that the static dev server forwards to a backend, from the
+/// `[package.metadata.leptos.proxy]` table: `"/api" = "http://localhost:8080"`
+#[derive(Debug, Clone)]
+pub struct ProxyRoute {
+    pub prefix: String,
+    pub target: Url,
+}
+
+impl ProxyRoute {
+    /// the routes, the longest prefix first
+    pub fn resolve(config: &BTreeMap<String, String>) -> Result<Vec<Self>> {
+        let mut routes = config
+            .iter()
+            .map(|(prefix, target)| {
+                ensure!(
+                    prefix.starts_with('/'),
+                    "The proxy path {prefix:?} must start with a /"
+                );
+                let target = Url::parse(target)
+                    .with_context(|| format!("Invalid proxy target {target:?} for {prefix}"))?;
+                ensure!(
+                    matches!(target.scheme(), "http" | "https"),
+                    "The proxy target {target} for {prefix} must be an http or https url"
+                );
+                Ok(Self {
+                    prefix: prefix.clone(),
+                    target,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        routes.sort_by_key(|route| std::cmp::Reverse(route.prefix.len()));
+        Ok(routes)
+    }
+
+    /// whether the url path is forwarded: the prefix matches whole path segments
+    pub fn matches(&self, path: &str) -> bool {
+        let prefix = self.prefix.trim_end_matches('/');
+        path.strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    }
+}
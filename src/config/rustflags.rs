@@ -0,0 +1,184 @@
+use std::env;
+
+use camino::{Utf8Path, Utf8PathBuf};
+
+use crate::{
+    ext::anyhow::{bail, Context, Result},
+    logger::GRAY,
+};
+
+/// The rustflags of the cargo config files, which cargo uses when none are set in the env:
+/// the `target.<triple>.rustflags` of the target built, or else the `build.rustflags`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CargoRustflags {
+    /// the target built without `--target`: the `build.target`, or else the host
+    default_target: Option<String>,
+    build: Vec<String>,
+    targets: Vec<(String, Vec<String>)>,
+}
+
+impl CargoRustflags {
+    /// Reads the config files cargo reads when run in the dir. Fails on the rustflags of the
+    /// `target.'cfg(..)'` tables, which can't be told to apply to a target.
+    pub fn read(dir: &Utf8Path) -> Result<Self> {
+        let mut configs = Vec::new();
+        for file in config_files(dir) {
+            log::debug!("Config reading {}", GRAY.paint(file.as_str()));
+            let text =
+                std::fs::read_to_string(&file).with_context(|| format!("Could not read {file}"))?;
+            let value: toml::Value =
+                toml::from_str(&text).with_context(|| format!("Invalid {file}"))?;
+            configs.push((file, value));
+        }
+        let mut rustflags = Self::from_configs(&configs)?;
+        if rustflags.default_target.is_none() && !rustflags.targets.is_empty() {
+            rustflags.default_target = Some(host_triple()?);
+        }
+        Ok(rustflags)
+    }
+
+    /// the rustflags of the configs, from the lowest precedence to the highest, whose arrays
+    /// cargo joins in this order
+    pub fn from_configs(configs: &[(Utf8PathBuf, toml::Value)]) -> Result<Self> {
+        let mut rustflags = Self::default();
+        for (file, config) in configs {
+            if let Some(build) = config.get("build") {
+                rustflags.build.extend(flags(build.get("rustflags")));
+                if let Some(target) = build.get("target").and_then(|t| t.as_str()) {
+                    rustflags.default_target = Some(target.to_string());
+                }
+            }
+            let targets = config.get("target").and_then(|t| t.as_table());
+            for (target, table) in targets.into_iter().flatten() {
+                let target_flags = flags(table.get("rustflags"));
+                if target_flags.is_empty() {
+                    continue;
+                }
+                if target.starts_with("cfg(") {
+                    bail!(
+                        "The rustflags of target.'{target}' in {file} can't be combined with the path remaps of --reproducible. Set them in a target.<triple> table or in RUSTFLAGS instead"
+                    );
+                }
+                match rustflags.targets.iter_mut().find(|(t, _)| t == target) {
+                    Some((_, existing)) => existing.extend(target_flags),
+                    None => rustflags.targets.push((target.clone(), target_flags)),
+                }
+            }
+        }
+        Ok(rustflags)
+    }
+
+    /// the rustflags cargo uses for the target, the default one without
+    pub fn for_target(&self, triple: Option<&str>) -> Vec<String> {
+        let triple = triple.or(self.default_target.as_deref());
+        let var = |name: &str| env::var(name).ok();
+        let target = triple
+            .and_then(|triple| self.targets.iter().find(|(t, _)| t == triple))
+            .map(|(_, flags)| flags.clone())
+            .unwrap_or_default();
+        // the env config of cargo comes after the config files
+        let target_env = triple.and_then(|triple| {
+            var(&format!(
+                "CARGO_TARGET_{}_RUSTFLAGS",
+                triple.to_uppercase().replace(['-', '.'], "_")
+            ))
+        });
+        let target: Vec<String> = target
+            .into_iter()
+            .chain(
+                target_env
+                    .iter()
+                    .flat_map(|f| f.split_whitespace().map(String::from)),
+            )
+            .collect();
+        if !target.is_empty() {
+            return target;
+        }
+        let build_env = var("CARGO_BUILD_RUSTFLAGS");
+        self.build
+            .iter()
+            .cloned()
+            .chain(
+                build_env
+                    .iter()
+                    .flat_map(|f| f.split_whitespace().map(String::from)),
+            )
+            .collect()
+    }
+}
+
+/// The rustflags set in the env, as given by `var`: `CARGO_ENCODED_RUSTFLAGS`, which takes
+/// precedence, or `RUSTFLAGS`. None when neither is set.
+pub fn env_rustflags(var: impl Fn(&str) -> Option<String>) -> Option<Vec<String>> {
+    match (var("CARGO_ENCODED_RUSTFLAGS"), var("RUSTFLAGS")) {
+        (Some(encoded), _) => Some(
+            encoded
+                .split('\x1f')
+                .filter(|flag| !flag.is_empty())
+                .map(str::to_string)
+                .collect(),
+        ),
+        (None, Some(flags)) => Some(flags.split_whitespace().map(str::to_string).collect()),
+        (None, None) => None,
+    }
+}
+
+/// The flags encoded as `CARGO_ENCODED_RUSTFLAGS`, which takes precedence over all the other
+/// rustflags and keeps the spaces of the paths.
+pub fn encoded_rustflags(flags: impl IntoIterator<Item = String>) -> (&'static str, String) {
+    let flags: Vec<String> = flags.into_iter().collect();
+    ("CARGO_ENCODED_RUSTFLAGS", flags.join("\x1f"))
+}
+
+pub fn cargo_home() -> Option<Utf8PathBuf> {
+    env::var("CARGO_HOME")
+        .map(Utf8PathBuf::from)
+        .ok()
+        .or_else(|| {
+            dirs::home_dir().and_then(|home| Utf8PathBuf::from_path_buf(home.join(".cargo")).ok())
+        })
+}
+
+/// the config files of cargo run in the dir, from the lowest precedence to the highest
+fn config_files(dir: &Utf8Path) -> Vec<Utf8PathBuf> {
+    let config_in = |dir: &Utf8Path| {
+        ["config.toml", "config"]
+            .iter()
+            .map(|name| dir.join(name))
+            .find(|file| file.is_file())
+    };
+    let mut files: Vec<Utf8PathBuf> = dir
+        .ancestors()
+        .filter_map(|dir| config_in(&dir.join(".cargo")))
+        .collect();
+    if let Some(home) = cargo_home().as_deref().and_then(config_in) {
+        if !files.contains(&home) {
+            files.push(home);
+        }
+    }
+    files.reverse();
+    files
+}
+
+fn flags(value: Option<&toml::Value>) -> Vec<String> {
+    match value {
+        Some(toml::Value::String(flags)) => flags.split_whitespace().map(String::from).collect(),
+        Some(toml::Value::Array(flags)) => flags
+            .iter()
+            .filter_map(|flag| flag.as_str().map(String::from))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn host_triple() -> Result<String> {
+    let output = std::process::Command::new("rustc")
+        .arg("-vV")
+        .output()
+        .context("Could not run rustc -vV for the host target")?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.strip_prefix("host: "))
+        .map(str::to_string)
+        .context("rustc -vV gave no host target")
+}
@@ -0,0 +1,64 @@
+use std::collections::BTreeMap;
+
+use schemars::{
+    gen::{SchemaGenerator, SchemaSettings},
+    schema::{RootSchema, Schema, SchemaObject, SubschemaValidation},
+    JsonSchema,
+};
+
+use super::project::{ProjectConfig, ProjectDefinition};
+
+/// The leptos metadata of a package or workspace, in the Cargo.toml or a Leptos.toml file
+#[derive(JsonSchema)]
+#[serde(untagged)]
+#[allow(dead_code, clippy::large_enum_variant)] // only the schema of these types is used
+enum LeptosMetadata {
+    /// `[package.metadata.leptos]`, or the Leptos.toml of a package
+    Package(PackageMetadata),
+    /// `[[workspace.metadata.leptos]]`
+    Workspace(Vec<WorkspaceProject>),
+    /// the Leptos.toml of a workspace root, with its `[[project]]` list
+    WorkspaceFile { project: Vec<WorkspaceProject> },
+}
+
+#[derive(JsonSchema)]
+#[allow(dead_code)]
+struct PackageMetadata {
+    #[serde(flatten)]
+    config: ProjectConfig,
+    /// the overrides of the config selected with `--env <name>`, by name
+    #[serde(default)]
+    env: BTreeMap<String, ProjectConfig>,
+    /// the overrides of the config for a profile, `release` or `dev`, or a `bin-target-triple`
+    #[serde(default)]
+    overrides: BTreeMap<String, ProjectConfig>,
+}
+
+#[derive(JsonSchema)]
+#[allow(dead_code)]
+struct WorkspaceProject {
+    #[serde(flatten)]
+    definition: ProjectDefinition,
+    #[serde(flatten)]
+    metadata: PackageMetadata,
+}
+
+/// The JSON Schema of the leptos metadata, for the editors to validate and complete it
+pub fn schema() -> RootSchema {
+    SchemaGenerator::new(SchemaSettings::draft07()).into_root_schema_for::<LeptosMetadata>()
+}
+
+/// the schema of the values that are either a single `T` or a list of them
+pub(crate) fn one_or_many_schema<T: JsonSchema>(gen: &mut SchemaGenerator) -> Schema {
+    SchemaObject {
+        subschemas: Some(Box::new(SubschemaValidation {
+            any_of: Some(vec![
+                gen.subschema_for::<T>(),
+                gen.subschema_for::<Vec<T>>(),
+            ]),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }
+    .into()
+}
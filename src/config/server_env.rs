@@ -0,0 +1,54 @@
+use std::collections::BTreeMap;
+
+use crate::ext::anyhow::{anyhow, bail, Result};
+
+/// The env vars of the server process in serve and watch mode, from the
+/// `[package.metadata.leptos.server-env]` table. A `${NAME}` in a value is replaced by the var
+/// NAME, either one of the `envs` of the project or one of the environment of cargo-leptos.
+pub fn interpolate_server_env(
+    config: &BTreeMap<String, String>,
+    envs: &[(String, String)],
+) -> Result<Vec<(String, String)>> {
+    let lookup = |name: &str| {
+        envs.iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, val)| val.clone())
+            .or_else(|| std::env::var(name).ok())
+    };
+    config
+        .iter()
+        .map(|(key, value)| {
+            let value =
+                interpolate(value, lookup).map_err(|e| anyhow!("Invalid server-env {key}: {e}"))?;
+            Ok((key.clone(), value))
+        })
+        .collect()
+}
+
+/// replaces the `${NAME}`s of the value, with `$$` for a literal `$`
+fn interpolate(value: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<String> {
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find('$') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+        if let Some(after) = rest.strip_prefix('$') {
+            out.push('$');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('{') {
+            let Some(end) = after.find('}') else {
+                bail!("unclosed ${{ in {value:?}");
+            };
+            let name = &after[..end];
+            match lookup(name) {
+                Some(val) => out.push_str(&val),
+                None => bail!("the env var {name} is not set"),
+            }
+            rest = &after[end + 1..];
+        } else {
+            out.push('$');
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
@@ -0,0 +1,153 @@
+use std::str::FromStr;
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use crate::ext::anyhow::{bail, ensure, Result};
+
+use super::ProjectConfig;
+
+/// what the robots.txt tells the crawlers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum RobotsMode {
+    /// the site may be indexed, but for the robots-disallow paths
+    Allow,
+    /// nothing may be indexed, such as for a staging site
+    DisallowAll,
+}
+
+impl FromStr for RobotsMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "allow" => Ok(Self::Allow),
+            "disallow-all" => Ok(Self::DisallowAll),
+            _ => bail!("Unknown robots-txt mode {s:?}, expected \"allow\" or \"disallow-all\""),
+        }
+    }
+}
+
+/// the robots.txt written into the site root
+#[derive(Debug)]
+pub struct RobotsTxt {
+    pub mode: RobotsMode,
+    /// the paths the crawlers may not index, when allowed
+    pub disallow: Vec<String>,
+    /// the url of the sitemap.xml, when the build writes one
+    pub sitemap: Option<String>,
+}
+
+impl RobotsTxt {
+    pub fn resolve(config: &ProjectConfig, release: bool) -> Option<Self> {
+        let mode = config.robots_txt?;
+        let sitemap = config
+            .sitemap_base_url
+            .as_ref()
+            .filter(|_| release && !config.csr)
+            .map(|url| {
+                format!(
+                    "{}{}/sitemap.xml",
+                    url.trim_end_matches('/'),
+                    config.base_url
+                )
+            });
+        Some(Self {
+            mode,
+            disallow: config.robots_disallow.clone(),
+            sitemap,
+        })
+    }
+
+    pub fn contents(&self) -> String {
+        let mut lines = vec!["User-agent: *".to_string()];
+        match self.mode {
+            RobotsMode::DisallowAll => lines.push("Disallow: /".to_string()),
+            RobotsMode::Allow => {
+                lines.push("Allow: /".to_string());
+                lines.extend(self.disallow.iter().map(|path| format!("Disallow: {path}")));
+                if let Some(sitemap) = &self.sitemap {
+                    lines.push(String::new());
+                    lines.push(format!("Sitemap: {sitemap}"));
+                }
+            }
+        }
+        lines.join("\n") + "\n"
+    }
+}
+
+/// the `.well-known/security.txt` written into the site root, as of RFC 9116
+#[derive(Debug)]
+pub struct SecurityTxt {
+    /// the uris, such as `mailto:security@example.com`, to report vulnerabilities to
+    pub contact: Vec<String>,
+    /// the date and time after which the file is stale, such as `2027-01-01T00:00:00Z`
+    pub expires: String,
+    pub policy: Option<String>,
+}
+
+impl SecurityTxt {
+    pub fn resolve(config: &ProjectConfig) -> Result<Option<Self>> {
+        if config.security_contact.is_empty() {
+            return Ok(None);
+        }
+        let Some(expires) = config.security_expires.clone() else {
+            bail!(
+                "The security.txt needs a security-expires date, such as \"2027-01-01T00:00:00Z\""
+            );
+        };
+        ensure!(
+            expires.contains('T') && !expires.contains(char::is_whitespace),
+            "The security-expires {expires:?} must be an RFC 3339 date and time, such as \"2027-01-01T00:00:00Z\""
+        );
+        Ok(Some(Self {
+            contact: config.security_contact.clone(),
+            expires,
+            policy: config.security_policy.clone(),
+        }))
+    }
+
+    pub fn contents(&self) -> String {
+        let mut lines: Vec<String> = self
+            .contact
+            .iter()
+            .map(|contact| format!("Contact: {contact}"))
+            .collect();
+        lines.push(format!("Expires: {}", self.expires));
+        if let Some(policy) = &self.policy {
+            lines.push(format!("Policy: {policy}"));
+        }
+        lines.join("\n") + "\n"
+    }
+}
+
+/// the `manifest.webmanifest` and the precaching service worker `sw.js` written into the site
+/// root, for the app to be installable and to work offline
+#[derive(Debug)]
+pub struct PwaConfig {
+    pub name: String,
+    pub short_name: Option<String>,
+    pub theme_color: Option<String>,
+    pub background_color: Option<String>,
+    /// the icons of the app, relative to the site root (copied there from the assets dir)
+    pub icons: Vec<String>,
+}
+
+impl PwaConfig {
+    pub fn resolve(config: &ProjectConfig, project_name: &str) -> Option<Self> {
+        if !config.pwa {
+            return None;
+        }
+        Some(Self {
+            name: config
+                .pwa_name
+                .clone()
+                .unwrap_or_else(|| project_name.to_string()),
+            short_name: config.pwa_short_name.clone(),
+            theme_color: config.pwa_theme_color.clone(),
+            background_color: config.pwa_background_color.clone(),
+            icons: config.pwa_icons.clone(),
+        })
+    }
+}
@@ -1,17 +1,192 @@
 use super::{ProjectConfig, TailwindConfig};
-use crate::service::site::{SiteFile, SourcedSiteFile};
-use anyhow::Result;
+use crate::{
+    ext::{
+        anyhow::{ensure, Result},
+        glob_set, PathBufExt, PathExt,
+    },
+    service::site::{SiteFile, SourcedSiteFile},
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use cargo_metadata::Metadata;
+use globset::GlobSet;
+use schemars::JsonSchema;
+use serde::Deserialize;
 
 #[derive(Debug, Clone)]
 pub struct StyleConfig {
-    pub file: Option<SourcedSiteFile>,
+    /// the style files, in the order their css is added to the stylesheet
+    pub files: Vec<SourcedSiteFile>,
     pub browserquery: String,
     pub tailwind: Option<TailwindConfig>,
+    pub modules: Option<CssModulesConfig>,
+    pub collector: Option<StyleCollectorConfig>,
+    /// purging the unused css in release builds, when enabled
+    pub purge: Option<PurgeConfig>,
+    /// the `--load-path`s given to sass
+    pub sass_load_paths: Vec<Utf8PathBuf>,
+    /// extra arguments given to sass
+    pub sass_args: Vec<String>,
+    /// the command the compiled css is piped through before lightningcss
+    pub postcss: Option<String>,
     pub site_file: SiteFile,
 }
 
+#[derive(Debug, Clone)]
+pub struct CssModulesConfig {
+    /// the dir with the `*.module.css`, `*.module.scss` and `*.module.sass` files
+    pub dir: Utf8PathBuf,
+    /// the generated rust module with the hashed class names
+    pub rust_file: Utf8PathBuf,
+    /// the compiled css of all the modules, added to the stylesheet
+    pub css_file: Utf8PathBuf,
+}
+
+#[derive(Clone)]
+pub struct PurgeConfig {
+    /// glob patterns of the class names that are kept even when not found in the sources
+    pub safelist: Vec<String>,
+    safelist_set: GlobSet,
+}
+
+impl PurgeConfig {
+    fn new(config: &ProjectConfig) -> Result<Option<Self>> {
+        if !config.css_purge {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            safelist: config.css_purge_safelist.clone(),
+            safelist_set: glob_set(&config.css_purge_safelist)?,
+        }))
+    }
+
+    pub fn is_safelisted(&self, class: &str) -> bool {
+        self.safelist_set.is_match(class)
+    }
+}
+
+impl std::fmt::Debug for PurgeConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PurgeConfig")
+            .field("safelist", &self.safelist)
+            .finish()
+    }
+}
+
+/// the css-in-rust crates whose collected css is added to the stylesheet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum StyleCollector {
+    /// the `stylance` cli bundles the css modules of the crates into their `output_file`
+    Stylance,
+    /// the `style!` macros write the css to `target/stylers/main.css` when compiling
+    Stylers,
+}
+
+#[derive(Debug, Clone)]
+pub struct StyleCollectorConfig {
+    pub kind: StyleCollector,
+    /// the css files written by the collector, added to the stylesheet
+    pub files: Vec<Utf8PathBuf>,
+    /// the dirs of the crates using stylance, which the stylance cli is run for
+    pub crates: Vec<Utf8PathBuf>,
+    /// the dirs with the stylance css modules
+    pub sources: Vec<Utf8PathBuf>,
+    /// the file extensions of the stylance css modules
+    pub extensions: Vec<String>,
+}
+
+/// the `[package.metadata.stylance]` table
+#[derive(Debug, Deserialize)]
+struct StylanceMetadata {
+    output_file: Option<Utf8PathBuf>,
+    folders: Option<Vec<Utf8PathBuf>>,
+    extensions: Option<Vec<String>>,
+}
+
+impl StyleCollectorConfig {
+    fn new(config: &ProjectConfig, metadata: &Metadata) -> Result<Option<Self>> {
+        let Some(kind) = config.style_collector else {
+            return Ok(None);
+        };
+        // relative to the configuration file
+        let file = config
+            .style_collector_file
+            .as_ref()
+            .map(|file| config.config_dir.join(file));
+
+        if kind == StyleCollector::Stylers {
+            return Ok(Some(Self {
+                kind,
+                // stylers writes it relative to the dir cargo is run in
+                files: vec![file.unwrap_or_else(|| Utf8PathBuf::from("target/stylers/main.css"))],
+                crates: Vec::new(),
+                sources: Vec::new(),
+                extensions: Vec::new(),
+            }));
+        }
+
+        let mut collector = Self {
+            kind,
+            files: file.into_iter().collect(),
+            crates: Vec::new(),
+            sources: Vec::new(),
+            extensions: Vec::new(),
+        };
+        let using_stylance = metadata.workspace_packages().into_iter().filter(|pkg| {
+            pkg.dependencies.iter().any(|dep| dep.name == "stylance")
+                || pkg.metadata.get("stylance").is_some()
+        });
+        for pkg in using_stylance {
+            let dir = pkg
+                .manifest_path
+                .clone()
+                .without_last()
+                .unbase(&metadata.workspace_root)?;
+            let stylance: StylanceMetadata = match pkg.metadata.get("stylance") {
+                Some(table) => serde_json::from_value(table.clone())?,
+                None => StylanceMetadata {
+                    output_file: None,
+                    folders: None,
+                    extensions: None,
+                },
+            };
+            if config.style_collector_file.is_none() {
+                collector
+                    .files
+                    .extend(stylance.output_file.map(|file| dir.join(relative(&file))));
+            }
+            let folders = stylance
+                .folders
+                .unwrap_or_else(|| vec![Utf8PathBuf::from("src")]);
+            collector
+                .sources
+                .extend(folders.iter().map(|folder| dir.join(relative(folder))));
+            for ext in stylance
+                .extensions
+                .unwrap_or_else(|| vec![".module.css".to_string(), ".module.scss".to_string()])
+            {
+                if !collector.extensions.contains(&ext) {
+                    collector.extensions.push(ext);
+                }
+            }
+            collector.crates.push(dir);
+        }
+        ensure!(
+            !collector.files.is_empty(),
+            "No stylance output file found. Set the output_file in [package.metadata.stylance] or the style-collector-file"
+        );
+        Ok(Some(collector))
+    }
+}
+
+/// stylance's paths are written as `./src/`
+fn relative(path: &Utf8Path) -> &Utf8Path {
+    path.strip_prefix("./").unwrap_or(path)
+}
+
 impl StyleConfig {
-    pub fn new(config: &ProjectConfig) -> Result<Self> {
+    pub fn new(config: &ProjectConfig, metadata: &Metadata) -> Result<Self> {
+        let target_dir = &metadata.target_directory;
         let site_rel = config
             .site_pkg_dir
             .join(&config.output_name)
@@ -21,20 +196,42 @@ impl StyleConfig {
             dest: config.site_root.join(&site_rel),
             site: site_rel,
         };
-        let style_file = config.style_file.as_ref().map(|file| {
-            // relative to the configuration file
-            let source = config.config_dir.join(file);
-            let site = config
-                .site_pkg_dir
-                .join(&config.output_name)
-                .with_extension("css");
-            let dest = config.site_root.join(&site);
-            SourcedSiteFile { source, dest, site }
-        });
+        let files = config
+            .style_file
+            .iter()
+            .map(|file| SourcedSiteFile {
+                // relative to the configuration file
+                source: config.config_dir.join(file),
+                dest: site_file.dest.clone(),
+                site: site_file.site.clone(),
+            })
+            .collect();
         Ok(Self {
-            file: style_file,
+            files,
             browserquery: config.browserquery.clone(),
             tailwind: TailwindConfig::new(config)?,
+            modules: config.css_modules_dir.as_ref().map(|dir| {
+                let generated = target_dir.join("css-modules").join(&config.output_name);
+                CssModulesConfig {
+                    // relative to the configuration file
+                    dir: config.config_dir.join(dir),
+                    rust_file: generated.with_extension("rs"),
+                    css_file: generated.with_extension("css"),
+                }
+            }),
+            collector: StyleCollectorConfig::new(config, metadata)?,
+            purge: PurgeConfig::new(config)?,
+            sass_load_paths: config
+                .sass_load_paths
+                .iter()
+                // relative to the configuration file
+                .map(|dir| config.config_dir.join(dir))
+                .collect(),
+            sass_args: config.sass_args.clone(),
+            postcss: match &config.postcss_cmd {
+                Some(cmd) => Some(cmd.clone()),
+                None => config.postcss.then(|| "npx postcss".to_string()),
+            },
             site_file,
         })
     }
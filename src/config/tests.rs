@@ -6,7 +6,8 @@ fn opts(project: Option<&str>) -> crate::config::Opts {
         js_minify: false,
         precompress: false,
         hot_reload: false,
-        project: project.map(|s| s.to_string()),
+        project: project.map(|s| s.to_string()).into_iter().collect(),
+        tag: Vec::new(),
         verbose: 0,
         features: Vec::new(),
         bin_features: Vec::new(),
@@ -14,6 +15,20 @@ fn opts(project: Option<&str>) -> crate::config::Opts {
         bin_cargo_args: None,
         lib_cargo_args: None,
         wasm_debug: false,
+        timings: false,
+        reproducible: false,
+        size_report: None,
+        size_baseline: None,
+        tls: false,
+        env: None,
+        addr: None,
+        host: None,
+        port: None,
+        reload_port: None,
+        port_fallback: false,
+        gateway: None,
+        all: false,
+        non_interactive: false,
     }
 }
 
@@ -84,3 +99,984 @@ fn test_workspace_bin_args_project2() {
 
     insta::assert_debug_snapshot!(conf);
 }
+
+#[test]
+fn test_workspace_project_patterns() {
+    let mut cli = opts(None);
+    cli.project = vec!["project2".to_string(), "proj*1".to_string()];
+
+    let conf = Config::test_load(cli, "examples", "examples/workspace/Cargo.toml", true, None);
+
+    let names: Vec<_> = conf.projects.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(names, ["project1", "project2"]);
+}
+
+#[test]
+fn test_workspace_env_project1() {
+    let mut cli = opts(Some("project1"));
+    cli.env = Some("staging".to_string());
+
+    let conf = Config::test_load(cli, "examples", "examples/workspace/Cargo.toml", true, None);
+
+    let proj = conf.current_project().unwrap();
+    assert_eq!(proj.site.addr.to_string(), "127.0.0.1:8080");
+    assert_eq!(proj.site.root_dir, "target/site/project1");
+}
+
+#[test]
+fn test_workspace_port_overrides_project1() {
+    let mut cli = opts(Some("project1"));
+    cli.port = Some(4300);
+    cli.reload_port = Some(4301);
+
+    let conf = Config::test_load(cli, "examples", "examples/workspace/Cargo.toml", true, None);
+
+    let proj = conf.current_project().unwrap();
+    assert_eq!(proj.site.addr.to_string(), "127.0.0.1:4300");
+    assert_eq!(proj.site.reload.port(), 4301);
+}
+
+#[test]
+fn test_export_config() {
+    let dir = temp_package(
+        r#"
+        export-routes = ["/", "/about"]
+        "#,
+    );
+    let conf = load_temp_package(&dir);
+    assert_eq!(conf.projects[0].export_routes, ["/", "/about"]);
+    assert_eq!(conf.projects[0].export_dir, "target/export/app");
+
+    // the root by default
+    let dir = temp_package("");
+    let conf = load_temp_package(&dir);
+    assert_eq!(conf.projects[0].export_routes, ["/"]);
+    assert!(!conf.projects[0].static_host_files);
+}
+
+#[test]
+fn test_prerender_routes() {
+    let dir = temp_package(
+        r#"
+        prerender-routes = ["/", "/about", "/blog/*"]
+        "#,
+    );
+    let conf = load_temp_package(&dir);
+    assert_eq!(
+        conf.projects[0].prerender_routes,
+        ["/", "/about", "/blog/*"]
+    );
+
+    // a csr site has no server to render the pages
+    let dir = temp_package(
+        r#"
+        csr = true
+        prerender-routes = ["/about"]
+        "#,
+    );
+    let conf = load_temp_package(&dir);
+    assert!(conf.projects[0].prerender_routes.is_empty());
+}
+
+#[test]
+fn test_fallback_route() {
+    let dir = temp_package(r#"fallback-route = "/404""#);
+    let conf = load_temp_package(&dir);
+    assert_eq!(conf.projects[0].fallback_route.as_deref(), Some("/404"));
+
+    // a csr site has no server to render it
+    let dir = temp_package(
+        r#"
+        csr = true
+        fallback-route = "/404"
+        "#,
+    );
+    let conf = load_temp_package(&dir);
+    assert_eq!(conf.projects[0].fallback_route, None);
+}
+
+#[test]
+fn test_sitemap_config() {
+    let dir = temp_package(
+        r#"
+        sitemap-base-url = "https://example.com/"
+        sitemap-exclude = ["/admin/*"]
+        "#,
+    );
+    let root = camino::Utf8Path::from_path(dir.path()).unwrap();
+    let mut cli = opts(None);
+    cli.release = true;
+    let conf = Config::test_load(
+        cli,
+        root.as_str(),
+        root.join("Cargo.toml").as_str(),
+        false,
+        None,
+    );
+    let proj = &conf.projects[0];
+    assert_eq!(
+        proj.sitemap_base_url.as_deref(),
+        Some("https://example.com/")
+    );
+    assert_eq!(proj.sitemap_exclude, ["/admin/*"]);
+
+    // only written by the release builds
+    let conf = load_temp_package(&dir);
+    assert_eq!(conf.projects[0].sitemap_base_url, None);
+}
+
+#[test]
+fn test_robots_and_security_txt() {
+    let dir = temp_package(
+        r#"
+        robots-txt = "allow"
+        robots-disallow = ["/admin"]
+        sitemap-base-url = "https://example.com"
+        security-contact = ["mailto:security@example.com"]
+        security-expires = "2027-01-01T00:00:00Z"
+
+        [package.metadata.leptos.env.staging]
+        robots-txt = "disallow-all"
+        "#,
+    );
+    let root = camino::Utf8Path::from_path(dir.path()).unwrap();
+    let mut cli = opts(None);
+    cli.release = true;
+    let conf = Config::test_load(
+        cli.clone(),
+        root.as_str(),
+        root.join("Cargo.toml").as_str(),
+        false,
+        None,
+    );
+    let proj = &conf.projects[0];
+    assert_eq!(
+        proj.robots_txt.as_ref().unwrap().contents(),
+        "User-agent: *\nAllow: /\nDisallow: /admin\n\nSitemap: https://example.com/sitemap.xml\n"
+    );
+    assert_eq!(
+        proj.security_txt.as_ref().unwrap().contents(),
+        "Contact: mailto:security@example.com\nExpires: 2027-01-01T00:00:00Z\n"
+    );
+
+    cli.env = Some("staging".to_string());
+    let conf = Config::test_load(
+        cli,
+        root.as_str(),
+        root.join("Cargo.toml").as_str(),
+        false,
+        None,
+    );
+    assert_eq!(
+        conf.projects[0].robots_txt.as_ref().unwrap().contents(),
+        "User-agent: *\nDisallow: /\n"
+    );
+
+    // the expiry date is required
+    let dir = temp_package(r#"security-contact = ["mailto:security@example.com"]"#);
+    let root = camino::Utf8Path::from_path(dir.path()).unwrap();
+    assert!(Config::load(opts(None), root, &root.join("Cargo.toml"), false, None).is_err());
+}
+
+#[test]
+fn test_end2end_envs() {
+    let mut cli = opts(Some("project1"));
+    cli.port = Some(4300);
+    cli.reload_port = Some(4301);
+
+    let conf = Config::test_load(
+        cli,
+        "examples",
+        "examples/workspace/Cargo.toml",
+        false,
+        None,
+    );
+
+    let proj = conf.current_project().unwrap();
+    let envs = proj.end2end_envs();
+    let env = |name: &str| {
+        envs.iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, val)| val.as_str())
+    };
+    assert_eq!(env("E2E_BASE_URL"), Some("http://127.0.0.1:4300"));
+    assert_eq!(env("LEPTOS_SITE_ADDR"), Some("127.0.0.1:4300"));
+    assert_eq!(env("LEPTOS_RELOAD_PORT"), Some("4301"));
+    let root = env("LEPTOS_SITE_ROOT").unwrap();
+    assert!(camino::Utf8Path::new(root).is_absolute());
+    assert!(root.ends_with("project1"), "{root}");
+}
+
+#[test]
+fn test_dotenvs_precedence() {
+    let dir = temp_dir::TempDir::new().unwrap();
+    let dir = camino::Utf8Path::from_path(dir.path()).unwrap();
+    std::fs::write(dir.join(".env"), "A=env\nB=env\nC=env\nD=env").unwrap();
+    std::fs::write(dir.join(".env.local"), "B=local\nC=local\nD=local").unwrap();
+    std::fs::write(dir.join(".env.staging"), "C=staging\nD=staging").unwrap();
+    std::fs::write(dir.join(".env.staging.local"), "D=staging.local").unwrap();
+
+    let vars = super::dotenvs::load_dotenvs(dir, Some("staging"))
+        .unwrap()
+        .unwrap();
+    let last = |key: &str| {
+        vars.iter()
+            .rev()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    };
+    assert_eq!(last("A"), Some("env"));
+    assert_eq!(last("B"), Some("local"));
+    assert_eq!(last("C"), Some("staging"));
+    assert_eq!(last("D"), Some("staging.local"));
+
+    let vars = super::dotenvs::load_dotenvs(dir, None).unwrap().unwrap();
+    assert!(!vars.iter().any(|(_, v)| v.starts_with("staging")));
+}
+
+#[test]
+fn test_dotenv_debounce_assets() {
+    let mut conf: super::ProjectConfig =
+        serde_json::from_value(serde_json::json!({ "watch-debounce-ms": 100 })).unwrap();
+    let dotenvs = vec![(
+        "LEPTOS_WATCH_DEBOUNCE_ASSETS_MS".to_string(),
+        "750".to_string(),
+    )];
+    super::dotenvs::overlay_env(&mut conf, Some(dotenvs)).unwrap();
+    assert_eq!(conf.watch_debounce_ms, 100);
+    assert_eq!(conf.watch_debounce_assets_ms, Some(750));
+}
+
+/// a single-package project in a temp dir, with the leptos metadata
+fn temp_package(metadata: &str) -> temp_dir::TempDir {
+    let dir = temp_dir::TempDir::new().unwrap();
+    let root = camino::Utf8Path::from_path(dir.path()).unwrap();
+    std::fs::create_dir_all(root.join("src")).unwrap();
+    std::fs::write(
+        root.join("Cargo.toml"),
+        format!(
+            r#"[package]
+name = "app"
+version = "0.1.0"
+edition = "2021"
+
+[lib]
+crate-type = ["cdylib", "rlib"]
+
+[package.metadata.leptos]
+{metadata}
+"#
+        ),
+    )
+    .unwrap();
+    std::fs::write(root.join("src/lib.rs"), "").unwrap();
+    std::fs::write(root.join("src/main.rs"), "fn main() {}").unwrap();
+    dir
+}
+
+fn load_temp_package(dir: &temp_dir::TempDir) -> Config {
+    let root = camino::Utf8Path::from_path(dir.path()).unwrap();
+    Config::test_load(
+        opts(None),
+        root.as_str(),
+        root.join("Cargo.toml").as_str(),
+        true,
+        None,
+    )
+}
+
+#[test]
+fn test_embed_site() {
+    let dir = temp_package("embed-site = true");
+    let conf = load_temp_package(&dir);
+    let proj = &conf.projects[0];
+    let file = proj.embed_file.as_ref().unwrap();
+    assert!(file.ends_with("leptos-embed/app.rs"));
+    // given to the server build, but not to the server
+    assert!(proj
+        .to_envs()
+        .contains(&("LEPTOS_EMBED_FILE", file.to_string())));
+    assert!(!proj
+        .bundle_envs()
+        .iter()
+        .any(|(name, _)| *name == "LEPTOS_EMBED_FILE"));
+
+    // a csr site has no server to embed it into
+    let dir = temp_package(
+        r#"
+        csr = true
+        embed-site = true
+        "#,
+    );
+    let conf = load_temp_package(&dir);
+    assert_eq!(conf.projects[0].embed_file, None);
+}
+
+#[test]
+fn test_wasm_server() {
+    let dir = temp_package(
+        r#"
+        bin-target-triple = "wasm32-wasip1"
+        bin-runner = "wasmtime serve"
+        "#,
+    );
+    let conf = load_temp_package(&dir);
+    // the cdylib of the package is the component run by the runtime
+    let bin = conf.projects[0].bin.as_ref().unwrap();
+    assert!(bin.is_wasm());
+    assert!(bin.wasm_lib);
+    assert!(bin.exe_file.ends_with("wasm32-wasip1/debug/app.wasm"));
+    assert_eq!(bin.runner.as_deref(), Some("wasmtime serve"));
+
+    // a bin target is built as a wasi command
+    let dir = temp_package(
+        r#"
+        bin-target-triple = "wasm32-wasip1"
+        bin-target = "app"
+        "#,
+    );
+    let conf = load_temp_package(&dir);
+    let bin = conf.projects[0].bin.as_ref().unwrap();
+    assert!(bin.is_wasm());
+    assert!(!bin.wasm_lib);
+    assert_eq!(bin.target, "app");
+}
+
+#[test]
+fn test_leptos_toml() {
+    let dir = temp_package(r#"site-addr = "127.0.0.1:3000""#);
+    std::fs::write(
+        dir.path().join("Leptos.toml"),
+        r#"site-addr = "127.0.0.1:4000""#,
+    )
+    .unwrap();
+
+    let proj = load_temp_package(&dir).current_project().unwrap();
+    assert_eq!(proj.site.addr.to_string(), "127.0.0.1:4000");
+}
+
+#[test]
+fn test_site_addrs() {
+    let dir = temp_package(r#"site-addr = ["127.0.0.1:4310", "[::1]:4310", "0.0.0.0:4311"]"#);
+    let proj = load_temp_package(&dir).current_project().unwrap();
+    let envs = proj.to_envs();
+    let env = |name: &str| {
+        envs.iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, val)| val.as_str())
+    };
+    // leptos parses LEPTOS_SITE_ADDR as a single address
+    assert_eq!(env("LEPTOS_SITE_ADDR"), Some("127.0.0.1:4310"));
+    assert_eq!(env("LEPTOS_SITE_ADDRS"), Some("[::1]:4310,0.0.0.0:4311"));
+    let envs = proj.end2end_envs();
+    let e2e_env = |name: &str| {
+        envs.iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, val)| val.as_str())
+    };
+    assert_eq!(e2e_env("LEPTOS_SITE_ADDR"), Some("127.0.0.1:4310"));
+    assert_eq!(
+        e2e_env("LEPTOS_SITE_ADDRS"),
+        Some("[::1]:4310,0.0.0.0:4311")
+    );
+
+    let dir = temp_package(r#"site-addr = "127.0.0.1:4310""#);
+    let proj = load_temp_package(&dir).current_project().unwrap();
+    assert!(!proj
+        .to_envs()
+        .iter()
+        .any(|(name, _)| *name == "LEPTOS_SITE_ADDRS"));
+}
+
+#[test]
+fn test_reload_external_port() {
+    let dir = temp_package("reload-external-port = 8001");
+    let proj = load_temp_package(&dir).current_project().unwrap();
+    // the port the client of leptos and the one of cargo-leptos connect to
+    assert_eq!(proj.site.reload_port(), 3001);
+    assert_eq!(proj.site.external_reload_port(), 8001);
+    assert!(proj
+        .to_envs()
+        .contains(&("LEPTOS_RELOAD_EXTERNAL_PORT", "8001".to_string())));
+}
+
+#[test]
+fn test_base_url() {
+    let dir = temp_package(r#"base-url = "/myapp/""#);
+
+    let proj = load_temp_package(&dir).current_project().unwrap();
+    assert_eq!(proj.site.url(), "http://127.0.0.1:3000/myapp");
+    assert_eq!(proj.site.url_of("pkg/app.js".into()), "/myapp/pkg/app.js");
+    assert!(proj
+        .to_envs()
+        .contains(&("CDN_PKG_PATH", "/myapp/pkg".to_string())));
+}
+
+#[test]
+fn test_asset_url_prefix() {
+    let dir = temp_package(r#"asset-url-prefix = "https://cdn.example.com/site/""#);
+    let root = camino::Utf8Path::from_path(dir.path()).unwrap();
+    let mut cli = opts(None);
+    cli.release = true;
+
+    let conf = Config::test_load(
+        cli,
+        root.as_str(),
+        root.join("Cargo.toml").as_str(),
+        false,
+        None,
+    );
+
+    let proj = conf.current_project().unwrap();
+    assert_eq!(
+        proj.site.asset_url("pkg/app.js".into()),
+        "https://cdn.example.com/site/pkg/app.js"
+    );
+    assert!(proj.to_envs().contains(&(
+        "CDN_PKG_PATH",
+        "https://cdn.example.com/site/pkg".to_string()
+    )));
+
+    let dev = load_temp_package(&dir).current_project().unwrap();
+    assert_eq!(dev.site.asset_url("pkg/app.js".into()), "/pkg/app.js");
+}
+
+#[test]
+fn test_schema() {
+    let schema = serde_json::to_value(super::schema()).unwrap();
+    let definitions = &schema["definitions"];
+
+    let config = &definitions["ProjectConfig"]["properties"];
+    assert!(config["site-addr"]["anyOf"].is_array());
+    assert!(config.get("config-dir").is_none());
+
+    let package = &definitions["PackageMetadata"]["properties"];
+    assert!(package.get("output-name").is_some());
+    assert_eq!(
+        package["env"]["additionalProperties"]["$ref"],
+        "#/definitions/ProjectConfig"
+    );
+
+    let project = &definitions["WorkspaceProject"];
+    assert!(project["required"]
+        .as_array()
+        .unwrap()
+        .contains(&"lib-package".into()));
+}
+
+#[test]
+fn test_server_env_interpolation() {
+    let config = [
+        ("API_URL", "http://${LEPTOS_SITE_ADDR}/api"),
+        ("PRICE", "$$5 ${LEPTOS_OUTPUT_NAME}"),
+    ]
+    .into_iter()
+    .map(|(key, val)| (key.to_string(), val.to_string()))
+    .collect();
+    let envs = [
+        ("LEPTOS_SITE_ADDR", "127.0.0.1:3000"),
+        ("LEPTOS_OUTPUT_NAME", "app"),
+    ]
+    .map(|(key, val)| (key.to_string(), val.to_string()));
+
+    let resolved = super::interpolate_server_env(&config, &envs).unwrap();
+    assert_eq!(
+        resolved,
+        [
+            (
+                "API_URL".to_string(),
+                "http://127.0.0.1:3000/api".to_string()
+            ),
+            ("PRICE".to_string(), "$5 app".to_string()),
+        ]
+    );
+
+    let config = [("URL".to_string(), "${CARGO_LEPTOS_UNSET_VAR}".to_string())].into();
+    assert!(super::interpolate_server_env(&config, &envs).is_err());
+}
+
+#[test]
+fn test_end2end_cmd_args() {
+    use super::{Cli, Commands};
+    use clap::Parser;
+
+    let cli = Cli::parse_from([
+        "cargo-leptos",
+        "end-to-end",
+        "--release",
+        "--",
+        "--headed",
+        "--grep",
+        "login",
+    ]);
+    let opts = cli.opts().unwrap();
+    assert!(opts.release);
+    // served at the configured port
+    assert_eq!(opts.port, None);
+    let Commands::EndToEnd(e2e) = cli.command else {
+        panic!("not end-to-end")
+    };
+    assert_eq!(e2e.cmd_args, ["--headed", "--grep", "login"]);
+}
+
+#[test]
+fn test_end2end_random_port() {
+    use super::Cli;
+    use clap::Parser;
+
+    let cli = Cli::parse_from(["cargo-leptos", "end-to-end", "--random-port"]);
+    assert_eq!(cli.opts().unwrap().port, Some(0));
+    // a given port wins
+    let cli = Cli::parse_from([
+        "cargo-leptos",
+        "end-to-end",
+        "--random-port",
+        "--port",
+        "3001",
+    ]);
+    assert_eq!(cli.opts().unwrap().port, Some(3001));
+}
+
+#[test]
+fn test_end2end_release() {
+    use super::Cli;
+    use clap::Parser;
+
+    let cli = Cli::parse_from([
+        "cargo-leptos",
+        "end-to-end",
+        "--release",
+        "--project",
+        "project1",
+    ]);
+    let conf = Config::test_load(
+        cli.opts().unwrap(),
+        "examples",
+        "examples/workspace/Cargo.toml",
+        false,
+        None,
+    );
+
+    // the optimized build, with the artifacts of the deployment
+    let proj = conf.current_project().unwrap();
+    assert!(proj.release);
+    assert!(proj.js_minify);
+    assert!(proj
+        .bin
+        .as_ref()
+        .unwrap()
+        .exe_file
+        .as_str()
+        .contains("release"));
+}
+
+#[test]
+fn test_bundle() {
+    use super::Cli;
+    use clap::Parser;
+
+    // the bundle is always of the release build
+    let cli = Cli::parse_from(["cargo-leptos", "bundle", "--project", "project1"]);
+    let conf = Config::test_load(
+        cli.opts().unwrap(),
+        "examples",
+        "examples/workspace/Cargo.toml",
+        false,
+        None,
+    );
+    let proj = conf.current_project().unwrap();
+    assert!(proj.release);
+
+    // the site root is next to the server, which runs in production
+    let envs = proj.bundle_envs();
+    let env = |name| {
+        envs.iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| v.as_str())
+    };
+    assert_eq!(env("LEPTOS_SITE_ROOT"), Some("site"));
+    assert_eq!(env("LEPTOS_ENV"), Some("PROD"));
+    assert_eq!(env("LEPTOS_LIB_DIR"), None);
+    assert!(env("LEPTOS_SITE_ADDR").is_some());
+
+    // run.sh gives leptos a single address, the server binds the others
+    let dir = temp_package(r#"site-addr = ["127.0.0.1:4320", "[::1]:4320"]"#);
+    let proj = load_temp_package(&dir).current_project().unwrap();
+    let envs = proj.bundle_envs();
+    assert!(envs.contains(&("LEPTOS_SITE_ADDR", "127.0.0.1:4320".to_string())));
+    assert!(envs.contains(&("LEPTOS_SITE_ADDRS", "[::1]:4320".to_string())));
+
+    let cli = Cli::parse_from([
+        "cargo-leptos",
+        "bundle",
+        "--systemd",
+        "--install-dir",
+        "/srv/app",
+    ]);
+    let super::Commands::Bundle(opts) = cli.command else {
+        panic!("not the bundle command");
+    };
+    assert!(opts.systemd);
+    assert_eq!(opts.install_dir.as_deref(), Some("/srv/app".into()));
+}
+
+#[test]
+fn test_workspace_unique_reload_ports() {
+    use camino::Utf8Path;
+
+    let manifest = Utf8Path::new("examples/workspace/Cargo.toml")
+        .canonicalize_utf8()
+        .unwrap();
+    let cwd = Utf8Path::new("examples").canonicalize_utf8().unwrap();
+    // run one at a time, the projects may share their ports
+    assert!(Config::load(opts(None), &cwd, &manifest, true, None).is_ok());
+
+    let mut cli = opts(None);
+    cli.all = true;
+    assert!(Config::load(cli, &cwd, &manifest, true, None).is_err());
+
+    let mut cli = opts(None);
+    cli.all = true;
+    cli.env = Some("staging".to_string());
+    let conf = Config::test_load(cli, "examples", "examples/workspace/Cargo.toml", true, None);
+    assert_eq!(conf.projects[0].site.reload.port(), 3001);
+    assert_ne!(conf.projects[1].site.reload.port(), 3001);
+}
+
+#[test]
+fn test_front_target_dir() {
+    let dir = temp_package("separate-front-target-dir = false");
+    let proj = load_temp_package(&dir).current_project().unwrap();
+    assert_eq!(proj.lib.front_target_path, proj.target_dir);
+
+    let dir = temp_package(r#"front-target-dir = "front-build""#);
+    let proj = load_temp_package(&dir).current_project().unwrap();
+    assert!(proj.lib.front_target_path.ends_with("front-build"));
+    assert!(proj
+        .lib
+        .wasm_file
+        .source
+        .starts_with("front-build/wasm32-unknown-unknown"));
+}
+
+/// a workspace of csr projects in a temp dir, with the packages `a`, `b` and `c`
+fn temp_workspace(metadata: &str) -> temp_dir::TempDir {
+    let dir = temp_dir::TempDir::new().unwrap();
+    let root = camino::Utf8Path::from_path(dir.path()).unwrap();
+    std::fs::write(
+        root.join("Cargo.toml"),
+        format!("[workspace]\nmembers = [\"a\", \"b\", \"c\"]\nresolver = \"2\"\n\n{metadata}"),
+    )
+    .unwrap();
+    for name in ["a", "b", "c"] {
+        std::fs::create_dir_all(root.join(name).join("src")).unwrap();
+        std::fs::write(
+            root.join(name).join("Cargo.toml"),
+            format!(
+                "[package]\nname = \"{name}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[lib]\ncrate-type = [\"cdylib\", \"rlib\"]\n"
+            ),
+        )
+        .unwrap();
+        std::fs::write(root.join(name).join("src/lib.rs"), "").unwrap();
+    }
+    dir
+}
+
+fn load_temp_workspace(dir: &temp_dir::TempDir) -> crate::ext::anyhow::Result<Config> {
+    let root = camino::Utf8Path::from_path(dir.path())
+        .unwrap()
+        .canonicalize_utf8()
+        .unwrap();
+    Config::load(opts(None), &root, &root.join("Cargo.toml"), false, None)
+}
+
+#[test]
+fn test_workspace_depends_on() {
+    let project = |name: &str, depends_on: &str| {
+        format!(
+            "[[workspace.metadata.leptos]]\nname = \"{name}\"\nlib-package = \"{name}\"\ncsr = true\ndepends-on = [{depends_on}]\n\n"
+        )
+    };
+    let dir = temp_workspace(
+        &[
+            project("a", r#""c""#),
+            project("b", ""),
+            project("c", r#""b""#),
+        ]
+        .concat(),
+    );
+    let conf = load_temp_workspace(&dir).unwrap();
+    let names: Vec<_> = conf.projects.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(names, ["b", "c", "a"]);
+
+    let dir = temp_workspace(&[project("a", r#""b""#), project("b", r#""a""#)].concat());
+    assert!(load_temp_workspace(&dir).is_err());
+
+    let dir = temp_workspace(&project("a", r#""x""#));
+    assert!(load_temp_workspace(&dir).is_err());
+}
+
+#[test]
+fn test_overrides() {
+    let dir = temp_package(
+        r#"bin-target-triple = "aarch64-unknown-linux-gnu"
+
+[package.metadata.leptos.overrides.release]
+site-addr = "127.0.0.1:4000"
+
+[package.metadata.leptos.overrides.aarch64-unknown-linux-gnu]
+hash-files = true
+"#,
+    );
+    let root = camino::Utf8Path::from_path(dir.path()).unwrap();
+    let mut cli = opts(None);
+    cli.release = true;
+    let conf = Config::test_load(
+        cli,
+        root.as_str(),
+        root.join("Cargo.toml").as_str(),
+        false,
+        None,
+    );
+    let proj = conf.current_project().unwrap();
+    assert_eq!(proj.site.addr.to_string(), "127.0.0.1:4000");
+    assert!(proj.hash_files);
+
+    let proj = load_temp_package(&dir).current_project().unwrap();
+    assert_eq!(proj.site.addr.to_string(), "127.0.0.1:3000");
+    assert!(proj.hash_files);
+}
+
+#[test]
+fn test_workspace_tags() {
+    let project = |name: &str, tags: &str| {
+        format!(
+            "[[workspace.metadata.leptos]]\nname = \"{name}\"\nlib-package = \"{name}\"\ncsr = true\ntags = [{tags}]\n\n"
+        )
+    };
+    let dir = temp_workspace(
+        &[
+            project("a", r#""admin""#),
+            project("b", r#""customer", "frontend""#),
+            project("c", r#""customer""#),
+        ]
+        .concat(),
+    );
+    let root = camino::Utf8Path::from_path(dir.path())
+        .unwrap()
+        .canonicalize_utf8()
+        .unwrap();
+    let load = |project: &[&str], tag: &[&str]| {
+        let mut cli = opts(None);
+        cli.project = project.iter().map(|p| p.to_string()).collect();
+        cli.tag = tag.iter().map(|t| t.to_string()).collect();
+        Config::load(cli, &root, &root.join("Cargo.toml"), false, None)
+    };
+    let names = |conf: Config| {
+        conf.projects
+            .iter()
+            .map(|p| p.name.clone())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(names(load(&[], &["customer"]).unwrap()), ["b", "c"]);
+    assert_eq!(
+        names(load(&[], &["admin", "frontend"]).unwrap()),
+        ["a", "b"]
+    );
+    assert_eq!(names(load(&["c"], &["customer"]).unwrap()), ["c"]);
+    assert!(load(&["a"], &["customer"]).is_err());
+}
+
+#[test]
+fn test_extra_wasm_packages() {
+    let dir = temp_workspace(
+        r#"[[workspace.metadata.leptos]]
+name = "a"
+lib-package = "a"
+csr = true
+extra-wasm-packages = ["b"]
+"#,
+    );
+    let conf = load_temp_workspace(&dir).unwrap();
+    let lib = &conf.current_project().unwrap().lib;
+    assert_eq!(lib.extra_wasm.len(), 1);
+    assert_eq!(lib.extra_wasm[0].wasm_file.site, "pkg/b_bg.wasm");
+    assert_eq!(lib.extra_wasm[0].js_file.site, "pkg/b.js");
+    assert!(lib.src_paths.contains(&"b/src".into()));
+
+    let dir = temp_workspace(
+        r#"[[workspace.metadata.leptos]]
+name = "a"
+lib-package = "a"
+csr = true
+extra-wasm-packages = ["missing"]
+"#,
+    );
+    assert!(load_temp_workspace(&dir).is_err());
+}
+
+#[test]
+fn test_bin_example() {
+    let dir = temp_package(r#"bin-example = "demo""#);
+    std::fs::create_dir_all(dir.path().join("examples")).unwrap();
+    std::fs::write(dir.path().join("examples/demo.rs"), "fn main() {}").unwrap();
+
+    let proj = load_temp_package(&dir).current_project().unwrap();
+    let bin = proj.bin.as_ref().unwrap();
+    assert!(bin.example);
+    assert_eq!(bin.target, "demo");
+    assert!(bin.exe_file.ends_with("debug/examples/demo"));
+}
+
+#[test]
+fn test_workspace_lib_ssr_features() {
+    let dir = temp_workspace(
+        r#"[[workspace.metadata.leptos]]
+name = "app"
+bin-package = "b"
+lib-package = "a"
+bin-features = ["ssr"]
+lib-features = ["hydrate"]
+"#,
+    );
+    let root = dir.path();
+    let package = |name: &str, features: &str| {
+        format!("[package]\nname = \"{name}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[lib]\ncrate-type = [\"cdylib\", \"rlib\"]\n\n[features]\n{features}")
+    };
+    std::fs::write(root.join("b/Cargo.toml"), package("b", "ssr = []\n")).unwrap();
+    std::fs::write(root.join("b/src/main.rs"), "fn main() {}").unwrap();
+
+    std::fs::write(
+        root.join("a/Cargo.toml"),
+        package("a", "ssr = []\nhydrate = []\n"),
+    )
+    .unwrap();
+    let conf = load_temp_workspace(&dir).unwrap();
+    let lib = &conf.current_project().unwrap().lib;
+    assert_eq!(lib.ssr_features, Some(vec!["ssr".to_string()]));
+
+    std::fs::write(root.join("a/Cargo.toml"), package("a", "hydrate = []\n")).unwrap();
+    let conf = load_temp_workspace(&dir).unwrap();
+    assert_eq!(conf.current_project().unwrap().lib.ssr_features, None);
+}
+
+fn cargo_configs(texts: &[&str]) -> Vec<(camino::Utf8PathBuf, toml::Value)> {
+    texts
+        .iter()
+        .map(|text| ("config.toml".into(), toml::from_str(text).unwrap()))
+        .collect()
+}
+
+#[test]
+fn test_cargo_rustflags() {
+    use super::rustflags::CargoRustflags;
+
+    let rustflags = CargoRustflags::from_configs(&cargo_configs(&[
+        "[build]\nrustflags = \"-C opt-level=2\"",
+        "[target.wasm32-unknown-unknown]\nrustflags = ['--cfg', 'getrandom_backend=\"wasm_js\"']",
+    ]))
+    .unwrap();
+    assert_eq!(
+        rustflags.for_target(Some("wasm32-unknown-unknown")),
+        ["--cfg", "getrandom_backend=\"wasm_js\""]
+    );
+    // without target rustflags, the build ones
+    assert_eq!(
+        rustflags.for_target(Some("x86_64-unknown-linux-gnu")),
+        ["-C", "opt-level=2"]
+    );
+
+    // the arrays are joined, the closest config last
+    let rustflags = CargoRustflags::from_configs(&cargo_configs(&[
+        "[target.x86_64-unknown-linux-gnu]\nrustflags = ['-Ctarget-cpu=native']\n[build]\ntarget = 'x86_64-unknown-linux-gnu'",
+        "[target.x86_64-unknown-linux-gnu]\nrustflags = ['--cfg=x']",
+    ]))
+    .unwrap();
+    assert_eq!(
+        rustflags.for_target(None),
+        ["-Ctarget-cpu=native", "--cfg=x"]
+    );
+
+    let cfg = cargo_configs(&["[target.'cfg(target_arch = \"wasm32\")']\nrustflags = ['--cfg=x']"]);
+    assert!(CargoRustflags::from_configs(&cfg).is_err());
+}
+
+#[test]
+fn test_env_rustflags() {
+    use super::rustflags::{encoded_rustflags, env_rustflags};
+
+    assert_eq!(env_rustflags(|_| None), None);
+
+    let rustflags = |name: &str| (name == "RUSTFLAGS").then(|| "-C  opt-level=2".to_string());
+    assert_eq!(env_rustflags(rustflags).unwrap(), ["-C", "opt-level=2"]);
+
+    let encoded =
+        |name: &str| (name == "CARGO_ENCODED_RUSTFLAGS").then(|| "--cfg\x1fa b".to_string());
+    let flags = env_rustflags(encoded).unwrap();
+    assert_eq!(flags, ["--cfg", "a b"]);
+    assert_eq!(
+        encoded_rustflags(
+            flags
+                .into_iter()
+                .chain(["--remap-path-prefix=/my work=.".into()])
+        ),
+        (
+            "CARGO_ENCODED_RUSTFLAGS",
+            "--cfg\x1fa b\x1f--remap-path-prefix=/my work=.".to_string()
+        )
+    );
+}
+
+#[test]
+fn test_json_hash_file() {
+    let dir = temp_package("hash-file-format = \"json\"");
+    let conf = load_temp_package(&dir);
+    let hash_file = &conf.projects[0].hash_file;
+    // leptos reads the text file, the json one is written next to it
+    assert_eq!(hash_file.rel, "hash.txt");
+    assert_eq!(hash_file.json_rel(), "hash.json");
+    assert_eq!(
+        hash_file.json_abs(),
+        hash_file.abs.with_file_name("hash.json")
+    );
+
+    let dir = temp_package("hash-file-format = \"json\"\nhash-file-name = \"hashes.json\"");
+    let conf = load_temp_package(&dir);
+    assert_eq!(
+        conf.projects[0].hash_file.json_rel(),
+        "hashes.manifest.json"
+    );
+}
+
+#[test]
+fn test_query_cache_bust_needs_csr() {
+    // the server renders the links to the pkg files, without the queries
+    let dir = temp_package("hash-files = true\ncache-bust = \"query\"");
+    let root = camino::Utf8Path::from_path(dir.path()).unwrap();
+    assert!(Config::load(opts(None), root, &root.join("Cargo.toml"), false, None).is_err());
+
+    let dir = temp_package("hash-files = true\ncache-bust = \"query\"\ncsr = true");
+    let conf = load_temp_package(&dir);
+    let proj = &conf.projects[0];
+    assert!(proj
+        .to_envs()
+        .contains(&("LEPTOS_HASH_FILES", "false".to_string())));
+}
+
+#[test]
+fn test_watch_ui_single_project() {
+    use super::Cli;
+    use clap::Parser;
+
+    assert!(Cli::try_parse_from(["cargo-leptos", "watch", "--ui"]).is_ok());
+    assert!(Cli::try_parse_from(["cargo-leptos", "watch", "--ui", "--all"]).is_err());
+    assert!(Cli::try_parse_from([
+        "cargo-leptos",
+        "watch",
+        "--ui",
+        "--gateway",
+        "127.0.0.1:8000"
+    ])
+    .is_err());
+}
@@ -0,0 +1,42 @@
+use camino::{Utf8Path, Utf8PathBuf};
+
+use crate::ext::anyhow::{bail, Result};
+
+/// The certificate the site is served with over https, with `--tls`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tls {
+    /// absolute path to the PEM certificate chain
+    pub cert: Utf8PathBuf,
+    /// absolute path to the PEM private key
+    pub key: Utf8PathBuf,
+    /// the certificate is generated with mkcert when missing
+    pub generated: bool,
+}
+
+impl Tls {
+    /// the `tls-cert` and `tls-key` paths are relative to the workspace root. Without them, a
+    /// certificate is generated in the target dir.
+    pub fn resolve(
+        cert: Option<&Utf8PathBuf>,
+        key: Option<&Utf8PathBuf>,
+        working_dir: &Utf8Path,
+        target_dir: &Utf8Path,
+    ) -> Result<Self> {
+        match (cert, key) {
+            (Some(cert), Some(key)) => Ok(Self {
+                cert: working_dir.join(cert),
+                key: working_dir.join(key),
+                generated: false,
+            }),
+            (None, None) => {
+                let dir = target_dir.join("cargo-leptos").join("tls");
+                Ok(Self {
+                    cert: dir.join("localhost.pem"),
+                    key: dir.join("localhost-key.pem"),
+                    generated: true,
+                })
+            }
+            _ => bail!("Both tls-cert and tls-key are needed to serve with a given certificate"),
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use std::str::FromStr;
+
+use camino::Utf8PathBuf;
+use schemars::{gen::SchemaGenerator, schema::Schema, JsonSchema};
+use serde::{Deserialize, Deserializer};
+
+use crate::ext::anyhow::{bail, Result};
+
+/// how the file system is watched for changes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum WatchBackend {
+    /// polls when the workspace is on a file system known to miss events, such as
+    /// network shares, docker volumes and windows drives in WSL
+    #[default]
+    Auto,
+    /// the events of the operating system
+    Native,
+    /// compares the files at each poll interval
+    Poll,
+}
+
+impl FromStr for WatchBackend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "native" => Ok(Self::Native),
+            "poll" => Ok(Self::Poll),
+            _ => bail!("Unknown watch backend {s:?}, expected \"auto\", \"native\" or \"poll\""),
+        }
+    }
+}
+
+/// what happens to the changes of the front sources that can't be patched into the page
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum HotPatchFallback {
+    /// rebuilt, the reason is logged at debug level
+    #[default]
+    Rebuild,
+    /// rebuilt, the reason is logged as a warning
+    Warn,
+    /// not rebuilt: patched only, the rest requires a manual rebuild
+    Strict,
+}
+
+impl FromStr for HotPatchFallback {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "rebuild" => Ok(Self::Rebuild),
+            "warn" => Ok(Self::Warn),
+            "strict" => Ok(Self::Strict),
+            _ => bail!(
+                "Unknown hot-patch fallback {s:?}, expected \"rebuild\", \"warn\" or \"strict\""
+            ),
+        }
+    }
+}
+
+/// the live-reload client scripts of watch mode
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum HotReloadClient {
+    /// the one of leptos, plus the one of cargo-leptos appended to the front js
+    #[default]
+    Default,
+    /// none, for pages that manage their own refresh
+    Disabled,
+    /// a script appended to the front js instead, relative to the working dir
+    Custom(Utf8PathBuf),
+}
+
+impl FromStr for HotReloadClient {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "true" => Ok(Self::Default),
+            "false" => Ok(Self::Disabled),
+            "" => bail!("Empty hot-reload client, expected true, false or a script path"),
+            path => Ok(Self::Custom(path.into())),
+        }
+    }
+}
+
+/// how the hot-reload client is given in the config
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum BoolOrPath {
+    Bool(bool),
+    Path(#[schemars(with = "String")] Utf8PathBuf),
+}
+
+impl<'de> Deserialize<'de> for HotReloadClient {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        Ok(match BoolOrPath::deserialize(deserializer)? {
+            BoolOrPath::Bool(true) => Self::Default,
+            BoolOrPath::Bool(false) => Self::Disabled,
+            BoolOrPath::Path(path) => Self::Custom(path),
+        })
+    }
+}
+
+impl JsonSchema for HotReloadClient {
+    fn schema_name() -> String {
+        "HotReloadClient".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        BoolOrPath::json_schema(gen)
+    }
+}
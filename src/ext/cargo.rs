@@ -1,9 +1,11 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet, VecDeque};
 
 use super::anyhow::Result;
 use super::{PathBufExt, PathExt};
 use camino::{Utf8Path, Utf8PathBuf};
-use cargo_metadata::{CrateType, Metadata, MetadataCommand, Package, PackageId, Resolve, Target};
+use cargo_metadata::{
+    CrateType, DependencyKind, Metadata, MetadataCommand, Package, PackageId, Resolve, Target,
+};
 
 pub trait PackageExt {
     fn has_bin_target(&self) -> bool;
@@ -11,6 +13,8 @@ pub trait PackageExt {
     fn cdylib_target(&self) -> Option<&Target>;
     fn target_list(&self) -> String;
     fn path_dependencies(&self) -> Vec<Utf8PathBuf>;
+    fn expand_features(&self, features: &[String]) -> HashSet<String>;
+    fn lib_src_dir(&self) -> Utf8PathBuf;
 }
 
 impl PackageExt for Package {
@@ -53,6 +57,28 @@ impl PackageExt for Package {
         }
         found
     }
+
+    /// the features, with those they enable (including the `dep:name` and `name/feature` ones)
+    fn expand_features(&self, features: &[String]) -> HashSet<String> {
+        let mut enabled = HashSet::new();
+        let mut queue = features.to_vec();
+        while let Some(feature) = queue.pop() {
+            if let Some(enables) = self.features.get(&feature) {
+                queue.extend(enables.iter().cloned());
+            }
+            enabled.insert(feature);
+        }
+        enabled
+    }
+
+    /// the dir of the lib target's root file, `src` by default
+    fn lib_src_dir(&self) -> Utf8PathBuf {
+        self.targets
+            .iter()
+            .find(|t| t.is_lib() || t.is_rlib() || t.is_cdylib() || t.is_proc_macro())
+            .and_then(|t| t.src_path.parent().map(Utf8Path::to_path_buf))
+            .unwrap_or_else(|| self.manifest_path.clone().without_last().join("src"))
+    }
 }
 
 pub trait MetadataExt {
@@ -61,6 +87,12 @@ pub trait MetadataExt {
     fn package_for(&self, id: &PackageId) -> Option<&Package>;
     fn path_dependencies(&self, id: &PackageId) -> Vec<Utf8PathBuf>;
     fn src_path_dependencies(&self, id: &PackageId) -> Vec<Utf8PathBuf>;
+    fn feature_src_paths(
+        &self,
+        id: &PackageId,
+        features: &[String],
+        default_features: bool,
+    ) -> Vec<Utf8PathBuf>;
 }
 
 impl MetadataExt for Metadata {
@@ -120,6 +152,88 @@ impl MetadataExt for Metadata {
             })
             .collect()
     }
+
+    /// The lib src dirs (relative to the workspace root when inside it) of the package and of
+    /// the local packages it depends on when built with the features. Unlike the resolve of
+    /// the metadata, which uses the default features, it follows the optional dependencies
+    /// enabled by the features, such as the components of a `hydrate` feature.
+    fn feature_src_paths(
+        &self,
+        id: &PackageId,
+        features: &[String],
+        default_features: bool,
+    ) -> Vec<Utf8PathBuf> {
+        let mut features = features.to_vec();
+        if default_features {
+            features.push("default".to_string());
+        }
+        let mut enabled: HashMap<PackageId, HashSet<String>> = HashMap::new();
+        let mut queue = VecDeque::from([(id.clone(), features)]);
+
+        while let Some((id, features)) = queue.pop_front() {
+            let Some(package) = self.package_for(&id) else {
+                continue;
+            };
+            let features = package.expand_features(&features);
+            match enabled.get_mut(&id) {
+                Some(known) if features.is_subset(known) => continue,
+                Some(known) => known.extend(features.iter().cloned()),
+                None => {
+                    enabled.insert(id.clone(), features.clone());
+                }
+            }
+            let features = &enabled[&id];
+
+            for dep in &package.dependencies {
+                let (Some(path), DependencyKind::Normal) = (&dep.path, dep.kind) else {
+                    continue;
+                };
+                let name = dep.rename.as_deref().unwrap_or(&dep.name);
+                let dep_features = features.iter().filter_map(|feature| {
+                    let (dep_name, dep_feature) = feature.split_once('/')?;
+                    (dep_name.trim_end_matches('?') == name).then(|| dep_feature.to_string())
+                });
+                let on = !dep.optional
+                    || features.contains(name)
+                    || features.contains(&format!("dep:{name}"))
+                    || features
+                        .iter()
+                        .any(|feature| feature.starts_with(&format!("{name}/")));
+                if !on {
+                    continue;
+                }
+                let mut dep_features = dep
+                    .features
+                    .iter()
+                    .cloned()
+                    .chain(dep_features)
+                    .collect::<Vec<_>>();
+                if dep.uses_default_features {
+                    dep_features.push("default".to_string());
+                }
+                if let Some(dep_package) = self
+                    .packages
+                    .iter()
+                    .find(|p| p.manifest_path.parent() == Some(path.as_path()))
+                {
+                    queue.push_back((dep_package.id.clone(), dep_features));
+                }
+            }
+        }
+
+        let root = &self.workspace_root;
+        let mut paths = enabled
+            .keys()
+            .filter_map(|id| self.package_for(id))
+            .map(|package| {
+                let dir = package.lib_src_dir();
+                dir.unbase(root).unwrap_or(dir)
+            })
+            .collect::<Vec<_>>();
+        paths.sort();
+        paths.dedup();
+        paths
+    }
 }
 
 pub trait ResolveExt {
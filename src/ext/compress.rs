@@ -7,10 +7,12 @@ use std::io::{BufReader, Write};
 use std::path::PathBuf;
 use tokio::time::Instant;
 
-pub async fn compress_static_files(path: PathBuf) -> Result<()> {
+/// Precompresses all files under `path`. The `mtime` is written into the gzip headers
+/// (instead of the current time) when building reproducibly.
+pub async fn compress_static_files(path: PathBuf, mtime: Option<u32>) -> Result<()> {
     let start = Instant::now();
 
-    tokio::task::spawn_blocking(move || compress_dir_all(path)).await??;
+    tokio::task::spawn_blocking(move || compress_dir_all(path, mtime)).await??;
 
     log::info!(
         "Precompression of static files finished after {} ms",
@@ -21,7 +23,7 @@ pub async fn compress_static_files(path: PathBuf) -> Result<()> {
 
 // This is sync / blocking because an async / parallel execution did provide only a small benefit
 // in performance (~4%) while needing quite a few more dependencies and much more verbose code.
-fn compress_dir_all(path: PathBuf) -> Result<()> {
+fn compress_dir_all(path: PathBuf, mtime: Option<u32>) -> Result<()> {
     log::trace!("FS compress_dir_all {:?}", path);
 
     let dir = fs::read_dir(&path).context(format!("Could not read {:?}", path))?;
@@ -32,7 +34,7 @@ fn compress_dir_all(path: PathBuf) -> Result<()> {
         let metadata = fs::metadata(&path)?;
 
         if metadata.is_dir() {
-            compress_dir_all(path)?;
+            compress_dir_all(path, mtime)?;
         } else {
             let pstr = path.to_str().unwrap_or_default();
             if pstr.ends_with(".gz") || pstr.ends_with(".br") {
@@ -43,7 +45,16 @@ fn compress_dir_all(path: PathBuf) -> Result<()> {
             let file = fs::read(&path)?;
 
             // gzip
-            let mut encoder = gzip::Encoder::new(Vec::new())?;
+            let mut encoder = match mtime {
+                Some(mtime) => {
+                    let header = gzip::HeaderBuilder::new().modification_time(mtime).finish();
+                    gzip::Encoder::with_options(
+                        Vec::new(),
+                        gzip::EncodeOptions::new().header(header),
+                    )?
+                }
+                None => gzip::Encoder::new(Vec::new())?,
+            };
             encoder.write_all(file.as_ref())?;
             let encoded_data = encoder.finish().into_result()?;
             let path_gz = format!("{}.gz", pstr);
@@ -1,5 +1,8 @@
 use crate::{
-    ext::anyhow::{bail, Context, Result},
+    ext::{
+        anyhow::{bail, Context, Result},
+        Progress,
+    },
     logger::GRAY,
 };
 use bytes::Bytes;
@@ -40,6 +43,8 @@ pub const ENV_VAR_LEPTOS_CARGO_GENERATE_VERSION: &str = "LEPTOS_CARGO_GENERATE_V
 pub const ENV_VAR_LEPTOS_TAILWIND_VERSION: &str = "LEPTOS_TAILWIND_VERSION";
 pub const ENV_VAR_LEPTOS_SASS_VERSION: &str = "LEPTOS_SASS_VERSION";
 pub const ENV_VAR_LEPTOS_WASM_OPT_VERSION: &str = "LEPTOS_WASM_OPT_VERSION";
+pub const ENV_VAR_LEPTOS_WASM_BINDGEN_VERSION: &str = "LEPTOS_WASM_BINDGEN_VERSION";
+pub const ENV_VAR_LEPTOS_GECKODRIVER_VERSION: &str = "LEPTOS_GECKODRIVER_VERSION";
 
 impl ExeMeta {
     #[allow(clippy::wrong_self_convention)]
@@ -94,12 +99,20 @@ impl<'a> ExeCache<'a> {
             GRAY.paint(&self.meta.url)
         );
 
-        let response = reqwest::get(&self.meta.url).await?;
+        let mut response = reqwest::get(&self.meta.url).await?;
+        if !response.status().is_success() {
+            bail!("Could not download from {}", self.meta.url);
+        }
 
-        match response.status().is_success() {
-            true => Ok(response.bytes().await?),
-            false => bail!("Could not download from {}", self.meta.url),
+        let total = response.content_length();
+        let progress = Progress::bytes(format!("Install {}", self.meta.name), total);
+        let mut data = Vec::with_capacity(total.unwrap_or(0) as usize);
+        while let Some(chunk) = response.chunk().await? {
+            progress.inc(chunk.len() as u64);
+            data.extend_from_slice(&chunk);
         }
+        progress.finish();
+        Ok(data.into())
     }
 
     fn extract_downloaded(&self, data: &Bytes) -> Result<()> {
@@ -216,6 +229,8 @@ pub enum Exe {
     Sass,
     WasmOpt,
     Tailwind,
+    WasmBindgenTestRunner,
+    GeckoDriver,
 }
 
 impl Exe {
@@ -262,6 +277,14 @@ impl Exe {
                 .exe_meta(target_os, target_arch)
                 .await
                 .dot()?,
+            Exe::WasmBindgenTestRunner => CommandWasmBindgenTestRunner
+                .exe_meta(target_os, target_arch)
+                .await
+                .dot()?,
+            Exe::GeckoDriver => CommandGeckoDriver
+                .exe_meta(target_os, target_arch)
+                .await
+                .dot()?,
         };
 
         Ok(exe)
@@ -310,6 +333,8 @@ struct CommandTailwind;
 struct CommandWasmOpt;
 struct CommandSass;
 struct CommandCargoGenerate;
+struct CommandWasmBindgenTestRunner;
+struct CommandGeckoDriver;
 
 #[async_trait]
 impl Command for CommandTailwind {
@@ -609,6 +634,125 @@ impl Command for CommandCargoGenerate {
     }
 }
 
+#[async_trait]
+impl Command for CommandWasmBindgenTestRunner {
+    fn name(&self) -> &'static str {
+        "wasm-bindgen-test-runner"
+    }
+    // must match the wasm-bindgen version of the tested project
+    fn default_version(&self) -> &'static str {
+        "0.2.100"
+    }
+    fn env_var_version_name(&self) -> &'static str {
+        ENV_VAR_LEPTOS_WASM_BINDGEN_VERSION
+    }
+    fn github_owner(&self) -> &'static str {
+        "rustwasm"
+    }
+    fn github_repo(&self) -> &'static str {
+        "wasm-bindgen"
+    }
+
+    fn download_url(&self, target_os: &str, target_arch: &str, version: &str) -> Result<String> {
+        Ok(format!(
+            "https://github.com/{}/{}/releases/download/{}/wasm-bindgen-{}-{}.tar.gz",
+            self.github_owner(),
+            self.github_repo(),
+            version,
+            version,
+            wasm_bindgen_target(target_os, target_arch)?
+        ))
+    }
+
+    fn executable_name(
+        &self,
+        target_os: &str,
+        target_arch: &str,
+        version: Option<&str>,
+    ) -> Result<String> {
+        let Some(version) = version else {
+            bail!("Version is required for wasm-bindgen-test-runner, none provided")
+        };
+        let target = wasm_bindgen_target(target_os, target_arch)?;
+        Ok(match target_os {
+            "windows" => format!("wasm-bindgen-{version}-{target}/{}.exe", self.name()),
+            _ => format!("wasm-bindgen-{version}-{target}/{}", self.name()),
+        })
+    }
+
+    fn manual_install_instructions(&self) -> String {
+        "Try manually installing wasm-bindgen-cli, with the wasm-bindgen version of the project: cargo install wasm-bindgen-cli --version <VERSION>".to_string()
+    }
+}
+
+/// the target triple of the wasm-bindgen release archives
+fn wasm_bindgen_target(target_os: &str, target_arch: &str) -> Result<&'static str> {
+    Ok(match (target_os, target_arch) {
+        ("linux", "x86_64") => "x86_64-unknown-linux-musl",
+        ("linux", "aarch64") => "aarch64-unknown-linux-gnu",
+        ("macos", "x86_64") => "x86_64-apple-darwin",
+        ("macos", "aarch64") => "aarch64-apple-darwin",
+        ("windows", "x86_64") => "x86_64-pc-windows-msvc",
+        _ => bail!("No wasm-bindgen tar binary found for {target_os} {target_arch}"),
+    })
+}
+
+#[async_trait]
+impl Command for CommandGeckoDriver {
+    fn name(&self) -> &'static str {
+        "geckodriver"
+    }
+    fn default_version(&self) -> &'static str {
+        "v0.35.0"
+    }
+    fn env_var_version_name(&self) -> &'static str {
+        ENV_VAR_LEPTOS_GECKODRIVER_VERSION
+    }
+    fn github_owner(&self) -> &'static str {
+        "mozilla"
+    }
+    fn github_repo(&self) -> &'static str {
+        "geckodriver"
+    }
+
+    fn download_url(&self, target_os: &str, target_arch: &str, version: &str) -> Result<String> {
+        let (platform, ext) = match (target_os, target_arch) {
+            ("linux", "x86_64") => ("linux64", "tar.gz"),
+            ("linux", "aarch64") => ("linux-aarch64", "tar.gz"),
+            ("macos", "x86_64") => ("macos", "tar.gz"),
+            ("macos", "aarch64") => ("macos-aarch64", "tar.gz"),
+            ("windows", "x86_64") => ("win64", "zip"),
+            _ => bail!("No geckodriver binary found for {target_os} {target_arch}"),
+        };
+        Ok(format!(
+            "https://github.com/{}/{}/releases/download/{}/geckodriver-{}-{}.{}",
+            self.github_owner(),
+            self.github_repo(),
+            version,
+            version,
+            platform,
+            ext
+        ))
+    }
+
+    fn executable_name(
+        &self,
+        target_os: &str,
+        _target_arch: &str,
+        _version: Option<&str>,
+    ) -> Result<String> {
+        Ok(match target_os {
+            "windows" => "geckodriver.exe".to_string(),
+            _ => "geckodriver".to_string(),
+        })
+    }
+
+    fn manual_install_instructions(&self) -> String {
+        "Try manually installing geckodriver: https://github.com/mozilla/geckodriver/releases"
+            .to_string()
+    }
+}
+
 #[async_trait]
 /// Template trait, implementors should only fill in
 /// the command-specific logic. Handles caching, latest
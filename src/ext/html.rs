@@ -0,0 +1,392 @@
+use std::collections::HashSet;
+
+/// elements whose content is copied as-is
+const RAW_TEXT: &[&str] = &["pre", "textarea", "script", "style"];
+
+/// elements around which whitespace doesn't render and can be dropped
+const BLOCK: &[&str] = &[
+    "!doctype", "html", "head", "body", "meta", "link", "title", "base", "script", "style",
+    "noscript", "template", "div", "p", "ul", "ol", "li", "dl", "dt", "dd", "section", "header",
+    "footer", "nav", "main", "article", "aside", "table", "thead", "tbody", "tfoot", "tr", "td",
+    "th", "form", "fieldset", "h1", "h2", "h3", "h4", "h5", "h6", "hr", "br",
+];
+
+/// the starts of the comments that leptos renders as hydration markers, such as
+/// `<!--hk=0-0|leptos-view-open-->` and `<!--<() />-->`, which the client finds its nodes by
+const HYDRATION_MARKERS: &[&str] = &["hk=", "leptos", "hot-reload", "<", "/"];
+
+/// Minifies html by stripping comments and collapsing whitespace.
+///
+/// The content of `pre`, `textarea`, `script` and `style` elements and the attribute values
+/// are left untouched, as are conditional comments (`<!--[if ...]>`) and the empty comments
+/// and hydration markers of leptos.
+pub fn minify_html(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+    let mut last_tag = String::new();
+
+    while !rest.is_empty() {
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            let end = comment.find("-->").map(|i| i + 3).unwrap_or(comment.len());
+            let kept = comment.starts_with("[if")
+                || comment.starts_with("-->")
+                || HYDRATION_MARKERS.iter().any(|m| comment.starts_with(m));
+            if kept {
+                out.push_str(&rest[..4 + end]);
+            }
+            rest = &comment[end..];
+            continue;
+        }
+
+        if is_tag_start(rest) {
+            let len = tag_len(rest);
+            let tag = &rest[..len];
+            push_tag(&mut out, tag);
+            rest = &rest[len..];
+
+            let name = tag_name(tag);
+            if !tag.starts_with("</") && RAW_TEXT.contains(&name.as_str()) {
+                let close = format!("</{name}");
+                let end = rest.to_ascii_lowercase().find(&close).unwrap_or(rest.len());
+                out.push_str(&rest[..end]);
+                rest = &rest[end..];
+            }
+            last_tag = name;
+            continue;
+        }
+
+        // a '<' that doesn't start a tag is part of the text, as is any first char
+        let first = rest.chars().next().map_or(0, char::len_utf8);
+        let end = rest[first..]
+            .find('<')
+            .map(|i| i + first)
+            .unwrap_or(rest.len());
+        let text = &rest[..end];
+        rest = &rest[end..];
+
+        if text.trim().is_empty() {
+            let next_tag = if is_tag_start(rest) {
+                tag_name(&rest[..tag_len(rest)])
+            } else {
+                String::new()
+            };
+            let droppable = out.is_empty()
+                || rest.is_empty()
+                || BLOCK.contains(&last_tag.as_str())
+                || BLOCK.contains(&next_tag.as_str());
+            if !droppable {
+                out.push(' ');
+            }
+        } else {
+            push_collapsed(&mut out, text);
+        }
+    }
+    out
+}
+
+/// the element names, classes and ids used in an html document
+#[derive(Debug, Default)]
+pub struct HtmlSelectors {
+    pub tags: HashSet<String>,
+    pub classes: HashSet<String>,
+    pub ids: HashSet<String>,
+}
+
+/// Lists the element names, classes and ids used in the given html.
+pub fn html_selectors(html: &str) -> HtmlSelectors {
+    let mut found = HtmlSelectors::default();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+        if !is_tag_start(rest) || rest.starts_with("</") || rest.starts_with("<!") {
+            rest = &rest[1..];
+            continue;
+        }
+        let len = tag_len(rest);
+        let tag = &rest[..len];
+        rest = &rest[len..];
+
+        let name = tag_name(tag);
+        for (attr, value) in attributes(&tag[1 + name.len()..]) {
+            match attr.as_str() {
+                "class" => found
+                    .classes
+                    .extend(value.split_ascii_whitespace().map(str::to_string)),
+                "id" => {
+                    found.ids.insert(value.to_string());
+                }
+                _ => {}
+            }
+        }
+        found.tags.insert(name);
+    }
+    found
+}
+
+/// the inline code of an html document, which a content security policy has to allow
+#[derive(Debug, Default)]
+pub struct InlineCode {
+    /// the content of the `<script>` elements without a `src`
+    pub scripts: Vec<String>,
+    /// the content of the `<style>` elements
+    pub styles: Vec<String>,
+    /// the values of the event handler attributes, such as `onload`
+    pub handlers: Vec<String>,
+}
+
+/// Lists the inline scripts, styles and event handlers of the given html.
+pub fn inline_code(html: &str) -> InlineCode {
+    let mut found = InlineCode::default();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+        if !is_tag_start(rest) || rest.starts_with("</") || rest.starts_with("<!") {
+            rest = &rest[1..];
+            continue;
+        }
+        let len = tag_len(rest);
+        let tag = &rest[..len];
+        rest = &rest[len..];
+
+        let name = tag_name(tag);
+        let attrs = attributes(&tag[1 + name.len()..]);
+        for (attr, value) in &attrs {
+            if attr.starts_with("on") {
+                found.handlers.push(value.to_string());
+            }
+        }
+        if name == "script" || name == "style" {
+            let close = format!("</{name}");
+            let end = rest.to_ascii_lowercase().find(&close).unwrap_or(rest.len());
+            let content = rest[..end].to_string();
+            rest = &rest[end..];
+            if name == "style" {
+                found.styles.push(content);
+            } else if !attrs.iter().any(|(attr, _)| attr == "src") {
+                found.scripts.push(content);
+            }
+        }
+    }
+    found
+}
+
+/// Replaces the `<link rel="stylesheet">` to the href with the given html. None when the html
+/// doesn't link to it.
+pub fn replace_stylesheet_link(html: &str, href: &str, with: &str) -> Option<String> {
+    let mut offset = 0;
+    while let Some(start) = html[offset..].find('<').map(|i| offset + i) {
+        let rest = &html[start..];
+        if !is_tag_start(rest) {
+            offset = start + 1;
+            continue;
+        }
+        let len = tag_len(rest);
+        let tag = &rest[..len];
+        let name = tag_name(tag);
+        if name == "link" {
+            let attrs = attributes(&tag[1 + name.len()..]);
+            let is = |attr: &str, value: &str| {
+                attrs
+                    .iter()
+                    .any(|(name, val)| name == attr && val.eq_ignore_ascii_case(value))
+            };
+            if is("rel", "stylesheet")
+                && attrs
+                    .iter()
+                    .any(|(name, val)| name == "href" && *val == href)
+            {
+                return Some(format!("{}{with}{}", &html[..start], &html[start + len..]));
+            }
+        }
+        offset = start + len;
+    }
+    None
+}
+
+/// the lowercase names and values of the attributes of a tag, without its `<name` prefix
+fn attributes(tag: &str) -> Vec<(String, &str)> {
+    let mut attrs = Vec::new();
+    let mut rest = tag.trim_end_matches('>').trim_end_matches('/');
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_ascii_whitespace() || c == '/');
+        let name_len = rest
+            .find(|c: char| c.is_ascii_whitespace() || c == '=')
+            .unwrap_or(rest.len());
+        if name_len == 0 {
+            return attrs;
+        }
+        let name = rest[..name_len].to_ascii_lowercase();
+        rest = rest[name_len..].trim_start();
+
+        let Some(value) = rest.strip_prefix('=') else {
+            attrs.push((name, ""));
+            continue;
+        };
+        let value = value.trim_start();
+        let (value, after) = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let end = value[1..].find(quote).map(|i| i + 1).unwrap_or(value.len());
+                (&value[1..end], value.get(end + 1..).unwrap_or_default())
+            }
+            _ => {
+                let end = value
+                    .find(|c: char| c.is_ascii_whitespace())
+                    .unwrap_or(value.len());
+                (&value[..end], &value[end..])
+            }
+        };
+        attrs.push((name, value));
+        rest = after;
+    }
+}
+
+fn is_tag_start(s: &str) -> bool {
+    s.starts_with('<')
+        && s[1..].starts_with(|c: char| c.is_ascii_alphabetic() || c == '/' || c == '!')
+}
+
+/// the length of the tag at the start of `s`, including the closing `>`
+fn tag_len(s: &str) -> usize {
+    let mut quote = None;
+    for (i, c) in s.char_indices().skip(1) {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '>') => return i + 1,
+            _ => {}
+        }
+    }
+    s.len()
+}
+
+/// the lowercase name of a tag, without the `/` of closing tags
+fn tag_name(tag: &str) -> String {
+    tag.trim_start_matches('<')
+        .trim_start_matches('/')
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '!')
+        .collect::<String>()
+        .to_ascii_lowercase()
+}
+
+/// pushes a tag, collapsing the whitespace between its attributes
+fn push_tag(out: &mut String, tag: &str) {
+    let mut quote = None;
+    let mut space = false;
+    for c in tag.chars() {
+        if quote.is_none() && c.is_ascii_whitespace() {
+            space = true;
+            continue;
+        }
+        // the space before `/>` is only optional after a quoted value: `<a href=b/>` is not `<a href=b />`
+        let optional = c == '>' || (c == '/' && out.ends_with(['"', '\'']));
+        if space && !optional {
+            out.push(' ');
+        }
+        space = false;
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            _ => {}
+        }
+        out.push(c);
+    }
+}
+
+fn push_collapsed(out: &mut String, text: &str) {
+    let mut space = false;
+    for c in text.chars() {
+        if c.is_ascii_whitespace() {
+            space = true;
+            continue;
+        }
+        if space {
+            out.push(' ');
+            space = false;
+        }
+        out.push(c);
+    }
+    if space {
+        out.push(' ');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{html_selectors, inline_code, minify_html, replace_stylesheet_link};
+
+    #[test]
+    fn test_html_selectors() {
+        let found = html_selectors(
+            r#"<main id="app"><p class="a md:flex  b" hidden>x</p><img src=a.png class="c" /></main>"#,
+        );
+        let mut classes = found.classes.into_iter().collect::<Vec<_>>();
+        classes.sort();
+        assert_eq!(classes, ["a", "b", "c", "md:flex"]);
+        assert!(found.ids.contains("app"));
+        assert!(found.tags.contains("img") && found.tags.contains("main"));
+    }
+
+    #[test]
+    fn test_inline_code() {
+        let found = inline_code(
+            r#"<head><script src="/pkg/app.js"></script><script type="module">import('/a.js')</script><style>p{}</style><link onload="this.rel='stylesheet'"></head>"#,
+        );
+        assert_eq!(found.scripts, ["import('/a.js')"]);
+        assert_eq!(found.styles, ["p{}"]);
+        assert_eq!(found.handlers, ["this.rel='stylesheet'"]);
+    }
+
+    #[test]
+    fn test_minify_html() {
+        let html = r#"<!DOCTYPE html>
+<html>
+  <head>
+    <!-- a comment -->
+    <link   rel="stylesheet"   href="/pkg/app.css" />
+    <script>
+      let a = 1;   // kept
+    </script>
+  </head>
+  <body>
+    <p class="a   b">Hello   <b>big</b>
+      <i>world</i></p>
+    <pre>  keep
+  this  </pre>
+  </body>
+</html>
+"#;
+        assert_eq!(
+            minify_html(html),
+            r#"<!DOCTYPE html><html><head><link rel="stylesheet" href="/pkg/app.css"/><script>
+      let a = 1;   // kept
+    </script></head><body><p class="a   b">Hello <b>big</b> <i>world</i></p><pre>  keep
+  this  </pre></body></html>"#
+        );
+
+        // the hydration markers of leptos are kept, unlike the other comments
+        assert_eq!(
+            minify_html(
+                "<main><!--hk=0-0|leptos-view-open--><p>a</p>  <!---->\n<!--<() />--><!-- note --></main>"
+            ),
+            "<main><!--hk=0-0|leptos-view-open--><p>a</p><!----><!--<() />--></main>"
+        );
+
+        // the text right after a tag may start with a multi-byte char
+        assert_eq!(
+            minify_html("<p>élan  <b>ü</b>\n  日本 <i>€</i>ß</p>"),
+            "<p>élan <b>ü</b> 日本 <i>€</i>ß</p>"
+        );
+    }
+
+    #[test]
+    fn test_replace_stylesheet_link() {
+        let html = r#"<head><link rel="preload" href="/pkg/app.css"><link id="leptos" rel="Stylesheet" href="/pkg/app.css"/></head>"#;
+        assert_eq!(
+            replace_stylesheet_link(html, "/pkg/app.css", "<style>a{}</style>").unwrap(),
+            r#"<head><link rel="preload" href="/pkg/app.css"><style>a{}</style></head>"#
+        );
+        assert_eq!(replace_stylesheet_link(html, "/pkg/other.css", ""), None);
+    }
+}
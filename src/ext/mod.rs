@@ -6,13 +6,19 @@ mod cargo;
 pub mod compress;
 pub mod exe;
 pub mod fs;
+mod html;
 mod path;
+pub mod progress;
 pub mod sync;
 mod util;
 
 pub use cargo::{MetadataExt, PackageExt};
 pub use exe::{Exe, ExeMeta};
+pub use html::{
+    html_selectors, inline_code, minify_html, replace_stylesheet_link, HtmlSelectors, InlineCode,
+};
 pub use path::{
     append_str_to_filename, determine_pdb_filename, remove_nested, PathBufExt, PathExt,
 };
-pub use util::{os_arch, StrAdditions};
+pub use progress::Progress;
+pub use util::{glob_base, glob_set, os_arch, StrAdditions};
@@ -0,0 +1,109 @@
+use std::{io::IsTerminal, sync::OnceLock, time::Duration};
+
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+
+use crate::{logger, service::dashboard};
+
+/// the progress lines drawn at once, as the stages run concurrently in watch mode
+static MULTI: OnceLock<MultiProgress> = OnceLock::new();
+
+/// A progress line on stderr, drawn until it is finished or dropped: a spinner with the elapsed
+/// time of a compute stage, or a bar of the bytes of a download. Nothing is drawn when stderr is
+/// not a terminal, with `--log-format json` or the dashboard.
+pub struct Progress {
+    bar: ProgressBar,
+}
+
+impl Progress {
+    /// a spinner with the elapsed time
+    pub fn spinner(label: impl Into<String>) -> Self {
+        Self::start(label.into(), None, "{spinner} {msg} {elapsed:.241}")
+    }
+
+    /// a bar of the bytes done, or their count when the total is unknown
+    pub fn bytes(label: impl Into<String>, total: Option<u64>) -> Self {
+        match total {
+            Some(total) if total > 0 => Self::start(
+                label.into(),
+                Some(total),
+                "{msg} [{bar:24}] {binary_bytes}/{binary_total_bytes}",
+            ),
+            _ => Self::start(label.into(), None, "{spinner} {msg} {binary_bytes}"),
+        }
+    }
+
+    fn start(label: String, total: Option<u64>, template: &str) -> Self {
+        // the dashboard draws the whole screen, so the bar isn't drawn over it
+        let target = match dashboard::is_active() {
+            true => ProgressDrawTarget::hidden(),
+            false => ProgressDrawTarget::stderr(),
+        };
+        let bar = ProgressBar::with_draw_target(total, target);
+        let style = ProgressStyle::with_template(template)
+            .expect("valid progress template")
+            .progress_chars("= ");
+        // the lines of the projects run at once are told apart by the tag of their logs
+        let label = match logger::project_tag() {
+            Some(tag) => format!("{tag} {label}"),
+            None => label,
+        };
+        let bar = bar.with_style(style).with_message(label);
+        let bar = match dashboard::is_active() {
+            true => bar,
+            false => multi().add(bar),
+        };
+        bar.enable_steady_tick(Duration::from_millis(100));
+        Self { bar }
+    }
+
+    pub fn inc(&self, bytes: u64) {
+        self.bar.inc(bytes);
+    }
+
+    /// clears the progress line
+    pub fn finish(self) {
+        self.bar.finish_and_clear();
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.bar.finish_and_clear();
+    }
+}
+
+fn multi() -> &'static MultiProgress {
+    MULTI.get_or_init(|| {
+        let target = match std::io::stderr().is_terminal() && !logger::is_json() {
+            true => ProgressDrawTarget::stderr(),
+            false => ProgressDrawTarget::hidden(),
+        };
+        MultiProgress::with_draw_target(target)
+    })
+}
+
+/// Writes the log lines with `write`, with the progress lines cleared meanwhile and redrawn below.
+pub fn suspend<R>(write: impl FnOnce() -> R) -> R {
+    match MULTI.get() {
+        Some(multi) => multi.suspend(write),
+        None => write(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Progress;
+
+    #[test]
+    fn test_progress() {
+        let progress = Progress::bytes("Install wasm-opt", Some(4 << 20));
+        progress.inc(3 << 20);
+        assert_eq!(progress.bar.length(), Some(4 << 20));
+        assert_eq!(progress.bar.position(), 3 << 20);
+        assert_eq!(progress.bar.message(), "Install wasm-opt");
+        progress.finish();
+
+        let progress = Progress::spinner("Front wasm-opt");
+        assert_eq!(progress.bar.length(), None);
+    }
+}
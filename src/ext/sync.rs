@@ -5,6 +5,7 @@ use std::{
     time::Duration,
 };
 use tokio::{
+    io::AsyncWriteExt,
     net::TcpStream,
     process::{Child, Command},
     sync::broadcast,
@@ -69,8 +70,18 @@ pub async fn wait_interruptible(
 }
 
 pub async fn wait_piped_interruptible(
+    name: &str,
+    cmd: Command,
+    interrupt_rx: broadcast::Receiver<()>,
+) -> Result<CommandResult<Output>> {
+    wait_piped_with_input_interruptible(name, cmd, None, interrupt_rx).await
+}
+
+/// Like [wait_piped_interruptible], writing the given input to the process' stdin.
+pub async fn wait_piped_with_input_interruptible(
     name: &str,
     mut cmd: Command,
+    input: Option<String>,
     mut interrupt_rx: broadcast::Receiver<()>,
 ) -> Result<CommandResult<Output>> {
     // see: https://docs.rs/tokio/latest/tokio/process/index.html
@@ -78,7 +89,18 @@ pub async fn wait_piped_interruptible(
     cmd.kill_on_drop(true);
     cmd.stdout(Stdio::piped());
     cmd.stderr(Stdio::piped());
-    let process = cmd.spawn()?;
+    if input.is_some() {
+        cmd.stdin(Stdio::piped());
+    }
+    let mut process = cmd.spawn()?;
+    if let (Some(input), Some(mut stdin)) = (input, process.stdin.take()) {
+        // written concurrently, as a large input would otherwise fill the pipes and block
+        tokio::spawn(async move {
+            if let Err(e) = stdin.write_all(input.as_bytes()).await {
+                log::debug!("Could not write to the stdin of the process: {e}");
+            }
+        });
+    }
     tokio::select! {
         res = process.wait_with_output() => match res {
             Ok(output) => {
@@ -50,6 +50,30 @@ async fn download_wasmopt() {
     assert!(e.exists(), "{:#?}\nFiles: \n{}", meta, ls(&dir));
 }
 
+#[tokio::test]
+async fn download_wasm_bindgen_test_runner() {
+    let dir = TempDir::new().unwrap();
+    let meta = Exe::WasmBindgenTestRunner.meta().await.unwrap();
+    let e = meta.with_cache_dir(dir.path()).await;
+
+    assert!(e.is_ok(), "{e:#?}\n{:#?}\nFiles: \n {}", meta, ls(&dir));
+
+    let e = e.unwrap();
+    assert!(e.exists(), "{:#?}\nFiles: \n{}", meta, ls(&dir));
+}
+
+#[tokio::test]
+async fn download_geckodriver() {
+    let dir = TempDir::new().unwrap();
+    let meta = Exe::GeckoDriver.meta().await.unwrap();
+    let e = meta.with_cache_dir(dir.path()).await;
+
+    assert!(e.is_ok(), "{e:#?}\n{:#?}\nFiles: \n {}", meta, ls(&dir));
+
+    let e = e.unwrap();
+    assert!(e.exists(), "{:#?}\nFiles: \n{}", meta, ls(&dir));
+}
+
 fn ls(dir: &TempDir) -> String {
     Utf8PathBuf::from_path_buf(dir.path().to_path_buf())
         .unwrap()
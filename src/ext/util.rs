@@ -1,5 +1,6 @@
 use crate::ext::anyhow::{bail, Context, Result};
 use camino::Utf8PathBuf;
+use globset::{Glob, GlobSet, GlobSetBuilder};
 use std::borrow::Cow;
 
 pub fn os_arch() -> Result<(&'static str, &'static str)> {
@@ -27,9 +28,44 @@ pub fn is_linux_musl_env() -> bool {
     cfg!(target_os = "linux") && cfg!(target_env = "musl")
 }
 
+/// builds a glob set from the given patterns, naming the offending pattern on errors
+pub fn glob_set(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern).context(format!("Invalid glob pattern {pattern:?}"))?);
+    }
+    builder.build().context("Could not build glob set")
+}
+
+/// The dir of a glob pattern before its first wildcard, from where the matching files can be
+/// found. `None` when the given path isn't a pattern.
+///
+/// ```
+/// use camino::Utf8Path;
+/// use cargo_leptos::ext::glob_base;
+///
+/// assert_eq!(glob_base("config/**/*.yaml").unwrap(), Utf8Path::new("config"));
+/// assert_eq!(glob_base("*.toml").unwrap(), Utf8Path::new(""));
+/// assert_eq!(glob_base("config/app.yaml"), None);
+/// ```
+pub fn glob_base(pattern: &str) -> Option<Utf8PathBuf> {
+    let is_wildcard = |part: &str| part.contains(['*', '?', '[', '{']);
+    if !is_wildcard(pattern) {
+        return None;
+    }
+    Some(
+        pattern
+            .split('/')
+            .take_while(|part| !is_wildcard(part))
+            .collect::<Vec<_>>()
+            .join("/")
+            .into(),
+    )
+}
+
 pub trait StrAdditions {
     fn with(&self, append: &str) -> String;
-    fn pad_left_to(&self, len: usize) -> Cow<str>;
+    fn pad_left_to(&self, len: usize) -> Cow<'_, str>;
     /// returns the string as a canonical path (creates the dir if necessary)
     fn to_created_dir(&self) -> Result<Utf8PathBuf>;
 }
@@ -41,7 +77,7 @@ impl StrAdditions for str {
         s
     }
 
-    fn pad_left_to(&self, len: usize) -> Cow<str> {
+    fn pad_left_to(&self, len: usize) -> Cow<'_, str> {
         let chars = self.chars().count();
         if chars < len {
             Cow::Owned(format!("{}{self}", " ".repeat(len - chars)))
@@ -66,7 +102,7 @@ impl StrAdditions for String {
         s
     }
 
-    fn pad_left_to(&self, len: usize) -> Cow<str> {
+    fn pad_left_to(&self, len: usize) -> Cow<'_, str> {
         self.as_str().pad_left_to(len)
     }
 
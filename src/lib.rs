@@ -22,11 +22,14 @@ use std::path::PathBuf;
 
 pub async fn run(args: Cli) -> Result<()> {
     let verbose = args.opts().map(|o| o.verbose).unwrap_or(0);
-    logger::setup(verbose, &args.log);
+    logger::setup(verbose, &args.log, args.log_format);
 
     if let New(new) = &args.command {
         return new.run().await;
     }
+    if let Commands::Config(config) = &args.command {
+        return config.run();
+    }
 
     let manifest_path = args
         .manifest_path
@@ -39,9 +42,20 @@ pub async fn run(args: Cli) -> Result<()> {
 
     let opts = args.opts().unwrap();
     let bin_args = args.bin_args();
+    let open = args.open();
+    let all = args.all();
+    let gateway = args.gateway();
 
     let watch = matches!(args.command, Commands::Watch(_));
-    let config = Config::load(opts, &cwd, &manifest_path, watch, bin_args).dot()?;
+    let mut config = Config::load(opts, &cwd, &manifest_path, watch, bin_args).dot()?;
+    if !all {
+        // serve and watch run a single project
+        let several = !matches!(args.command, Commands::Serve(_) | Commands::Watch(_));
+        config.pick_project(several)?;
+    }
+    if let [proj] = &config.projects[..] {
+        logger::set_project(&proj.name);
+    }
     env::set_current_dir(&config.working_dir).dot()?;
     log::debug!(
         "Path working dir {}",
@@ -70,13 +84,26 @@ pub async fn run(args: Cli) -> Result<()> {
     }
 
     let _monitor = Interrupt::run_ctrl_c_monitor();
-    use Commands::{Build, EndToEnd, New, Serve, Test, Watch};
+    if all && (config.projects.len() > 1 || gateway.is_some()) {
+        return command::run_all(&config, &args.command, open, gateway).await;
+    }
+    use Commands::{
+        Analyze, Bench, Build, Bundle, Coverage, EndToEnd, Export, New, ProxyConfig, Routes, Serve,
+        Test, Watch,
+    };
     match args.command {
-        New(_) => panic!(),
+        New(_) | Commands::Config(_) => panic!(),
         Build(_) => command::build_all(&config).await,
-        Serve(_) => command::serve(&config.current_project()?).await,
-        Test(_) => command::test_all(&config).await,
-        EndToEnd(_) => command::end2end_all(&config).await,
-        Watch(_) => command::watch(&config.current_project()?).await,
+        Serve(opts) => command::serve(&config.current_project()?, open, opts.prod).await,
+        Test(opts) => command::test_all(&config, opts.wasm).await,
+        Bench(opts) => command::bench_all(&config, &opts).await,
+        Coverage(opts) => command::coverage_all(&config, opts.hydrate, opts.html).await,
+        Export(opts) => command::export_all(&config, opts.out.as_deref()).await,
+        Bundle(opts) => command::bundle_all(&config, &opts).await,
+        Routes(opts) => command::routes_all(&config, &opts).await,
+        ProxyConfig(opts) => command::proxy_config_all(&config, &opts),
+        Analyze(opts) => command::analyze_all(&config, &opts).await,
+        EndToEnd(opts) => command::end2end_all(&config, &opts).await,
+        Watch(opts) => command::watch(&[config.current_project()?], &opts, open).await,
     }
 }
@@ -1,13 +1,22 @@
 use ansi_term::{Colour::Fixed, Style};
 use flexi_logger::{
     filter::{LogLineFilter, LogLineWriter},
-    DeferredNow, Level, Record,
+    DeferredNow, Level, LevelFilter, Record,
 };
+use serde_json::{json, Value};
+use std::cell::Cell;
+use std::future::Future;
 use std::io::Write;
-use std::sync::OnceLock;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tokio::task::JoinHandle;
 
 use crate::ext::anyhow::Context;
-use crate::{config::Log, ext::StrAdditions};
+use crate::{
+    config::{Log, LogDirective, LogFormat},
+    ext::{progress, StrAdditions},
+    service::dashboard,
+};
 
 // https://gist.github.com/fnky/458719343aabd01cfb17a3a4f7296797
 lazy_static::lazy_static! {
@@ -19,15 +28,30 @@ lazy_static::lazy_static! {
 
    pub static ref GRAY: ansi_term::Color = Fixed(241);
    pub static ref BOLD: ansi_term::Style = Style::new().bold();
-   static ref LOG_SELECT: OnceLock<LogFlag> = OnceLock::new();
+   static ref LOG_SELECT: OnceLock<LogSelect> = OnceLock::new();
+   static ref LOG_FORMAT: OnceLock<LogFormat> = OnceLock::new();
+   static ref PROJECT: Mutex<Option<String>> = Mutex::new(None);
+   /// the names of the projects run at once, whose records are tagged with the project
+   static ref PROJECT_TAGS: OnceLock<Vec<String>> = OnceLock::new();
 }
 
-pub fn setup(verbose: u8, logs: &[Log]) {
-    let log_level = match verbose {
-        0 => "info",
-        1 => "debug",
-        _ => "trace",
-    };
+/// the colors of the project names tagging the records
+const TAG_COLORS: [u8; 6] = [39, 170, 142, 208, 75, 204];
+
+thread_local! {
+    /// the stage timed by the record being logged, for its json fields
+    static STAGE: Cell<Option<(&'static str, Duration)>> = const { Cell::new(None) };
+}
+
+tokio::task_local! {
+    /// the project of the task logging the record, when several projects are run at once
+    static TASK_PROJECT: String;
+}
+
+pub fn setup(verbose: u8, logs: &[LogDirective], log_format: LogFormat) {
+    let select = LogSelect::new(verbose, logs);
+    // the records the directives log above the verbosity are filtered by the Filter
+    let log_level = select.max_level().as_str().to_ascii_lowercase();
 
     // OnceLock::get_or_try_init() is more idiomatic, but unstable at the moment
     _ = LOG_SELECT.get_or_init(|| {
@@ -35,44 +59,150 @@ pub fn setup(verbose: u8, logs: &[Log]) {
             .with_context(|| "Logger setup failed")
             .unwrap()
             .filter(Box::new(Filter))
-            .format(format)
+            .format(match log_format {
+                LogFormat::Text => format,
+                LogFormat::Json => json_format,
+            })
             .start()
             .unwrap();
 
-        LogFlag::new(logs)
+        select
     });
+    _ = LOG_FORMAT.get_or_init(|| log_format);
 }
 
-#[derive(Debug, Clone, Copy)]
-struct LogFlag(u8);
+/// whether the logs are json records, which no progress line is drawn among
+pub fn is_json() -> bool {
+    LOG_FORMAT.get() == Some(&LogFormat::Json)
+}
+
+/// Sets the project of the json records, as its build starts.
+pub fn set_project(name: &str) {
+    *PROJECT.lock().unwrap() = Some(name.to_string());
+}
 
-impl LogFlag {
-    fn new(logs: &[Log]) -> Self {
-        Self(logs.iter().fold(0, |acc, f| acc | f.flag()))
+/// Tags the text records with the project of the task logging them, as the projects are run at
+/// once in this process.
+pub fn tag_projects(names: &[String]) {
+    _ = PROJECT_TAGS.get_or_init(|| names.to_vec());
+}
+
+/// Runs the future with its records, and the ones of the tasks it starts with [`spawn`], tagged
+/// with the project.
+pub async fn in_project<F: Future>(name: &str, future: F) -> F::Output {
+    TASK_PROJECT.scope(name.to_string(), future).await
+}
+
+/// Spawns the task like `tokio::spawn`, its records tagged with the project of the current task.
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    match TASK_PROJECT.try_with(Clone::clone) {
+        Ok(name) => tokio::spawn(TASK_PROJECT.scope(name, future)),
+        Err(_) => tokio::spawn(future),
     }
+}
+
+/// the project of the current task, if any
+pub fn task_project() -> Option<String> {
+    TASK_PROJECT.try_with(Clone::clone).ok()
+}
+
+/// the colored and padded name of the project of the current task, when the projects are tagged
+pub fn project_tag() -> Option<String> {
+    let names = PROJECT_TAGS.get()?;
+    let name = task_project()?;
+    let width = names.iter().map(String::len).max().unwrap_or_default();
+    let index = names.iter().position(|n| *n == name).unwrap_or_default();
+    let color = Fixed(TAG_COLORS[index % TAG_COLORS.len()]);
+    Some(color.paint(format!("{name:<width$} |")).to_string())
+}
 
-    fn is_set(&self, log: Log) -> bool {
-        log.flag() & self.0 != 0
+/// Logs the duration of a build stage, as a debug record, or an info one with its stage and
+/// duration fields with `--log-format json`.
+pub fn log_stage(stage: &'static str, duration: Duration) {
+    STAGE.with(|cell| cell.set(Some((stage, duration))));
+    let secs = duration.as_secs_f64();
+    if is_json() {
+        log::info!("Timing {stage} finished in {secs:.2}s");
+    } else {
+        log::debug!("Timing {stage} finished in {secs:.2}s");
     }
+    STAGE.with(|cell| cell.set(None));
+}
+
+/// the level of the logs of cargo-leptos, and the directives of the subsystems
+#[derive(Debug, Clone)]
+struct LogSelect {
+    level: LevelFilter,
+    directives: Vec<LogDirective>,
+}
 
-    fn matches(&self, target: &str) -> bool {
-        self.do_server_log(target) || self.do_wasm_log(target)
+impl LogSelect {
+    fn new(verbose: u8, logs: &[LogDirective]) -> Self {
+        let level = match verbose {
+            0 => LevelFilter::Info,
+            1 => LevelFilter::Debug,
+            _ => LevelFilter::Trace,
+        };
+        Self {
+            level,
+            directives: logs.to_vec(),
+        }
     }
 
-    fn do_server_log(&self, target: &str) -> bool {
-        self.is_set(Log::Server) && (target.starts_with("hyper") || target.starts_with("axum"))
+    /// the level of the most verbose logs selected
+    fn max_level(&self) -> LevelFilter {
+        self.directives
+            .iter()
+            .filter_map(|directive| directive.level)
+            .fold(self.level, Ord::max)
     }
 
-    fn do_wasm_log(&self, target: &str) -> bool {
-        self.is_set(Log::Wasm) && (target.starts_with("wasm") || target.starts_with("walrus"))
+    /// Whether to log the record: the errors, those of cargo-leptos up to the level, and those
+    /// of the subsystems selected up to their level. The last directive of a subsystem wins.
+    fn allows(&self, record: &Record) -> bool {
+        if record.level() == Level::Error {
+            return true;
+        }
+        let own = record.target().starts_with("cargo_leptos");
+        // the message is only formatted for the stage directives
+        let has_stages = self
+            .directives
+            .iter()
+            .any(|directive| matches!(directive.log, Log::Stage(_)));
+        let args = (own && has_stages).then(|| record.args().to_string());
+        let tag = args.as_deref().map(|args| split(args).0);
+        let directive = self
+            .directives
+            .iter()
+            .rev()
+            .find(|directive| directive.log.matches(record.target(), tag));
+        let level = match directive {
+            Some(LogDirective {
+                level: Some(level), ..
+            }) => *level,
+            Some(LogDirective {
+                log: Log::Stage(_), ..
+            }) => LevelFilter::Debug.max(self.level),
+            Some(_) => self.level,
+            None if own => self.level,
+            None => return false,
+        };
+        record.level() <= level
     }
 }
 
 impl Log {
-    fn flag(&self) -> u8 {
+    /// whether the record of the target, with the stage tag of the cargo-leptos records, is of
+    /// the subsystem
+    fn matches(&self, target: &str, tag: Option<&str>) -> bool {
         match self {
-            Self::Wasm => 0b0000_0001,
-            Self::Server => 0b0000_0010,
+            Self::Server => target.starts_with("hyper") || target.starts_with("axum"),
+            Self::Wasm => target.starts_with("wasm") || target.starts_with("walrus"),
+            Self::Stage(name) => tag.is_some_and(|tag| tag.eq_ignore_ascii_case(name)),
         }
     }
 }
@@ -86,6 +216,9 @@ fn format(
     let args = record.args().to_string();
 
     let lvl_color = record.level().color();
+    if let Some(tag) = project_tag() {
+        write!(write, "{tag} ")?;
+    }
 
     if let Some(dep) = dependency(record) {
         let dep = format!("[{}]", dep);
@@ -98,6 +231,71 @@ fn format(
     }
 }
 
+/// Writes the record as a json object on a line, with the stage tag of the message as its
+/// stage, and the project being built.
+fn json_format(
+    write: &mut dyn Write,
+    now: &mut DeferredNow,
+    record: &Record<'_>,
+) -> Result<(), std::io::Error> {
+    let project = task_project().or_else(|| PROJECT.lock().unwrap().clone());
+    let record = json_record(
+        &now.format_rfc3339(),
+        record,
+        project.as_deref(),
+        STAGE.with(Cell::get),
+    );
+    write!(write, "{record}")
+}
+
+fn json_record(
+    time: &str,
+    record: &Record<'_>,
+    project: Option<&str>,
+    timed: Option<(&str, Duration)>,
+) -> Value {
+    let args = strip_ansi(&record.args().to_string());
+    let (stage, message) = match dependency(record) {
+        Some(_) => (None, args.as_str()),
+        None => match split(&args) {
+            ("", rest) => (None, rest),
+            (word, rest) => (Some(word), rest),
+        },
+    };
+    let mut value = json!({
+        "time": time,
+        "level": record.level().as_str().to_ascii_lowercase(),
+        "target": record.target(),
+        "stage": stage,
+        "project": project,
+        "message": message,
+    });
+    if let Some((stage, duration)) = timed {
+        value["stage"] = json!(stage);
+        value["duration_ms"] = json!(duration.as_millis() as u64);
+    }
+    value
+}
+
+/// the text without its ansi color codes
+pub fn strip_ansi(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // up to the final letter of the escape sequence
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
+
 fn split(args: &str) -> (&str, &str) {
     match args.find(' ') {
         Some(i) => (&args[..i], &args[i + 1..]),
@@ -123,13 +321,21 @@ impl LogLineFilter for Filter {
         record: &Record,
         log_line_writer: &dyn LogLineWriter,
     ) -> std::io::Result<()> {
-        let target = record.target();
-        if record.level() == Level::Error
-            || target.starts_with("cargo_leptos")
-            // LOG_SELECT will have been initialized by now, get_or_init() not required
-            || LOG_SELECT.get().is_some_and(|flag| flag.matches(target))
-        {
-            log_line_writer.write(now, record)?;
+        // LOG_SELECT will have been initialized by now, get_or_init() not required
+        if !LOG_SELECT.get().is_some_and(|select| select.allows(record)) {
+            return Ok(());
+        }
+        if dashboard::is_active() {
+            // the dashboard shows the lines in its logs, and the warnings in its errors
+            let mut line = Vec::new();
+            format(&mut line, now, record)?;
+            let line = String::from_utf8_lossy(&line).to_string();
+            if record.level() <= Level::Warn {
+                dashboard::error(strip_ansi(&line).trim());
+            }
+            dashboard::log_line(line);
+        } else {
+            progress::suspend(|| log_line_writer.write(now, record))?;
         }
         Ok(())
     }
@@ -150,3 +356,107 @@ impl LevelExt for Level {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{json_record, strip_ansi, LogSelect, GRAY};
+    use crate::config::{Cli, Log, LogDirective};
+    use clap::Parser;
+    use flexi_logger::{Level, LevelFilter, Record};
+    use std::time::Duration;
+
+    #[test]
+    fn test_log_select() {
+        let cli = Cli::parse_from([
+            "cargo-leptos",
+            "--log",
+            "notify=trace,Reload=debug,cargo=warn",
+            "--log",
+            "server",
+            "build",
+        ]);
+        assert_eq!(
+            cli.log[1],
+            LogDirective {
+                log: Log::Stage("reload".to_string()),
+                level: Some(LevelFilter::Debug),
+            }
+        );
+        assert!("notify=loud".parse::<LogDirective>().is_err());
+
+        let select = LogSelect::new(0, &cli.log);
+        assert_eq!(select.max_level(), LevelFilter::Trace);
+        let allows = |level: Level, target: &str, args: std::fmt::Arguments| {
+            select.allows(
+                &Record::builder()
+                    .level(level)
+                    .target(target)
+                    .args(args)
+                    .build(),
+            )
+        };
+        let own = "cargo_leptos::service::notify";
+        assert!(allows(Level::Trace, own, format_args!("Notify event")));
+        assert!(!allows(Level::Trace, own, format_args!("Front event")));
+        assert!(allows(Level::Info, own, format_args!("Front compiled")));
+        assert!(!allows(Level::Info, own, format_args!("Cargo finished")));
+        assert!(allows(Level::Warn, own, format_args!("Cargo failed")));
+        assert!(allows(
+            Level::Info,
+            "hyper::server",
+            format_args!("listening")
+        ));
+        assert!(!allows(Level::Debug, "hyper::server", format_args!("conn")));
+        assert!(!allows(
+            Level::Info,
+            "walrus::module",
+            format_args!("parsed")
+        ));
+        assert!(allows(
+            Level::Error,
+            "walrus::module",
+            format_args!("failed")
+        ));
+    }
+
+    #[test]
+    fn test_json_record() {
+        let path = GRAY.paint("target/site").to_string();
+        let record = |target: &'static str, args: std::fmt::Arguments, f: &dyn Fn(&Record)| {
+            f(&Record::builder()
+                .level(Level::Info)
+                .target(target)
+                .args(args)
+                .build())
+        };
+        assert_eq!(strip_ansi(&path), "target/site");
+
+        record(
+            "cargo_leptos::compile::front",
+            format_args!("Front compiled {path}"),
+            &|record| {
+                let value = json_record("2024-05-01T12:00:00Z", record, Some("app"), None);
+                assert_eq!(
+                    value.to_string(),
+                    r#"{"level":"info","message":"compiled target/site","project":"app","stage":"Front","target":"cargo_leptos::compile::front","time":"2024-05-01T12:00:00Z"}"#
+                );
+            },
+        );
+        record(
+            "cargo_leptos::compile::timings",
+            format_args!("Timing wasm-opt finished in 1.50s"),
+            &|record| {
+                let timed = Some(("wasm-opt", Duration::from_millis(1500)));
+                let value = json_record("", record, None, timed);
+                assert_eq!(value["stage"], "wasm-opt");
+                assert_eq!(value["duration_ms"], 1500);
+                assert!(value["project"].is_null());
+            },
+        );
+        record("hyper::server", format_args!("listening"), &|record| {
+            let value = json_record("", record, None, None);
+            assert!(value["stage"].is_null());
+            assert_eq!(value["message"], "listening");
+        });
+    }
+}
@@ -0,0 +1,85 @@
+use std::{sync::Arc, time::Duration};
+
+use crate::{
+    config::Project,
+    logger::{self, GRAY},
+    signal::{Interrupt, ReloadSignal, ReloadType},
+};
+use tokio::{process::Command, select, sync::broadcast, task::JoinHandle, time::sleep};
+
+/// Opens the site in the default browser once it answers. With `after_reload`, because the
+/// first build failed, only once the browser is told to reload after a successful rebuild.
+pub fn spawn_open(proj: &Arc<Project>, after_reload: bool) -> JoinHandle<()> {
+    let path = proj.open_path.trim_start_matches('/');
+    let url = format!("{}/{path}", proj.site.local_url());
+    let mut reload = ReloadSignal::subscribe(proj);
+    let mut int = Interrupt::subscribe_shutdown();
+
+    logger::spawn(async move {
+        let served = async {
+            if after_reload {
+                wait_full_reload(&mut reload).await;
+            }
+            wait_answering(&url).await;
+        };
+        select! {
+            _ = served => open(&url).await,
+            _ = int.recv() => {}
+        }
+    })
+}
+
+async fn wait_full_reload(rx: &mut broadcast::Receiver<ReloadType>) {
+    loop {
+        match rx.recv().await {
+            Ok(ReloadType::Full) => return,
+            Err(broadcast::error::RecvError::Closed) => std::future::pending().await,
+            _ => {}
+        }
+    }
+}
+
+/// polls the site until it answers, with any status
+async fn wait_answering(url: &str) {
+    // the certificate may not be trusted (yet) by cargo-leptos
+    let client = reqwest::Client::builder()
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap_or_default();
+    while client.get(url).send().await.is_err() {
+        sleep(Duration::from_millis(100)).await;
+    }
+}
+
+async fn open(url: &str) {
+    log::info!("Serve opening {}", GRAY.paint(url));
+    match opener(url).status().await {
+        Ok(status) if status.success() => {}
+        Ok(status) => {
+            log::warn!("Serve could not open the browser: the opener exited with {status}")
+        }
+        Err(e) => log::warn!("Serve could not open the browser: {e}"),
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn opener(url: &str) -> Command {
+    let mut command = Command::new("open");
+    command.arg(url);
+    command
+}
+
+#[cfg(windows)]
+fn opener(url: &str) -> Command {
+    let mut command = Command::new("cmd");
+    // the empty title keeps start from taking the url for one
+    command.args(["/C", "start", ""]).arg(url);
+    command
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn opener(url: &str) -> Command {
+    let mut command = Command::new("xdg-open");
+    command.arg(url);
+    command
+}
@@ -0,0 +1,505 @@
+use std::{
+    collections::VecDeque,
+    io::IsTerminal,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, Once,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    execute,
+    terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use ratatui::{
+    backend::CrosstermBackend,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::Paragraph,
+    Frame, Terminal,
+};
+
+use crate::{
+    config::Project,
+    ext::anyhow::Result,
+    logger::strip_ansi,
+    signal::{Interrupt, Outcome, Product},
+};
+
+/// whether the dashboard is drawn, for the logs and the output of the processes to go to it
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+lazy_static::lazy_static! {
+    static ref STATE: Mutex<Dashboard> = Mutex::new(Dashboard::default());
+}
+
+/// the stages listed from the start, before they first run
+const STAGES: [&str; 6] = [
+    "front",
+    "server",
+    "wasm-bindgen",
+    "wasm-opt",
+    "style",
+    "assets",
+];
+/// the log lines kept for scrolling
+const MAX_LOGS: usize = 2000;
+const MAX_ERRORS: usize = 5;
+const REDRAW: Duration = Duration::from_millis(200);
+/// how long the keys are waited for before checking that the dashboard is still drawn
+const KEY_POLL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StageStatus {
+    Pending,
+    Running(Instant),
+    Done(Duration),
+    Failed(Duration),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerStatus {
+    Stopped,
+    Starting,
+    Running,
+    Crashed(String),
+}
+
+/// What the dashboard of `watch --ui` shows: the status of the build stages and of the server,
+/// the duration of the last build, the recent errors and the logs, with the server output.
+#[derive(Debug)]
+pub struct Dashboard {
+    pub project: String,
+    pub url: String,
+    pub stages: Vec<(String, StageStatus)>,
+    pub build_started: Option<Instant>,
+    /// the duration of the last build, and whether it succeeded
+    pub last_build: Option<(Duration, bool)>,
+    pub server: ServerStatus,
+    pub errors: VecDeque<String>,
+    pub logs: VecDeque<String>,
+    /// the lines scrolled up from the end of the logs, 0 following them
+    pub scroll: usize,
+}
+
+impl Default for Dashboard {
+    fn default() -> Self {
+        Self {
+            project: String::new(),
+            url: String::new(),
+            stages: STAGES
+                .iter()
+                .map(|stage| (stage.to_string(), StageStatus::Pending))
+                .collect(),
+            build_started: None,
+            last_build: None,
+            server: ServerStatus::Stopped,
+            errors: VecDeque::new(),
+            logs: VecDeque::new(),
+            scroll: 0,
+        }
+    }
+}
+
+impl Dashboard {
+    fn set_stage(&mut self, stage: &str, status: StageStatus) {
+        match self.stages.iter_mut().find(|(name, _)| name == stage) {
+            Some((_, current)) => *current = status,
+            None => self.stages.push((stage.to_string(), status)),
+        }
+    }
+
+    fn push_log(&mut self, line: String) {
+        self.logs.push_back(line);
+        if self.logs.len() > MAX_LOGS {
+            self.logs.pop_front();
+        }
+        // the scrolled view stays on the same lines
+        if self.scroll > 0 {
+            self.scroll = (self.scroll + 1).min(self.logs.len());
+        }
+    }
+
+    /// scrolls the logs on the Up, Down, PgUp, PgDn and End keys (or k, j and G)
+    pub fn handle_key(&mut self, key: KeyCode, page: usize) {
+        let max = self.logs.len();
+        self.scroll = match key {
+            KeyCode::Up | KeyCode::Char('k') => (self.scroll + 1).min(max),
+            KeyCode::Down | KeyCode::Char('j') => self.scroll.saturating_sub(1),
+            KeyCode::PageUp => (self.scroll + page).min(max),
+            KeyCode::PageDown => self.scroll.saturating_sub(page),
+            KeyCode::End | KeyCode::Char('G') => 0,
+            _ => self.scroll,
+        };
+    }
+}
+
+pub fn is_active() -> bool {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+/// Draws the dashboard in the alternate screen of the terminal until the guard is dropped.
+/// None when stdout is not a terminal.
+pub fn spawn(proj: &Project) -> Option<DashboardGuard> {
+    if !std::io::stdout().is_terminal() {
+        log::warn!("Watch --ui needs a terminal, logging as usual");
+        return None;
+    }
+    restore_on_panic();
+    let mut terminal = match open_terminal() {
+        Ok(terminal) => terminal,
+        Err(e) => {
+            close_terminal();
+            log::warn!("Watch --ui could not draw in the terminal, logging as usual: {e}");
+            return None;
+        }
+    };
+    {
+        let mut state = STATE.lock().unwrap();
+        state.project = proj.name.clone();
+        state.url = proj.site.url();
+    }
+    ACTIVE.store(true, Ordering::SeqCst);
+    let drawer = std::thread::spawn(move || {
+        while is_active() {
+            _ = terminal.draw(|frame| render(&STATE.lock().unwrap(), frame, Instant::now()));
+            std::thread::sleep(REDRAW);
+        }
+    });
+    // the raw mode turns ctrl-c into a key, which shuts down as the signal does
+    let runtime = tokio::runtime::Handle::current();
+    let keys = std::thread::spawn(move || {
+        while is_active() {
+            // polled so that the thread stops with the dashboard, its keys left to the shell
+            match event::poll(KEY_POLL) {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(_) => return,
+            }
+            let Ok(Event::Key(key)) = event::read() else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                log::info!("Leptos ctrl-c received");
+                runtime.spawn(Interrupt::request_shutdown());
+                continue;
+            }
+            let page = terminal::size().map_or(24, |(_, height)| height as usize / 2);
+            STATE.lock().unwrap().handle_key(key.code, page);
+        }
+    });
+    Some(DashboardGuard {
+        threads: vec![drawer, keys],
+    })
+}
+
+/// Restores the terminal on a panic, before the message of the previous hook is printed, as
+/// the raw mode and the alternate screen would otherwise hide it and stay after the exit.
+fn restore_on_panic() {
+    static HOOK: Once = Once::new();
+    HOOK.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if ACTIVE.swap(false, Ordering::SeqCst) {
+                close_terminal();
+            }
+            previous(info);
+        }));
+    });
+}
+
+/// Reads the keys as they are typed, without echoing them, and draws in the alternate screen.
+fn open_terminal() -> std::io::Result<Terminal<CrosstermBackend<std::io::Stdout>>> {
+    terminal::enable_raw_mode()?;
+    execute!(std::io::stdout(), EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(std::io::stdout()))?;
+    terminal.hide_cursor()?;
+    Ok(terminal)
+}
+
+fn close_terminal() {
+    _ = terminal::disable_raw_mode();
+    _ = execute!(
+        std::io::stdout(),
+        LeaveAlternateScreen,
+        crossterm::cursor::Show
+    );
+}
+
+/// restores the terminal when dropped
+pub struct DashboardGuard {
+    /// the drawer and the keys threads, which stop once the dashboard is inactive
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl Drop for DashboardGuard {
+    fn drop(&mut self) {
+        ACTIVE.store(false, Ordering::SeqCst);
+        for thread in self.threads.drain(..) {
+            _ = thread.join();
+        }
+        close_terminal();
+        // the errors stay in sight once the screen is gone
+        for error in &STATE.lock().unwrap().errors {
+            eprintln!("{error}");
+        }
+    }
+}
+
+/// draws the dashboard in the frame, its lines cut to the width
+pub fn render(state: &Dashboard, frame: &mut Frame, now: Instant) {
+    let area = frame.area();
+    let lines = lines(state, area.height as usize, now);
+    frame.render_widget(Paragraph::new(lines), area);
+}
+
+/// the lines of the dashboard, filling the height
+fn lines(state: &Dashboard, height: usize, now: Instant) -> Vec<Line<'static>> {
+    let red = Style::new().fg(Color::Indexed(196));
+    let green = Style::new().fg(Color::Indexed(77));
+    let yellow = Style::new().fg(Color::Indexed(214));
+    let gray = Style::new().fg(Color::Indexed(241));
+    let bold = Style::new().add_modifier(Modifier::BOLD);
+    let secs = |duration: Duration| format!("{:.2}s", duration.as_secs_f64());
+
+    let server = match &state.server {
+        ServerStatus::Stopped => Span::styled("stopped", gray),
+        ServerStatus::Starting => Span::styled("starting", yellow),
+        ServerStatus::Running => Span::styled("running", green),
+        ServerStatus::Crashed(status) => Span::styled(format!("crashed ({status})"), red),
+    };
+    let build = match (state.build_started, state.last_build) {
+        (Some(started), _) => Span::styled(format!("building {}", secs(now - started)), yellow),
+        (None, Some((duration, true))) => {
+            Span::styled(format!("built in {}", secs(duration)), green)
+        }
+        (None, Some((duration, false))) => {
+            Span::styled(format!("failed after {}", secs(duration)), red)
+        }
+        (None, None) => Span::styled("not built", gray),
+    };
+    let mut lines = vec![
+        Line::from(vec![
+            Span::styled(state.project.clone(), bold),
+            Span::raw(format!(" {}  server ", state.url)),
+            server,
+            Span::raw("  "),
+            build,
+        ]),
+        Line::default(),
+    ];
+
+    lines.push(Line::styled("Stages", bold));
+    for (stage, status) in &state.stages {
+        let status = match status {
+            StageStatus::Pending => Span::styled("-", gray),
+            StageStatus::Running(started) => {
+                Span::styled(format!("running {}", secs(now - *started)), yellow)
+            }
+            StageStatus::Done(duration) => Span::styled(secs(*duration), green),
+            StageStatus::Failed(duration) => {
+                Span::styled(format!("failed after {}", secs(*duration)), red)
+            }
+        };
+        lines.push(Line::from(vec![
+            Span::raw(format!("  {stage:14} ")),
+            status,
+        ]));
+    }
+
+    lines.push(Line::default());
+    lines.push(Line::styled("Errors", bold));
+    if state.errors.is_empty() {
+        lines.push(Line::styled("  none", gray));
+    }
+    for error in &state.errors {
+        lines.push(Line::styled(format!("  {error}"), red));
+    }
+
+    lines.push(Line::default());
+    let scrolled = match state.scroll {
+        0 => String::new(),
+        lines => format!(", {lines} lines up"),
+    };
+    lines.push(Line::from(vec![
+        Span::styled("Logs", bold),
+        Span::styled(
+            format!(" (Up/Down/PgUp/PgDn scroll, End follows{scrolled})"),
+            gray,
+        ),
+    ]));
+    let rows = height.saturating_sub(lines.len());
+    let end = state.logs.len().saturating_sub(state.scroll);
+    let start = end.saturating_sub(rows);
+    // the colors of the log lines are ansi codes, which the cells don't take
+    lines.extend(
+        state
+            .logs
+            .range(start..end)
+            .map(|line| Line::raw(strip_ansi(line))),
+    );
+
+    lines.truncate(height);
+    lines
+}
+
+/// adds a line to the logs, as written by the logger or the processes
+pub fn log_line(line: impl Into<String>) {
+    STATE.lock().unwrap().push_log(line.into());
+}
+
+/// adds an error to the recent ones
+pub fn error(text: impl Into<String>) {
+    let mut state = STATE.lock().unwrap();
+    state.errors.push_back(text.into());
+    if state.errors.len() > MAX_ERRORS {
+        state.errors.pop_front();
+    }
+}
+
+/// the duration of a stage timed by the build
+pub fn stage_done(stage: &str, duration: Duration) {
+    if is_active() {
+        STATE
+            .lock()
+            .unwrap()
+            .set_stage(stage, StageStatus::Done(duration));
+    }
+}
+
+/// Shows the stage of the handle as running until it ends. The stages with nothing to build
+/// keep their previous status.
+pub fn track(
+    stage: &'static str,
+    hdl: tokio::task::JoinHandle<Result<Outcome<Product>>>,
+) -> tokio::task::JoinHandle<Result<Outcome<Product>>> {
+    if !is_active() {
+        return hdl;
+    }
+    let started = Instant::now();
+    let previous = {
+        let mut state = STATE.lock().unwrap();
+        let previous = state.stages.iter().find(|(name, _)| name == stage);
+        let previous = previous.map_or(StageStatus::Pending, |(_, status)| *status);
+        state.set_stage(stage, StageStatus::Running(started));
+        previous
+    };
+    tokio::spawn(async move {
+        let res = hdl.await?;
+        let status = match &res {
+            Ok(Outcome::Success(Product::None)) | Ok(Outcome::Stopped) => previous,
+            Ok(Outcome::Success(_)) => StageStatus::Done(started.elapsed()),
+            _ => StageStatus::Failed(started.elapsed()),
+        };
+        STATE.lock().unwrap().set_stage(stage, status);
+        res
+    })
+}
+
+pub fn build_started() {
+    if is_active() {
+        STATE.lock().unwrap().build_started = Some(Instant::now());
+    }
+}
+
+pub fn build_finished(success: bool) {
+    if !is_active() {
+        return;
+    }
+    let mut state = STATE.lock().unwrap();
+    if let Some(started) = state.build_started.take() {
+        state.last_build = Some((started.elapsed(), success));
+    }
+}
+
+pub fn server_status(status: ServerStatus) {
+    if !is_active() {
+        return;
+    }
+    STATE.lock().unwrap().server = status;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render, Dashboard, ServerStatus, StageStatus};
+    use crossterm::event::KeyCode;
+    use ratatui::{backend::TestBackend, Terminal};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_render() {
+        let now = Instant::now();
+        let mut state = Dashboard {
+            project: "app".to_string(),
+            url: "http://127.0.0.1:3000".to_string(),
+            last_build: Some((Duration::from_millis(2310), true)),
+            server: ServerStatus::Crashed("exit status: 101".to_string()),
+            ..Dashboard::default()
+        };
+        state.set_stage("front", StageStatus::Done(Duration::from_millis(1200)));
+        state.set_stage("server", StageStatus::Running(now - Duration::from_secs(3)));
+        state.set_stage("style", StageStatus::Failed(Duration::from_millis(50)));
+        state
+            .errors
+            .push_back("Dart Sass failed: expected \";\"".to_string());
+        for i in 0..30 {
+            state.push_log(format!("log {i}"));
+        }
+        let screen = |state: &Dashboard| {
+            let mut terminal = Terminal::new(TestBackend::new(80, 24)).unwrap();
+            terminal.draw(|frame| render(state, frame, now)).unwrap();
+            let buffer = terminal.backend().buffer();
+            (0..buffer.area.height)
+                .map(|y| {
+                    let line: String = (0..buffer.area.width)
+                        .map(|x| buffer[(x, y)].symbol())
+                        .collect();
+                    line.trim_end().to_string()
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+                .trim_end()
+                .to_string()
+        };
+        insta::assert_snapshot!(screen(&state), @r###"
+        app http://127.0.0.1:3000  server crashed (exit status: 101)  built in 2.31s
+
+        Stages
+          front          1.20s
+          server         running 3.00s
+          wasm-bindgen   -
+          wasm-opt       -
+          style          failed after 0.05s
+          assets         -
+
+        Errors
+          Dart Sass failed: expected ";"
+
+        Logs (Up/Down/PgUp/PgDn scroll, End follows)
+        log 20
+        log 21
+        log 22
+        log 23
+        log 24
+        log 25
+        log 26
+        log 27
+        log 28
+        log 29
+        "###);
+
+        // the view stays on the same lines while scrolled
+        state.handle_key(KeyCode::PageUp, 5);
+        state.push_log("log 30".to_string());
+        assert_eq!(state.scroll, 6);
+        assert!(screen(&state).ends_with("log 24"));
+        state.handle_key(KeyCode::Char('G'), 5);
+        assert!(screen(&state).ends_with("log 30"));
+    }
+}
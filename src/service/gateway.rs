@@ -0,0 +1,271 @@
+use std::{convert::Infallible, net::SocketAddr, sync::Arc};
+
+use crate::{
+    config::{Config, ProxyRoute},
+    ext::anyhow::{bail, Context, Result},
+    logger::GRAY,
+    service::{proxy, site::local_addr},
+    signal::Interrupt,
+};
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Request, State,
+    },
+    http::header::ACCESS_CONTROL_ALLOW_ORIGIN,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    routing::get,
+    Router,
+};
+use futures_util::{
+    future,
+    stream::{self, BoxStream},
+    StreamExt,
+};
+use reqwest::Url;
+use std::future::IntoFuture;
+use tokio::{
+    net::TcpListener,
+    select,
+    task::{JoinHandle, JoinSet},
+};
+
+struct Gateway {
+    /// the base url of each project, forwarded to its site
+    routes: Vec<ProxyRoute>,
+    client: reqwest::Client,
+}
+
+/// the reload channel of a project, relayed at a port of the gateway of its own
+struct ReloadRelay {
+    /// the event stream of the reload server of the project
+    events: Url,
+    client: reqwest::Client,
+}
+
+/// Serves the projects at a single address: each one at its `base-url`, or at the root without
+/// one. The reload channel of each project is relayed at its `gateway_port` on the ip of the
+/// gateway, which its pages connect to: a single port couldn't tell the projects apart, as the
+/// leptos client connects to `/live_reload` whatever the base-url of its page, and the handshake
+/// of its websocket doesn't say which page it is from.
+pub async fn spawn(addr: SocketAddr, config: &Config) -> Result<JoinHandle<Result<()>>> {
+    // the sites served with --tls have certificates that may not be trusted by cargo-leptos,
+    // and the redirects of the sites are passed on to the browser
+    let client = reqwest::Client::builder()
+        .danger_accept_invalid_certs(true)
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap_or_default();
+    let mut routes = Vec::new();
+    let mut relays = Vec::new();
+    for proj in &config.projects {
+        let prefix = if proj.site.base_url.is_empty() {
+            "/".to_string()
+        } else {
+            proj.site.base_url.clone()
+        };
+        // the routes are in the order of the projects until sorted
+        if let Some(other) = routes.iter().position(|r: &ProxyRoute| r.prefix == prefix) {
+            bail!(
+                "The projects {} and {} are both served at {prefix}. Give them distinct base-urls to run them behind the gateway",
+                config.projects[other].name,
+                proj.name
+            );
+        }
+        let scheme = proj.site.scheme();
+        let site = local_addr(proj.site.addr);
+        routes.push(ProxyRoute {
+            prefix,
+            target: Url::parse(&format!("{scheme}://{site}"))?,
+        });
+        // the reload channel of a relayed site goes through its site-addr
+        let reload = if proj.site.relay {
+            Url::parse(&format!("{scheme}://{site}/live_reload/events"))?
+        } else {
+            let reload = local_addr(proj.site.reload);
+            Url::parse(&format!("http://{reload}/live_reload/events"))?
+        };
+        relays.push(ReloadRelay {
+            events: reload,
+            client: client.clone(),
+        });
+    }
+    routes.sort_by_key(|route| std::cmp::Reverse(route.prefix.len()));
+
+    let listener = TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Could not serve the gateway at {addr}"))?;
+    log::info!("Serve gateway at {}", GRAY.paint(format!("http://{addr}")));
+    for route in &routes {
+        log::info!(
+            "Serving {} from {}",
+            route.prefix,
+            GRAY.paint(route.target.as_str())
+        );
+    }
+    let gateway = Arc::new(Gateway { routes, client });
+    let mut servers = JoinSet::new();
+    servers.spawn(serve(
+        listener,
+        Router::new().fallback(handle).with_state(gateway),
+    ));
+
+    for (proj, relay) in config.projects.iter().zip(relays) {
+        let port = proj.site.gateway_port.unwrap_or_default();
+        let listener = TcpListener::bind(SocketAddr::new(addr.ip(), port))
+            .await
+            .with_context(|| format!("Could not relay the reload channel of {}", proj.name))?;
+        log::debug!(
+            "Serve gateway relaying the reload channel of {} at port {port}",
+            proj.name
+        );
+        let app = Router::new()
+            .route("/live_reload", get(websocket_handler))
+            .route("/live_reload/events", get(events_handler))
+            .with_state(Arc::new(relay));
+        servers.spawn(serve(listener, app));
+    }
+    Ok(tokio::spawn(async move {
+        while let Some(res) = servers.join_next().await {
+            res??;
+        }
+        log::debug!("Serve gateway stopped");
+        Ok(())
+    }))
+}
+
+async fn serve(listener: TcpListener, app: Router) -> Result<()> {
+    let mut int = Interrupt::subscribe_shutdown();
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async move {
+            let _ = int.recv().await;
+        })
+        .into_future()
+        .await?;
+    Ok(())
+}
+
+async fn handle(State(gateway): State<Arc<Gateway>>, req: Request) -> Response {
+    match gateway.routes.iter().find(|r| r.matches(req.uri().path())) {
+        Some(route) => proxy::forward(&gateway.client, route, req).await,
+        None => {
+            log::debug!(
+                "Serve gateway has no project at {}",
+                GRAY.paint(req.uri().path())
+            );
+            (axum::http::StatusCode::NOT_FOUND, "Not found").into_response()
+        }
+    }
+}
+
+async fn websocket_handler(
+    State(relay): State<Arc<ReloadRelay>>,
+    ws: WebSocketUpgrade,
+) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| websocket(relay, socket))
+}
+
+/// sends the reload messages of the project, until a full reload or the page is closed
+async fn websocket(relay: Arc<ReloadRelay>, mut socket: WebSocket) {
+    let mut messages = event_data(relay.client.clone(), relay.events.clone());
+    loop {
+        select! {
+            msg = messages.next() => {
+                let Some(msg) = msg else { return };
+                let full = serde_json::from_str::<serde_json::Value>(&msg)
+                    .is_ok_and(|msg| msg["all"] == true);
+                if socket.send(Message::Text(msg)).await.is_err() || full {
+                    let _ = socket.close().await;
+                    return;
+                }
+            }
+            incoming = socket.recv() => {
+                if !matches!(incoming, Some(Ok(msg)) if !matches!(msg, Message::Close(_))) {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+async fn events_handler(State(relay): State<Arc<ReloadRelay>>) -> impl IntoResponse {
+    let events = event_data(relay.client.clone(), relay.events.clone())
+        .map(|data| Ok::<_, Infallible>(Event::default().data(data)));
+    (
+        [(ACCESS_CONTROL_ALLOW_ORIGIN, "*")],
+        Sse::new(events).keep_alive(KeepAlive::default()),
+    )
+}
+
+/// the data of the server-sent events of a reload server, none when it isn't running
+fn event_data(client: reqwest::Client, url: Url) -> BoxStream<'static, String> {
+    stream::once(async move {
+        match client.get(url.clone()).send().await {
+            Ok(resp) => Some(resp.bytes_stream()),
+            Err(e) => {
+                log::debug!(
+                    "Serve gateway could not reach {}: {e}",
+                    GRAY.paint(url.as_str())
+                );
+                None
+            }
+        }
+    })
+    .filter_map(future::ready)
+    .flatten()
+    .scan(Vec::new(), |buf, chunk| {
+        let Ok(chunk) = chunk else {
+            return future::ready(None);
+        };
+        buf.extend_from_slice(&chunk);
+        future::ready(Some(stream::iter(take_events(buf))))
+    })
+    .flatten()
+    .boxed()
+}
+
+/// The data of the complete events of the buffer, which are removed from it. They are decoded
+/// once complete, as the chunks of the stream may split a char.
+fn take_events(buf: &mut Vec<u8>) -> Vec<String> {
+    let mut events = Vec::new();
+    while let Some(end) = buf.windows(2).position(|w| w == b"\n\n") {
+        let event = String::from_utf8_lossy(&buf[..end]).into_owned();
+        let data = event
+            .lines()
+            .filter_map(|line| line.strip_prefix("data:"))
+            .map(|data| data.strip_prefix(' ').unwrap_or(data))
+            .collect::<Vec<_>>();
+        if !data.is_empty() {
+            events.push(data.join("\n"));
+        }
+        buf.drain(..end + 2);
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::take_events;
+
+    #[test]
+    fn test_take_events() {
+        let stream =
+            "data: {\"error\":\"expected `é`\"}\n\n:keep-alive\n\ndata: {\"all\":true}\n\n";
+        let bytes = stream.as_bytes();
+        // split inside the é
+        let split = stream.find('é').unwrap() + 1;
+        let mut buf = bytes[..split].to_vec();
+        assert!(take_events(&mut buf).is_empty());
+        buf.extend_from_slice(&bytes[split..bytes.len() - 1]);
+        assert_eq!(
+            take_events(&mut buf),
+            vec!["{\"error\":\"expected `é`\"}".to_string()]
+        );
+        buf.push(b'\n');
+        assert_eq!(take_events(&mut buf), vec!["{\"all\":true}".to_string()]);
+        assert!(buf.is_empty());
+    }
+}
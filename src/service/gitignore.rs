@@ -0,0 +1,182 @@
+use crate::logger::GRAY;
+use camino::{Utf8Path, Utf8PathBuf};
+use ignore::{
+    gitignore::{Gitignore, GitignoreBuilder},
+    Match, WalkBuilder,
+};
+use std::collections::BTreeMap;
+
+/// the per dir ignore files, by increasing precedence
+const IGNORE_FILES: &[&str] = &[".gitignore", ".ignore"];
+
+/// The ignore files of the watched dirs, matched the way git does: the deepest dir with a rule
+/// for the path decides, and a `!negated` rule re-includes. Within a dir, `.ignore` takes
+/// precedence over `.gitignore`, which takes precedence over `.git/info/exclude`. The watched
+/// roots are configured explicitly, so the ones that are ignored are watched as a whole.
+pub struct GitIgnores {
+    workspace: Utf8PathBuf,
+    roots: Vec<Utf8PathBuf>,
+    /// the roots that are ignored, whose files are never ignored
+    ignored_roots: Vec<Utf8PathBuf>,
+    /// the matchers by dir, the deepest first
+    dirs: Vec<Gitignore>,
+    /// the global git excludes (`core.excludesFile`)
+    global: Gitignore,
+}
+
+impl GitIgnores {
+    /// Finds the ignore files of the (absolute) roots, their sub dirs and their parent dirs up to
+    /// the git repository, or the workspace root when it isn't in a repository.
+    pub fn new(workspace: &Utf8Path, roots: Vec<Utf8PathBuf>) -> Self {
+        let mut files: BTreeMap<Utf8PathBuf, Vec<Utf8PathBuf>> = BTreeMap::new();
+
+        let repo = workspace
+            .ancestors()
+            .find(|dir| dir.join(".git").exists())
+            .unwrap_or(workspace)
+            .to_path_buf();
+
+        for root in &roots {
+            for dir in root.ancestors().skip(1) {
+                if !dir.starts_with(&repo) {
+                    break;
+                }
+                add_ignore_files(&mut files, dir);
+            }
+            // the walk doesn't enter the ignored dirs
+            let walk = WalkBuilder::new(root)
+                .hidden(false)
+                .require_git(false)
+                .filter_entry(|entry| entry.file_name() != ".git")
+                .build();
+            for entry in walk.flatten() {
+                if entry.file_type().is_some_and(|t| t.is_dir()) {
+                    if let Some(dir) = Utf8Path::from_path(entry.path()) {
+                        add_ignore_files(&mut files, dir);
+                    }
+                }
+            }
+        }
+
+        let mut dirs = files
+            .into_iter()
+            .filter_map(|(dir, files)| {
+                let mut builder = GitignoreBuilder::new(&dir);
+                for file in &files {
+                    if let Some(e) = builder.add(file) {
+                        log::warn!("Notify could not read {}: {e}", GRAY.paint(file.as_str()));
+                    }
+                }
+                builder
+                    .build()
+                    .map_err(|e| log::warn!("Notify invalid ignore file in {dir}: {e}"))
+                    .ok()
+            })
+            .collect::<Vec<_>>();
+        dirs.sort_by_key(|gitignore| std::cmp::Reverse(gitignore.path().components().count()));
+
+        let (global, err) = GitignoreBuilder::new(&repo).build_global();
+        if let Some(e) = err {
+            log::debug!("Notify could not read the global git excludes: {e}");
+        }
+        log::trace!(
+            "Notify found the ignore files of {} dirs",
+            GRAY.paint(dirs.len().to_string())
+        );
+
+        let mut ignores = Self {
+            workspace: workspace.to_path_buf(),
+            roots,
+            ignored_roots: Vec::new(),
+            dirs,
+            global,
+        };
+        ignores.ignored_roots = ignores
+            .roots
+            .iter()
+            .filter(|root| ignores.matches(root, root.is_dir()))
+            .cloned()
+            .collect();
+        for root in &ignores.ignored_roots {
+            log::debug!(
+                "Notify watching the ignored {} as it is configured",
+                GRAY.paint(root.as_str())
+            );
+        }
+        ignores
+    }
+
+    /// re-reads the ignore files, after one of them changed
+    pub fn reload(&self) -> Self {
+        Self::new(&self.workspace, self.roots.clone())
+    }
+
+    /// whether the (absolute) path is ignored, and not in an ignored root
+    pub fn is_ignored(&self, path: &Utf8Path, is_dir: bool) -> bool {
+        !self.ignored_roots.iter().any(|root| path.starts_with(root)) && self.matches(path, is_dir)
+    }
+
+    fn matches(&self, path: &Utf8Path, is_dir: bool) -> bool {
+        for gitignore in &self.dirs {
+            if !path.starts_with(gitignore.path()) {
+                continue;
+            }
+            match gitignore.matched_path_or_any_parents(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+        path.starts_with(self.global.path())
+            && self
+                .global
+                .matched_path_or_any_parents(path, is_dir)
+                .is_ignore()
+    }
+}
+
+/// whether a change to the file (relative or not) changes what is ignored
+pub fn is_ignore_file(path: &Utf8Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| IGNORE_FILES.contains(&name))
+        || path.ends_with(".git/info/exclude")
+}
+
+fn add_ignore_files(files: &mut BTreeMap<Utf8PathBuf, Vec<Utf8PathBuf>>, dir: &Utf8Path) {
+    if files.contains_key(dir) {
+        return;
+    }
+    let exclude = dir.join(".git/info/exclude");
+    let found = std::iter::once(exclude)
+        .chain(IGNORE_FILES.iter().map(|name| dir.join(name)))
+        .filter(|file| file.is_file())
+        .collect::<Vec<_>>();
+    if !found.is_empty() {
+        files.insert(dir.to_path_buf(), found);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GitIgnores;
+    use camino::Utf8Path;
+
+    #[test]
+    fn test_ignored_roots() {
+        let dir = temp_dir::TempDir::new().unwrap();
+        let root = Utf8Path::from_path(dir.path()).unwrap();
+        for sub in ["src", "generated", "other"] {
+            std::fs::create_dir_all(root.join(sub)).unwrap();
+        }
+        std::fs::write(root.join(".gitignore"), "generated/\nother/\n*.gen.rs\n").unwrap();
+
+        let ignores = GitIgnores::new(root, vec![root.join("src"), root.join("generated")]);
+        // the configured dir is watched as a whole
+        assert!(!ignores.is_ignored(&root.join("generated/data.json"), false));
+        assert!(!ignores.is_ignored(&root.join("generated/code.gen.rs"), false));
+        // the rules still apply in the other dirs
+        assert!(ignores.is_ignored(&root.join("src/code.gen.rs"), false));
+        assert!(!ignores.is_ignored(&root.join("src/lib.rs"), false));
+        assert!(ignores.is_ignored(&root.join("other/file.txt"), false));
+    }
+}
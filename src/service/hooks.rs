@@ -0,0 +1,99 @@
+use std::sync::Arc;
+
+use crate::{
+    config::Project,
+    ext::{
+        anyhow::{Context, Result},
+        sync::{wait_interruptible, CommandResult},
+    },
+    logger::{self, GRAY},
+    signal::{Interrupt, Outcome, ReloadSignal, ReloadType},
+};
+use tokio::{process::Command, sync::broadcast, task::JoinHandle};
+
+/// Runs the hook's shell command from the workspace root, with the `LEPTOS_*` env vars of the
+/// project. It is killed when cargo-leptos shuts down.
+pub async fn run_hook(proj: &Project, name: &str, cmd: &str) -> Result<Outcome<()>> {
+    run(proj, name, cmd, Interrupt::subscribe_shutdown()).await
+}
+
+async fn run(
+    proj: &Project,
+    name: &str,
+    cmd: &str,
+    interrupt: broadcast::Receiver<()>,
+) -> Result<Outcome<()>> {
+    log::info!("Hooks running {name}: {}", GRAY.paint(cmd));
+    let mut command = shell(cmd);
+    command
+        .current_dir(&proj.working_dir)
+        .envs(proj.to_envs())
+        .kill_on_drop(true);
+    let process = command
+        .spawn()
+        .context(format!("Could not spawn the {name} hook {cmd:?}"))?;
+
+    let outcome = match wait_interruptible("Hook", process, interrupt).await? {
+        CommandResult::Success(()) => Outcome::Success(()),
+        CommandResult::Interrupted => Outcome::Stopped,
+        CommandResult::Failure(()) => {
+            log::warn!("Hooks {name} failed: {}", GRAY.paint(cmd));
+            Outcome::Failed
+        }
+    };
+    Ok(outcome)
+}
+
+/// the on-change hooks with the indexes, in order
+pub async fn run_change_hooks(proj: &Project, indexes: &[usize]) -> Result<Outcome<()>> {
+    for index in indexes {
+        let Some(hook) = proj.hooks.on_change.get(*index) else {
+            continue;
+        };
+        let name = format!("on-change {}", hook.patterns.join(", "));
+        match run_hook(proj, &name, &hook.cmd).await? {
+            Outcome::Success(()) => {}
+            outcome => return Ok(outcome),
+        }
+    }
+    Ok(Outcome::Success(()))
+}
+
+/// Runs the post-reload hook each time the browser is told to reload. A run still going
+/// when the sources change again is stopped.
+pub fn spawn_post_reload(proj: &Arc<Project>) -> Option<JoinHandle<()>> {
+    let cmd = proj.hooks.post_reload.clone()?;
+    let proj = proj.clone();
+    let mut rx = ReloadSignal::subscribe(&proj);
+
+    Some(logger::spawn(async move {
+        loop {
+            match rx.recv().await {
+                Ok(ReloadType::Full | ReloadType::Style) => {}
+                Ok(ReloadType::ViewPatches(_) | ReloadType::Error(_)) => continue,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return,
+            }
+            if Interrupt::is_shutdown_requested().await {
+                return;
+            }
+            if let Err(e) = run(&proj, "post-reload", &cmd, Interrupt::subscribe_any(&proj)).await {
+                log::error!("Hooks {e:#}");
+            }
+        }
+    }))
+}
+
+#[cfg(not(windows))]
+fn shell(cmd: &str) -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(cmd);
+    command
+}
+
+#[cfg(windows)]
+fn shell(cmd: &str) -> Command {
+    let mut command = Command::new("cmd");
+    command.arg("/C").arg(cmd);
+    command
+}
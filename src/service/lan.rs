@@ -0,0 +1,114 @@
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+
+use crate::service::site::Site;
+use ansi_term::Colour::{Black, White};
+use qrcode::{Color, EcLevel, QrCode};
+
+/// the light modules around the code, in the terminal
+const QUIET_ZONE: usize = 2;
+
+/// Logs the urls of the site on the local network when it listens beyond the loopback, with the
+/// QR code of the first one, so that phones can open it.
+pub fn announce(site: &Site) {
+    let urls = lan_ips(site.addr.ip())
+        .into_iter()
+        .map(|ip| site.url_at(ip))
+        .collect::<Vec<_>>();
+    let Some(first) = urls.first() else {
+        return;
+    };
+    for url in &urls {
+        log::info!("Serve on the network at {url}");
+    }
+    match QrCode::with_error_correction_level(first.as_bytes(), EcLevel::L) {
+        // a single record, the lines aligned after the tag
+        Ok(qr) => log::info!(
+            "Serve the QR code of {first}\n{}",
+            qr_lines(&qr)
+                .iter()
+                .map(|line| format!("{:12} {line}", ""))
+                .collect::<Vec<_>>()
+                .join("\n")
+        ),
+        Err(e) => log::debug!("Serve no QR code of {first}: {e}"),
+    }
+}
+
+/// The lines of the code drawn with half blocks, two rows per line, dark on light whatever the
+/// colors of the terminal.
+fn qr_lines(qr: &QrCode) -> Vec<String> {
+    let size = qr.width();
+    let colors = qr.to_colors();
+    let full = size + QUIET_ZONE * 2;
+    let dark = |x: usize, y: usize| {
+        (QUIET_ZONE..size + QUIET_ZONE).contains(&x)
+            && (QUIET_ZONE..size + QUIET_ZONE).contains(&y)
+            && colors[(y - QUIET_ZONE) * size + x - QUIET_ZONE] == Color::Dark
+    };
+    (0..full)
+        .step_by(2)
+        .map(|y| {
+            let line = (0..full)
+                .map(|x| match (dark(x, y), dark(x, y + 1)) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                })
+                .collect::<String>();
+            Black.on(White).paint(line).to_string()
+        })
+        .collect()
+}
+
+/// the addresses of the machine the site can be reached at from the local network
+fn lan_ips(ip: IpAddr) -> Vec<IpAddr> {
+    if ip.is_loopback() {
+        return Vec::new();
+    }
+    if !ip.is_unspecified() {
+        return vec![ip];
+    }
+    // the source addresses of the routes out, found without sending anything
+    let mut probes = vec![("0.0.0.0:0", "192.0.2.1:80")];
+    if ip.is_ipv6() {
+        // usually dual-stack
+        probes.push(("[::]:0", "[2001:db8::1]:80"));
+    }
+    probes
+        .into_iter()
+        .filter_map(|(bind, remote)| {
+            let socket = UdpSocket::bind(bind).ok()?;
+            socket.connect(remote).ok()?;
+            socket.local_addr().ok().map(|addr: SocketAddr| addr.ip())
+        })
+        .filter(|ip| !ip.is_loopback() && !ip.is_unspecified() && !is_link_local(ip))
+        .collect()
+}
+
+fn is_link_local(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_link_local(),
+        IpAddr::V6(ip) => (ip.segments()[0] & 0xffc0) == 0xfe80,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{qr_lines, QUIET_ZONE};
+    use crate::logger::strip_ansi;
+    use qrcode::{EcLevel, QrCode};
+
+    #[test]
+    fn test_qr_lines() {
+        let qr =
+            QrCode::with_error_correction_level(b"http://192.168.1.2:3000", EcLevel::L).unwrap();
+        let lines: Vec<String> = qr_lines(&qr).iter().map(|l| strip_ansi(l)).collect();
+        let full = qr.width() + QUIET_ZONE * 2;
+        assert_eq!(lines.len(), full.div_ceil(2));
+        assert!(lines.iter().all(|line| line.chars().count() == full));
+        assert_eq!(lines[0].trim(), "");
+        // the top of the finder pattern of the top left corner
+        assert!(lines[1].starts_with("  █▀▀▀▀▀█ "));
+    }
+}
@@ -1,5 +1,16 @@
+pub mod browser;
+pub mod dashboard;
+pub mod gateway;
+pub mod gitignore;
+pub mod hooks;
+pub mod lan;
+pub mod notification;
 pub mod notify;
 pub mod patch;
+pub mod proxy;
+pub mod relay;
 pub mod reload;
 pub mod serve;
 pub mod site;
+pub mod static_server;
+pub mod tls;
@@ -0,0 +1,74 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use notify_rust::Notification;
+
+use crate::config::Project;
+
+/// whether the ends of the watch builds are notified, with `watch --notify`
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Notifies the end of a successful build, for the developer in another window to know that
+/// the site is up to date.
+pub fn build_succeeded(proj: &Project) {
+    notify(
+        format!("{} built", proj.name),
+        format!("Served at {}", proj.site.url()),
+    );
+}
+
+/// notifies the failure of a build, with the first line of its errors
+pub fn build_failed(proj: &Project, errors: Option<&str>) {
+    notify(format!("{} build failed", proj.name), failure_body(errors));
+}
+
+/// the failed step and the first line of its output, as recorded for the error overlay
+fn failure_body(errors: Option<&str>) -> String {
+    let lines: Vec<&str> = errors
+        .into_iter()
+        .flat_map(str::lines)
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .take(2)
+        .collect();
+    match lines.is_empty() {
+        true => "See the logs".to_string(),
+        false => lines.join(": ").chars().take(200).collect(),
+    }
+}
+
+fn notify(title: String, body: String) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    // the build goes on without waiting for the notification server
+    tokio::task::spawn_blocking(move || {
+        let shown = Notification::new()
+            .appname("cargo-leptos")
+            .summary(&title)
+            .body(&body)
+            .show();
+        if let Err(e) = shown {
+            log::debug!("Watch could not notify: {e}");
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::failure_body;
+
+    #[test]
+    fn test_notification() {
+        assert_eq!(
+            failure_body(Some(
+                "Cargo server build failed\n\nerror[E0425]: cannot find value `x`\n --> src/main.rs"
+            )),
+            "Cargo server build failed: error[E0425]: cannot find value `x`"
+        );
+        assert_eq!(failure_body(None), "See the logs");
+    }
+}
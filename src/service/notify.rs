@@ -1,35 +1,112 @@
-use crate::compile::Change;
-use crate::config::Project;
+use crate::compile::{sass_dependencies, Change};
+use crate::config::{Project, StyleCollector, WatchBackend};
 use crate::ext::anyhow::{anyhow, Result};
+use crate::service::gitignore::{is_ignore_file, GitIgnores};
 use crate::signal::Interrupt;
 use crate::{
-    ext::{remove_nested, PathBufExt, PathExt},
+    ext::{glob_base, remove_nested, PathBufExt, PathExt},
     logger::GRAY,
 };
-use camino::Utf8PathBuf;
+use camino::{Utf8Path, Utf8PathBuf};
 use itertools::Itertools;
-use notify::event::ModifyKind;
-use notify::{Event, EventKind, RecursiveMode, Watcher};
-use std::collections::HashSet;
+use notify::event::{MetadataKind, ModifyKind};
+use notify::{
+    Event, EventHandler, EventKind, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher,
+};
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use std::time::Duration;
 use tokio::task::JoinHandle;
 
 pub(crate) const FALLBACK_POLLING_TIMEOUT: Duration = Duration::from_millis(200);
 
-pub async fn spawn(proj: &Arc<Project>) -> Result<JoinHandle<()>> {
+lazy_static::lazy_static! {
+    /// the sass files imported by the style files of each project, by name
+    static ref STYLE_DEPS: RwLock<HashMap<String, HashSet<Utf8PathBuf>>> = RwLock::new(HashMap::new());
+    /// the ignore files of the watched paths of each project, when watch-gitignore is on
+    static ref GIT_IGNORES: RwLock<HashMap<String, GitIgnores>> = RwLock::new(HashMap::new());
+}
+
+/// Watches the files of the projects, with a single watcher of the configured backend (the one
+/// of the first project), sending the changes of each project to its watch loop.
+pub async fn spawn(projects: &[Arc<Project>]) -> Result<JoinHandle<()>> {
+    let mut set = HashSet::new();
+    for proj in projects {
+        let paths = project_paths(proj);
+        // the paths are all configured, so they are watched even when ignored
+        if proj.watch_gitignore {
+            let roots = paths
+                .iter()
+                .map(|path| proj.working_dir.join(path))
+                .collect();
+            let ignores = GitIgnores::new(&proj.working_dir, roots);
+            GIT_IGNORES
+                .write()
+                .unwrap()
+                .insert(proj.name.clone(), ignores);
+        }
+        set.extend(paths);
+    }
+    let paths = remove_nested(set.into_iter());
+
+    log::info!(
+        "Notify watching paths {}",
+        GRAY.paint(paths.iter().join(", "))
+    );
+    let projects = projects.to_vec();
+
+    Ok(tokio::spawn(async move { run(&paths, projects).await }))
+}
+
+/// the existing paths watched for the project
+fn project_paths(proj: &Project) -> Vec<Utf8PathBuf> {
     let mut set: HashSet<Utf8PathBuf> = HashSet::from_iter(vec![]);
 
     set.extend(proj.lib.src_paths.clone());
-    set.extend(proj.bin.src_paths.clone());
-    set.extend(proj.watch_additional_files.clone());
+    set.extend(proj.lib.front_src_paths.clone());
+    if let Some(bin) = &proj.bin {
+        set.extend(bin.src_paths.clone());
+    }
+    set.extend(proj.watch_additional_paths());
     set.insert(proj.js_dir.clone());
+    set.extend(
+        proj.hooks
+            .on_change
+            .iter()
+            .flat_map(|hook| &hook.patterns)
+            .filter_map(|pattern| glob_base(pattern)),
+    );
 
-    if let Some(file) = &proj.style.file {
+    for file in &proj.style.files {
         set.insert(file.source.clone().without_last());
     }
 
+    // partials can live outside of the style file's dir, e.g. in another crate
+    let style_deps = style_dependencies(proj);
+    set.extend(style_deps.iter().map(|dep| dep.clone().without_last()));
+    STYLE_DEPS
+        .write()
+        .unwrap()
+        .insert(proj.name.clone(), style_deps);
+
+    if let Some(modules) = &proj.style.modules {
+        set.insert(modules.dir.clone());
+    }
+
+    if let Some(collector) = &proj.style.collector {
+        set.extend(collector.sources.clone());
+        // stylance's files are written by the style build itself
+        if collector.kind == StyleCollector::Stylers {
+            set.extend(
+                collector
+                    .files
+                    .iter()
+                    .map(|file| file.clone().without_last()),
+            );
+        }
+    }
+
     if let Some(tailwind) = &proj.style.tailwind {
         set.insert(tailwind.config_file.clone());
         set.insert(tailwind.input_file.clone());
@@ -39,25 +116,21 @@ pub async fn spawn(proj: &Arc<Project>) -> Result<JoinHandle<()>> {
         set.insert(assets.dir.clone());
     }
 
-    let paths = remove_nested(set.into_iter().filter(|path| Path::new(path).exists()));
-
-    log::info!(
-        "Notify watching paths {}",
-        GRAY.paint(paths.iter().join(", "))
-    );
-    let proj = proj.clone();
-
-    Ok(tokio::spawn(async move { run(&paths, proj).await }))
+    remove_nested(set.into_iter().filter(|path| Path::new(path).exists()))
 }
 
-async fn run(paths: &[Utf8PathBuf], proj: Arc<Project>) {
-    let (sync_tx, sync_rx) = std::sync::mpsc::channel();
+async fn run(paths: &[Utf8PathBuf], projects: Vec<Arc<Project>>) {
+    let (sync_tx, sync_rx) = std::sync::mpsc::channel::<notify::Result<Event>>();
 
-    let proj = proj.clone();
+    let proj = projects[0].clone();
     tokio::task::spawn_blocking(move || {
         while let Ok(event) = sync_rx.recv() {
             match event {
-                Ok(event) => handle(event, proj.clone()),
+                Ok(event) => {
+                    for proj in &projects {
+                        handle(event.clone(), proj);
+                    }
+                }
                 Err(err) => {
                     log::trace!("Notify error: {err:?}");
                     return;
@@ -67,11 +140,7 @@ async fn run(paths: &[Utf8PathBuf], proj: Arc<Project>) {
         log::debug!("Notify stopped");
     });
 
-    let mut watcher = notify::RecommendedWatcher::new(
-        sync_tx,
-        notify::Config::default().with_poll_interval(FALLBACK_POLLING_TIMEOUT),
-    )
-    .expect("failed to build file system watcher");
+    let mut watcher = watcher(&proj, sync_tx).expect("failed to build file system watcher");
 
     for path in paths {
         if let Err(e) = watcher.watch(Path::new(path), RecursiveMode::Recursive) {
@@ -84,7 +153,69 @@ async fn run(paths: &[Utf8PathBuf], proj: Arc<Project>) {
     }
 }
 
-fn handle(event: Event, proj: Arc<Project>) {
+/// Creates the watcher of the configured backend. The auto backend polls when the workspace is
+/// on a file system known to miss the native events.
+pub(crate) fn watcher<F: EventHandler>(
+    proj: &Project,
+    handler: F,
+) -> notify::Result<Box<dyn Watcher + Send>> {
+    let poll = match proj.watch_backend {
+        WatchBackend::Native => false,
+        WatchBackend::Poll => true,
+        WatchBackend::Auto => match unreliable_fs(&proj.working_dir) {
+            Some(fs) => {
+                log::info!("Notify polling for changes as the workspace is on a {fs} file system");
+                true
+            }
+            None => false,
+        },
+    };
+    if poll {
+        log::debug!(
+            "Notify polling every {}ms",
+            proj.watch_poll_interval.as_millis()
+        );
+        let config = notify::Config::default().with_poll_interval(proj.watch_poll_interval);
+        Ok(Box::new(PollWatcher::new(handler, config)?))
+    } else {
+        let config = notify::Config::default().with_poll_interval(FALLBACK_POLLING_TIMEOUT);
+        Ok(Box::new(RecommendedWatcher::new(handler, config)?))
+    }
+}
+
+/// file systems whose changes aren't (always) reported by the native events
+const UNRELIABLE_FS: &[&str] = &[
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smb3",
+    "smbfs",
+    "9p",
+    "drvfs",
+    "vboxsf",
+    "virtiofs",
+    "fakeowner",
+    "fuse.grpcfuse",
+    "fuse.sshfs",
+];
+
+/// the type of the file system of the dir, read from `/proc/mounts`, when it misses events
+fn unreliable_fs(dir: &Utf8Path) -> Option<String> {
+    let mounts = std::fs::read_to_string("/proc/mounts").ok()?;
+    let (_, fs) = mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_ascii_whitespace().skip(1);
+            // spaces in the mount points are escaped
+            let mount = fields.next()?.replace("\\040", " ");
+            Some((mount, fields.next()?))
+        })
+        .filter(|(mount, _)| dir.starts_with(mount))
+        .max_by_key(|(mount, _)| mount.len())?;
+    UNRELIABLE_FS.contains(&fs).then(|| fs.to_string())
+}
+
+fn handle(event: Event, proj: &Project) {
     if event.paths.is_empty() {
         return;
     }
@@ -92,18 +223,23 @@ fn handle(event: Event, proj: Arc<Project>) {
     if let EventKind::Any
     | EventKind::Other
     | EventKind::Access(_)
-    | EventKind::Modify(ModifyKind::Any | ModifyKind::Other | ModifyKind::Metadata(_)) =
-        event.kind
+    | EventKind::Modify(ModifyKind::Any | ModifyKind::Other) = event.kind
     {
         return;
     };
+    // the poll watcher reports the changed files by their modification time
+    if let EventKind::Modify(ModifyKind::Metadata(kind)) = event.kind {
+        if kind != MetadataKind::WriteTime {
+            return;
+        }
+    }
 
     log::trace!("Notify handle {}", GRAY.paint(format!("{:?}", event.paths)));
 
     let paths: Vec<_> = event
         .paths
         .into_iter()
-        .filter_map(|p| match convert(&p, &proj) {
+        .filter_map(|p| match convert(&p, proj) {
             Ok(p) => Some(p),
             Err(e) => {
                 log::info!("{e}");
@@ -115,14 +251,47 @@ fn handle(event: Event, proj: Arc<Project>) {
     let mut changes = Vec::new();
 
     for path in paths {
+        if proj.is_watch_ignored(&path) {
+            log::trace!("Notify ignored change {}", GRAY.paint(path.to_string()));
+            continue;
+        }
+
+        if proj.watch_gitignore {
+            let mut ignores = GIT_IGNORES.write().unwrap();
+            if let Some(current) = ignores.get_mut(&proj.name) {
+                if is_ignore_file(&path) {
+                    log::debug!("Notify ignore file change {}", GRAY.paint(path.to_string()));
+                    *current = current.reload();
+                    continue;
+                }
+                if is_gitignored(current, &path, proj) && !is_configured_file(&path, proj) {
+                    log::trace!("Notify git ignored change {}", GRAY.paint(path.to_string()));
+                    continue;
+                }
+            }
+        }
+
         if let Some(assets) = &proj.assets {
-            if path.starts_with(&assets.dir) {
+            let included = path
+                .unbase(&assets.dir)
+                .is_ok_and(|rel| assets.is_included(&rel));
+            if included {
                 log::debug!("Notify asset change {}", GRAY.paint(path.to_string()));
                 changes.push(Change::Asset);
             }
         }
 
-        let lib_rs = path.starts_with_any(&proj.lib.src_paths) && path.is_ext_any(&["rs"]);
+        if proj.patch_only()
+            && path.starts_with_any(&proj.lib.front_src_paths)
+            && path.is_ext_any(&["rs"])
+        {
+            log::trace!("Notify patch only change {}", GRAY.paint(path.to_string()));
+            continue;
+        }
+
+        let lib_rs = (path.starts_with_any(&proj.lib.src_paths)
+            || path.starts_with_any(&proj.lib.front_src_paths))
+            && path.is_ext_any(&["rs"]);
         let lib_js = path.starts_with(&proj.js_dir) && path.is_ext_any(&["js"]);
 
         if lib_rs || lib_js {
@@ -130,15 +299,50 @@ fn handle(event: Event, proj: Arc<Project>) {
             changes.push(Change::LibSource);
         }
 
-        if path.starts_with_any(&proj.bin.src_paths) && path.is_ext_any(&["rs"]) {
+        let bin_rs = proj
+            .bin
+            .as_ref()
+            .is_some_and(|bin| path.starts_with_any(&bin.src_paths));
+        if bin_rs && path.is_ext_any(&["rs"]) {
             log::debug!("Notify bin source change {}", GRAY.paint(path.to_string()));
             changes.push(Change::BinSource);
         }
 
-        if let Some(file) = &proj.style.file {
-            let src = file.source.clone().without_last();
-            if path.starts_with(src) && path.is_ext_any(&["scss", "sass", "css"]) {
-                log::debug!("Notify style change {}", GRAY.paint(path.to_string()));
+        let style_dir = proj
+            .style
+            .files
+            .iter()
+            .any(|file| path.starts_with(file.source.clone().without_last()));
+        let style_dep = STYLE_DEPS
+            .read()
+            .unwrap()
+            .get(&proj.name)
+            .is_some_and(|deps| deps.contains(&path));
+        if (style_dir && path.is_ext_any(&["scss", "sass", "css"])) || style_dep {
+            log::debug!("Notify style change {}", GRAY.paint(path.to_string()));
+            changes.push(Change::Style)
+        }
+
+        if let Some(modules) = &proj.style.modules {
+            if path.starts_with(&modules.dir) && path.is_ext_any(&["css", "scss", "sass"]) {
+                log::debug!("Notify css module change {}", GRAY.paint(path.to_string()));
+                changes.push(Change::Style)
+            }
+        }
+
+        if let Some(collector) = &proj.style.collector {
+            let stylance_module = path.starts_with_any(&collector.sources)
+                && collector
+                    .extensions
+                    .iter()
+                    .any(|ext| path.as_str().ends_with(ext.as_str()));
+            let stylers_css =
+                collector.kind == StyleCollector::Stylers && collector.files.contains(&path);
+            if stylance_module || stylers_css {
+                log::debug!(
+                    "Notify collected style change {}",
+                    GRAY.paint(path.to_string())
+                );
                 changes.push(Change::Style)
             }
         }
@@ -152,7 +356,7 @@ fn handle(event: Event, proj: Arc<Project>) {
             }
         }
 
-        if path.starts_with_any(&proj.watch_additional_files) {
+        if proj.is_additional_file(&path) {
             log::debug!(
                 "Notify additional file change {}",
                 GRAY.paint(path.to_string())
@@ -160,8 +364,18 @@ fn handle(event: Event, proj: Arc<Project>) {
             changes.push(Change::Additional);
         }
 
+        for index in proj.hooks.matching(&path) {
+            log::debug!("Notify hook file change {}", GRAY.paint(path.to_string()));
+            changes.push(Change::Hook(index));
+        }
+
         if !changes.is_empty() {
-            Interrupt::send(&changes);
+            if changes.contains(&Change::Style) {
+                // the imports might have changed
+                let deps = style_dependencies(proj);
+                STYLE_DEPS.write().unwrap().insert(proj.name.clone(), deps);
+            }
+            Interrupt::send(proj, &changes);
         } else {
             log::trace!(
                 "Notify changed but not watched: {}",
@@ -171,6 +385,39 @@ fn handle(event: Event, proj: Arc<Project>) {
     }
 }
 
+fn is_gitignored(ignores: &GitIgnores, path: &Utf8Path, proj: &Project) -> bool {
+    let path = proj.working_dir.join(path);
+    ignores.is_ignored(&path, path.is_dir())
+}
+
+/// The files watched by configuration, even when ignored: the additional files and the ones
+/// matching the additional globs, and the generated files such as the collected css of stylers,
+/// usually in ignored dirs such as `target`.
+fn is_configured_file(path: &Utf8Path, proj: &Project) -> bool {
+    let collected = proj
+        .style
+        .collector
+        .as_ref()
+        .is_some_and(|collector| collector.files.iter().any(|file| file == path));
+    collected || proj.is_additional_file(path)
+}
+
+/// the sass files imported by the style files, relative to the working dir like the
+/// paths of the notify events
+fn style_dependencies(proj: &Project) -> HashSet<Utf8PathBuf> {
+    let sources = proj
+        .style
+        .files
+        .iter()
+        .map(|file| file.source.clone())
+        .collect::<Vec<_>>();
+    sass_dependencies(&sources, &proj.style.sass_load_paths)
+        .into_iter()
+        .filter_map(|dep| dep.canonicalize_utf8().ok())
+        .map(|dep| dep.unbase(&proj.working_dir).unwrap_or(dep))
+        .collect()
+}
+
 pub(crate) fn convert(p: &Path, proj: &Project) -> Result<Utf8PathBuf> {
     let p = Utf8PathBuf::from_path_buf(p.to_path_buf())
         .map_err(|e| anyhow!("Could not convert to a Utf8PathBuf: {e:?}"))?;
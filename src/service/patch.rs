@@ -1,13 +1,16 @@
-use crate::config::Project;
+use crate::config::{HotPatchFallback, Project};
 use crate::ext::anyhow::Result;
 use crate::ext::PathBufExt;
 use crate::signal::{Interrupt, ReloadSignal};
-use crate::{ext::remove_nested, logger::GRAY};
+use crate::{
+    ext::remove_nested,
+    logger::{self, GRAY},
+};
 use camino::Utf8PathBuf;
 use itertools::Itertools;
 use leptos_hot_reload::ViewMacros;
-use notify::event::ModifyKind;
-use notify::{Event, EventKind, RecursiveMode, Watcher};
+use notify::event::{MetadataKind, ModifyKind};
+use notify::{Event, EventKind, RecursiveMode};
 use std::collections::HashSet;
 use std::path::Path;
 use std::sync::Arc;
@@ -17,7 +20,7 @@ pub async fn spawn(proj: &Arc<Project>, view_macros: &ViewMacros) -> Result<Join
     let view_macros = view_macros.to_owned();
     let mut set: HashSet<Utf8PathBuf> = HashSet::from_iter(vec![]);
 
-    set.extend(proj.lib.src_paths.clone());
+    set.extend(proj.lib.front_src_paths.clone());
 
     let paths = remove_nested(set.into_iter());
 
@@ -27,19 +30,19 @@ pub async fn spawn(proj: &Arc<Project>, view_macros: &ViewMacros) -> Result<Join
     );
     let proj = proj.clone();
 
-    Ok(tokio::spawn(
-        async move { run(&paths, proj, view_macros).await },
-    ))
+    Ok(logger::spawn(async move {
+        run(&paths, proj, view_macros).await
+    }))
 }
 
 async fn run(paths: &[Utf8PathBuf], proj: Arc<Project>, view_macros: ViewMacros) {
     let (sync_tx, sync_rx) = std::sync::mpsc::channel();
 
-    let proj = proj.clone();
+    let handler_proj = proj.clone();
     tokio::task::spawn_blocking(move || {
         while let Ok(event) = sync_rx.recv() {
             match event {
-                Ok(event) => handle(event, proj.clone(), view_macros.clone()),
+                Ok(event) => handle(event, handler_proj.clone(), view_macros.clone()),
                 Err(err) => {
                     log::trace!("Notify error: {err:?}");
                     return;
@@ -49,11 +52,8 @@ async fn run(paths: &[Utf8PathBuf], proj: Arc<Project>, view_macros: ViewMacros)
         log::debug!("Notify stopped");
     });
 
-    let mut watcher = notify::RecommendedWatcher::new(
-        sync_tx,
-        notify::Config::default().with_poll_interval(super::notify::FALLBACK_POLLING_TIMEOUT),
-    )
-    .expect("failed to build file system watcher");
+    let mut watcher =
+        super::notify::watcher(&proj, sync_tx).expect("failed to build file system watcher");
 
     for path in paths {
         if let Err(e) = watcher.watch(Path::new(path), RecursiveMode::Recursive) {
@@ -74,11 +74,16 @@ fn handle(event: Event, proj: Arc<Project>, view_macros: ViewMacros) {
     if let EventKind::Any
     | EventKind::Other
     | EventKind::Access(_)
-    | EventKind::Modify(ModifyKind::Any | ModifyKind::Other | ModifyKind::Metadata(_)) =
-        event.kind
+    | EventKind::Modify(ModifyKind::Any | ModifyKind::Other) = event.kind
     {
         return;
     };
+    // the poll watcher reports the changed files by their modification time
+    if let EventKind::Modify(ModifyKind::Metadata(kind)) = event.kind {
+        if kind != MetadataKind::WriteTime {
+            return;
+        }
+    }
 
     log::trace!("Notify handle {}", GRAY.paint(format!("{:?}", event.paths)));
 
@@ -95,13 +100,30 @@ fn handle(event: Event, proj: Arc<Project>, view_macros: ViewMacros) {
         .collect();
 
     for path in paths {
-        if path.starts_with_any(&proj.lib.src_paths) && path.is_ext_any(&["rs"]) {
+        if path.starts_with_any(&proj.lib.front_src_paths) && path.is_ext_any(&["rs"]) {
             // Check if it's possible to patch
-            let patches = view_macros.patch(&path);
-            if let Ok(Some(patch)) = patches {
-                log::debug!("Patching view.");
-                ReloadSignal::send_view_patches(&patch);
+            match view_macros.patch(&path) {
+                Ok(Some(patch)) if !patch.0.is_empty() => {
+                    log::debug!("Patching view.");
+                    ReloadSignal::send_view_patches(&proj, &patch);
+                }
+                Ok(Some(_)) => not_patched(&proj, &path, "no view template changed"),
+                Ok(None) => not_patched(&proj, &path, "views were added or removed"),
+                Err(e) => not_patched(&proj, &path, &format!("{e}")),
             }
         }
     }
 }
+
+fn not_patched(proj: &Project, path: &Utf8PathBuf, reason: &str) {
+    let path = GRAY.paint(path.as_str());
+    match proj.hot_patch_fallback {
+        HotPatchFallback::Rebuild => log::debug!("Patch not possible for {path}: {reason}"),
+        HotPatchFallback::Warn => {
+            log::warn!("Patch not possible for {path}: {reason}. Rebuilding")
+        }
+        HotPatchFallback::Strict => log::warn!(
+            "Patch not possible for {path}: {reason}. Not rebuilding (hot-patch-fallback is strict)"
+        ),
+    }
+}
@@ -0,0 +1,225 @@
+use crate::{
+    config::ProxyRoute,
+    ext::anyhow::{bail, Context, Result},
+    logger::GRAY,
+    service::tls,
+};
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{header, HeaderMap, HeaderName, StatusCode},
+    response::{IntoResponse, Response},
+};
+use hyper::client::conn::http1;
+use hyper_util::rt::TokioIo;
+use tokio::{
+    io::{copy_bidirectional, AsyncRead, AsyncWrite},
+    net::TcpStream,
+};
+use tokio_rustls::rustls::pki_types::ServerName;
+
+/// the headers of a single connection, which aren't forwarded
+const HOP_BY_HOP: &[HeaderName] = &[
+    header::CONNECTION,
+    header::PROXY_AUTHENTICATE,
+    header::PROXY_AUTHORIZATION,
+    header::TE,
+    header::TRAILER,
+    header::TRANSFER_ENCODING,
+    header::UPGRADE,
+];
+
+/// Forwards the request to the backend of the route, websockets included, keeping its path.
+pub async fn forward(client: &reqwest::Client, route: &ProxyRoute, req: Request) -> Response {
+    let path = req
+        .uri()
+        .path_and_query()
+        .map_or("/", |pq| pq.as_str())
+        .to_string();
+    let res = if is_upgrade(req.headers()) {
+        upgrade(route, &path, req).await
+    } else {
+        http(client, route, &path, req).await
+    };
+    res.unwrap_or_else(|e| {
+        log::warn!(
+            "Serve proxy to {} failed: {e:#}",
+            GRAY.paint(route.target.as_str())
+        );
+        (StatusCode::BAD_GATEWAY, format!("{e:#}")).into_response()
+    })
+}
+
+async fn http(
+    client: &reqwest::Client,
+    route: &ProxyRoute,
+    path: &str,
+    req: Request,
+) -> Result<Response> {
+    let url = format!("{}{path}", route.target.as_str().trim_end_matches('/'));
+    log::debug!("Serve proxy {} {}", req.method(), GRAY.paint(&url));
+    let (parts, body) = req.into_parts();
+    let mut headers = parts.headers;
+    strip_hop_by_hop(&mut headers);
+    headers.remove(header::HOST);
+
+    let resp = client
+        .request(parts.method, &url)
+        .headers(headers)
+        .body(reqwest::Body::wrap_stream(body.into_data_stream()))
+        .send()
+        .await?;
+
+    let mut response = Response::builder().status(resp.status());
+    for (name, value) in resp.headers() {
+        if !HOP_BY_HOP.contains(name) {
+            response = response.header(name, value);
+        }
+    }
+    Ok(response.body(Body::from_stream(resp.bytes_stream()))?)
+}
+
+/// Sends the upgrade request to the backend and, once it switched protocols, relays the
+/// bytes of both connections. The https backends are the local sites served with `--tls`,
+/// whose certificates are accepted as they are.
+async fn upgrade(route: &ProxyRoute, path: &str, req: Request) -> Result<Response> {
+    let target = &route.target;
+    let host = target.host_str().context("The proxy target has no host")?;
+    let port = target.port_or_known_default().unwrap_or(80);
+    // under the path of the target, as the http requests
+    let path = format!("{}{path}", target.path().trim_end_matches('/'));
+    log::debug!(
+        "Serve proxy websocket {}",
+        GRAY.paint(format!("{}://{host}:{port}{path}", target.scheme()))
+    );
+    let backend = TcpStream::connect((host, port)).await?;
+    match target.scheme() {
+        "http" => upgrade_with(backend, host, port, &path, req).await,
+        "https" => {
+            let name = ServerName::try_from(host.to_string())?;
+            let backend = tls::local_connector()?.connect(name, backend).await?;
+            upgrade_with(backend, host, port, &path, req).await
+        }
+        scheme => bail!("The {scheme} backends aren't proxied for websockets"),
+    }
+}
+
+async fn upgrade_with<S>(
+    backend: S,
+    host: &str,
+    port: u16,
+    path: &str,
+    mut req: Request,
+) -> Result<Response>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (mut sender, conn) = http1::handshake(TokioIo::new(backend)).await?;
+    tokio::spawn(async move {
+        if let Err(e) = conn.with_upgrades().await {
+            log::debug!("Serve proxy websocket connection failed: {e}");
+        }
+    });
+    let mut backend_req = Request::builder()
+        .method(req.method())
+        .uri(path)
+        .header(header::HOST, format!("{host}:{port}"));
+    for (name, value) in req.headers() {
+        if name != header::HOST {
+            backend_req = backend_req.header(name, value);
+        }
+    }
+    let mut resp = sender
+        .send_request(backend_req.body(Body::empty())?)
+        .await?;
+    // a refusal is passed on with its body
+    if resp.status() != StatusCode::SWITCHING_PROTOCOLS {
+        return Ok(resp.map(Body::new));
+    }
+
+    let mut response = Response::builder().status(resp.status());
+    for (name, value) in resp.headers() {
+        response = response.header(name, value);
+    }
+    let client_upgrade = hyper::upgrade::on(&mut req);
+    let backend_upgrade = hyper::upgrade::on(&mut resp);
+    tokio::spawn(async move {
+        let res = async {
+            let mut client = TokioIo::new(client_upgrade.await?);
+            let mut backend = TokioIo::new(backend_upgrade.await?);
+            copy_bidirectional(&mut client, &mut backend).await?;
+            anyhow::Ok(())
+        };
+        if let Err(e) = res.await {
+            log::debug!("Serve proxy websocket closed: {e}");
+        }
+    });
+    Ok(response.body(Body::empty())?)
+}
+
+fn is_upgrade(headers: &HeaderMap) -> bool {
+    headers.contains_key(header::UPGRADE)
+}
+
+fn strip_hop_by_hop(headers: &mut HeaderMap) {
+    for name in HOP_BY_HOP {
+        headers.remove(name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::forward;
+    use crate::config::ProxyRoute;
+    use axum::{body::Body, extract::Request, http::HeaderValue};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    #[tokio::test]
+    async fn test_upgrade_refused() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let backend = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut head = Vec::new();
+            while !head.ends_with(b"\r\n\r\n") {
+                head.push(stream.read_u8().await.unwrap());
+            }
+            // the body is longer than the reads of the head, and the connection stays open
+            let body = "refused ".repeat(1000);
+            let response = format!(
+                "HTTP/1.1 403 Forbidden\r\ncontent-length: {}\r\n\r\n{body}",
+                body.len()
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+            (String::from_utf8_lossy(&head).into_owned(), stream)
+        });
+
+        let route = ProxyRoute {
+            prefix: "/ws".to_string(),
+            target: format!("http://{addr}/app/").parse().unwrap(),
+        };
+        let req = Request::builder()
+            .uri("/ws/chat?room=1")
+            .header("upgrade", "websocket")
+            .header("connection", "upgrade")
+            .header(
+                "x-name",
+                HeaderValue::from_bytes("josé".as_bytes()).unwrap(),
+            )
+            .body(Body::empty())
+            .unwrap();
+        let response = forward(&reqwest::Client::new(), &route, req).await;
+        assert_eq!(response.status(), 403);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body.len(), 8000);
+
+        let (head, _stream) = backend.await.unwrap();
+        assert!(head.starts_with("GET /app/ws/chat?room=1 HTTP/1.1\r\n"));
+        assert!(head.contains("x-name: josé\r\n"));
+    }
+}
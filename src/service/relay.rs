@@ -0,0 +1,201 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use super::{proxy, tls};
+use crate::{
+    config::{Project, ProxyRoute},
+    ext::anyhow::Result,
+    logger::{self, GRAY},
+    service::site::Site,
+    signal::Interrupt,
+};
+use axum::{
+    extract::{Request, State},
+    response::Response,
+    Router,
+};
+use hyper::server::conn::http1;
+use hyper_util::{rt::TokioIo, service::TowerToHyperService};
+use reqwest::Url;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpListener,
+    select,
+    task::{JoinHandle, JoinSet},
+};
+use tokio_rustls::TlsAcceptor;
+
+/// the paths of the reload channel, relayed to the reload server
+const RELOAD_PATH: &str = "/live_reload";
+
+struct Relay {
+    server: ProxyRoute,
+    reload: ProxyRoute,
+    client: reqwest::Client,
+}
+
+/// Listens on the site addrs and relays each request either to the reload server, when it is
+/// for the reload channel, or to the server, so that only the site addr needs to be reachable
+/// (such as through a single forwarded port). With `--tls`, it terminates the https
+/// connections. The connections are kept alive, each of their requests routed by its own path.
+pub async fn spawn(proj: &Arc<Project>) -> Result<JoinHandle<()>> {
+    let site = proj.site.clone();
+    let acceptor = match &site.tls {
+        Some(config) => Some(tls::acceptor(proj, config).await?),
+        None => None,
+    };
+    let mut listeners = Vec::new();
+    for addr in site.addrs() {
+        listeners.push(TcpListener::bind(addr).await?);
+    }
+    log::debug!(
+        "Reload relaying {} to the server at {} and the reload server at {}",
+        GRAY.paint(site.addrs_string()),
+        GRAY.paint(site.server_addr.to_string()),
+        GRAY.paint(site.reload.to_string())
+    );
+    let app = Router::new()
+        .fallback(handle)
+        .with_state(Arc::new(relay(&site)?));
+
+    let mut relays = JoinSet::new();
+    for listener in listeners {
+        relays.spawn(accept(listener, app.clone(), acceptor.clone()));
+    }
+    Ok(logger::spawn(async move {
+        while relays.join_next().await.is_some() {}
+    }))
+}
+
+fn relay(site: &Site) -> Result<Relay> {
+    let route = |addr: SocketAddr| -> Result<ProxyRoute> {
+        Ok(ProxyRoute {
+            prefix: "/".to_string(),
+            target: Url::parse(&format!("http://{addr}"))?,
+        })
+    };
+    // the redirects of the server are passed on to the browser
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap_or_default();
+    Ok(Relay {
+        server: route(site.server_addr)?,
+        reload: route(site.reload)?,
+        client,
+    })
+}
+
+async fn accept(listener: TcpListener, app: Router, acceptor: Option<TlsAcceptor>) {
+    let mut int = Interrupt::subscribe_shutdown();
+    loop {
+        select! {
+            res = listener.accept() => match res {
+                Ok((stream, _)) => {
+                    let (app, acceptor) = (app.clone(), acceptor.clone());
+                    tokio::spawn(async move {
+                        let res = match acceptor {
+                            Some(acceptor) => match acceptor.accept(stream).await {
+                                Ok(stream) => serve(stream, app).await,
+                                Err(e) => Err(e.into()),
+                            },
+                            None => serve(stream, app).await,
+                        };
+                        if let Err(e) = res {
+                            log::trace!("Reload relay connection closed: {e}");
+                        }
+                    });
+                }
+                Err(e) => log::debug!("Reload relay could not accept: {e}"),
+            },
+            _ = int.recv() => return,
+        }
+    }
+}
+
+/// serves the requests of the connection, upgrades to websockets included
+async fn serve<S>(stream: S, app: Router) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    http1::Builder::new()
+        .serve_connection(TokioIo::new(stream), TowerToHyperService::new(app))
+        .with_upgrades()
+        .await?;
+    Ok(())
+}
+
+async fn handle(State(relay): State<Arc<Relay>>, req: Request) -> Response {
+    let route = match is_reload_path(req.uri().path()) {
+        true => &relay.reload,
+        false => &relay.server,
+    };
+    proxy::forward(&relay.client, route, req).await
+}
+
+fn is_reload_path(path: &str) -> bool {
+    path.strip_prefix(RELOAD_PATH)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(['/', '?']))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{accept, handle, is_reload_path, Relay};
+    use crate::config::ProxyRoute;
+    use axum::Router;
+    use std::{sync::Arc, time::Duration};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+        time::timeout,
+    };
+
+    #[test]
+    fn test_is_reload_path() {
+        assert!(is_reload_path("/live_reload"));
+        assert!(is_reload_path("/live_reload/events"));
+        assert!(!is_reload_path("/live_reload_other"));
+    }
+
+    async fn backend(name: &'static str) -> ProxyRoute {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = Router::new().fallback(move || async move { name });
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        ProxyRoute {
+            prefix: "/".to_string(),
+            target: format!("http://{addr}").parse().unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_relay_routes_each_request() {
+        let relay = Relay {
+            server: backend("from-server").await,
+            reload: backend("from-reload").await,
+            client: reqwest::Client::new(),
+        };
+        let app = Router::new().fallback(handle).with_state(Arc::new(relay));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(accept(listener, app, None));
+
+        // pipelined on a single connection, which stays open
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /page HTTP/1.1\r\nhost: x\r\n\r\nGET /live_reload/events HTTP/1.1\r\nhost: x\r\n\r\n")
+            .await
+            .unwrap();
+        let mut read = String::new();
+        while !read.contains("from-reload") {
+            let mut buf = [0; 4096];
+            let n = timeout(Duration::from_secs(5), stream.read(&mut buf))
+                .await
+                .unwrap()
+                .unwrap();
+            assert!(n > 0, "the connection closed: {read}");
+            read.push_str(&String::from_utf8_lossy(&buf[..n]));
+        }
+        assert!(read.find("from-server").unwrap() < read.find("from-reload").unwrap());
+        assert!(!read.to_ascii_lowercase().contains("connection: close"));
+    }
+}
@@ -1,43 +1,62 @@
+use crate::compile;
 use crate::config::Project;
 use crate::ext::sync::wait_for_socket;
-use crate::logger::GRAY;
+use crate::logger::{self, GRAY};
 use crate::signal::Interrupt;
 use crate::signal::{ReloadSignal, ReloadType};
 use axum::{
-    extract::ws::{Message, WebSocket, WebSocketUpgrade},
-    response::IntoResponse,
-    routing::get,
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    http::{header::ACCESS_CONTROL_ALLOW_ORIGIN, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse,
+    },
+    routing::{get, post},
     Router,
 };
+use futures_util::stream;
 use serde::Serialize;
-use std::sync::Arc;
 use std::{fmt::Display, net::SocketAddr};
+use std::{sync::Arc, time::Duration};
 use tokio::net::TcpListener;
-use tokio::{net::TcpStream, select, sync::RwLock, task::JoinHandle};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::{net::TcpStream, select, task::JoinHandle};
 
-lazy_static::lazy_static! {
-  static ref SITE_ADDR: RwLock<SocketAddr> = RwLock::new(SocketAddr::new([127,0,0,1].into(), 3000));
-  static ref CSS_LINK: RwLock<String> = RwLock::new(String::default());
+/// the time given to the clients to save the page state before a full reload
+const STASH_DELAY: Duration = Duration::from_millis(50);
+
+/// the reload server of a project
+struct Reload {
+    proj: Arc<Project>,
+    /// the server, none for csr projects
+    site_addr: Option<SocketAddr>,
+    css_link: String,
 }
 
 pub async fn spawn(proj: &Arc<Project>) -> JoinHandle<()> {
     let proj = proj.clone();
 
-    let mut site_addr = SITE_ADDR.write().await;
-    *site_addr = proj.site.addr;
-    if let Some(file) = &proj.style.file {
-        let mut css_link = CSS_LINK.write().await;
+    // the hashed stylesheet the server links to, which the rebuilt css is copied over
+    let site = compile::stylesheet_site_path(&proj).unwrap_or_else(|e| {
+        log::debug!("Reload {e:#}");
+        proj.style.site_file.site.clone()
+    });
+    let reload = Arc::new(Reload {
+        proj: proj.clone(),
+        site_addr: proj.bin.is_some().then_some(proj.site.server_addr),
         // Always use `/` as separator in links
-        *css_link = file
-            .site
+        css_link: site
             .components()
             .map(|c| c.as_str())
             .collect::<Vec<_>>()
-            .join("/");
-    }
+            .join("/"),
+    });
 
-    tokio::spawn(async move {
-        let _change = ReloadSignal::subscribe();
+    logger::spawn(async move {
+        let _change = ReloadSignal::subscribe(&proj);
 
         let reload_addr = proj.site.reload;
 
@@ -49,7 +68,11 @@ pub async fn spawn(proj: &Arc<Project>) -> JoinHandle<()> {
 
             return;
         }
-        let route = Router::new().route("/live_reload", get(websocket_handler));
+        let route = Router::new()
+            .route("/live_reload", get(websocket_handler))
+            .route("/live_reload/events", get(events_handler))
+            .route("/rebuild", post(rebuild_handler))
+            .with_state(reload);
 
         log::debug!(
             "Reload server started {}",
@@ -66,13 +89,49 @@ pub async fn spawn(proj: &Arc<Project>) -> JoinHandle<()> {
     })
 }
 
-async fn websocket_handler(ws: WebSocketUpgrade) -> impl IntoResponse {
-    ws.on_upgrade(websocket)
+/// lets editors and scripts trigger a full rebuild: `curl -X POST localhost:3001/rebuild`
+async fn rebuild_handler(State(reload): State<Arc<Reload>>) -> StatusCode {
+    log::info!("Reload rebuild requested");
+    Interrupt::send_rebuild(&reload.proj).await;
+    StatusCode::ACCEPTED
 }
 
-async fn websocket(mut stream: WebSocket) {
-    let mut rx = ReloadSignal::subscribe();
-    let mut int = Interrupt::subscribe_any();
+async fn websocket_handler(
+    State(reload): State<Arc<Reload>>,
+    ws: WebSocketUpgrade,
+) -> impl IntoResponse {
+    ws.on_upgrade(move |stream| websocket(reload, stream))
+}
+
+/// The reload messages as server-sent events, for the browsers that can't get a websocket
+/// through. The page is on another port, hence the CORS header.
+async fn events_handler(State(reload): State<Arc<Reload>>) -> impl IntoResponse {
+    log::trace!("Reload event stream connected");
+    let rx = ReloadSignal::subscribe(&reload.proj);
+    let events = stream::unfold((reload, rx), |(reload, mut rx)| async move {
+        loop {
+            let msg = match rx.recv().await {
+                Ok(ReloadType::Full) => BrowserMessage::all(),
+                Ok(ReloadType::Style) => BrowserMessage::css(&reload.css_link),
+                Ok(ReloadType::ViewPatches(data)) => BrowserMessage::view(data),
+                Ok(ReloadType::Error(errors)) => BrowserMessage::error(errors),
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            };
+            wait_for_site(&reload, &msg).await;
+            log::debug!(r#"Reload sent "{msg}" to browser event stream"#);
+            return Some((Event::default().json_data(&msg), (reload, rx)));
+        }
+    });
+    (
+        [(ACCESS_CONTROL_ALLOW_ORIGIN, "*")],
+        Sse::new(events).keep_alive(KeepAlive::default()),
+    )
+}
+
+async fn websocket(reload: Arc<Reload>, mut stream: WebSocket) {
+    let mut rx = ReloadSignal::subscribe(&reload.proj);
+    let mut int = Interrupt::subscribe_shutdown();
 
     log::trace!("Reload websocket connected");
     tokio::spawn(async move {
@@ -81,19 +140,26 @@ async fn websocket(mut stream: WebSocket) {
                 res = rx.recv() =>{
                     match res {
                         Ok(ReloadType::Full) => {
-                            send_and_close(stream, BrowserMessage::all()).await;
+                            // lets the cargo-leptos client save the page state before the
+                            // leptos client reloads
+                            send(&reload, &mut stream, BrowserMessage::reloading()).await;
+                            tokio::time::sleep(STASH_DELAY).await;
+                            send_and_close(&reload, stream, BrowserMessage::all()).await;
                             return
                         }
                         Ok(ReloadType::Style) => {
-                            send(&mut stream, BrowserMessage::css().await).await;
+                            send(&reload, &mut stream, BrowserMessage::css(&reload.css_link)).await;
                         },
                         Ok(ReloadType::ViewPatches(data)) => {
-                            send(&mut stream, BrowserMessage::view(data)).await;
+                            send(&reload, &mut stream, BrowserMessage::view(data)).await;
+                        }
+                        Ok(ReloadType::Error(errors)) => {
+                            send(&reload, &mut stream, BrowserMessage::error(errors)).await;
                         }
                         Err(e) => log::debug!("Reload recive error {e}")
                     }
                 }
-                _ = int.recv(), if Interrupt::is_shutdown_requested().await => {
+                _ = int.recv() => {
                     log::trace!("Reload websocket closed");
                     return
                 },
@@ -102,11 +168,17 @@ async fn websocket(mut stream: WebSocket) {
     });
 }
 
-async fn send(stream: &mut WebSocket, msg: BrowserMessage) {
-    let site_addr = *SITE_ADDR.read().await;
+async fn wait_for_site(reload: &Reload, msg: &BrowserMessage) {
+    let Some(site_addr) = reload.site_addr else {
+        return;
+    };
     if !wait_for_socket("Reload", site_addr).await {
-        log::warn!(r#"Reload could not send "{msg}" to websocket"#);
+        log::warn!(r#"Reload could not send "{msg}" to browser"#);
     }
+}
+
+async fn send(reload: &Reload, stream: &mut WebSocket, msg: BrowserMessage) {
+    wait_for_site(reload, &msg).await;
 
     let text = serde_json::to_string(&msg).unwrap();
     match stream.send(Message::Text(text)).await {
@@ -119,8 +191,8 @@ async fn send(stream: &mut WebSocket, msg: BrowserMessage) {
     }
 }
 
-async fn send_and_close(mut stream: WebSocket, msg: BrowserMessage) {
-    send(&mut stream, msg).await;
+async fn send_and_close(reload: &Reload, mut stream: WebSocket, msg: BrowserMessage) {
+    send(reload, &mut stream, msg).await;
     let _ = stream.close().await;
     log::trace!("Reload websocket closed");
 }
@@ -130,18 +202,25 @@ struct BrowserMessage {
     css: Option<String>,
     view: Option<String>,
     all: bool,
+    /// shown by the error overlay, ignored by the leptos reload client
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    /// a full reload follows, ignored by the leptos reload client
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    reloading: bool,
 }
 
 impl BrowserMessage {
-    async fn css() -> Self {
-        let link = CSS_LINK.read().await.clone();
+    fn css(link: &str) -> Self {
         if link.is_empty() {
             log::error!("Reload internal error: sending css reload but no css file is set.");
         }
         Self {
-            css: Some(link),
+            css: Some(link.to_string()),
             view: None,
             all: false,
+            error: None,
+            reloading: false,
         }
     }
 
@@ -150,6 +229,28 @@ impl BrowserMessage {
             css: None,
             view: Some(data),
             all: false,
+            error: None,
+            reloading: false,
+        }
+    }
+
+    fn error(errors: String) -> Self {
+        Self {
+            css: None,
+            view: None,
+            all: false,
+            error: Some(errors),
+            reloading: false,
+        }
+    }
+
+    fn reloading() -> Self {
+        Self {
+            css: None,
+            view: None,
+            all: false,
+            error: None,
+            reloading: true,
         }
     }
 
@@ -158,6 +259,8 @@ impl BrowserMessage {
             css: None,
             view: None,
             all: true,
+            error: None,
+            reloading: false,
         }
     }
 }
@@ -166,6 +269,10 @@ impl Display for BrowserMessage {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if let Some(css) = &self.css {
             write!(f, "reload {}", css)
+        } else if self.error.is_some() {
+            write!(f, "build errors")
+        } else if self.reloading {
+            write!(f, "reloading")
         } else {
             write!(f, "reload all")
         }
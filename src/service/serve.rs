@@ -1,31 +1,98 @@
 use std::sync::Arc;
 
 use crate::{
-    config::Project,
-    ext::{anyhow::Result, append_str_to_filename, determine_pdb_filename, fs},
-    logger::GRAY,
+    config::{interpolate_server_env, Project},
+    ext::progress,
+    ext::{
+        anyhow::{bail, Context, Result},
+        append_str_to_filename, determine_pdb_filename, fs,
+    },
+    logger::{self, GRAY},
+    service::{
+        dashboard::{self, ServerStatus},
+        site::local_addr,
+    },
     signal::{Interrupt, ReloadSignal, ServerRestart},
 };
+use ansi_term::Colour::Fixed;
 use camino::Utf8PathBuf;
+use std::{
+    future::Future,
+    io::Write,
+    process::{ExitStatus, Stdio},
+    time::Duration,
+};
 use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, BufReader},
+    net::TcpStream,
     process::{Child, Command},
     select,
+    sync::broadcast,
     task::JoinHandle,
+    time::{sleep_until, Instant},
 };
 
+/// the delay before restarting a crashed server, doubled after each crash
+const RESTART_BACKOFF: Duration = Duration::from_millis(500);
+/// the longest delay, and how long a server must have run for its crash to reset the delay
+const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(30);
+
 pub async fn spawn(proj: &Arc<Project>) -> JoinHandle<Result<()>> {
     let mut int = Interrupt::subscribe_shutdown();
     let proj = proj.clone();
-    let mut change = ServerRestart::subscribe();
-    tokio::spawn(async move {
+    let mut change = ServerRestart::subscribe(&proj);
+    logger::spawn(async move {
         let mut server = ServerProcess::start_new(&proj).await?;
+        // for the dashboard to show it running
+        if dashboard::is_active() && !wait_healthy_or_shutdown(&proj, &mut server, &mut int).await {
+            server.kill().await;
+            return Ok(());
+        }
+        let mut started = Instant::now();
+        let mut backoff = RESTART_BACKOFF;
+        let mut restart_at: Option<Instant> = None;
         loop {
             select! {
               res = change.recv() => {
                 if let Ok(()) = res {
                       server.restart().await?;
-                      ReloadSignal::send_full();
+                      started = Instant::now();
+                      backoff = RESTART_BACKOFF;
+                      restart_at = None;
+                      if !wait_healthy_or_shutdown(&proj, &mut server, &mut int).await {
+                          server.kill().await;
+                          return Ok(());
+                      }
+                      ReloadSignal::send_full(&proj);
+                }
+              },
+              status = server.exited() => {
+                let status = status?;
+                if status.success() {
+                    log::info!("Serve the server exited");
+                    dashboard::server_status(ServerStatus::Stopped);
+                    continue;
+                }
+                dashboard::server_status(ServerStatus::Crashed(status.to_string()));
+                if started.elapsed() > MAX_RESTART_BACKOFF {
+                    backoff = RESTART_BACKOFF;
+                }
+                log::error!(
+                    "Serve the server crashed with {status}, see its output above. Restarting in {}ms",
+                    backoff.as_millis()
+                );
+                restart_at = Some(Instant::now() + backoff);
+                backoff = (backoff * 2).min(MAX_RESTART_BACKOFF);
+              },
+              _ = sleep_until(restart_at.unwrap_or_else(Instant::now)), if restart_at.is_some() => {
+                restart_at = None;
+                server.start().await?;
+                started = Instant::now();
+                if !wait_healthy_or_shutdown(&proj, &mut server, &mut int).await {
+                    server.kill().await;
+                    return Ok(());
                 }
+                ReloadSignal::send_full(&proj);
               },
               _ = int.recv() => {
                     server.kill().await;
@@ -39,7 +106,7 @@ pub async fn spawn(proj: &Arc<Project>) -> JoinHandle<Result<()>> {
 pub async fn spawn_oneshot(proj: &Arc<Project>) -> JoinHandle<Result<()>> {
     let mut int = Interrupt::subscribe_shutdown();
     let proj = proj.clone();
-    tokio::spawn(async move {
+    logger::spawn(async move {
         let mut server = ServerProcess::start_new(&proj).await?;
         select! {
           _ = server.wait() => {},
@@ -51,25 +118,246 @@ pub async fn spawn_oneshot(proj: &Arc<Project>) -> JoinHandle<Result<()>> {
     })
 }
 
+/// how long to wait for the server to answer before giving up
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Starts the built server, fetches the server rendered html of the given paths and
+/// stops the server again.
+pub async fn fetch_pages(proj: &Project, paths: &[&str]) -> Result<Vec<String>> {
+    let addr = local_addr(proj.site.server_addr);
+    let mut server = ServerProcess::start_new(proj).await?;
+    let base = format!("http://{addr}{}", proj.site.base_url);
+    let pages = fetch_from(&mut server, &base, paths).await;
+    server.kill().await;
+    pages
+}
+
+/// Starts the built server, waits until it is ready and runs the future against it. The server
+/// is stopped afterwards whatever the outcome, as when the future ends on an interrupt.
+pub async fn run_against<T>(proj: &Project, run: impl Future<Output = Result<T>>) -> Result<T> {
+    let mut server = ServerProcess::start_new(proj).await?;
+    let res = async {
+        wait_ready(proj, &mut server).await?;
+        run.await
+    }
+    .await;
+    server.kill().await;
+    res
+}
+
+/// Waits until the server is ready, as for [`wait_healthy`], failing when it exits or after
+/// the startup timeout.
+async fn wait_ready(proj: &Project, server: &mut ServerProcess) -> Result<()> {
+    let client = reqwest::Client::new();
+    let start_time = Instant::now();
+    loop {
+        if is_healthy(proj, &client).await {
+            log::debug!("Serve ready after {:?}", start_time.elapsed());
+            return Ok(());
+        }
+        if let Some(status) = server.try_wait()? {
+            bail!("The server exited with {status} before it was ready");
+        }
+        if start_time.elapsed() > STARTUP_TIMEOUT {
+            bail!(
+                "The server was not ready at {} within {STARTUP_TIMEOUT:?}",
+                proj.site.server_addr
+            );
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+/// whether the server answers with a success status on the health path when configured, or
+/// else accepts connections
+async fn is_healthy(proj: &Project, client: &reqwest::Client) -> bool {
+    let addr = local_addr(proj.site.server_addr);
+    match &proj.reload_health_path {
+        Some(path) => client
+            .get(format!("http://{addr}{path}"))
+            .send()
+            .await
+            .is_ok_and(|resp| resp.status().is_success()),
+        None => TcpStream::connect(addr).await.is_ok(),
+    }
+}
+
+/// Waits until the restarted server answers, with a success status on the health path when
+/// configured or else by accepting connections, so that the browser isn't reloaded before
+/// the server listens. Gives up when the server exits or after the startup timeout.
+async fn wait_healthy(proj: &Project, server: &mut ServerProcess) {
+    let client = reqwest::Client::new();
+    let start_time = Instant::now();
+    loop {
+        if is_healthy(proj, &client).await {
+            log::debug!("Serve healthy after {:?}", start_time.elapsed());
+            dashboard::server_status(ServerStatus::Running);
+            return;
+        }
+        if !matches!(server.try_wait(), Ok(None)) {
+            return;
+        }
+        if start_time.elapsed() > STARTUP_TIMEOUT {
+            log::warn!("Serve not healthy within {STARTUP_TIMEOUT:?}, reloading anyway");
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+/// [`wait_healthy`] unless cargo-leptos shuts down meanwhile, which returns false
+async fn wait_healthy_or_shutdown(
+    proj: &Project,
+    server: &mut ServerProcess,
+    int: &mut broadcast::Receiver<()>,
+) -> bool {
+    select! {
+        _ = wait_healthy(proj, server) => true,
+        _ = int.recv() => false,
+    }
+}
+
+async fn fetch_from(server: &mut ServerProcess, base: &str, paths: &[&str]) -> Result<Vec<String>> {
+    let client = reqwest::Client::new();
+    let start_time = tokio::time::Instant::now();
+    loop {
+        if client.get(base).send().await.is_ok() {
+            break;
+        }
+        if let Some(status) = server.try_wait()? {
+            bail!("The server exited with {status} before it could be fetched from");
+        }
+        if start_time.elapsed() > STARTUP_TIMEOUT {
+            bail!("The server did not answer at {base} within {STARTUP_TIMEOUT:?}");
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    let mut pages = Vec::new();
+    for path in paths {
+        let url = format!("{base}{path}");
+        log::debug!("Serve fetching {}", GRAY.paint(&url));
+        let page = client
+            .get(&url)
+            .send()
+            .await
+            .and_then(|resp| resp.error_for_status())
+            .context(format!("Could not fetch {url}"))?
+            .text()
+            .await?;
+        pages.push(page);
+    }
+    Ok(pages)
+}
+
+/// Pipes the output of the server, for its lines to be tagged, except with `--log-format json`
+/// where it is left as is rather than mixed into the records.
+fn pipe_output(command: &mut Command) {
+    if !logger::is_json() || dashboard::is_active() {
+        command.stdout(Stdio::piped()).stderr(Stdio::piped());
+    }
+}
+
+/// Forwards the piped output of the server a line at a time with the `[server]` tag, to our
+/// stdout and stderr as it wrote them, or to the logs of the dashboard.
+fn forward_output(child: &mut Child) {
+    fn forward<W: Write + 'static>(
+        output: Option<impl AsyncRead + Unpin + Send + 'static>,
+        write: fn() -> W,
+    ) {
+        let Some(output) = output else {
+            return;
+        };
+        logger::spawn(async move {
+            let project = logger::project_tag();
+            let mut output = BufReader::new(output);
+            let mut buf = Vec::new();
+            // the output isn't necessarily utf-8, and a line that isn't mustn't stop the forwarding
+            while let Ok(1..) = output.read_until(b'\n', &mut buf).await {
+                let read = String::from_utf8_lossy(&buf);
+                let line = tagged(read.trim_end_matches(['\n', '\r']), project.as_deref());
+                buf.clear();
+                if dashboard::is_active() {
+                    dashboard::log_line(line);
+                    continue;
+                }
+                _ = progress::suspend(|| writeln!(write(), "{line}"));
+            }
+        });
+    }
+    forward(child.stdout.take(), std::io::stdout);
+    forward(child.stderr.take(), std::io::stderr);
+}
+
+/// the line of the server output with its tag, telling it from the build logs, after the tag of
+/// the project when the projects are run at once
+fn tagged(line: &str, project: Option<&str>) -> String {
+    let server = Fixed(37).paint("[server]");
+    match project {
+        Some(project) => format!("{project} {server} {line}"),
+        None => format!("{server} {line}"),
+    }
+}
+
 struct ServerProcess {
     process: Option<Child>,
-    envs: Vec<(&'static str, String)>,
+    /// the url the browser opens
+    url: String,
+    envs: Vec<(String, String)>,
     binary: Utf8PathBuf,
     bin_args: Option<Vec<String>>,
+    /// the command of the wasm runtime running the server
+    runner: Option<Vec<String>>,
 }
 
 impl ServerProcess {
-    fn new(proj: &Project) -> Self {
-        Self {
-            process: None,
-            envs: proj.to_envs(),
-            binary: proj.bin.exe_file.clone(),
-            bin_args: proj.bin.bin_args.clone(),
+    fn new(proj: &Project) -> Result<Self> {
+        let Some(bin) = &proj.bin else {
+            bail!("The csr project {} has no server to run", proj.name);
+        };
+        let mut envs: Vec<(String, String)> = proj
+            .to_envs()
+            .into_iter()
+            .map(|(key, val)| (key.to_string(), val))
+            .collect();
+        // behind the relay, which listens on all the addresses, the server listens on another port
+        if proj.site.relay {
+            envs.retain(|(key, _)| key != "LEPTOS_SITE_ADDRS");
+            for (key, val) in envs.iter_mut() {
+                if key == "LEPTOS_SITE_ADDR" {
+                    *val = proj.site.server_addr.to_string();
+                }
+            }
         }
+        let server_env = interpolate_server_env(&proj.server_env, &envs)?;
+        envs.extend(server_env);
+        let runner = if bin.is_wasm() {
+            let Some(runner) = &bin.runner else {
+                bail!(
+                    "The wasm server {} needs a bin-runner to run, such as \"wasmtime serve\"",
+                    bin.exe_file
+                );
+            };
+            let runner: Vec<String> = shlex::Shlex::new(runner).collect();
+            if runner.is_empty() {
+                bail!("The bin-runner of {} is empty", proj.name);
+            }
+            Some(runner)
+        } else {
+            None
+        };
+        Ok(Self {
+            process: None,
+            url: proj.site.url(),
+            envs,
+            binary: bin.exe_file.clone(),
+            bin_args: bin.bin_args.clone(),
+            runner,
+        })
     }
 
     async fn start_new(proj: &Project) -> Result<Self> {
-        let mut me = Self::new(proj);
+        let mut me = Self::new(proj)?;
         me.start().await?;
         Ok(me)
     }
@@ -81,6 +369,7 @@ impl ServerProcess {
             } else {
                 log::trace!("Serve stopped");
             }
+            dashboard::server_status(ServerStatus::Stopped);
             self.process = None;
         }
     }
@@ -92,6 +381,25 @@ impl ServerProcess {
         Ok(())
     }
 
+    fn try_wait(&mut self) -> Result<Option<ExitStatus>> {
+        match self.process.as_mut() {
+            Some(proc) => Ok(proc.try_wait()?),
+            None => bail!("The server binary {} was not found", self.binary),
+        }
+    }
+
+    /// waits for the server to exit on its own, which is pending while no server runs
+    async fn exited(&mut self) -> Result<ExitStatus> {
+        match self.process.as_mut() {
+            Some(proc) => {
+                let status = proc.wait().await?;
+                self.process = None;
+                Ok(status)
+            }
+            None => std::future::pending().await,
+        }
+    }
+
     async fn wait(&mut self) -> Result<()> {
         if let Some(proc) = self.process.as_mut() {
             if let Err(e) = proc.wait().await {
@@ -105,7 +413,26 @@ impl ServerProcess {
 
     async fn start(&mut self) -> Result<()> {
         let bin = &self.binary;
-        let child = if bin.exists() {
+        let child = if let (true, Some(runner)) = (bin.exists(), &self.runner) {
+            log::debug!(
+                "Serve running {} with {}",
+                GRAY.paint(bin.as_str()),
+                runner.join(" ")
+            );
+            let mut command = Command::new(&runner[0]);
+            command
+                .args(&runner[1..])
+                .arg(bin)
+                .args(self.bin_args.iter().flatten())
+                .envs(self.envs.clone());
+            pipe_output(&mut command);
+            let mut cmd = command
+                .spawn()
+                .context(format!("Could not run the bin-runner {}", runner[0]))?;
+            forward_output(&mut cmd);
+            log::info!("Serving at {}", self.url);
+            Some(cmd)
+        } else if bin.exists() {
             // windows doesn't like to overwrite a running binary, so we copy it to a new name
             let bin_path = if cfg!(target_os = "windows") {
                 // solution to allow cargo to overwrite a running binary on some platforms:
@@ -138,30 +465,41 @@ impl ServerProcess {
             };
 
             log::debug!("Serve running {}", GRAY.paint(bin_path.as_str()));
-            let cmd = Some(
-                Command::new(bin_path)
-                    .envs(self.envs.clone())
-                    .args(bin_args)
-                    .spawn()?,
-            );
-            let port = self
-                .envs
-                .iter()
-                .find_map(|(k, v)| {
-                    if k == &"LEPTOS_SITE_ADDR" {
-                        Some(v.to_string())
-                    } else {
-                        None
-                    }
-                })
-                .unwrap_or_default();
-            log::info!("Serving at http://{port}");
-            cmd
+            let mut command = Command::new(bin_path);
+            command.envs(self.envs.clone()).args(bin_args);
+            pipe_output(&mut command);
+            let mut cmd = command.spawn()?;
+            forward_output(&mut cmd);
+            log::info!("Serving at {}", self.url);
+            Some(cmd)
         } else {
             log::debug!("Serve no exe found {}", GRAY.paint(bin.as_str()));
             None
         };
+        if child.is_some() {
+            dashboard::server_status(ServerStatus::Starting);
+        }
         self.process = child;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::tagged;
+    use crate::logger::strip_ansi;
+
+    #[test]
+    fn test_tagged() {
+        let line = tagged("listening on \x1b[1mhttp://127.0.0.1:3000\x1b[0m", None);
+        assert_eq!(
+            strip_ansi(&line),
+            "[server] listening on http://127.0.0.1:3000"
+        );
+        let line = tagged("listening on 127.0.0.1:3001", Some("shop |"));
+        assert_eq!(
+            strip_ansi(&line),
+            "shop | [server] listening on 127.0.0.1:3001"
+        );
+    }
+}
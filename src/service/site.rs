@@ -1,17 +1,19 @@
 use std::{
+    borrow::Cow,
     collections::HashMap,
     fmt::{self, Display},
-    net::SocketAddr,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::SystemTime,
 };
 
 use camino::{Utf8Path, Utf8PathBuf};
 use tokio::sync::RwLock;
 
 use crate::{
-    config::ProjectConfig,
+    config::{ProjectConfig, Tls},
     ext::{
-        anyhow::{Context, Result},
-        fs, PathBufExt,
+        anyhow::{bail, Context, Result},
+        fs, minify_html, PathBufExt,
     },
 };
 
@@ -73,22 +75,67 @@ impl std::fmt::Debug for SiteFile {
     }
 }
 
+/// how many ports are tried from the port in use, with `--port-fallback`
+const PORT_FALLBACK_TRIES: u16 = 100;
+
+/// modification time and size of an asset source file
+pub type AssetStamp = (SystemTime, u64);
+
+/// an asset copied to the site: the stamp of its source file and its path relative to the site
+/// root, which has its hash once renamed with `hash-assets`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SiteAsset {
+    pub stamp: AssetStamp,
+    pub dest: Utf8PathBuf,
+}
+
 pub struct Site {
     pub addr: SocketAddr,
+    /// the addresses the site is also served at
+    pub other_addrs: Vec<SocketAddr>,
+    /// where the reload server listens: a free local port when relayed
     pub reload: SocketAddr,
+    /// where the server binary listens: a free local port when relayed
+    pub server_addr: SocketAddr,
+    /// the reload channel is relayed through the site addr, which forwards everything else to
+    /// the server (`reload-via-site-addr`)
+    pub relay: bool,
+    /// served over https by the relay, with `--tls`
+    pub tls: Option<Tls>,
+    /// the port at which the gateway the project is run behind relays its reload channel
+    pub gateway_port: Option<u16>,
+    /// the port the browser reaches the reload server at, behind a proxy
+    pub reload_external_port: Option<u16>,
     pub root_dir: Utf8PathBuf,
     pub pkg_dir: Utf8PathBuf,
+    /// the path prefix the site is hosted under, without trailing slash: empty for the root
+    pub base_url: String,
+    /// the url the site files are served from, once uploaded to a cdn
+    pub asset_url_prefix: Option<String>,
+    /// minify the html files written to the site
+    pub minify_html: bool,
     file_reg: RwLock<HashMap<String, u64>>,
     ext_file_reg: RwLock<HashMap<String, u64>>,
+    /// assets copied to the site, by their path relative to the assets dir
+    pub asset_reg: RwLock<HashMap<Utf8PathBuf, SiteAsset>>,
 }
 
 impl fmt::Debug for Site {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Site")
             .field("addr", &self.addr)
+            .field("other_addrs", &self.other_addrs)
             .field("reload", &self.reload)
+            .field("server_addr", &self.server_addr)
+            .field("relay", &self.relay)
+            .field("tls", &self.tls)
+            .field("gateway_port", &self.gateway_port)
+            .field("reload_external_port", &self.reload_external_port)
             .field("root_dir", &self.root_dir)
             .field("pkg_dir", &self.pkg_dir)
+            .field("base_url", &self.base_url)
+            .field("asset_url_prefix", &self.asset_url_prefix)
+            .field("minify_html", &self.minify_html)
             .field("file_reg", &self.file_reg.blocking_read())
             .field("ext_file_reg", &self.ext_file_reg.blocking_read())
             .finish()
@@ -96,16 +143,146 @@ impl fmt::Debug for Site {
 }
 
 impl Site {
-    pub fn new(config: &ProjectConfig) -> Self {
-        let mut reload = config.site_addr;
-        reload.set_port(config.reload_port);
-        Self {
-            addr: config.site_addr,
+    pub fn new(
+        config: &ProjectConfig,
+        minify_html: bool,
+        relay: bool,
+        tls: Option<Tls>,
+        port_fallback: bool,
+        gateway_port: Option<u16>,
+    ) -> Result<Self> {
+        // the relay terminates the tls connections
+        let relay = relay || tls.is_some();
+        let addr = free_port(config.site_addr[0], port_fallback, &[])?;
+        let (reload, server_addr) = if relay {
+            (free_local_addr()?, free_local_addr()?)
+        } else {
+            let mut reload = addr;
+            reload.set_port(config.reload_port);
+            (free_port(reload, port_fallback, &[addr.port()])?, addr)
+        };
+        Ok(Self {
+            addr,
+            other_addrs: config.site_addr[1..].to_vec(),
             reload,
+            server_addr,
+            relay,
+            tls,
+            gateway_port,
+            reload_external_port: config.reload_external_port,
             root_dir: config.site_root.clone(),
             pkg_dir: config.site_pkg_dir.clone(),
+            base_url: config.base_url.clone(),
+            asset_url_prefix: config.asset_url_prefix.clone(),
+            minify_html,
             file_reg: Default::default(),
             ext_file_reg: Default::default(),
+            asset_reg: Default::default(),
+        })
+    }
+
+    /// all the addresses the site is served at, the primary one first
+    pub fn addrs(&self) -> Vec<SocketAddr> {
+        let mut addrs = vec![self.addr];
+        addrs.extend(&self.other_addrs);
+        addrs
+    }
+
+    /// the addresses after the primary one, comma separated, as in `LEPTOS_SITE_ADDRS`. None
+    /// when the site has a single address
+    pub fn other_addrs_string(&self) -> Option<String> {
+        let addrs = self
+            .other_addrs
+            .iter()
+            .map(|a| a.to_string())
+            .collect::<Vec<_>>();
+        (!addrs.is_empty()).then(|| addrs.join(","))
+    }
+
+    /// all the addresses, comma separated
+    pub fn addrs_string(&self) -> String {
+        let addrs = self
+            .addrs()
+            .iter()
+            .map(|a| a.to_string())
+            .collect::<Vec<_>>();
+        addrs.join(",")
+    }
+
+    /// the addresses the server listens at: a free local one when relayed
+    pub fn server_addrs(&self) -> Vec<SocketAddr> {
+        if self.relay {
+            vec![self.server_addr]
+        } else {
+            self.addrs()
+        }
+    }
+
+    /// the url the browser opens
+    pub fn url(&self) -> String {
+        format!("{}://{}{}", self.scheme(), self.addr, self.base_url)
+    }
+
+    /// the url of the site on this machine
+    pub fn local_url(&self) -> String {
+        format!(
+            "{}://{}{}",
+            self.scheme(),
+            local_addr(self.addr),
+            self.base_url
+        )
+    }
+
+    /// the url of the site at another address of this machine
+    pub fn url_at(&self, ip: IpAddr) -> String {
+        format!(
+            "{}://{}{}",
+            self.scheme(),
+            SocketAddr::new(ip, self.addr.port()),
+            self.base_url
+        )
+    }
+
+    /// the url of a file relative to the site root, absolute from the domain
+    pub fn url_of(&self, rel: &Utf8Path) -> String {
+        format!("{}/{}", self.base_url, url_path(rel))
+    }
+
+    /// the url the pages link a file relative to the site root with: on the cdn of the
+    /// `asset-url-prefix` when there is one
+    pub fn asset_url(&self, rel: &Utf8Path) -> String {
+        match &self.asset_url_prefix {
+            Some(prefix) => format!("{prefix}/{}", url_path(rel)),
+            None => self.url_of(rel),
+        }
+    }
+
+    /// https when the relay serves the site with `--tls`
+    pub fn scheme(&self) -> &'static str {
+        if self.tls.is_some() {
+            "https"
+        } else {
+            "http"
+        }
+    }
+
+    /// the port the browser connects to for the reload channel
+    pub fn reload_port(&self) -> u16 {
+        if let Some(port) = self.gateway_port {
+            port
+        } else if self.relay {
+            self.addr.port()
+        } else {
+            self.reload.port()
+        }
+    }
+
+    /// the port of the reload channel in the pages, as the leptos client picks it: the
+    /// `reload-external-port` when set, unless the gateway relays the channel
+    pub fn external_reload_port(&self) -> u16 {
+        match self.reload_external_port {
+            Some(port) if self.gateway_port.is_none() => port,
+            _ => self.reload_port(),
         }
     }
 
@@ -125,9 +302,16 @@ impl Site {
         Ok(true)
     }
 
+    /// Copies the source to the dest unless the output, minified when html, is the one written
+    /// before. The registry keeps the hashes of the outputs, as the dest files are hashed when
+    /// not registered yet.
     pub async fn updated(&self, file: &SourcedSiteFile) -> Result<bool> {
         fs::create_dir_all(file.dest.clone().without_last()).await?;
 
+        if self.minify_html && is_html(&file.dest) {
+            let data = fs::read(&file.source).await?;
+            return self.updated_with(&file.as_site_file(), &data).await;
+        }
         let new_hash = file_hash(&file.source).await?;
         let cur_hash = self.current_hash(&file.site, &file.dest).await?;
 
@@ -156,20 +340,36 @@ impl Site {
     pub async fn updated_with(&self, file: &SiteFile, data: &[u8]) -> Result<bool> {
         fs::create_dir_all(file.dest.clone().without_last()).await?;
 
-        let new_hash = seahash::hash(data);
+        let data = self.minified(&file.dest, data);
+        let new_hash = seahash::hash(&data);
         let cur_hash = self.current_hash(&file.site, &file.dest).await?;
 
         if Some(new_hash) == cur_hash {
             return Ok(false);
         }
 
-        fs::write(&file.dest, &data).await?;
+        fs::write(&file.dest, data).await?;
 
         let mut reg = self.file_reg.write().await;
         reg.insert(file.site.to_string(), new_hash);
         Ok(true)
     }
 
+    /// the data to write to the `dest` file, minified if it is an html file and
+    /// html minification is enabled
+    pub fn minified<'a>(&self, dest: &Utf8Path, data: &'a [u8]) -> Cow<'a, [u8]> {
+        if !self.minify_html || !is_html(dest) {
+            return Cow::Borrowed(data);
+        }
+        match std::str::from_utf8(data) {
+            Ok(html) => Cow::Owned(minify_html(html).into_bytes()),
+            Err(_) => {
+                log::warn!("Site could not minify {dest} as it is not valid UTF-8");
+                Cow::Borrowed(data)
+            }
+        }
+    }
+
     async fn current_hash(&self, site: &Utf8Path, dest: &Utf8Path) -> Result<Option<u64>> {
         if let Some(hash) = self.file_reg.read().await.get(site.as_str()).copied() {
             Ok(Some(hash))
@@ -181,7 +381,69 @@ impl Site {
     }
 }
 
+pub fn is_html(file: &Utf8Path) -> bool {
+    matches!(file.extension(), Some("html" | "htm"))
+}
+
 async fn file_hash(file: &Utf8Path) -> Result<u64> {
     let data = fs::read(&file).await?;
     Ok(seahash::hash(&data))
 }
+
+/// the address to connect to: the loopback address when listening on all of them
+pub fn local_addr(mut addr: SocketAddr) -> SocketAddr {
+    if addr.ip().is_unspecified() {
+        addr.set_ip(match addr.ip() {
+            IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::LOCALHOST),
+            IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        });
+    }
+    addr
+}
+
+/// The address with a port that is free for now, other than the taken ones: a random one for
+/// port 0 and, with the fallback, the next one above the port when it is in use.
+pub fn free_port(addr: SocketAddr, fallback: bool, taken: &[u16]) -> Result<SocketAddr> {
+    if addr.port() == 0 {
+        loop {
+            let listener = std::net::TcpListener::bind(addr)
+                .with_context(|| format!("Could not find a free port at {}", addr.ip()))?;
+            let free = listener.local_addr()?;
+            if !taken.contains(&free.port()) {
+                return Ok(free);
+            }
+        }
+    }
+    if !fallback {
+        return Ok(addr);
+    }
+    let mut candidate = addr;
+    for port in addr.port()..addr.port().saturating_add(PORT_FALLBACK_TRIES) {
+        candidate.set_port(port);
+        if !taken.contains(&port) && std::net::TcpListener::bind(candidate).is_ok() {
+            if port != addr.port() {
+                log::warn!("Serve port {} is in use, using {port}", addr.port());
+            }
+            return Ok(candidate);
+        }
+    }
+    bail!(
+        "No free port within {PORT_FALLBACK_TRIES} ports above {}",
+        addr
+    )
+}
+
+/// a local address with a port that is free for now
+fn free_local_addr() -> Result<SocketAddr> {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")
+        .context("Could not find a free port for the relay")?;
+    Ok(listener.local_addr()?)
+}
+
+/// the path with forward slashes
+fn url_path(rel: &Utf8Path) -> String {
+    rel.components()
+        .map(|c| c.as_str())
+        .collect::<Vec<_>>()
+        .join("/")
+}
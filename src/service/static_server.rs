@@ -0,0 +1,288 @@
+use std::{io::Write, net::SocketAddr, sync::Arc};
+
+use crate::{
+    config::{Project, ProxyRoute},
+    ext::anyhow::{Context, Result},
+    logger::{self, GRAY},
+    service::proxy,
+    signal::Interrupt,
+};
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{header, request::Parts, HeaderName, HeaderValue, Method, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    Router,
+};
+use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
+use libflate::gzip;
+use percent_encoding::percent_decode_str;
+use std::future::IntoFuture;
+use tokio::{
+    net::TcpListener,
+    task::{JoinHandle, JoinSet},
+};
+
+/// the smallest file compressed on the fly
+const MIN_COMPRESS_SIZE: usize = 1024;
+
+struct StaticSite {
+    root: Utf8PathBuf,
+    /// the path prefix of the site, empty for the root
+    base_url: String,
+    proxy: Vec<ProxyRoute>,
+    headers: Vec<(HeaderName, HeaderValue)>,
+    client: reqwest::Client,
+}
+
+/// Serves the static site of a csr project, which has no server: the files of the site root
+/// with their mime types, compressed when the browser accepts it, and the `index.html` for the
+/// paths without an extension, so that the routes of the history API load the app. With a
+/// `base-url`, the site is served under it. The paths of the `proxy` table are forwarded to their
+/// backends, the others get the headers of the `headers` table.
+pub async fn spawn(proj: &Arc<Project>) -> JoinHandle<Result<()>> {
+    let site = Arc::new(StaticSite {
+        root: proj.site.root_dir.clone(),
+        base_url: proj.site.base_url.clone(),
+        proxy: proj.proxy.clone(),
+        headers: proj.headers.clone(),
+        // the redirects of the backends are passed on to the browser
+        client: reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap_or_default(),
+    });
+    let addrs = proj.site.server_addrs();
+    let url = proj.site.url();
+    logger::spawn(async move { serve(site, addrs, url).await })
+}
+
+async fn serve(site: Arc<StaticSite>, addrs: Vec<SocketAddr>, url: String) -> Result<()> {
+    let mut listeners = Vec::new();
+    for addr in addrs {
+        let listener = TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("Could not serve the static site at {addr}"))?;
+        listeners.push(listener);
+    }
+    log::info!(
+        "Serving the static site of {} at {url}",
+        GRAY.paint(site.root.as_str())
+    );
+    for route in &site.proxy {
+        log::info!(
+            "Serving {} from {}",
+            route.prefix,
+            GRAY.paint(route.target.as_str())
+        );
+    }
+    let app = Router::new().fallback(handle).with_state(site);
+    let mut servers = JoinSet::new();
+    for listener in listeners {
+        let mut int = Interrupt::subscribe_shutdown();
+        servers.spawn(
+            axum::serve(listener, app.clone())
+                .with_graceful_shutdown(async move {
+                    let _ = int.recv().await;
+                })
+                .into_future(),
+        );
+    }
+    while let Some(res) = servers.join_next().await {
+        res??;
+    }
+    log::debug!("Serve static site stopped");
+    Ok(())
+}
+
+async fn handle(State(site): State<Arc<StaticSite>>, req: Request) -> Response {
+    if let Some(route) = site.proxy.iter().find(|r| r.matches(req.uri().path())) {
+        return proxy::forward(&site.client, route, req).await;
+    }
+    let (req, _) = req.into_parts();
+    let mut response = match req.uri.path().strip_prefix(site.base_url.as_str()) {
+        Some(path) if path.is_empty() || path.starts_with('/') => {
+            serve_file(&site.root, &req, path).await
+        }
+        // the root leads to the site
+        _ if req.uri.path() == "/" => {
+            Redirect::temporary(&format!("{}/", site.base_url)).into_response()
+        }
+        _ => {
+            log::debug!(
+                "Serve not under the base url {}",
+                GRAY.paint(req.uri.path())
+            );
+            (StatusCode::NOT_FOUND, "Not found").into_response()
+        }
+    };
+    // replacing the defaults, such as the cache-control
+    for (name, value) in &site.headers {
+        response.headers_mut().insert(name.clone(), value.clone());
+    }
+    response
+}
+
+/// serves the file of the path, relative to the base url
+async fn serve_file(root: &Utf8Path, req: &Parts, path: &str) -> Response {
+    let (method, headers) = (&req.method, &req.headers);
+    if method != Method::GET && method != Method::HEAD {
+        return StatusCode::METHOD_NOT_ALLOWED.into_response();
+    }
+    let Some(file) = resolve(root, path) else {
+        log::debug!("Serve not found {}", GRAY.paint(req.uri.path()));
+        return (StatusCode::NOT_FOUND, "Not found").into_response();
+    };
+
+    let accepted = headers
+        .get(header::ACCEPT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    let mime = mime_type(&file);
+    match read(&file, mime, accepted).await {
+        Ok((data, encoding)) => {
+            let mut response = Response::builder()
+                .header(header::CONTENT_TYPE, mime)
+                .header(header::CACHE_CONTROL, "no-cache")
+                .header(header::VARY, "Accept-Encoding");
+            if let Some(encoding) = encoding {
+                response = response.header(header::CONTENT_ENCODING, encoding);
+            }
+            let body = if method == Method::HEAD {
+                Body::empty()
+            } else {
+                Body::from(data)
+            };
+            response.body(body).unwrap_or_default()
+        }
+        Err(e) => {
+            log::warn!("Serve could not read {}: {e:#}", GRAY.paint(file.as_str()));
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// the file of the url path: the `index.html` of the dirs and, for the paths without an
+/// extension that aren't files, the one of the site root. Only plain names are followed, so
+/// that the paths can't leave the site root
+fn resolve(root: &Utf8Path, path: &str) -> Option<Utf8PathBuf> {
+    let path = percent_decode_str(path).decode_utf8().ok()?;
+    let rel = Utf8Path::new(path.trim_start_matches('/'));
+    if !rel
+        .components()
+        .all(|component| matches!(component, Utf8Component::Normal(_)))
+    {
+        return None;
+    }
+    let file = root.join(rel);
+    let file = if file.is_dir() {
+        file.join("index.html")
+    } else {
+        file
+    };
+    if file.is_file() {
+        return Some(file);
+    }
+    let index = root.join("index.html");
+    (rel.extension().is_none() && index.is_file()).then_some(index)
+}
+
+/// The file, precompressed (`--precompress`) or compressed on the fly when the browser accepts
+/// it, with its content encoding.
+async fn read(
+    file: &Utf8Path,
+    mime: &str,
+    accepted: &str,
+) -> Result<(Vec<u8>, Option<&'static str>)> {
+    for (encoding, ext) in [("br", "br"), ("gzip", "gz")] {
+        let precompressed = Utf8PathBuf::from(format!("{file}.{ext}"));
+        if accepts(accepted, encoding) && precompressed.is_file() {
+            return Ok((tokio::fs::read(&precompressed).await?, Some(encoding)));
+        }
+    }
+    let data = tokio::fs::read(file).await?;
+    if data.len() < MIN_COMPRESS_SIZE || !compressible(mime) || !accepts(accepted, "gzip") {
+        return Ok((data, None));
+    }
+    let mut encoder = gzip::Encoder::new(Vec::new())?;
+    encoder.write_all(&data)?;
+    Ok((encoder.finish().into_result()?, Some("gzip")))
+}
+
+fn accepts(accepted: &str, encoding: &str) -> bool {
+    accepted
+        .split(',')
+        .any(|value| value.split(';').next().unwrap_or_default().trim() == encoding)
+}
+
+fn compressible(mime: &str) -> bool {
+    mime.starts_with("text/")
+        || mime.starts_with("application/javascript")
+        || mime.starts_with("application/json")
+        || mime.starts_with("application/wasm")
+        || mime.starts_with("application/manifest+json")
+        || mime.starts_with("image/svg+xml")
+}
+
+fn mime_type(file: &Utf8Path) -> &'static str {
+    match file.extension().unwrap_or_default() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "application/javascript; charset=utf-8",
+        "json" | "map" => "application/json",
+        "webmanifest" => "application/manifest+json",
+        "wasm" => "application/wasm",
+        "txt" => "text/plain; charset=utf-8",
+        "xml" => "text/xml; charset=utf-8",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "mp3" => "audio/mpeg",
+        "pdf" => "application/pdf",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::resolve;
+    use camino::Utf8Path;
+
+    #[test]
+    fn test_resolve() {
+        let dir = temp_dir::TempDir::new().unwrap();
+        let base = Utf8Path::from_path(dir.path()).unwrap();
+        let root = base.join("site");
+        std::fs::create_dir_all(root.join("docs")).unwrap();
+        std::fs::write(root.join("index.html"), "").unwrap();
+        std::fs::write(root.join("docs/index.html"), "").unwrap();
+        std::fs::write(root.join("app.js"), "").unwrap();
+        std::fs::write(base.join("secret.txt"), "").unwrap();
+
+        assert_eq!(resolve(&root, "/"), Some(root.join("index.html")));
+        assert_eq!(resolve(&root, "/app.js"), Some(root.join("app.js")));
+        assert_eq!(resolve(&root, "/docs"), Some(root.join("docs/index.html")));
+        // the routes of the app
+        assert_eq!(resolve(&root, "/users/1"), Some(root.join("index.html")));
+        assert_eq!(resolve(&root, "/missing.js"), None);
+
+        // only plain names are followed
+        assert_eq!(resolve(&root, "/../secret.txt"), None);
+        assert_eq!(resolve(&root, "/%2e%2e/secret.txt"), None);
+        assert_eq!(resolve(&root, "/docs/../../secret.txt"), None);
+        assert_eq!(resolve(&root, "/./app.js"), None);
+        assert_eq!(resolve(&root, &format!("/{base}/secret.txt")), None);
+        #[cfg(windows)]
+        assert_eq!(resolve(&root, "/..\\secret.txt"), None);
+    }
+}
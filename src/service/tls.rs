@@ -0,0 +1,157 @@
+use std::{io::BufReader, sync::Arc};
+
+use crate::{
+    config::{Project, Tls},
+    ext::{
+        anyhow::{anyhow, bail, Context, Result},
+        fs,
+    },
+    logger::GRAY,
+};
+use tokio::process::Command;
+use tokio_rustls::{
+    rustls::{
+        client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+        crypto::{ring, verify_tls12_signature, verify_tls13_signature, CryptoProvider},
+        pki_types::{CertificateDer, ServerName, UnixTime},
+        ClientConfig, DigitallySignedStruct, ServerConfig, SignatureScheme,
+    },
+    TlsAcceptor, TlsConnector,
+};
+
+/// The acceptor of the https connections to the site, generating the certificate first when
+/// it's missing.
+pub async fn acceptor(proj: &Project, tls: &Tls) -> Result<TlsAcceptor> {
+    if tls.generated && !(tls.cert.exists() && tls.key.exists()) {
+        generate(proj, tls).await?;
+    }
+    let certs = {
+        let data = fs::read(&tls.cert).await?;
+        rustls_pemfile::certs(&mut BufReader::new(data.as_slice()))
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| format!("Invalid tls certificate {}", tls.cert))?
+    };
+    let key = {
+        let data = fs::read(&tls.key).await?;
+        rustls_pemfile::private_key(&mut BufReader::new(data.as_slice()))
+            .with_context(|| format!("Invalid tls key {}", tls.key))?
+            .ok_or_else(|| anyhow!("No private key found in {}", tls.key))?
+    };
+
+    let mut config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .context("Could not use the tls certificate")?;
+    // the relay serves the connections with http/1.1, which its websockets are upgraded from
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Generates a certificate for the local hosts with mkcert, signed by its locally-trusted CA
+async fn generate(proj: &Project, tls: &Tls) -> Result<()> {
+    if let Some(dir) = tls.cert.parent() {
+        fs::create_dir_all(dir).await?;
+    }
+    let mut hosts = vec![
+        "localhost".to_string(),
+        "127.0.0.1".to_string(),
+        "::1".to_string(),
+    ];
+    let ip = proj.site.addr.ip();
+    if !ip.is_unspecified() && !ip.is_loopback() {
+        hosts.push(ip.to_string());
+    }
+
+    log::info!(
+        "Serve generating a certificate with mkcert in {}",
+        GRAY.paint(tls.cert.as_str())
+    );
+    let output = Command::new("mkcert")
+        .arg("-cert-file")
+        .arg(&tls.cert)
+        .arg("-key-file")
+        .arg(&tls.key)
+        .args(&hosts)
+        .output()
+        .await;
+    match output {
+        Ok(output) if output.status.success() => {
+            log::info!(
+                "Serve the certificate is trusted by the browsers once {} ran",
+                GRAY.paint("mkcert -install")
+            );
+            Ok(())
+        }
+        Ok(output) => bail!(
+            "mkcert could not generate the certificate: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => bail!(
+            "--tls needs mkcert (https://github.com/FiloSottile/mkcert) to generate a \
+             certificate, or the tls-cert and tls-key of one"
+        ),
+        Err(e) => Err(e).context("Could not run mkcert"),
+    }
+}
+
+/// The connector to the https sites of the local projects, whose certificates may not be
+/// trusted (yet) by cargo-leptos, so they are accepted as they are.
+pub fn local_connector() -> Result<TlsConnector> {
+    let provider = Arc::new(ring::default_provider());
+    let config = ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(AnyCertificate(provider)))
+        .with_no_client_auth();
+    Ok(TlsConnector::from(Arc::new(config)))
+}
+
+/// accepts any certificate, checking only the signatures of the handshake
+#[derive(Debug)]
+struct AnyCertificate(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AnyCertificate {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, tokio_rustls::rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
@@ -1,3 +1,8 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
 use tokio::{
     signal,
     sync::{broadcast, RwLock},
@@ -5,15 +10,19 @@ use tokio::{
 };
 
 use crate::compile::{Change, ChangeSet};
+use crate::config::Project;
 
 lazy_static::lazy_static! {
-  static ref ANY_INTERRUPT: broadcast::Sender<()> = broadcast::channel(10).0;
+  /// the changes of each project, by name, which wake its watch loop and stop its builds
+  static ref ANY_INTERRUPT: Mutex<HashMap<String, broadcast::Sender<()>>> = Mutex::new(HashMap::new());
   static ref SHUTDOWN: broadcast::Sender<()> = broadcast::channel(1).0;
 
   static ref SHUTDOWN_REQUESTED: RwLock<bool> = RwLock::new(false);
-  static ref SOURCE_CHANGES: RwLock<ChangeSet> = RwLock::new(ChangeSet::default());
+  static ref SOURCE_CHANGES: RwLock<HashMap<String, ChangeSet>> = RwLock::new(HashMap::new());
 }
 
+/// The changes and the shutdown. The changes are those of a project, as several projects can be
+/// watched at once, while the shutdown is for all of them.
 pub struct Interrupt {}
 
 impl Interrupt {
@@ -21,43 +30,59 @@ impl Interrupt {
         *SHUTDOWN_REQUESTED.read().await
     }
 
-    pub fn subscribe_any() -> broadcast::Receiver<()> {
-        ANY_INTERRUPT.subscribe()
+    /// the changes of the project, and the shutdown
+    pub fn subscribe_any(proj: &Project) -> broadcast::Receiver<()> {
+        any_sender(proj).subscribe()
     }
 
     pub fn subscribe_shutdown() -> broadcast::Receiver<()> {
         SHUTDOWN.subscribe()
     }
 
-    pub async fn get_source_changes() -> ChangeSet {
-        SOURCE_CHANGES.read().await.clone()
+    pub async fn get_source_changes(proj: &Project) -> ChangeSet {
+        let changes = SOURCE_CHANGES.read().await;
+        changes.get(&proj.name).cloned().unwrap_or_default()
     }
 
-    pub async fn clear_source_changes() {
-        let mut ch = SOURCE_CHANGES.write().await;
-        ch.clear();
+    pub async fn clear_source_changes(proj: &Project) {
+        SOURCE_CHANGES.write().await.remove(&proj.name);
         log::trace!("Interrupt source changed cleared");
     }
 
-    pub fn send_all_changed() {
+    /// clears the on-change hook changes only, keeping those of the files the hooks wrote
+    pub async fn clear_hook_changes(proj: &Project) {
+        if let Some(changes) = SOURCE_CHANGES.write().await.get_mut(&proj.name) {
+            changes.remove_hooks();
+        }
+        log::trace!("Interrupt hook changes cleared");
+    }
+
+    pub fn send_all_changed(proj: &Project) {
         let mut ch = SOURCE_CHANGES.blocking_write();
-        *ch = ChangeSet::all_changes();
+        ch.insert(proj.name.clone(), ChangeSet::all_changes());
+        drop(ch);
+        Self::send_any(proj)
+    }
+
+    /// requests a full rebuild, for the triggers other than the watched files
+    pub async fn send_rebuild(proj: &Project) {
+        let mut ch = SOURCE_CHANGES.write().await;
+        ch.insert(proj.name.clone(), ChangeSet::all_changes());
         drop(ch);
-        Self::send_any()
+        Self::send_any(proj);
     }
 
-    pub fn send(changes: &[Change]) {
+    pub fn send(proj: &Project, changes: &[Change]) {
         let mut ch = SOURCE_CHANGES.blocking_write();
+        let ch = ch.entry(proj.name.clone()).or_default();
         for change in changes {
             ch.add(change.clone());
         }
-        drop(ch);
-
-        Self::send_any();
+        Self::send_any(proj);
     }
 
-    fn send_any() {
-        if let Err(e) = ANY_INTERRUPT.send(()) {
+    fn send_any(proj: &Project) {
+        if let Err(e) = any_sender(proj).send(()) {
             log::error!("Interrupt error could not send due to: {e}");
         } else {
             log::trace!("Interrupt send done");
@@ -69,7 +94,32 @@ impl Interrupt {
             *SHUTDOWN_REQUESTED.write().await = true;
         }
         _ = SHUTDOWN.send(());
-        _ = ANY_INTERRUPT.send(());
+        for sender in ANY_INTERRUPT.lock().unwrap().values() {
+            _ = sender.send(());
+        }
+    }
+
+    /// Requests a full rebuild on each SIGUSR1, e.g. from an editor: `pkill -USR1 cargo-leptos`
+    #[cfg(unix)]
+    pub fn run_rebuild_signal_monitor(projects: &[Arc<Project>]) -> JoinHandle<()> {
+        use signal::unix::{signal, SignalKind};
+
+        let projects = projects.to_vec();
+        tokio::spawn(async move {
+            let mut usr1 = match signal(SignalKind::user_defined1()) {
+                Ok(usr1) => usr1,
+                Err(e) => {
+                    log::warn!("Leptos could not listen for SIGUSR1: {e}");
+                    return;
+                }
+            };
+            while usr1.recv().await.is_some() {
+                log::info!("Leptos SIGUSR1 received, rebuilding");
+                for proj in &projects {
+                    Interrupt::send_rebuild(proj).await;
+                }
+            }
+        })
     }
 
     pub fn run_ctrl_c_monitor() -> JoinHandle<()> {
@@ -80,3 +130,13 @@ impl Interrupt {
         })
     }
 }
+
+/// the channel of the changes of the project, created with its first use
+fn any_sender(proj: &Project) -> broadcast::Sender<()> {
+    ANY_INTERRUPT
+        .lock()
+        .unwrap()
+        .entry(proj.name.clone())
+        .or_insert_with(|| broadcast::channel(10).0)
+        .clone()
+}
@@ -1,10 +1,16 @@
+use crate::config::Project;
 use derive_more::Display;
 use itertools::Itertools;
-use std::{collections::HashSet, fmt};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::Mutex,
+};
 use tokio::sync::broadcast;
 
 lazy_static::lazy_static! {
-  static ref SERVER_RESTART_CHANNEL: broadcast::Sender::<()> = broadcast::channel::<()>(1).0;
+  /// the server restarts of each project, by name
+  static ref SERVER_RESTART_CHANNELS: Mutex<HashMap<String, broadcast::Sender::<()>>> = Mutex::new(HashMap::new());
 }
 
 #[derive(Debug, PartialEq, Eq, Hash)]
@@ -55,6 +61,10 @@ impl ProductSet {
         self.0.len() == 1 && self.0.iter().any(|p| matches!(p, Product::Style(_)))
     }
 
+    pub fn contains_style(&self) -> bool {
+        self.0.iter().any(|p| matches!(p, Product::Style(_)))
+    }
+
     pub fn contains(&self, product: &Product) -> bool {
         self.0.contains(product)
     }
@@ -81,14 +91,23 @@ impl fmt::Display for ProductSet {
 pub struct ServerRestart {}
 
 impl ServerRestart {
-    pub fn subscribe() -> broadcast::Receiver<()> {
-        SERVER_RESTART_CHANNEL.subscribe()
+    pub fn subscribe(proj: &Project) -> broadcast::Receiver<()> {
+        Self::sender(proj).subscribe()
     }
 
-    pub fn send() {
+    pub fn send(proj: &Project) {
         log::trace!("Server restart sent");
-        if let Err(e) = SERVER_RESTART_CHANNEL.send(()) {
+        if let Err(e) = Self::sender(proj).send(()) {
             log::error!("Error could not send product changes due to {e}")
         }
     }
+
+    fn sender(proj: &Project) -> broadcast::Sender<()> {
+        SERVER_RESTART_CHANNELS
+            .lock()
+            .unwrap()
+            .entry(proj.name.clone())
+            .or_insert_with(|| broadcast::channel(1).0)
+            .clone()
+    }
 }
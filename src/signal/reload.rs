@@ -1,8 +1,16 @@
+use crate::config::Project;
 use leptos_hot_reload::diff::Patches;
+use std::{collections::HashMap, sync::Mutex, time::Duration};
 use tokio::sync::broadcast;
 
+/// the window in which successive full and style reloads are sent as one
+const COALESCE_WINDOW: Duration = Duration::from_millis(50);
+
 lazy_static::lazy_static! {
-  static ref RELOAD_CHANNEL: broadcast::Sender::<ReloadType> = broadcast::channel::<ReloadType>(1).0;
+  /// the reload channel of each project, by name
+  static ref RELOAD_CHANNELS: Mutex<HashMap<String, broadcast::Sender::<ReloadType>>> = Mutex::new(HashMap::new());
+  /// the full or style reload of each project waiting for the end of the window
+  static ref PENDING: Mutex<HashMap<String, ReloadType>> = Mutex::new(HashMap::new());
 }
 
 #[derive(Debug, Clone)]
@@ -10,26 +18,66 @@ pub enum ReloadType {
     Full,
     Style,
     ViewPatches(String),
+    /// the output of the failed build steps
+    Error(String),
 }
 
+/// The reloads of the pages of a project, as several projects can be watched at once.
 pub struct ReloadSignal {}
 
 impl ReloadSignal {
-    pub fn send_full() {
-        if let Err(e) = RELOAD_CHANNEL.send(ReloadType::Full) {
-            log::error!(r#"Error could not send reload "Full" due to: {e}"#);
+    pub fn send_full(proj: &Project) {
+        Self::coalesce(proj, ReloadType::Full);
+    }
+    pub fn send_style(proj: &Project) {
+        Self::coalesce(proj, ReloadType::Style);
+    }
+
+    /// Sends the reload at the end of the window, after the `reload-delay-ms` of the project,
+    /// together with the ones that follow within it: a full reload supersedes a style reload.
+    fn coalesce(proj: &Project, reload: ReloadType) {
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return Self::send(&proj.name, reload);
+        };
+        let mut pending = PENDING.lock().unwrap();
+        match pending.get_mut(&proj.name) {
+            Some(pending) => {
+                if matches!(reload, ReloadType::Full) {
+                    *pending = reload;
+                }
+                log::trace!("Reload coalesced");
+            }
+            None => {
+                pending.insert(proj.name.clone(), reload);
+                let delay = proj.reload_delay + COALESCE_WINDOW;
+                let name = proj.name.clone();
+                runtime.spawn(async move {
+                    tokio::time::sleep(delay).await;
+                    let reload = PENDING.lock().unwrap().remove(&name);
+                    if let Some(reload) = reload {
+                        Self::send(&name, reload);
+                    }
+                });
+            }
         }
     }
-    pub fn send_style() {
-        if let Err(e) = RELOAD_CHANNEL.send(ReloadType::Style) {
-            log::error!(r#"Error could not send reload "Style" due to: {e}"#);
+
+    fn send(name: &str, reload: ReloadType) {
+        if let Err(e) = sender(name).send(reload.clone()) {
+            log::error!(r#"Error could not send reload "{reload:?}" due to: {e}"#);
+        }
+    }
+
+    pub fn send_error(proj: &Project, errors: String) {
+        if let Err(e) = sender(&proj.name).send(ReloadType::Error(errors)) {
+            log::error!(r#"Error could not send reload "Error" due to: {e}"#);
         }
     }
 
-    pub fn send_view_patches(view_patches: &Patches) {
+    pub fn send_view_patches(proj: &Project, view_patches: &Patches) {
         match serde_json::to_string(view_patches) {
             Ok(data) => {
-                if let Err(e) = RELOAD_CHANNEL.send(ReloadType::ViewPatches(data)) {
+                if let Err(e) = sender(&proj.name).send(ReloadType::ViewPatches(data)) {
                     log::error!(r#"Error could not send reload "View Patches" due to: {e}"#);
                 }
             }
@@ -37,7 +85,17 @@ impl ReloadSignal {
         }
     }
 
-    pub fn subscribe() -> broadcast::Receiver<ReloadType> {
-        RELOAD_CHANNEL.subscribe()
+    pub fn subscribe(proj: &Project) -> broadcast::Receiver<ReloadType> {
+        sender(&proj.name).subscribe()
     }
 }
+
+/// the reload channel of the project, created with its first use
+fn sender(name: &str) -> broadcast::Sender<ReloadType> {
+    RELOAD_CHANNELS
+        .lock()
+        .unwrap()
+        .entry(name.to_string())
+        .or_insert_with(|| broadcast::channel(1).0)
+        .clone()
+}